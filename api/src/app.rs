@@ -1,14 +1,39 @@
 use axum::response::Response;
-use mysql::Pool;
 
 use std::sync::Arc;
 
-use crate::{ snowflake::SnowflakeGenerator, utilities::app_error::AppError };
+use crate::{
+    config::AppConfig,
+    shutdown::Shutdown,
+    snowflake::SnowflakeGenerator,
+    storage::{ Connection, Storage },
+    utilities::{ app_error::AppError, blocking::with_conn },
+};
 
 #[derive(Debug, Clone)]
 pub struct AppState {
-    pub db_pool: Pool,
+    /// Where entities are kept, MySQL outside of tests.
+    pub storage: Storage,
     pub snowflake_generator: Arc<SnowflakeGenerator>,
+    /// Settings loaded at startup, including the secret of the tokens.
+    pub config: Arc<AppConfig>,
+    /// Triggered when the server starts draining, see `run`.
+    pub shutdown: Shutdown,
+}
+
+impl AppState {
+    /// Runs `f` with a connection of the storage on the blocking thread pool.
+    ///
+    /// The `mysql` crate is synchronous, so handlers do their database work, and anything else
+    /// that blocks like hashing passwords, inside `f` rather than on the async runtime.
+    pub async fn db<T, E, F>(&self, f: F) -> std::result::Result<T, E>
+        where
+            F: FnOnce(&mut Connection) -> std::result::Result<T, E> + Send + 'static,
+            T: Send + 'static,
+            E: From<mysql::Error> + Send + 'static
+    {
+        with_conn(&self.storage, f).await
+    }
 }
 
 pub type AppResult<T> = std::result::Result<T, AppError>;
@@ -0,0 +1,319 @@
+use std::{ env, fmt, net::SocketAddr, path::Path, time::Duration };
+
+use axum::http::HeaderValue;
+use serde::Deserialize;
+
+use crate::snowflake::MAX_MACHINE_ID;
+
+/// Log levels `log.level` accepts, from the most to the least verbose.
+pub const LOG_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+/// Log formats `log.format` accepts: human-readable lines or one JSON object per line.
+pub const LOG_FORMATS: [&str; 2] = ["text", "json"];
+
+/// Settings of the application, loaded once at startup by `AppConfig::load`.
+///
+/// Values come from the TOML file named by `CONFIG_FILE`, `config.toml` by default, then from
+/// environment variables, which take precedence. Missing values fall back to the defaults
+/// below, except the database URL and the JWT secret that must be given.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub snowflake: SnowflakeConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// `LISTEN_ADDRESS`, defaults to `127.0.0.1:8080`
+    pub listen_address: String,
+    /// `CORS_ORIGINS`, comma-separated in the environment. Cross-origin requests are refused
+    /// when empty, the default.
+    pub cors_origins: Vec<String>,
+    /// `DRAIN_TIMEOUT_SECS`, how long in-flight requests and background workers get to finish
+    /// on shutdown, defaults to 30
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen_address: "127.0.0.1:8080".to_string(),
+            cors_origins: Vec::new(),
+            drain_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// `DATABASE_URL`, required
+    pub url: String,
+    /// `DATABASE_POOL_MIN`, idle connections kept open, defaults to 10
+    pub pool_min: usize,
+    /// `DATABASE_POOL_MAX`, defaults to 100
+    pub pool_max: usize,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        // the defaults of the `mysql` crate
+        DatabaseConfig { url: String::new(), pool_min: 10, pool_max: 100 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// `JWT_SECRET`, signs and verifies access and refresh tokens, required
+    pub jwt_secret: String,
+    /// `HASH_COST`, bcrypt cost of the password hashes, defaults to 12
+    pub hash_cost: u32,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig { jwt_secret: String::new(), hash_cost: bcrypt::DEFAULT_COST }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SnowflakeConfig {
+    /// `MACHINE_ID`, must be unique among the instances sharing a database, defaults to 0
+    pub machine_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LogConfig {
+    /// `LOG_LEVEL`, one of `LOG_LEVELS`, defaults to `info`
+    pub level: String,
+    /// `LOG_FORMAT`, one of `LOG_FORMATS`, defaults to `text`
+    pub format: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig { level: "info".to_string(), format: "text".to_string() }
+    }
+}
+
+impl AppConfig {
+    /// Loads the configuration of the process and validates it.
+    pub fn load() -> Result<Self, ConfigError> {
+        let file = env::var("CONFIG_FILE").ok();
+        let path = file.as_deref().unwrap_or("config.toml");
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => Some(contents),
+            // the default file is optional, a file that was asked for isn't
+            Err(_) if file.is_none() && !Path::new(path).exists() => None,
+            Err(error) => {
+                return Err(ConfigError(vec![format!("Cannot read `{}`: {}", path, error)]));
+            }
+        };
+
+        Self::from_sources(contents.as_deref(), |name| env::var(name).ok())
+    }
+
+    /// Builds the configuration from the contents of a TOML file and the variables returned by
+    /// `env`, then validates it.
+    pub fn from_sources(
+        file: Option<&str>,
+        env: impl Fn(&str) -> Option<String>
+    ) -> Result<Self, ConfigError> {
+        let mut config = match file {
+            Some(contents) =>
+                toml
+                    ::from_str::<AppConfig>(contents)
+                    .map_err(|error| ConfigError(vec![format!("Invalid config file: {}", error)]))?,
+            None => AppConfig::default(),
+        };
+
+        let mut errors = Vec::new();
+        let mut parse = |name: &str, target: &mut dyn FnMut(&str) -> bool| {
+            if let Some(value) = env(name) {
+                if !target(value.trim()) {
+                    errors.push(format!("Invalid value for {}: `{}`", name, value));
+                }
+            }
+        };
+        parse("LISTEN_ADDRESS", &mut |value| {
+            config.server.listen_address = value.to_string();
+            true
+        });
+        parse("CORS_ORIGINS", &mut |value| {
+            config.server.cors_origins = value
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(str::to_string)
+                .collect();
+            true
+        });
+        parse("DRAIN_TIMEOUT_SECS", &mut |value| {
+            value.parse().map(|secs| config.server.drain_timeout_secs = secs).is_ok()
+        });
+        parse("DATABASE_URL", &mut |value| {
+            config.database.url = value.to_string();
+            true
+        });
+        parse("DATABASE_POOL_MIN", &mut |value| {
+            value.parse().map(|min| config.database.pool_min = min).is_ok()
+        });
+        parse("DATABASE_POOL_MAX", &mut |value| {
+            value.parse().map(|max| config.database.pool_max = max).is_ok()
+        });
+        parse("JWT_SECRET", &mut |value| {
+            config.auth.jwt_secret = value.to_string();
+            true
+        });
+        parse("HASH_COST", &mut |value| {
+            value.parse().map(|cost| config.auth.hash_cost = cost).is_ok()
+        });
+        parse("MACHINE_ID", &mut |value| {
+            value.parse().map(|id| config.snowflake.machine_id = id).is_ok()
+        });
+        parse("LOG_LEVEL", &mut |value| {
+            config.log.level = value.to_lowercase();
+            true
+        });
+        parse("LOG_FORMAT", &mut |value| {
+            config.log.format = value.to_lowercase();
+            true
+        });
+
+        errors.extend(config.validate());
+        if errors.is_empty() { Ok(config) } else { Err(ConfigError(errors)) }
+    }
+
+    /// Lists the problems of the configuration.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.listen_address().is_none() {
+            errors.push(
+                format!("server.listen_address `{}` is not an address", self.server.listen_address)
+            );
+        }
+        for origin in &self.server.cors_origins {
+            let valid =
+                (origin.starts_with("http://") || origin.starts_with("https://")) &&
+                HeaderValue::from_str(origin).is_ok();
+            if !valid {
+                errors.push(format!("server.cors_origins `{}` is not an origin", origin));
+            }
+        }
+        if self.database.url.is_empty() {
+            errors.push("database.url must be set, e.g. with DATABASE_URL".to_string());
+        }
+        if self.database.pool_max == 0 || self.database.pool_min > self.database.pool_max {
+            errors.push(
+                "database.pool_max must be positive and at least database.pool_min".to_string()
+            );
+        }
+        if self.auth.jwt_secret.is_empty() {
+            errors.push("auth.jwt_secret must be set, e.g. with JWT_SECRET".to_string());
+        }
+        if !(4..=31).contains(&self.auth.hash_cost) {
+            errors.push("auth.hash_cost must be between 4 and 31".to_string());
+        }
+        if !(0..=MAX_MACHINE_ID).contains(&self.snowflake.machine_id) {
+            errors.push(format!("snowflake.machine_id must be between 0 and {}", MAX_MACHINE_ID));
+        }
+        if !LOG_LEVELS.contains(&self.log.level.as_str()) {
+            errors.push(format!("log.level must be one of {}", LOG_LEVELS.join(", ")));
+        }
+        if !LOG_FORMATS.contains(&self.log.format.as_str()) {
+            errors.push(format!("log.format must be one of {}", LOG_FORMATS.join(", ")));
+        }
+
+        errors
+    }
+
+    pub fn listen_address(&self) -> Option<SocketAddr> {
+        self.server.listen_address.parse().ok()
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.drain_timeout_secs)
+    }
+}
+
+/// Everything wrong with the configuration, reported at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:\n  {}", self.0.join("\n  "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_load_config() {
+        let file =
+            r#"
+            [server]
+            listen_address = "0.0.0.0:3000"
+            cors_origins = ["https://app.workbase.dev"]
+
+            [database]
+            url = "mysql://root@localhost:3306/workbase"
+            pool_max = 20
+
+            [auth]
+            jwt_secret = "from-file"
+
+            [log]
+            format = "json"
+            "#;
+        let env = HashMap::from([
+            ("JWT_SECRET", "from-env"),
+            ("HASH_COST", "4"),
+            ("MACHINE_ID", "3"),
+            ("DRAIN_TIMEOUT_SECS", "5"),
+        ]);
+        let config = AppConfig::from_sources(Some(file), |name| {
+            env.get(name).map(|value| value.to_string())
+        }).unwrap();
+
+        assert_eq!(config.listen_address(), Some(SocketAddr::from(([0, 0, 0, 0], 3000))));
+        assert_eq!(config.server.cors_origins, vec!["https://app.workbase.dev"]);
+        assert_eq!(config.database.pool_min, 10);
+        assert_eq!(config.database.pool_max, 20);
+        // the environment overrides the file
+        assert_eq!(config.auth.jwt_secret, "from-env");
+        assert_eq!(config.auth.hash_cost, 4);
+        assert_eq!(config.snowflake.machine_id, 3);
+        assert_eq!(config.drain_timeout(), Duration::from_secs(5));
+        assert_eq!(config.log.level, "info");
+        assert_eq!(config.log.format, "json");
+
+        // every problem is reported at once
+        let env = HashMap::from([
+            ("LISTEN_ADDRESS", "localhost"),
+            ("HASH_COST", "cheap"),
+            ("MACHINE_ID", "32"),
+            ("LOG_LEVEL", "verbose"),
+            ("LOG_FORMAT", "xml"),
+        ]);
+        let error = AppConfig::from_sources(None, |name| {
+            env.get(name).map(|value| value.to_string())
+        }).unwrap_err();
+        assert_eq!(error.0.len(), 7, "{}", error);
+    }
+}
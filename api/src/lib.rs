@@ -1,8 +1,12 @@
 use app::AppState;
-use axum::Router;
+use axum::{ http::HeaderValue, Router };
 use models::result::Result;
+use shutdown::shutdown_signal;
+use std::future::IntoFuture;
+use tokio::time::Instant;
+use tower_http::cors::{ AllowOrigin, Any, CorsLayer };
 
-use crate::router::router::create_router;
+use crate::router::router::create_app;
 
 pub mod utilities;
 pub mod snowflake;
@@ -10,21 +14,86 @@ pub mod prototypes;
 pub mod models;
 pub mod router;
 pub mod queries;
+#[cfg(test)]
 pub mod tests;
 pub mod app;
+pub mod config;
+pub mod migrations;
+pub mod storage;
+pub mod metrics;
+pub mod shutdown;
 
 /// Starts the Axum web server and sets up routing.
 ///
 /// This function initializes the Axum router with the provided application state,
-/// then binds and serves the application on a specified address.
+/// then binds and serves the application on the address of its configuration.
+///
+/// It returns after a graceful shutdown, started by SIGTERM or SIGINT: new requests are refused
+/// while the ones in flight and the background workers get the drain timeout to finish.
 pub async fn run(app_state: AppState) -> Result<()> {
-    let app: Router = Router::new().nest("/api", create_router(app_state).await);
+    let config = app_state.config.clone();
+    let shutdown = app_state.shutdown.clone();
+    let mut app: Router = create_app(app_state).await;
+
+    if !config.server.cors_origins.is_empty() {
+        let origins = config.server.cors_origins
+            .iter()
+            .filter_map(|origin| HeaderValue::from_str(origin).ok())
+            .collect::<Vec<_>>();
+        app = app.layer(
+            CorsLayer::new()
+                .allow_origin(AllowOrigin::list(origins))
+                .allow_methods(Any)
+                .allow_headers(Any)
+        );
+    }
+
+    let address = config.listen_address().ok_or("Invalid listen address")?;
+    tracing::info!("Starting server on {}", address);
+
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown.trigger();
+        }
+    });
+
+    // once triggered, the server stops accepting connections and waits for the open ones
+    let server = axum
+        ::serve(listener, app.into_make_service())
+        .with_graceful_shutdown({
+            let shutdown = shutdown.clone();
+            async move { shutdown.triggered().await }
+        })
+        .into_future();
+    tokio::pin!(server);
+
+    // the server only returns by itself on errors or once every connection is closed
+    let drained = tokio::select! {
+        result = &mut server => {
+            result?;
+            true
+        }
+        _ = shutdown.triggered() => false,
+    };
 
-    println!("Starting server on 127.0.0.1:8080");
-    let address = std::net::SocketAddr::from(([127, 0, 0, 1], 8080));
+    let deadline = Instant::now() + config.drain_timeout();
+    tracing::info!(
+        "Shutting down, draining for up to {} seconds",
+        config.server.drain_timeout_secs
+    );
+    if !drained {
+        match tokio::time::timeout_at(deadline, &mut server).await {
+            Ok(result) => result?,
+            // queries already running on the blocking pool still complete before the runtime
+            // exits, only their responses are lost
+            Err(_) => tracing::warn!("Drain timeout elapsed, closing the remaining connections"),
+        }
+    }
+    shutdown.stop_workers(deadline.saturating_duration_since(Instant::now())).await;
 
-    // axum::serve::Serve::bind(&address).serve(app.into_make_service()).await.unwrap();
-    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
-    axum::serve(listener, app.into_make_service()).await.unwrap();
+    tracing::info!("Server stopped");
     Ok(())
 }
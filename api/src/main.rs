@@ -4,30 +4,110 @@ use mysql::*;
 
 use dotenv::dotenv;
 
-use api::{ run, queries::create_tables, snowflake::SnowflakeGenerator, app::AppState };
+use api::{
+    run,
+    migrations,
+    snowflake::SnowflakeGenerator,
+    app::AppState,
+    storage::{ Connection, Storage },
+    config::AppConfig,
+    utilities::logging::init_tracing,
+    metrics::metrics,
+    shutdown::Shutdown,
+};
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let db_url: String = env
-        ::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set in the .env file");
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    init_tracing(&config.log);
 
-    let pool = Pool::new(db_url.as_str()).expect("failed to create Pool from db_url");
+    let pool_constraints = PoolConstraints::new(
+        config.database.pool_min,
+        config.database.pool_max
+    ).expect("pool sizes are validated with the config");
+    let opts = Opts::from_url(&config.database.url).expect("failed to parse the database url");
+    let opts = OptsBuilder::from_opts(opts).pool_opts(
+        PoolOpts::default().with_constraints(pool_constraints)
+    );
+    let pool = Pool::new(opts).expect("failed to create Pool from db_url");
+    metrics().db_connections_max.set(config.database.pool_max as i64);
 
     {
-        let mut conn: PooledConn = pool
-            .get_conn()
-            .expect("'failed to establish connection with db'");
-        let _ = create_tables(&mut conn);
+        let mut conn = Connection::from_pool(&pool).expect(
+            "'failed to establish connection with db'"
+        );
+
+        // `api migrate [up [VERSION] | down VERSION | status]` manages the schema and exits
+        let args: Vec<String> = env::args().collect();
+        if args.get(1).map(String::as_str) == Some("migrate") {
+            if let Err(error) = migrate(&mut conn, &args[2..]) {
+                eprintln!("Migration failed: {}", error);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        if let Err(error) = migrations::ensure_up_to_date(&mut conn) {
+            eprintln!("Database schema check failed: {}", error);
+            std::process::exit(1);
+        }
     }
 
-    let machine_id = 1; // Set this based on your deployment strategy
-    let snowflake_generator = Arc::new(SnowflakeGenerator::new(machine_id));
+    let snowflake_generator = Arc::new(SnowflakeGenerator::new(config.snowflake.machine_id));
 
     let app_state = AppState {
-        db_pool: pool,
+        storage: Storage::MySql(pool),
         snowflake_generator,
+        config: Arc::new(config),
+        shutdown: Shutdown::default(),
+    };
+    if let Err(error) = run(app_state).await {
+        tracing::error!("Server error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn migrate(conn: &mut Connection, args: &[String]) -> api::models::result::Result<()> {
+    let version = |arg: Option<&String>| -> api::models::result::Result<Option<u32>> {
+        Ok(arg.map(|version| version.parse::<u32>()).transpose()?)
     };
-    let _ = run(app_state).await;
+
+    match args.first().map(String::as_str) {
+        None | Some("up") => {
+            for version in migrations::migrate_up(conn, version(args.get(1))?)? {
+                println!("Applied migration {:04}", version);
+            }
+        }
+        Some("down") => {
+            let target = version(args.get(1))?.ok_or("Usage: api migrate down VERSION")?;
+            for version in migrations::migrate_down(conn, target)? {
+                println!("Reverted migration {:04}", version);
+            }
+        }
+        Some("status") => {
+            for migration in migrations::applied_migrations(conn)? {
+                println!(
+                    "applied  {:04}_{} at {}",
+                    migration.version,
+                    migration.name,
+                    migration.applied_at
+                );
+            }
+            for migration in migrations::pending_migrations(conn)? {
+                println!("pending  {}", migration.label());
+            }
+        }
+        Some(command) => {
+            return Err(format!("Unknown migrate command `{}`", command).into());
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,264 @@
+use std::{ sync::OnceLock, time::Duration };
+
+use axum::{ http::{ header, StatusCode }, response::{ IntoResponse, Response } };
+use prometheus::{
+    Encoder,
+    Histogram,
+    HistogramOpts,
+    HistogramVec,
+    IntCounter,
+    IntCounterVec,
+    IntGauge,
+    Opts,
+    Registry,
+    TextEncoder,
+};
+
+/// Kinds of shift requests counted by `shift_requests_created_total` and
+/// `shift_requests_approved_total`.
+pub const SHIFT_REQUEST_KINDS: [&str; 4] = ["trade", "cover", "pickup", "time_off"];
+
+/// Metrics of the process, served in the Prometheus text format at `/metrics`.
+///
+/// They are process-wide like the `tracing` subscriber: the Snowflake generator and the storage
+/// record them without access to the `AppState`.
+pub struct Metrics {
+    registry: Registry,
+    /// `http_requests_total{method, route, status}`, `route` is the template the request
+    /// matched, e.g. `/api/users/:id`
+    pub http_requests: IntCounterVec,
+    /// `http_request_duration_seconds{method, route}`
+    pub http_request_duration: HistogramVec,
+    /// `http_errors_total{status, code}`, the error responses built from an `AppError`
+    pub http_errors: IntCounterVec,
+    /// `db_pool_connections_in_use`, connections taken from the MySQL pool
+    pub db_connections_in_use: IntGauge,
+    /// `db_pool_connections_max`, the size of the MySQL pool
+    pub db_connections_max: IntGauge,
+    /// `db_pool_wait_seconds`, time spent waiting for a connection of the pool
+    pub db_connection_wait: Histogram,
+    /// `snowflake_ids_generated_total`
+    pub snowflake_ids: IntCounter,
+    /// `shift_requests_created_total{kind}`, one of `SHIFT_REQUEST_KINDS`
+    pub shift_requests_created: IntCounterVec,
+    /// `shift_requests_approved_total{kind}`, one of `SHIFT_REQUEST_KINDS`
+    pub shift_requests_approved: IntCounterVec,
+}
+
+/// The metrics of the process, registered on first use.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        fn register<M: prometheus::core::Collector + Clone + 'static>(
+            registry: &Registry,
+            metric: prometheus::Result<M>
+        ) -> M {
+            let metric = metric.expect("metric options are valid");
+            registry.register(Box::new(metric.clone())).expect("metric names are unique");
+            metric
+        }
+
+        Metrics {
+            http_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("http_requests_total", "HTTP requests handled"),
+                    &["method", "route", "status"]
+                )
+            ),
+            http_request_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "http_request_duration_seconds",
+                        "Time taken to handle HTTP requests"
+                    ),
+                    &["method", "route"]
+                )
+            ),
+            http_errors: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("http_errors_total", "Error responses sent"),
+                    &["status", "code"]
+                )
+            ),
+            db_connections_in_use: register(
+                &registry,
+                IntGauge::new("db_pool_connections_in_use", "Connections taken from the pool")
+            ),
+            db_connections_max: register(
+                &registry,
+                IntGauge::new("db_pool_connections_max", "Size of the connection pool")
+            ),
+            db_connection_wait: register(
+                &registry,
+                Histogram::with_opts(
+                    HistogramOpts::new(
+                        "db_pool_wait_seconds",
+                        "Time spent waiting for a connection of the pool"
+                    )
+                )
+            ),
+            snowflake_ids: register(
+                &registry,
+                IntCounter::new("snowflake_ids_generated_total", "Snowflake IDs generated")
+            ),
+            shift_requests_created: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("shift_requests_created_total", "Shift requests created"),
+                    &["kind"]
+                )
+            ),
+            shift_requests_approved: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("shift_requests_approved_total", "Shift requests approved"),
+                    &["kind"]
+                )
+            ),
+            registry,
+        }
+    }
+
+    /// Counts a handled request. `route` is `None` when no route matched.
+    pub fn observe_request(
+        &self,
+        method: &str,
+        route: Option<&str>,
+        status: StatusCode,
+        elapsed: Duration
+    ) {
+        let route = route.unwrap_or("unmatched");
+        self.http_requests.with_label_values(&[method, route, status.as_str()]).inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Marks a connection of the pool as used until the returned guard is dropped.
+    pub fn connection_in_use(&self) -> ConnectionInUse {
+        self.db_connections_in_use.inc();
+        ConnectionInUse(self.db_connections_in_use.clone())
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics are encodable");
+        String::from_utf8(buffer).expect("the text format is UTF-8")
+    }
+}
+
+/// Returned by `Metrics::connection_in_use`.
+#[derive(Debug)]
+pub struct ConnectionInUse(IntGauge);
+
+impl Drop for ConnectionInUse {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// `GET /metrics`, for Prometheus to scrape.
+pub async fn get_metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, TextEncoder::new().format_type().to_string())],
+        metrics().render(),
+    ).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{ body::Body, http::{ Method, Request } };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        router::{ router::create_app, tests::initialize_memory_app_state },
+        snowflake::SnowflakeGenerator,
+        storage::{ Connection, Storage },
+    };
+
+    /// The value of the sample `name` in `rendered`, e.g. `http_requests_total{...}`.
+    fn sample(rendered: &str, name: &str) -> Option<f64> {
+        rendered
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .and_then(|value| value.parse().ok())
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        // other tests record metrics concurrently, only increases are checked
+        let before = metrics().render();
+        let ids_before = sample(&before, "snowflake_ids_generated_total").unwrap_or(0.0);
+        SnowflakeGenerator::new(1).generate_id();
+
+        let app = create_app(initialize_memory_app_state()).await;
+        let get = |uri: &str| Request::builder().method(Method::GET).uri(uri).body(Body::empty());
+        let response = app.clone().oneshot(get("/api/users/42").unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        // the routes at the root are counted too
+        let response = app.clone().oneshot(get("/health/live").unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(get("/metrics").unwrap()).await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; version=0.0.4"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rendered = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(sample(&rendered, "snowflake_ids_generated_total").unwrap() > ids_before);
+        // requests are labelled with the template of their route, not their path
+        assert!(
+            sample(
+                &rendered,
+                r#"http_requests_total{method="GET",route="/api/users/:id",status="401"}"#
+            ).unwrap() >= 1.0,
+            "{}",
+            rendered
+        );
+        assert!(
+            sample(&rendered, r#"http_errors_total{code="UNAUTHORIZED",status="401"}"#).unwrap() >=
+                1.0
+        );
+        assert!(
+            sample(
+                &rendered,
+                r#"http_requests_total{method="GET",route="/health/live",status="200"}"#
+            ).unwrap() >= 1.0
+        );
+        assert!(rendered.contains("db_pool_connections_in_use"));
+    }
+
+    #[test]
+    fn test_pool_connections_in_use() -> crate::models::result::Result<()> {
+        // only MySQL has a pool, the test runs against it when `DATABASE_URL_TEST` is set
+        let storage = crate::tests::initialize_test_db()?;
+        let Storage::MySql(pool) = &storage else {
+            return Ok(());
+        };
+
+        // connections taken outside of `with_conn` are counted as well, until they are dropped
+        let in_use = || metrics().db_connections_in_use.get();
+        let conn = storage.get_conn()?;
+        let other_conn = Connection::from_pool(pool)?;
+        assert!(in_use() >= 2);
+        drop((other_conn, conn));
+
+        crate::tests::cleanup_test_db(storage.get_conn()?)?;
+
+        Ok(())
+    }
+}
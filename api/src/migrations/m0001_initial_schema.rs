@@ -0,0 +1,159 @@
+use super::Migration;
+
+/// The tables as created by `create_tables` before migrations existed.
+///
+/// `time_off_requests` is missing: its statement had a syntax error back then, so those
+/// databases never had the table. `m0005_time_off_requests` creates it.
+pub fn migration() -> Migration {
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        check: None,
+        up: vec![
+            "CREATE TABLE IF NOT EXISTS users (
+                id BIGINT NOT NULL PRIMARY KEY,
+                email VARCHAR(100) NOT NULL UNIQUE,
+                encrypted_password VARCHAR(255) NOT NULL,
+                first_name VARCHAR(100) NOT NULL,
+                last_name VARCHAR(100) NOT NULL,
+                date_of_birth DATE NOT NULL,
+                phone_number VARCHAR(20) NULL UNIQUE,
+                avatar VARCHAR(255) NULL,
+                is_active BOOLEAN NOT NULL DEFAULT TRUE
+            );",
+            "CREATE TABLE IF NOT EXISTS organizations (
+                id BIGINT NOT NULL PRIMARY KEY,
+                name VARCHAR(100) NOT NULL,
+                description TEXT,
+                owner_id BIGINT NOT NULL,
+                timezone VARCHAR(50) NOT NULL DEFAULT 'America/Los_Angeles',
+                icon VARCHAR(255) NULL,
+                is_active BOOLEAN NOT NULL DEFAULT TRUE,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
+            );",
+            "CREATE TABLE IF NOT EXISTS org_jobs (
+                id BIGINT NOT NULL PRIMARY KEY,
+                org_id BIGINT,
+                name VARCHAR(100) NOT NULL,
+                description TEXT,
+                base_pay_rate FLOAT,
+                color VARCHAR(6),
+                update_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE
+            );",
+            "CREATE TABLE IF NOT EXISTS org_members (
+                user_id BIGINT NOT NULL,
+                org_id BIGINT NOT NULL,
+                job_id BIGINT NOT NULL,
+                joined_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+                FOREIGN KEY (job_id) REFERENCES org_jobs(id) ON DELETE CASCADE,
+                UNIQUE KEY user_org_unique (user_id, org_id)
+            );",
+            "CREATE TABLE IF NOT EXISTS availability_requests (
+                id BIGINT NOT NULL PRIMARY KEY,
+                user_id BIGINT NOT NULL,
+                org_id BIGINT NOT NULL,
+                start_date DATE NOT NULL,
+                status ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DENIED') NOT NULL DEFAULT 'PENDING',
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE
+            );",
+            "CREATE TABLE IF NOT EXISTS availability_details (
+                request_id BIGINT NOT NULL,
+                day_of_week ENUM('MONDAY', 'TUESDAY', 'WEDNESDAY', 'THURSDAY', 'FRIDAY', 'SATURDAY', 'SUNDAY') NOT NULL,
+                is_available BOOLEAN NOT NULL,
+                whole_day BOOLEAN NOT NULL DEFAULT FALSE,
+                preferred_start_time TIME,
+                preferred_end_time TIME,
+                FOREIGN KEY (request_id) REFERENCES availability_requests(id) ON DELETE CASCADE
+            );",
+            "CREATE TABLE IF NOT EXISTS work_schedules (
+                id BIGINT NOT NULL PRIMARY KEY,
+                org_id BIGINT NOT NULL,
+                published BOOLEAN NOT NULL DEFAULT FALSE,
+                start_date DATE NOT NULL,
+                end_date DATE NOT NULL,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+                UNIQUE KEY org_work_schedule (org_id, start_date)
+            );",
+            "CREATE TABLE IF NOT EXISTS shifts (
+                id BIGINT NOT NULL PRIMARY KEY,
+                user_id BIGINT NOT NULL,
+                schedule_id BIGINT NOT NULL,
+                job_id BIGINT NOT NULL,
+                start_time TIMESTAMP NOT NULL,
+                end_time TIMESTAMP NOT NULL,
+                pay_rate FLOAT,
+                note TEXT,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (schedule_id) REFERENCES work_schedules(id) ON DELETE CASCADE,
+                FOREIGN KEY (job_id) REFERENCES org_jobs(id) ON DELETE CASCADE
+            );",
+            "CREATE TABLE IF NOT EXISTS open_shifts (
+                id BIGINT NOT NULL PRIMARY KEY,
+                schedule_id BIGINT NOT NULL,
+                job_id BIGINT NOT NULL,
+                start_time TIME NOT NULL,
+                end_time TIME NOT NULL,
+                pay_rate FLOAT,
+                FOREIGN KEY (schedule_id) REFERENCES work_schedules(id) ON DELETE CASCADE,
+                FOREIGN KEY (job_id) REFERENCES org_jobs(id) ON DELETE CASCADE
+            );",
+            "CREATE TABLE IF NOT EXISTS shift_covers (
+                id BIGINT NOT NULL PRIMARY KEY,
+                shift_id BIGINT NOT NULL,
+                cover_user_id BIGINT NOT NULL,
+                status ENUM('PENDING', 'PEER_ACCEPTED', 'PEER_DECLINED', 'APPROVED', 'DECLINED', 'CANCELLED') NOT NULL DEFAULT 'PENDING',
+                admin_id BIGINT,
+                note TEXT,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                FOREIGN KEY (shift_id) REFERENCES shifts(id) ON DELETE CASCADE,
+                FOREIGN KEY (cover_user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (admin_id) REFERENCES users(id) ON DELETE SET NULL
+            );",
+            "CREATE TABLE IF NOT EXISTS shift_trades (
+                id BIGINT NOT NULL PRIMARY KEY,
+                shift1_id BIGINT NOT NULL,
+                shift2_id BIGINT NOT NULL,
+                status ENUM('PENDING', 'PEER_ACCEPTED', 'PEER_DECLINED', 'APPROVED', 'DECLINED', 'CANCELLED') NOT NULL DEFAULT 'PENDING',
+                admin_id BIGINT,
+                note TEXT,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                FOREIGN KEY (shift1_id) REFERENCES shifts(id) ON DELETE CASCADE,
+                FOREIGN KEY (shift2_id) REFERENCES shifts(id) ON DELETE CASCADE,
+                FOREIGN KEY (admin_id) REFERENCES users(id) ON DELETE SET NULL
+            );",
+            "CREATE TABLE IF NOT EXISTS shift_pickups (
+                id BIGINT NOT NULL PRIMARY KEY,
+                openshift_id BIGINT NOT NULL,
+                user_id BIGINT NOT NULL,
+                status ENUM('PENDING', 'PEER_ACCEPTED', 'PEER_DECLINED', 'APPROVED', 'DECLINED', 'CANCELLED') NOT NULL DEFAULT 'PENDING',
+                admin_id BIGINT,
+                note TEXT,
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                FOREIGN KEY (openshift_id) REFERENCES open_shifts(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (admin_id) REFERENCES users(id) ON DELETE SET NULL
+            );"
+        ],
+        down: vec![
+            "DROP TABLE IF EXISTS shift_pickups;",
+            "DROP TABLE IF EXISTS shift_trades;",
+            "DROP TABLE IF EXISTS shift_covers;",
+            "DROP TABLE IF EXISTS open_shifts;",
+            "DROP TABLE IF EXISTS shifts;",
+            "DROP TABLE IF EXISTS work_schedules;",
+            "DROP TABLE IF EXISTS availability_details;",
+            "DROP TABLE IF EXISTS availability_requests;",
+            "DROP TABLE IF EXISTS org_members;",
+            "DROP TABLE IF EXISTS org_jobs;",
+            "DROP TABLE IF EXISTS organizations;",
+            "DROP TABLE IF EXISTS users;"
+        ],
+    }
+}
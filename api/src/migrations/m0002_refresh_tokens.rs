@@ -0,0 +1,21 @@
+use super::Migration;
+
+/// Server-side records of the issued refresh tokens, so they can be revoked.
+pub fn migration() -> Migration {
+    Migration {
+        version: 2,
+        name: "refresh_tokens",
+        check: None,
+        up: vec![
+            "CREATE TABLE refresh_tokens (
+                id BIGINT NOT NULL PRIMARY KEY,
+                user_id BIGINT NOT NULL,
+                expires_at TIMESTAMP NOT NULL,
+                revoked BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );"
+        ],
+        down: vec!["DROP TABLE refresh_tokens;"],
+    }
+}
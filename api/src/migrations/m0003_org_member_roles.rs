@@ -0,0 +1,20 @@
+use super::Migration;
+
+/// Gives every member a role. Owners get theirs back from their organization.
+pub fn migration() -> Migration {
+    Migration {
+        version: 3,
+        name: "org_member_roles",
+        check: None,
+        up: vec![
+            "ALTER TABLE org_members
+            ADD COLUMN role ENUM('OWNER', 'ADMIN', 'MANAGER', 'EMPLOYEE') NOT NULL DEFAULT 'EMPLOYEE'
+            AFTER job_id;",
+            "UPDATE org_members
+            JOIN organizations ON organizations.id = org_members.org_id
+                AND organizations.owner_id = org_members.user_id
+            SET org_members.role = 'OWNER';"
+        ],
+        down: vec!["ALTER TABLE org_members DROP COLUMN role;"],
+    }
+}
@@ -0,0 +1,16 @@
+use super::Migration;
+
+/// How the pickups of the open shifts of an organization are approved.
+pub fn migration() -> Migration {
+    Migration {
+        version: 4,
+        name: "pickup_modes",
+        check: None,
+        up: vec![
+            "ALTER TABLE organizations
+            ADD COLUMN pickup_mode ENUM('FIRST_COME_FIRST_SERVED', 'MANAGER_CHOICE') NOT NULL DEFAULT 'MANAGER_CHOICE'
+            AFTER is_active;"
+        ],
+        down: vec!["ALTER TABLE organizations DROP COLUMN pickup_mode;"],
+    }
+}
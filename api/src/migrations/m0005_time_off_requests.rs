@@ -0,0 +1,27 @@
+use super::Migration;
+
+/// Time off requests, missing from the initial schema.
+pub fn migration() -> Migration {
+    Migration {
+        version: 5,
+        name: "time_off_requests",
+        check: None,
+        up: vec![
+            "CREATE TABLE time_off_requests (
+                id BIGINT NOT NULL PRIMARY KEY,
+                user_id BIGINT NOT NULL,
+                org_id BIGINT NOT NULL,
+                start_time DATETIME NOT NULL,
+                end_time DATETIME NOT NULL,
+                reason TEXT,
+                status ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DECLINED') NOT NULL DEFAULT 'PENDING',
+                updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+                admin_id BIGINT,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+                FOREIGN KEY (admin_id) REFERENCES users(id) ON DELETE SET NULL
+            );"
+        ],
+        down: vec!["DROP TABLE time_off_requests;"],
+    }
+}
@@ -0,0 +1,24 @@
+use super::Migration;
+
+/// Renames the 'DENIED' status of availability requests to 'DECLINED', like the other requests.
+pub fn migration() -> Migration {
+    Migration {
+        version: 6,
+        name: "declined_status",
+        check: None,
+        up: vec![
+            "ALTER TABLE availability_requests MODIFY status
+            ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DENIED', 'DECLINED') NOT NULL DEFAULT 'PENDING';",
+            "UPDATE availability_requests SET status = 'DECLINED' WHERE status = 'DENIED';",
+            "ALTER TABLE availability_requests MODIFY status
+            ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DECLINED') NOT NULL DEFAULT 'PENDING';"
+        ],
+        down: vec![
+            "ALTER TABLE availability_requests MODIFY status
+            ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DENIED', 'DECLINED') NOT NULL DEFAULT 'PENDING';",
+            "UPDATE availability_requests SET status = 'DENIED' WHERE status = 'DECLINED';",
+            "ALTER TABLE availability_requests MODIFY status
+            ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DENIED') NOT NULL DEFAULT 'PENDING';"
+        ],
+    }
+}
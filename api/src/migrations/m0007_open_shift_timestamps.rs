@@ -0,0 +1,69 @@
+use crate::{ storage::{ Connection, Queryable }, models::result::Result };
+use super::{ Migration, MigrationError };
+
+/// Open shifts used to store their times as `TIME`, without a date, and had no headcount.
+///
+/// The date of a row is only known when its schedule lasts a single day: it is moved to that
+/// day, and to the next one when it ends before it starts. Rows of longer schedules are refused,
+/// see `check`.
+pub fn migration() -> Migration {
+    Migration {
+        version: 7,
+        name: "open_shift_timestamps",
+        check: Some(check),
+        up: vec![
+            "ALTER TABLE open_shifts
+            ADD COLUMN start_at TIMESTAMP NULL AFTER job_id,
+            ADD COLUMN end_at TIMESTAMP NULL AFTER start_at;",
+            "UPDATE open_shifts open_shift
+            JOIN work_schedules ws ON ws.id = open_shift.schedule_id
+            SET
+                open_shift.start_at = TIMESTAMP(ws.start_date, open_shift.start_time),
+                open_shift.end_at = IF(
+                    open_shift.end_time > open_shift.start_time,
+                    TIMESTAMP(ws.start_date, open_shift.end_time),
+                    TIMESTAMP(ws.start_date + INTERVAL 1 DAY, open_shift.end_time)
+                );",
+            "ALTER TABLE open_shifts
+            DROP COLUMN start_time,
+            DROP COLUMN end_time,
+            CHANGE COLUMN start_at start_time TIMESTAMP NOT NULL,
+            CHANGE COLUMN end_at end_time TIMESTAMP NOT NULL,
+            ADD COLUMN headcount INT NOT NULL DEFAULT 1,
+            ADD COLUMN filled_count INT NOT NULL DEFAULT 0;"
+        ],
+        down: vec![
+            "ALTER TABLE open_shifts
+            DROP COLUMN headcount,
+            DROP COLUMN filled_count,
+            MODIFY start_time TIME NOT NULL,
+            MODIFY end_time TIME NOT NULL;"
+        ],
+    }
+}
+
+/// Fails with the open shifts whose schedule spans several days, any of which could be theirs.
+fn check(conn: &mut Connection) -> Result<()> {
+    let rows: Vec<(i64, i64)> = conn.query(
+        "SELECT open_shift.id, ws.id
+        FROM open_shifts open_shift
+        JOIN work_schedules ws ON ws.id = open_shift.schedule_id
+        WHERE ws.end_date <> ws.start_date
+        ORDER BY open_shift.id;"
+    )?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    Err(
+        Box::new(MigrationError::UnconvertibleRows {
+            migration: migration().label(),
+            rows: rows
+                .into_iter()
+                .map(|(id, schedule_id)| {
+                    format!("open shift {} of multi-day schedule {}", id, schedule_id)
+                })
+                .collect(),
+        })
+    )
+}
@@ -0,0 +1,473 @@
+use chrono::NaiveDateTime;
+use mysql::*;
+use mysql::prelude::FromRow;
+use sha2::{ Digest, Sha256 };
+
+use crate::{
+    storage::{ Connection, Queryable },
+    models::result::Result,
+    utilities::parse_chrono::convert_to_naive_date_time,
+};
+
+mod m0001_initial_schema;
+mod m0002_refresh_tokens;
+mod m0003_org_member_roles;
+mod m0004_pickup_modes;
+mod m0005_time_off_requests;
+mod m0006_declined_status;
+mod m0007_open_shift_timestamps;
+
+/// A numbered change to the database schema.
+///
+/// Once a migration has been applied somewhere, its `up` statements must not be edited: the
+/// checksum stored in `schema_migrations` would no longer match and startup fails. Change the
+/// schema with a new migration instead, and keep the `create_*_table_query` functions of the
+/// models in line with it.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    /// Runs before `up` and fails when existing rows can't be converted by it
+    pub check: Option<fn(&mut Connection) -> Result<()>>,
+    pub up: Vec<&'static str>,
+    pub down: Vec<&'static str>,
+}
+
+impl Migration {
+    /// Hex encoded SHA-256 of the `up` statements.
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        for statement in &self.up {
+            hasher.update(statement.as_bytes());
+            hasher.update(b"\0");
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    pub fn label(&self) -> String {
+        format!("{:04}_{}", self.version, self.name)
+    }
+}
+
+/// Every migration, ordered by version.
+pub fn migrations() -> Vec<Migration> {
+    vec![
+        m0001_initial_schema::migration(),
+        m0002_refresh_tokens::migration(),
+        m0003_org_member_roles::migration(),
+        m0004_pickup_modes::migration(),
+        m0005_time_off_requests::migration(),
+        m0006_declined_status::migration(),
+        m0007_open_shift_timestamps::migration()
+    ]
+}
+
+/// A row of `schema_migrations`.
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: NaiveDateTime,
+}
+
+impl FromRow for AppliedMigration {
+    fn from_row_opt(row: Row) -> std::result::Result<Self, FromRowError> {
+        Ok(AppliedMigration {
+            version: row.get("version").ok_or(FromRowError(row.clone()))?,
+            name: row.get("name").ok_or(FromRowError(row.clone()))?,
+            checksum: row.get("checksum").ok_or(FromRowError(row.clone()))?,
+            applied_at: convert_to_naive_date_time(
+                row.get("applied_at").ok_or(FromRowError(row.clone()))?
+            ).map_err(|_| FromRowError(row.clone()))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationError {
+    /// An applied migration was edited since
+    ChecksumMismatch {
+        version: u32,
+        name: String,
+    },
+    /// The database has a migration applied that this build doesn't know about
+    UnknownVersion {
+        version: u32,
+    },
+    /// Some migrations haven't been applied yet
+    SchemaBehind {
+        pending: Vec<String>,
+    },
+    /// Existing rows can't be converted by the migration and have to be fixed by hand first
+    UnconvertibleRows {
+        migration: String,
+        rows: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::ChecksumMismatch { version, name } =>
+                write!(f, "Migration {:04}_{} was edited after being applied", version, name),
+            MigrationError::UnknownVersion { version } =>
+                write!(f, "Migration {:04} is applied but unknown to this build", version),
+            MigrationError::SchemaBehind { pending } =>
+                write!(
+                    f,
+                    "The database schema is behind, pending migrations: {}. Run `api migrate up`",
+                    pending.join(", ")
+                ),
+            MigrationError::UnconvertibleRows { migration, rows } =>
+                write!(
+                    f,
+                    "Migration {} can't convert these rows, fix them and run it again: {}",
+                    migration,
+                    rows.join(", ")
+                ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+fn table_exists(conn: &mut Connection, table_name: &str) -> Result<bool> {
+    let count: Option<i64> = conn.exec_first(
+        "SELECT COUNT(*) FROM information_schema.TABLES
+        WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = :table_name;",
+        params! { "table_name" => table_name }
+    )?;
+    Ok(count.unwrap_or(0) > 0)
+}
+
+fn create_migrations_table(conn: &mut Connection) -> Result<()> {
+    conn.query_drop(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INT UNSIGNED NOT NULL PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            checksum CHAR(64) NOT NULL,
+            applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );"
+    )?;
+    Ok(())
+}
+
+fn record_migration(conn: &mut Connection, migration: &Migration) -> Result<()> {
+    conn.exec_drop(
+        "INSERT INTO schema_migrations (version, name, checksum) VALUES (:version, :name, :checksum);",
+        params! {
+            "version" => migration.version,
+            "name" => migration.name,
+            "checksum" => migration.checksum(),
+        }
+    )?;
+    Ok(())
+}
+
+/// Retrieves the applied migrations, ordered by version.
+pub fn applied_migrations(conn: &mut Connection) -> Result<Vec<AppliedMigration>> {
+    if !table_exists(conn, "schema_migrations")? {
+        return Ok(Vec::new());
+    }
+    Ok(conn.query("SELECT * FROM schema_migrations ORDER BY version;")?)
+}
+
+/// Checks the applied migrations against the known ones and returns the pending ones.
+pub fn pending_migrations(conn: &mut Connection) -> Result<Vec<Migration>> {
+    let applied = applied_migrations(conn)?;
+    let mut migrations = migrations();
+
+    for applied_migration in &applied {
+        let Some(migration) = migrations
+            .iter()
+            .find(|migration| migration.version == applied_migration.version) else {
+            return Err(
+                Box::new(MigrationError::UnknownVersion { version: applied_migration.version })
+            );
+        };
+        if migration.checksum() != applied_migration.checksum {
+            return Err(
+                Box::new(MigrationError::ChecksumMismatch {
+                    version: migration.version,
+                    name: migration.name.to_string(),
+                })
+            );
+        }
+    }
+
+    migrations.retain(|migration| {
+        !applied.iter().any(|applied_migration| applied_migration.version == migration.version)
+    });
+    Ok(migrations)
+}
+
+/// Fails with a `MigrationError` unless every migration has been applied, unedited.
+pub fn ensure_up_to_date(conn: &mut Connection) -> Result<()> {
+    let pending = pending_migrations(conn)?;
+    if !pending.is_empty() {
+        return Err(
+            Box::new(MigrationError::SchemaBehind {
+                pending: pending.iter().map(Migration::label).collect(),
+            })
+        );
+    }
+    Ok(())
+}
+
+/// Databases created before migrations existed have their tables but no `schema_migrations`.
+///
+/// Those tables are the initial schema, which is recorded as applied. The following migrations
+/// upgrade them like any other database.
+fn adopt_legacy_schema(conn: &mut Connection) -> Result<()> {
+    if table_exists(conn, "schema_migrations")? || !table_exists(conn, "users")? {
+        return Ok(());
+    }
+
+    let initial_schema = m0001_initial_schema::migration();
+    // the tables are created with `IF NOT EXISTS`, this only adds the missing ones
+    for statement in &initial_schema.up {
+        conn.query_drop(statement)?;
+    }
+    create_migrations_table(conn)?;
+    record_migration(conn, &initial_schema)
+}
+
+/// Applies the pending migrations up to `target`, or all of them, and returns their versions.
+///
+/// MySQL commits schema changes right away, so a migration failing halfway is left partially
+/// applied and not recorded. A failing `check` stops before any of its statements run.
+pub fn migrate_up(conn: &mut Connection, target: Option<u32>) -> Result<Vec<u32>> {
+    adopt_legacy_schema(conn)?;
+    create_migrations_table(conn)?;
+
+    let mut applied = Vec::new();
+    for migration in pending_migrations(conn)? {
+        if target.is_some_and(|target| migration.version > target) {
+            break;
+        }
+        if let Some(check) = migration.check {
+            check(conn)?;
+        }
+        for statement in &migration.up {
+            conn.query_drop(statement)?;
+        }
+        record_migration(conn, &migration)?;
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
+
+/// Reverts the applied migrations above `target`, latest first, and returns their versions.
+pub fn migrate_down(conn: &mut Connection, target: u32) -> Result<Vec<u32>> {
+    let migrations = migrations();
+    let mut reverted = Vec::new();
+
+    for applied_migration in applied_migrations(conn)?.into_iter().rev() {
+        if applied_migration.version <= target {
+            break;
+        }
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.version == applied_migration.version)
+            .ok_or(MigrationError::UnknownVersion { version: applied_migration.version })?;
+        for statement in &migration.down {
+            conn.query_drop(statement)?;
+        }
+        conn.exec_drop(
+            "DELETE FROM schema_migrations WHERE version = :version;",
+            params! { "version" => migration.version }
+        )?;
+        reverted.push(migration.version);
+    }
+
+    Ok(reverted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ queries::create_tables, tests::{ initialize_test_db, cleanup_test_db } };
+
+    /// The columns of every table but `schema_migrations`, as `table.column type null default`.
+    fn columns(conn: &mut Connection) -> Result<Vec<String>> {
+        let columns: Vec<(String, String, String, String, Option<String>)> = conn.query(
+            "SELECT TABLE_NAME, COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE, COLUMN_DEFAULT
+            FROM information_schema.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME <> 'schema_migrations'
+            ORDER BY TABLE_NAME, ORDINAL_POSITION;"
+        )?;
+        Ok(
+            columns
+                .into_iter()
+                .map(|(table, column, column_type, nullable, default)| {
+                    format!("{}.{} {} {} {:?}", table, column, column_type, nullable, default)
+                })
+                .collect()
+        )
+    }
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let migrations = migrations();
+        assert!(migrations.windows(2).all(|pair| pair[0].version < pair[1].version));
+        assert!(migrations.iter().all(|migration| !migration.up.is_empty()));
+    }
+
+    #[test]
+    fn test_migrations() -> Result<()> {
+        // The test database is migrated up on creation
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+        ensure_up_to_date(&mut conn)?;
+        assert_eq!(applied_migrations(&mut conn)?.len(), migrations().len());
+        assert!(migrate_up(&mut conn, None)?.is_empty());
+
+        // Reverting everything drops the tables
+        let reverted = migrate_down(&mut conn, 0)?;
+        assert_eq!(reverted.len(), migrations().len());
+        assert!(!table_exists(&mut conn, "users")?);
+        let error = ensure_up_to_date(&mut conn).unwrap_err();
+        assert!(
+            matches!(
+                error.downcast_ref::<MigrationError>(),
+                Some(MigrationError::SchemaBehind { .. })
+            )
+        );
+
+        assert_eq!(migrate_up(&mut conn, Some(1))?, vec![1]);
+        assert!(table_exists(&mut conn, "users")?);
+
+        // Editing an applied migration is detected
+        conn.query_drop("UPDATE schema_migrations SET checksum = 'edited' WHERE version = 1;")?;
+        let error = ensure_up_to_date(&mut conn).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<MigrationError>(),
+            Some(
+                &(MigrationError::ChecksumMismatch {
+                    version: 1,
+                    name: "initial_schema".to_string(),
+                })
+            )
+        );
+
+        cleanup_test_db(conn)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrations_match_the_models() -> Result<()> {
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+        let migrated = columns(&mut conn)?;
+
+        migrate_down(&mut conn, 0)?;
+        conn.query_drop("DROP TABLE schema_migrations;")?;
+        create_tables(&mut conn)?;
+        assert_eq!(columns(&mut conn)?, migrated);
+
+        cleanup_test_db(conn)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_schema_is_upgraded() -> Result<()> {
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+        migrate_down(&mut conn, 0)?;
+        conn.query_drop("DROP TABLE schema_migrations;")?;
+
+        // the tables as created before migrations existed, with some rows
+        for statement in m0001_initial_schema::migration().up {
+            conn.query_drop(statement)?;
+        }
+        conn.query_drop(
+            "INSERT INTO users (id, email, encrypted_password, first_name, last_name, date_of_birth)
+            VALUES (1, 'owner@example.com', '', 'Owner', 'User', '1990-01-01'),
+                (2, 'member@example.com', '', 'Member', 'User', '1990-01-01');
+            INSERT INTO organizations (id, name, owner_id) VALUES (10, 'Legacy', 1);
+            INSERT INTO org_jobs (id, org_id, name) VALUES (20, 10, 'Cook');
+            INSERT INTO org_members (user_id, org_id, job_id) VALUES (1, 10, 20), (2, 10, 20);
+            INSERT INTO availability_requests (id, user_id, org_id, start_date, status)
+            VALUES (30, 2, 10, '2024-01-01', 'DENIED');
+            INSERT INTO work_schedules (id, org_id, start_date, end_date)
+            VALUES (40, 10, '2024-01-08', '2024-01-08');
+            INSERT INTO open_shifts (id, schedule_id, job_id, start_time, end_time)
+            VALUES (50, 40, 20, '09:00:00', '17:00:00'), (51, 40, 20, '22:00:00', '06:00:00');"
+        )?;
+
+        assert_eq!(migrate_up(&mut conn, None)?, (2..=migrations().len() as u32).collect::<Vec<_>>());
+        ensure_up_to_date(&mut conn)?;
+
+        let roles: Vec<(i64, String)> = conn.query(
+            "SELECT user_id, role FROM org_members ORDER BY user_id;"
+        )?;
+        assert_eq!(roles, vec![(1, "OWNER".to_string()), (2, "EMPLOYEE".to_string())]);
+        let pickup_mode: Option<String> = conn.query_first(
+            "SELECT pickup_mode FROM organizations WHERE id = 10;"
+        )?;
+        assert_eq!(pickup_mode.as_deref(), Some("MANAGER_CHOICE"));
+        let status: Option<String> = conn.query_first(
+            "SELECT status FROM availability_requests WHERE id = 30;"
+        )?;
+        assert_eq!(status.as_deref(), Some("DECLINED"));
+        assert!(table_exists(&mut conn, "time_off_requests")?);
+        assert!(table_exists(&mut conn, "refresh_tokens")?);
+        let open_shifts: Vec<(i64, String, String)> = conn.query(
+            "SELECT id, CAST(start_time AS CHAR), CAST(end_time AS CHAR) FROM open_shifts ORDER BY id;"
+        )?;
+        assert_eq!(
+            open_shifts,
+            vec![
+                (50, "2024-01-08 09:00:00".to_string(), "2024-01-08 17:00:00".to_string()),
+                (51, "2024-01-08 22:00:00".to_string(), "2024-01-09 06:00:00".to_string())
+            ]
+        );
+
+        cleanup_test_db(conn)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_open_shifts_without_a_date_are_refused() -> Result<()> {
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+        migrate_down(&mut conn, 6)?;
+        conn.query_drop("DELETE FROM open_shifts;")?;
+        conn.query_drop(
+            "INSERT INTO users (id, email, encrypted_password, first_name, last_name, date_of_birth)
+            VALUES (1, 'owner@example.com', '', 'Owner', 'User', '1990-01-01');
+            INSERT INTO organizations (id, name, owner_id) VALUES (10, 'Legacy', 1);
+            INSERT INTO org_jobs (id, org_id, name) VALUES (20, 10, 'Cook');
+            INSERT INTO work_schedules (id, org_id, start_date, end_date)
+            VALUES (40, 10, '2024-01-08', '2024-01-14');
+            INSERT INTO open_shifts (id, schedule_id, job_id, start_time, end_time)
+            VALUES (50, 40, 20, '09:00:00', '17:00:00');"
+        )?;
+
+        // The shift could be on any day of the week, it isn't guessed
+        let error = migrate_up(&mut conn, None).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<MigrationError>(),
+            Some(
+                &(MigrationError::UnconvertibleRows {
+                    migration: "0007_open_shift_timestamps".to_string(),
+                    rows: vec!["open shift 50 of multi-day schedule 40".to_string()],
+                })
+            )
+        );
+        assert_eq!(pending_migrations(&mut conn)?.len(), migrations().len() - 6);
+
+        // Once fixed by hand, the migration goes through
+        conn.query_drop("UPDATE work_schedules SET end_date = start_date WHERE id = 40;")?;
+        assert_eq!(migrate_up(&mut conn, None)?[0], 7);
+        ensure_up_to_date(&mut conn)?;
+
+        cleanup_test_db(conn)?;
+
+        Ok(())
+    }
+}
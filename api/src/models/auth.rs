@@ -0,0 +1,66 @@
+use chrono::NaiveDateTime;
+use mysql::*;
+use mysql::prelude::*;
+use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
+
+use crate::utilities::parse_chrono::convert_to_naive_date_time;
+
+/// SQL statement to create the table `refresh_tokens`
+pub fn create_refresh_tokens_table_query() -> String {
+    "
+    CREATE TABLE IF NOT EXISTS refresh_tokens (
+        id BIGINT NOT NULL PRIMARY KEY,
+        user_id BIGINT NOT NULL,
+        expires_at TIMESTAMP NOT NULL,
+        revoked BOOLEAN NOT NULL DEFAULT FALSE,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+    );
+    ".to_string()
+}
+
+/// Server-side record of an issued refresh token.
+///
+/// The refresh token handed to the client is a signed token whose `jti` is the `id` of this row,
+/// so a token can be revoked (logout, rotation, password change) by flagging the row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub expires_at: NaiveDateTime,
+    pub revoked: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl FromRow for RefreshToken {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        Ok(RefreshToken {
+            id: row.get("id").ok_or(FromRowError(row.clone()))?,
+            user_id: row.get("user_id").ok_or(FromRowError(row.clone()))?,
+            expires_at: convert_to_naive_date_time(
+                row.get("expires_at").ok_or(FromRowError(row.clone()))?
+            ).map_err(|_| FromRowError(row.clone()))?,
+            revoked: row.get("revoked").ok_or(FromRowError(row.clone()))?,
+            created_at: convert_to_naive_date_time(
+                row.get("created_at").ok_or(FromRowError(row.clone()))?
+            ).map_err(|_| FromRowError(row.clone()))?,
+        })
+    }
+}
+
+/// Tokens returned to the client after a successful login or refresh.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuthTokens {
+    pub user_id: i64,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// Lifetime of the access token, in seconds.
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RequestRefreshToken {
+    pub refresh_token: String,
+}
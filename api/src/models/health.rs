@@ -0,0 +1,67 @@
+use serde::{ Deserialize, Serialize };
+
+/// Whether a component, or the whole instance, can serve requests.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum HealthStatus {
+    UP,
+    DOWN,
+}
+
+impl HealthStatus {
+    pub fn from_ok(ok: bool) -> Self {
+        if ok { HealthStatus::UP } else { HealthStatus::DOWN }
+    }
+}
+
+/// The body of `GET /health/live`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseLiveness {
+    pub status: HealthStatus,
+}
+
+/// The body of `GET /health/ready`, `UP` only if every check is and the instance isn't draining.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseReadiness {
+    pub status: HealthStatus,
+    /// The instance is shutting down, see `shutdown::Shutdown`
+    pub draining: bool,
+    pub database: DatabaseHealth,
+    pub schema: SchemaHealth,
+    pub snowflake: SnowflakeHealth,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DatabaseHealth {
+    pub status: HealthStatus,
+    /// `mysql` or `memory`
+    pub backend: String,
+    /// Round trip of a `SELECT 1`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SchemaHealth {
+    pub status: HealthStatus,
+    /// Version of the last migration known to the binary
+    pub expected_version: u32,
+    /// Version of the last migration applied to the database, if known
+    pub applied_version: Option<u32>,
+    /// Migrations the binary expects that the database lacks
+    pub pending: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnowflakeHealth {
+    pub status: HealthStatus,
+    pub machine_id: i64,
+    /// Milliseconds since the Unix epoch of the last generated ID, 0 if none was
+    pub last_timestamp: i64,
+    /// How far the clock went back before `last_timestamp`, IDs could collide meanwhile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_behind_ms: Option<i64>,
+}
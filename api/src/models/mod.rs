@@ -4,6 +4,9 @@ pub mod organization;
 pub mod org_member;
 pub mod org_job;
 pub mod schedule;
+pub mod auth;
+pub mod pagination;
+pub mod health;
 
 use serde::{ Serialize, Deserialize };
 
@@ -17,6 +20,16 @@ pub struct ResponseDataList<T> where T: Serialize {
     pub data: Vec<T>,
 }
 
+/// A page of a list, see `pagination::ListQuery`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseDataPage<T> where T: Serialize {
+    pub data: Vec<T>,
+    /// Pass as `?cursor=` to get the next page, `None` on the last page
+    pub next_cursor: Option<i64>,
+    /// Number of rows matching the filters, across all pages
+    pub total: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ResponseDataMessage {
     pub message: String,
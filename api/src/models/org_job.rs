@@ -2,8 +2,10 @@ use chrono::NaiveDateTime;
 use mysql::*;
 use mysql::prelude::*;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 pub fn create_org_job_table() -> String {
     "
@@ -20,7 +22,7 @@ pub fn create_org_job_table() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct OrgJob {
     pub id: i64,
     pub org_id: i64,
@@ -47,7 +49,7 @@ impl FromRow for OrgJob {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct RequestCreateOrgJob {
     pub org_id: i64,
     pub name: String,
@@ -56,10 +58,34 @@ pub struct RequestCreateOrgJob {
     pub color: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+impl Validate for RequestCreateOrgJob {
+    fn validate_fields(&self, validator: &mut Validator) {
+        validator.text("name", &self.name, 100);
+        validator.non_negative("base_pay_rate", self.base_pay_rate);
+        if let Some(color) = &self.color {
+            validator.hex_color("color", color);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateOrgJob {
     pub name: Option<String>,
     pub description: Option<String>,
     pub base_pay_rate: Option<f32>,
     pub color: Option<String>,
 }
+
+impl Validate for RequestUpdateOrgJob {
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let Some(name) = &self.name {
+            validator.text("name", name, 100);
+        }
+        if let Some(base_pay_rate) = self.base_pay_rate {
+            validator.non_negative("base_pay_rate", base_pay_rate);
+        }
+        if let Some(color) = &self.color {
+            validator.hex_color("color", color);
+        }
+    }
+}
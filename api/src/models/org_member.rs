@@ -1,8 +1,12 @@
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
 use mysql::{ prelude::FromRow, FromRowError };
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 pub fn create_org_members_table_query() -> String {
     "
@@ -10,6 +14,7 @@ pub fn create_org_members_table_query() -> String {
         user_id BIGINT NOT NULL,
         org_id BIGINT NOT NULL,
         job_id BIGINT NOT NULL,
+        role ENUM('OWNER', 'ADMIN', 'MANAGER', 'EMPLOYEE') NOT NULL DEFAULT 'EMPLOYEE',
         joined_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
         FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
         FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
@@ -19,38 +24,165 @@ pub fn create_org_members_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct OrgMember {
     pub user_id: i64,
     pub org_id: i64,
     pub job_id: i64,
+    pub role: OrgRole,
     pub joined_at: NaiveDateTime,
 }
 
 // For creating a new user-organization relationship
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateOrgMember {
     pub user_id: i64,
     pub org_id: i64,
     pub job_id: i64,
+    /// Defaults to `EMPLOYEE`
+    pub role: Option<OrgRole>,
+}
+
+impl Validate for RequestCreateOrgMember {
+    /// Only holds references, checked by the database.
+    fn validate_fields(&self, _validator: &mut Validator) {}
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateOrgMember {
-    pub user_id: i64,
-    pub org_id: i64,
     pub job_id: Option<i64>,
+    pub role: Option<OrgRole>,
+}
+
+impl Validate for RequestUpdateOrgMember {
+    /// Only holds references, checked by the database.
+    fn validate_fields(&self, _validator: &mut Validator) {}
 }
 
 impl FromRow for OrgMember {
     fn from_row_opt(row: mysql::Row) -> Result<Self, mysql::FromRowError> where Self: Sized {
+        let role: String = row.get("role").ok_or(FromRowError(row.clone()))?;
         Ok(OrgMember {
             user_id: row.get("user_id").ok_or(FromRowError(row.clone()))?,
             org_id: row.get("org_id").ok_or(FromRowError(row.clone()))?,
             job_id: row.get("job_id").ok_or(FromRowError(row.clone()))?,
+            role: OrgRole::from_str(&role).map_err(|_| FromRowError(row.clone()))?,
             joined_at: convert_to_naive_date_time(
                 row.get("joined_at").ok_or(FromRowError(row.clone()))?
             ).map_err(|_| FromRowError(row.clone()))?,
         })
     }
 }
+
+/// Role of a member inside an organization, from most to least privileged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, ToSchema)]
+pub enum OrgRole {
+    OWNER,
+    ADMIN,
+    MANAGER,
+    #[default]
+    EMPLOYEE,
+}
+
+/// Actions inside an organization that are restricted to some roles.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub enum OrgPermission {
+    /// Read the schedules, shifts and requests of the organization
+    VIEW_ORGANIZATION,
+    /// Update the organization itself
+    MANAGE_ORGANIZATION,
+    /// Add, remove and change the role or job of members, and manage jobs
+    MANAGE_MEMBERS,
+    /// Grant or revoke the OWNER role, and remove owners
+    MANAGE_OWNERS,
+    /// Create, edit and publish work schedules and their shifts
+    PUBLISH_SCHEDULES,
+    /// Approve or decline shift trades, covers, pickups, time off and availability requests
+    APPROVE_REQUESTS,
+}
+
+impl OrgRole {
+    pub fn has_permission(&self, permission: &OrgPermission) -> bool {
+        match permission {
+            OrgPermission::VIEW_ORGANIZATION => true,
+            OrgPermission::MANAGE_ORGANIZATION | OrgPermission::MANAGE_MEMBERS =>
+                matches!(self, OrgRole::OWNER | OrgRole::ADMIN),
+            OrgPermission::MANAGE_OWNERS => matches!(self, OrgRole::OWNER),
+            OrgPermission::PUBLISH_SCHEDULES | OrgPermission::APPROVE_REQUESTS =>
+                matches!(self, OrgRole::OWNER | OrgRole::ADMIN | OrgRole::MANAGER),
+        }
+    }
+}
+
+impl FromStr for OrgRole {
+    type Err = OrgRoleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "OWNER" => Ok(OrgRole::OWNER),
+            "ADMIN" => Ok(OrgRole::ADMIN),
+            "MANAGER" => Ok(OrgRole::MANAGER),
+            "EMPLOYEE" => Ok(OrgRole::EMPLOYEE),
+            _ => Err(OrgRoleParseError),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrgRoleParseError;
+
+impl std::fmt::Display for OrgRoleParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid value for OrgRole")
+    }
+}
+
+impl std::error::Error for OrgRoleParseError {}
+
+impl std::fmt::Display for OrgRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrgRole::OWNER => write!(f, "OWNER"),
+            OrgRole::ADMIN => write!(f, "ADMIN"),
+            OrgRole::MANAGER => write!(f, "MANAGER"),
+            OrgRole::EMPLOYEE => write!(f, "EMPLOYEE"),
+        }
+    }
+}
+
+/// Returned when a user lacks the permission required for an action in an organization.
+#[derive(Debug, Clone)]
+pub struct PermissionDeniedError {
+    pub user_id: i64,
+    pub org_id: i64,
+    pub permission: OrgPermission,
+}
+
+impl std::fmt::Display for PermissionDeniedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "User {} does not have the {:?} permission in organization {}",
+            self.user_id,
+            self.permission,
+            self.org_id
+        )
+    }
+}
+
+impl std::error::Error for PermissionDeniedError {}
+
+/// Returned when a change would leave an organization without an owner.
+#[derive(Debug, Clone)]
+pub struct LastOwnerError {
+    pub org_id: i64,
+}
+
+impl std::fmt::Display for LastOwnerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Organization {} must keep at least one owner", self.org_id)
+    }
+}
+
+impl std::error::Error for LastOwnerError {}
@@ -1,9 +1,13 @@
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use mysql::*;
 use mysql::prelude::*;
 
 use crate::utilities::parse_chrono::{ convert_to_naive_date, convert_to_naive_date_time };
+use crate::prototypes::validate::{ Validate, Validator };
 use super::user::PartialUser;
 
 pub fn create_organizations_table_query() -> String {
@@ -16,13 +20,14 @@ pub fn create_organizations_table_query() -> String {
         timezone VARCHAR(50) NOT NULL DEFAULT 'America/Los_Angeles',
         icon VARCHAR(255) NULL,
         is_active BOOLEAN NOT NULL DEFAULT TRUE,
+        pickup_mode ENUM('FIRST_COME_FIRST_SERVED', 'MANAGER_CHOICE') NOT NULL DEFAULT 'MANAGER_CHOICE',
         updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
         FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
     );
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Organization {
     pub id: i64,
     pub name: String,
@@ -31,6 +36,7 @@ pub struct Organization {
     pub timezone: String,
     pub icon: Option<String>,
     pub is_active: bool,
+    pub pickup_mode: PickupMode,
     pub updated_at: NaiveDateTime,
     pub owner: Option<PartialUser>,
 }
@@ -53,6 +59,7 @@ impl FromRow for Organization {
         } else {
             None
         };
+        let pickup_mode: String = row.get("pickup_mode").ok_or(FromRowError(row.clone()))?;
 
         Ok(Organization {
             id: row.get("id").ok_or(FromRowError(row.clone()))?,
@@ -62,6 +69,7 @@ impl FromRow for Organization {
             timezone: row.get("timezone").ok_or(FromRowError(row.clone()))?,
             icon: row.get("icon").ok_or(FromRowError(row.clone()))?,
             is_active: row.get("is_active").ok_or(FromRowError(row.clone()))?,
+            pickup_mode: PickupMode::from_str(&pickup_mode).map_err(|_| FromRowError(row.clone()))?,
             updated_at: convert_to_naive_date_time(
                 row.get("updated_at").ok_or(FromRowError(row.clone()))?
             ).map_err(|_| FromRowError(row.clone()))?,
@@ -70,7 +78,7 @@ impl FromRow for Organization {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateOrganization {
     pub name: String,
     pub description: Option<String>,
@@ -79,11 +87,81 @@ pub struct RequestCreateOrganization {
     pub icon: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+impl Validate for RequestCreateOrganization {
+    fn validate_fields(&self, validator: &mut Validator) {
+        validator.text("name", &self.name, 100);
+        if let Some(timezone) = &self.timezone {
+            validator.timezone("timezone", timezone);
+        }
+        if let Some(icon) = &self.icon {
+            validator.max_length("icon", icon, 255);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateOrganization {
     pub name: Option<String>,
     pub description: Option<String>,
     pub owner_id: Option<i64>,
     pub timezone: Option<String>,
     pub icon: Option<String>,
+    pub pickup_mode: Option<PickupMode>,
+}
+
+impl Validate for RequestUpdateOrganization {
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let Some(name) = &self.name {
+            validator.text("name", name, 100);
+        }
+        if let Some(timezone) = &self.timezone {
+            validator.timezone("timezone", timezone);
+        }
+        if let Some(icon) = &self.icon {
+            validator.max_length("icon", icon, 255);
+        }
+    }
+}
+
+/// How the open shifts of an organization are handed out.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, ToSchema)]
+pub enum PickupMode {
+    /// The first member asking for an open shift gets it
+    FIRST_COME_FIRST_SERVED,
+    /// Members ask for open shifts and a manager picks who gets them
+    #[default]
+    MANAGER_CHOICE,
+}
+
+impl FromStr for PickupMode {
+    type Err = PickupModeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "FIRST_COME_FIRST_SERVED" => Ok(PickupMode::FIRST_COME_FIRST_SERVED),
+            "MANAGER_CHOICE" => Ok(PickupMode::MANAGER_CHOICE),
+            _ => Err(PickupModeParseError),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PickupModeParseError;
+
+impl std::fmt::Display for PickupModeParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid value for PickupMode")
+    }
+}
+
+impl std::error::Error for PickupModeParseError {}
+
+impl std::fmt::Display for PickupMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PickupMode::FIRST_COME_FIRST_SERVED => write!(f, "FIRST_COME_FIRST_SERVED"),
+            PickupMode::MANAGER_CHOICE => write!(f, "MANAGER_CHOICE"),
+        }
+    }
 }
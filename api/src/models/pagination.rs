@@ -0,0 +1,281 @@
+use std::{ collections::HashMap, str::FromStr };
+
+use mysql::Value;
+use serde::{ Serialize, Deserialize };
+
+/// Page size used when the request doesn't set `limit`.
+pub const DEFAULT_PAGE_LIMIT: u32 = 50;
+/// Largest page a request can ask for.
+pub const MAX_PAGE_LIMIT: u32 = 200;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SortOrder {
+    ASC,
+    DESC,
+}
+
+impl FromStr for SortOrder {
+    type Err = ListQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "ASC" => Ok(SortOrder::ASC),
+            "DESC" => Ok(SortOrder::DESC),
+            _ => Err(ListQueryError(format!("Invalid sort order `{}`, use asc or desc", s))),
+        }
+    }
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortOrder::ASC => write!(f, "ASC"),
+            SortOrder::DESC => write!(f, "DESC"),
+        }
+    }
+}
+
+/// A comparison a list query filters a column with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterOperator {
+    /// `?column=value`
+    EQUAL,
+    /// `?column_from=value`, inclusive
+    FROM,
+    /// `?column_to=value`, inclusive
+    TO,
+}
+
+impl FilterOperator {
+    pub fn sql(&self) -> &'static str {
+        match self {
+            FilterOperator::EQUAL => "=",
+            FilterOperator::FROM => ">=",
+            FilterOperator::TO => "<=",
+        }
+    }
+}
+
+/// The query parameters of list endpoints.
+///
+/// * `limit` - Size of the page, `DEFAULT_PAGE_LIMIT` by default and at most `MAX_PAGE_LIMIT`.
+/// * `cursor` - The `next_cursor` of the previous page.
+/// * `sort` and `order` - Column to sort on, and `asc` or `desc`.
+///
+/// Every other parameter filters on a column: `?status=PENDING` keeps the rows with that value,
+/// `?start_time_from=` and `?start_time_to=` the ones in a range. Which columns can be sorted and
+/// filtered on depends on the model, unknown ones are rejected when the query runs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListQuery {
+    pub limit: Option<u32>,
+    pub cursor: Option<i64>,
+    pub sort: Option<String>,
+    pub order: Option<SortOrder>,
+    pub filters: Vec<ListFilter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListFilter {
+    /// The query parameter, e.g. `start_time_from`
+    pub param: String,
+    pub value: String,
+}
+
+impl ListQuery {
+    /// Reads a list query from the query parameters of a request.
+    pub fn from_params(params: HashMap<String, String>) -> Result<Self, ListQueryError> {
+        let mut query = ListQuery::default();
+        for (param, value) in params {
+            match param.as_str() {
+                "limit" => {
+                    let limit = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|limit| *limit > 0)
+                        .ok_or_else(|| ListQueryError("`limit` must be a positive number".into()))?;
+                    query.limit = Some(limit);
+                }
+                "cursor" => {
+                    query.cursor = Some(
+                        value.parse().map_err(|_| ListQueryError("Invalid `cursor`".into()))?
+                    );
+                }
+                "sort" => {
+                    query.sort = Some(value);
+                }
+                "order" => {
+                    query.order = Some(value.parse()?);
+                }
+                _ => query.filters.push(ListFilter { param, value }),
+            }
+        }
+        // keep the generated SQL, and so its cached statement, the same for the same filters
+        query.filters.sort_by(|a, b| a.param.cmp(&b.param));
+        Ok(query)
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT)
+    }
+
+    /// Checks the sort and the filters of the query against the columns of a model.
+    pub fn resolve(
+        &self,
+        sort_columns: &[&str],
+        filter_columns: &[&str],
+        default_sort: (&str, SortOrder)
+    ) -> Result<ResolvedListQuery, ListQueryError> {
+        let (default_sort, default_order) = default_sort;
+        let sort = self.sort.as_deref().unwrap_or(default_sort);
+        if !sort_columns.contains(&sort) {
+            return Err(ListQueryError(format!("Cannot sort on `{}`", sort)));
+        }
+
+        let filters = self.filters
+            .iter()
+            .map(|filter| {
+                let (column, operator) = filter.resolve(filter_columns)?;
+                Ok((column, operator, filter.sql_value()))
+            })
+            .collect::<Result<Vec<_>, ListQueryError>>()?;
+
+        Ok(ResolvedListQuery {
+            sort: sort.to_string(),
+            order: self.order.unwrap_or(default_order),
+            filters,
+        })
+    }
+}
+
+/// The sort and filters of a `ListQuery`, checked against the columns of a model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedListQuery {
+    pub sort: String,
+    pub order: SortOrder,
+    /// Column, comparison and value of each filter
+    pub filters: Vec<(String, FilterOperator, Value)>,
+}
+
+impl ListFilter {
+    /// Resolves the column and comparison of the filter among `columns`.
+    pub fn resolve(&self, columns: &[&str]) -> Result<(String, FilterOperator), ListQueryError> {
+        if columns.contains(&self.param.as_str()) {
+            return Ok((self.param.clone(), FilterOperator::EQUAL));
+        }
+        for (suffix, operator) in [
+            ("_from", FilterOperator::FROM),
+            ("_to", FilterOperator::TO),
+        ] {
+            if let Some(column) = self.param.strip_suffix(suffix) {
+                if columns.contains(&column) {
+                    return Ok((column.to_string(), operator));
+                }
+            }
+        }
+        Err(ListQueryError(format!("Unknown filter `{}`", self.param)))
+    }
+
+    /// The value to compare the column with, booleans are stored as numbers.
+    pub fn sql_value(&self) -> Value {
+        match self.value.as_str() {
+            "true" => Value::from(1),
+            "false" => Value::from(0),
+            value => Value::from(value),
+        }
+    }
+}
+
+/// Restricts a list to the rows of a parent, e.g. the shift covers of an organization.
+///
+/// `columns` are columns of the table the rows must be equal to. `condition` is a SQL condition
+/// on the columns of the table, qualified with its name, and of the tables in `joins`. Its named
+/// parameters are given in `params`. Only MySQL evaluates conditions, prefer `columns` when the
+/// table has the column to compare with.
+#[derive(Debug, Clone, Default)]
+pub struct ListScope {
+    pub columns: Vec<(String, Value)>,
+    pub joins: String,
+    pub condition: Option<String>,
+    pub params: Vec<(String, Value)>,
+}
+
+impl ListScope {
+    /// Every row of the table.
+    pub fn all() -> Self {
+        ListScope::default()
+    }
+
+    /// The rows whose `column` equals `value`.
+    pub fn column(column: &str, value: impl Into<Value>) -> Self {
+        ListScope {
+            columns: vec![(column.to_string(), value.into())],
+            ..Default::default()
+        }
+    }
+
+    /// The rows matching `condition`.
+    pub fn filter(condition: impl Into<String>, params: Vec<(&str, Value)>) -> Self {
+        ListScope {
+            condition: Some(condition.into()),
+            params: params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Joins other tables to evaluate the condition.
+    pub fn join(mut self, joins: impl Into<String>) -> Self {
+        self.joins = joins.into();
+        self
+    }
+}
+
+/// A list query that can't run, because of an invalid parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListQueryError(pub String);
+
+impl std::fmt::Display for ListQueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ListQueryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_query_params() {
+        let params = HashMap::from([
+            ("limit".to_string(), "500".to_string()),
+            ("cursor".to_string(), "42".to_string()),
+            ("order".to_string(), "desc".to_string()),
+            ("status".to_string(), "PENDING".to_string()),
+            ("start_time_from".to_string(), "2024-01-01T00:00:00".to_string()),
+        ]);
+        let query = ListQuery::from_params(params).unwrap();
+        assert_eq!(query.limit(), MAX_PAGE_LIMIT);
+        assert_eq!(query.cursor, Some(42));
+        assert_eq!(query.order, Some(SortOrder::DESC));
+
+        let columns = ["status", "start_time"];
+        let resolved = query.resolve(&["id"], &columns, ("id", SortOrder::ASC)).unwrap();
+        assert_eq!(resolved.sort, "id");
+        assert_eq!(resolved.order, SortOrder::DESC);
+        assert_eq!(resolved.filters, vec![
+            ("start_time".to_string(), FilterOperator::FROM, Value::from("2024-01-01T00:00:00")),
+            ("status".to_string(), FilterOperator::EQUAL, Value::from("PENDING")),
+        ]);
+        let unsortable = ListQuery { sort: Some("status".to_string()), ..Default::default() };
+        assert!(unsortable.resolve(&["id"], &columns, ("id", SortOrder::ASC)).is_err());
+
+        let unknown = ListFilter { param: "password".to_string(), value: "x".to_string() };
+        assert!(unknown.resolve(&columns).is_err());
+        let invalid_limit = HashMap::from([("limit".to_string(), "0".to_string())]);
+        assert!(ListQuery::from_params(invalid_limit).is_err());
+    }
+}
@@ -1,3 +1,15 @@
 use std::error::Error;
 
 pub type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync + 'static>>;
+
+/// The entity a query looks for doesn't exist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotFoundError(pub String);
+
+impl std::fmt::Display for NotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for NotFoundError {}
@@ -4,12 +4,14 @@ use chrono::{ NaiveDate, NaiveDateTime };
 use mysql::*;
 use mysql::prelude::*;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 
 use crate::utilities::parse_chrono::{ convert_to_naive_date, convert_to_naive_date_time };
+use crate::prototypes::validate::{ Validate, Validator };
 
 use super::{
     request_status::ScheduleRequestStatus,
-    availability_detail::RequestCreateAvailabilityDetail,
+    availability_detail::{ AvailabilityDetail, RequestCreateAvailabilityDetail },
 };
 
 pub fn create_availability_requests_table_query() -> String {
@@ -19,7 +21,7 @@ pub fn create_availability_requests_table_query() -> String {
         user_id BIGINT NOT NULL,
         org_id BIGINT NOT NULL,
         start_date DATE NOT NULL,
-        status ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DENIED') NOT NULL DEFAULT 'PENDING',
+        status ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DECLINED') NOT NULL DEFAULT 'PENDING',
         updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
         FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
         FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE
@@ -27,7 +29,7 @@ pub fn create_availability_requests_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AvailabilityRequest {
     pub id: i64,
     pub user_id: i64,
@@ -57,7 +59,7 @@ impl FromRow for AvailabilityRequest {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateAvailability {
     pub user_id: i64,
     pub org_id: i64,
@@ -65,7 +67,58 @@ pub struct RequestCreateAvailability {
     pub details: Vec<RequestCreateAvailabilityDetail>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+impl Validate for RequestCreateAvailability {
+    fn validate_fields(&self, validator: &mut Validator) {
+        for (index, detail) in self.details.iter().enumerate() {
+            validator.nested(&format!("details[{}]", index), detail);
+            let repeated = self.details[..index]
+                .iter()
+                .any(|other| other.day_of_week == detail.day_of_week);
+            validator.check(
+                !repeated,
+                &format!("details[{}].day_of_week", index),
+                "is already given"
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateAvailability {
     pub status: Option<ScheduleRequestStatus>,
 }
+
+impl Validate for RequestUpdateAvailability {
+    /// Status transitions are checked by `AvailabilityRequestQueries::update_entity`.
+    fn validate_fields(&self, _validator: &mut Validator) {}
+}
+
+/// An availability request along with its weekly details.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AvailabilityWithDetails {
+    pub request: AvailabilityRequest,
+    pub details: Vec<AvailabilityDetail>,
+}
+
+/// The availability in effect for every member of an organization over a week.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AvailabilityWeek {
+    pub start_date: NaiveDate,
+    pub members: Vec<MemberAvailability>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemberAvailability {
+    pub user_id: i64,
+    /// The 7 days of the week, starting on `AvailabilityWeek::start_date`
+    pub days: Vec<AvailabilityDay>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AvailabilityDay {
+    pub date: NaiveDate,
+    /// The approved availability request in effect that day, if any
+    pub request_id: Option<i64>,
+    /// The details of that request for the day of the week, if it has any
+    pub detail: Option<AvailabilityDetail>,
+}
@@ -1,11 +1,13 @@
 use std::str::FromStr;
 
-use chrono::NaiveTime;
+use chrono::{ NaiveTime, Weekday };
 use mysql::*;
 use mysql::prelude::*;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 
 use crate::utilities::parse_chrono::convert_to_naive_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 pub fn create_availability_details_table_query() -> String {
     "
@@ -21,7 +23,7 @@ pub fn create_availability_details_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AvailabilityDetail {
     pub request_id: i64,
     pub day_of_week: DayOfWeek,
@@ -59,7 +61,7 @@ impl FromRow for AvailabilityDetail {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestCreateAvailabilityDetail {
     pub day_of_week: DayOfWeek,
     pub is_available: bool,
@@ -68,7 +70,19 @@ pub struct RequestCreateAvailabilityDetail {
     pub preferred_end_time: Option<NaiveTime>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+impl Validate for RequestCreateAvailabilityDetail {
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let (Some(start), Some(end)) = (self.preferred_start_time, self.preferred_end_time) {
+            validator.check(
+                end > start,
+                "preferred_end_time",
+                "must be after preferred_start_time"
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, ToSchema)]
 pub enum DayOfWeek {
     #[default]
     MONDAY,
@@ -80,6 +94,20 @@ pub enum DayOfWeek {
     SUNDAY,
 }
 
+impl From<Weekday> for DayOfWeek {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Mon => DayOfWeek::MONDAY,
+            Weekday::Tue => DayOfWeek::TUESDAY,
+            Weekday::Wed => DayOfWeek::WEDNESDAY,
+            Weekday::Thu => DayOfWeek::THURSDAY,
+            Weekday::Fri => DayOfWeek::FRIDAY,
+            Weekday::Sat => DayOfWeek::SATURDAY,
+            Weekday::Sun => DayOfWeek::SUNDAY,
+        }
+    }
+}
+
 impl FromStr for DayOfWeek {
     type Err = DayOfWeekParseError;
 
@@ -1,6 +1,8 @@
 use std::str::FromStr;
 
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
+use crate::prototypes::validate::{ Validate, Validator };
 
 pub mod availability;
 pub mod availability_detail;
@@ -14,7 +16,7 @@ pub mod work_schedule;
 pub mod timeoff_request;
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 pub enum ShiftRequestStatus {
     PENDING,
     PEER_ACCEPTED,
@@ -24,6 +26,37 @@ pub enum ShiftRequestStatus {
     CANCELLED,
 }
 
+impl ShiftRequestStatus {
+    /// Whether a request in this status can move to `next`.
+    ///
+    /// With `peer_step`, the request goes through the other member first: `PENDING` becomes
+    /// `PEER_ACCEPTED` or `PEER_DECLINED`, and only a `PEER_ACCEPTED` request can be `APPROVED`.
+    /// Without it, a manager approves `PENDING` requests directly. Managers can decline and
+    /// requesters can cancel a request until it is settled.
+    pub fn can_transition_to(&self, next: &ShiftRequestStatus, peer_step: bool) -> bool {
+        use ShiftRequestStatus::*;
+
+        match (self, next) {
+            (PENDING, PEER_ACCEPTED | PEER_DECLINED) => peer_step,
+            (PENDING, APPROVED) => !peer_step,
+            (PEER_ACCEPTED, APPROVED) => peer_step,
+            (PENDING | PEER_ACCEPTED, DECLINED | CANCELLED) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the request can't change anymore.
+    pub fn is_settled(&self) -> bool {
+        matches!(
+            self,
+            ShiftRequestStatus::PEER_DECLINED |
+                ShiftRequestStatus::APPROVED |
+                ShiftRequestStatus::DECLINED |
+                ShiftRequestStatus::CANCELLED
+        )
+    }
+}
+
 impl FromStr for ShiftRequestStatus {
     type Err = ShiftRequestStatusParseError;
 
@@ -64,9 +97,41 @@ impl ToString for ShiftRequestStatus {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateShiftRequest {
     pub status: Option<ShiftRequestStatus>, // Consider using an Enum in Rust for stronger type safety
     pub admin_id: Option<i64>,
     pub note: Option<String>,
 }
+
+impl Validate for RequestUpdateShiftRequest {
+    /// Status transitions are checked by the `update_entity` of each kind of request.
+    fn validate_fields(&self, _validator: &mut Validator) {}
+}
+
+/// Body of the endpoints moving a shift request along its workflow.
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
+pub struct RequestShiftRequestAction {
+    pub note: Option<String>,
+}
+
+/// Returned when a request can't move from its current status to the requested one.
+#[derive(Debug, Clone)]
+pub struct InvalidStatusTransitionError {
+    pub from: String,
+    pub to: String,
+}
+
+impl InvalidStatusTransitionError {
+    pub fn new(from: &impl ToString, to: &impl ToString) -> Self {
+        InvalidStatusTransitionError { from: from.to_string(), to: to.to_string() }
+    }
+}
+
+impl std::fmt::Display for InvalidStatusTransitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cannot move a {} request to {}", self.from, self.to)
+    }
+}
+
+impl std::error::Error for InvalidStatusTransitionError {}
@@ -1,9 +1,11 @@
 use chrono::NaiveDateTime;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use mysql::*;
 use mysql::prelude::*;
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 pub fn create_open_shifts_table_query() -> String {
     "
@@ -11,16 +13,18 @@ pub fn create_open_shifts_table_query() -> String {
         id BIGINT NOT NULL PRIMARY KEY,
         schedule_id BIGINT NOT NULL,
         job_id BIGINT NOT NULL,
-        start_time TIME NOT NULL,
-        end_time TIME NOT NULL,
+        start_time TIMESTAMP NOT NULL,
+        end_time TIMESTAMP NOT NULL,
         pay_rate FLOAT,
+        headcount INT NOT NULL DEFAULT 1,
+        filled_count INT NOT NULL DEFAULT 0,
         FOREIGN KEY (schedule_id) REFERENCES work_schedules(id) ON DELETE CASCADE,
         FOREIGN KEY (job_id) REFERENCES org_jobs(id) ON DELETE CASCADE
     );
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct OpenShift {
     pub id: i64,
     pub schedule_id: i64,
@@ -28,6 +32,17 @@ pub struct OpenShift {
     pub start_time: NaiveDateTime,
     pub end_time: NaiveDateTime,
     pub pay_rate: Option<f32>,
+    /// Number of members needed for the shift
+    pub headcount: i32,
+    /// Number of members who already picked the shift up
+    pub filled_count: i32,
+}
+
+impl OpenShift {
+    /// Whether every spot of the shift has been picked up.
+    pub fn is_filled(&self) -> bool {
+        self.filled_count >= self.headcount
+    }
 }
 
 impl FromRow for OpenShift {
@@ -43,24 +58,50 @@ impl FromRow for OpenShift {
                 row.get("end_time").ok_or(FromRowError(row.clone()))?
             ).map_err(|_| FromRowError(row.clone()))?,
             pay_rate: row.get("pay_rate").ok_or(FromRowError(row.clone()))?,
+            headcount: row.get("headcount").ok_or(FromRowError(row.clone()))?,
+            filled_count: row.get("filled_count").ok_or(FromRowError(row.clone()))?,
         })
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateOpenShift {
-    pub user_id: i64,
     pub schedule_id: i64,
     pub job_id: i64,
     pub start_time: NaiveDateTime,
     pub end_time: NaiveDateTime,
     pub pay_rate: Option<f32>,
+    /// Defaults to a single member
+    pub headcount: Option<i32>,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+impl Validate for RequestCreateOpenShift {
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let Some(pay_rate) = self.pay_rate {
+            validator.non_negative("pay_rate", pay_rate);
+        }
+        if let Some(headcount) = self.headcount {
+            validator.at_least("headcount", headcount.into(), 1);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateOpenShift {
     pub job_id: Option<i64>,
     pub start_time: Option<NaiveDateTime>,
     pub end_time: Option<NaiveDateTime>,
     pub pay_rate: Option<f32>,
+    pub headcount: Option<i32>,
+}
+
+impl Validate for RequestUpdateOpenShift {
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let Some(pay_rate) = self.pay_rate {
+            validator.non_negative("pay_rate", pay_rate);
+        }
+        if let Some(headcount) = self.headcount {
+            validator.at_least("headcount", headcount.into(), 1);
+        }
+    }
 }
@@ -1,8 +1,9 @@
 use std::str::FromStr;
 
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 pub enum ScheduleRequestStatus {
     PENDING,
     CANCELLED,
@@ -10,6 +11,18 @@ pub enum ScheduleRequestStatus {
     APPROVED,
 }
 
+impl ScheduleRequestStatus {
+    /// Whether a request in this status can move to `next`.
+    ///
+    /// Managers approve or decline `PENDING` requests. Members can cancel their request while it
+    /// is pending, and withdraw it once approved.
+    pub fn can_transition_to(&self, next: &ScheduleRequestStatus) -> bool {
+        use ScheduleRequestStatus::*;
+
+        matches!((self, next), (PENDING, APPROVED | DECLINED | CANCELLED) | (APPROVED, CANCELLED))
+    }
+}
+
 impl FromStr for ScheduleRequestStatus {
     type Err = ScheduleRequestStatusParseError;
 
@@ -1,9 +1,11 @@
 use chrono::NaiveDateTime;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use mysql::*;
 use mysql::prelude::*;
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 pub fn create_shifts_table_query() -> String {
     "
@@ -23,7 +25,7 @@ pub fn create_shifts_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Shift {
     pub id: i64,
     pub user_id: i64,
@@ -37,7 +39,6 @@ pub struct Shift {
 
 impl FromRow for Shift {
     fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
-        println!("{row:?}");
         Ok(Shift {
             id: row.get("id").ok_or(FromRowError(row.clone()))?,
             user_id: row.get("user_id").ok_or(FromRowError(row.clone()))?,
@@ -55,7 +56,7 @@ impl FromRow for Shift {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateShift {
     pub user_id: i64,
     pub schedule_id: i64,
@@ -64,9 +65,21 @@ pub struct RequestCreateShift {
     pub end_time: NaiveDateTime,
     pub pay_rate: Option<f32>,
     pub note: Option<String>,
+    /// What to do when the member's availability or time off conflicts with the shift.
+    /// Defaults to `WARN`
+    pub availability_policy: Option<AvailabilityPolicy>,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+impl Validate for RequestCreateShift {
+    /// The times are checked against the `ShiftValidationRule`s when the shift is saved.
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let Some(pay_rate) = self.pay_rate {
+            validator.non_negative("pay_rate", pay_rate);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateShift {
     pub job_id: Option<i64>,
     pub start_time: Option<NaiveDateTime>,
@@ -74,3 +87,144 @@ pub struct RequestUpdateShift {
     pub pay_rate: Option<f32>,
     pub note: Option<String>,
 }
+
+impl Validate for RequestUpdateShift {
+    /// The times are checked against the `ShiftValidationRule`s when the shift is saved.
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let Some(pay_rate) = self.pay_rate {
+            validator.non_negative("pay_rate", pay_rate);
+        }
+    }
+}
+
+/// A shift with the job and the assigned user joined in, for display.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ShiftDetail {
+    pub id: i64,
+    pub user_id: i64,
+    pub schedule_id: i64,
+    pub job_id: i64,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub pay_rate: Option<f32>,
+    pub note: Option<String>,
+    pub job_name: String,
+    pub job_color: Option<String>,
+    pub user_first_name: String,
+    pub user_last_name: String,
+}
+
+impl FromRow for ShiftDetail {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        Ok(ShiftDetail {
+            id: row.get("id").ok_or(FromRowError(row.clone()))?,
+            user_id: row.get("user_id").ok_or(FromRowError(row.clone()))?,
+            schedule_id: row.get("schedule_id").ok_or(FromRowError(row.clone()))?,
+            job_id: row.get("job_id").ok_or(FromRowError(row.clone()))?,
+            start_time: convert_to_naive_date_time(
+                row.get("start_time").ok_or(FromRowError(row.clone()))?
+            ).map_err(|_| FromRowError(row.clone()))?,
+            end_time: convert_to_naive_date_time(
+                row.get("end_time").ok_or(FromRowError(row.clone()))?
+            ).map_err(|_| FromRowError(row.clone()))?,
+            pay_rate: row.get("pay_rate").ok_or(FromRowError(row.clone()))?,
+            note: row.get("note").ok_or(FromRowError(row.clone()))?,
+            job_name: row.get("job_name").ok_or(FromRowError(row.clone()))?,
+            job_color: row.get("job_color").ok_or(FromRowError(row.clone()))?,
+            user_first_name: row.get("user_first_name").ok_or(FromRowError(row.clone()))?,
+            user_last_name: row.get("user_last_name").ok_or(FromRowError(row.clone()))?,
+        })
+    }
+}
+
+/// Rules a shift must follow to be booked.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub enum ShiftValidationRule {
+    /// `end_time` must be after `start_time`
+    END_BEFORE_START,
+    /// The shift must fall within the `start_date..=end_date` of its work schedule
+    OUTSIDE_SCHEDULE,
+    /// The member can't be booked on two overlapping shifts
+    OVERLAP,
+    /// The shift raises scheduling warnings and was created with the `REJECT` availability policy
+    AVAILABILITY,
+    /// The shift has already started and can't be handed over anymore
+    ALREADY_STARTED,
+    /// The shifts of a trade belong to different organizations
+    DIFFERENT_ORGANIZATIONS,
+    /// The member taking over the shift is not a member of its organization
+    NOT_A_MEMBER,
+    /// The member taking over the shift is already working it
+    SAME_MEMBER,
+    /// Every spot of the open shift has already been given out
+    ALREADY_FILLED,
+}
+
+/// Returned when a shift breaks one of the `ShiftValidationRule`s.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShiftValidationError {
+    pub rule: ShiftValidationRule,
+    pub message: String,
+    /// The existing shift colliding with the new one, for `OVERLAP`
+    pub conflicting_shift: Option<Shift>,
+    /// The scheduling warnings raised by the shift, for `AVAILABILITY`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<SchedulingWarning>,
+}
+
+impl ShiftValidationError {
+    pub fn new(rule: ShiftValidationRule, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            message: message.into(),
+            conflicting_shift: None,
+            warnings: vec![],
+        }
+    }
+}
+
+impl std::fmt::Display for ShiftValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ShiftValidationError {}
+
+/// Whether scheduling warnings block the creation of a shift.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, ToSchema)]
+pub enum AvailabilityPolicy {
+    /// Create the shift and report the warnings
+    #[default]
+    WARN,
+    /// Refuse the shift if it raises any warning
+    REJECT,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub enum SchedulingWarningKind {
+    /// The member's availability marks them unavailable on that weekday
+    UNAVAILABLE,
+    /// The shift starts or ends outside the member's preferred hours for that weekday
+    OUTSIDE_PREFERRED_TIME,
+    /// The member has approved time off during the shift
+    TIME_OFF,
+}
+
+/// A soft conflict between a shift and the member's approved availability or time off.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SchedulingWarning {
+    pub kind: SchedulingWarningKind,
+    pub message: String,
+    /// The approved time off request, for `TIME_OFF`
+    pub time_off_request_id: Option<i64>,
+}
+
+/// Returned when a shift is created, with the scheduling warnings it raised.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ShiftCreated {
+    pub id: i64,
+    pub warnings: Vec<SchedulingWarning>,
+}
@@ -2,9 +2,11 @@ use std::str::FromStr;
 
 use chrono::NaiveDateTime;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use mysql::{ *, prelude::FromRow };
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 use super::ShiftRequestStatus;
 
@@ -25,7 +27,7 @@ pub fn create_shift_covers_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ShiftCover {
     pub id: i64,
     pub shift_id: i64,
@@ -54,8 +56,13 @@ impl FromRow for ShiftCover {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateShiftCover {
     pub shift_id: i64,
     pub cover_user_id: i64,
 }
+
+impl Validate for RequestCreateShiftCover {
+    /// Only holds references, checked by the database.
+    fn validate_fields(&self, _validator: &mut Validator) {}
+}
@@ -2,9 +2,11 @@ use std::str::FromStr;
 
 use chrono::NaiveDateTime;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use mysql::{ *, prelude::FromRow };
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 use super::ShiftRequestStatus;
 
@@ -25,7 +27,7 @@ pub fn create_shift_pickups_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ShiftPickup {
     pub id: i64,
     pub openshift_id: i64,
@@ -54,8 +56,13 @@ impl FromRow for ShiftPickup {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateShiftPickup {
     pub openshift_id: i64,
     pub user_id: i64,
 }
+
+impl Validate for RequestCreateShiftPickup {
+    /// Only holds references, checked by the database.
+    fn validate_fields(&self, _validator: &mut Validator) {}
+}
@@ -2,9 +2,11 @@ use std::str::FromStr;
 
 use chrono::NaiveDateTime;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use mysql::{ *, prelude::FromRow };
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
 use super::ShiftRequestStatus;
 
@@ -25,7 +27,7 @@ pub fn create_shift_trades_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ShiftTrade {
     pub id: i64,
     pub shift1_id: i64,
@@ -54,8 +56,18 @@ impl FromRow for ShiftTrade {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateShiftTrade {
     pub shift1_id: i64,
     pub shift2_id: i64,
 }
+
+impl Validate for RequestCreateShiftTrade {
+    fn validate_fields(&self, validator: &mut Validator) {
+        validator.check(
+            self.shift2_id != self.shift1_id,
+            "shift2_id",
+            "must differ from shift1_id"
+        );
+    }
+}
@@ -4,10 +4,12 @@ use chrono::NaiveDateTime;
 use mysql::*;
 use mysql::prelude::*;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 
 use crate::utilities::parse_chrono::convert_to_naive_date_time;
+use crate::prototypes::validate::{ Validate, Validator };
 
-use super::request_status::ScheduleRequestStatus;
+use super::{ request_status::ScheduleRequestStatus, shift::Shift };
 
 pub fn create_time_off_requests_table_query() -> String {
     "
@@ -15,20 +17,20 @@ pub fn create_time_off_requests_table_query() -> String {
         id BIGINT NOT NULL PRIMARY KEY,
         user_id BIGINT NOT NULL,
         org_id BIGINT NOT NULL,
-        start_time DATE NOT NULL,
-        end_time DATE NOT NULL,
+        start_time DATETIME NOT NULL,
+        end_time DATETIME NOT NULL,
         reason TEXT,
-        status ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DENIED') NOT NULL DEFAULT 'PENDING',
+        status ENUM('PENDING', 'CANCELLED', 'APPROVED', 'DECLINED') NOT NULL DEFAULT 'PENDING',
         updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
         admin_id BIGINT,
         FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
-        FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE
+        FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
         FOREIGN KEY (admin_id) REFERENCES users(id) ON DELETE SET NULL
     );
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TimeOffRequest {
     pub id: i64,
     pub user_id: i64,
@@ -66,7 +68,7 @@ impl FromRow for TimeOffRequest {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateTimeOff {
     pub user_id: i64,
     pub org_id: i64,
@@ -75,9 +77,47 @@ pub struct RequestCreateTimeOff {
     pub reason: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+impl Validate for RequestCreateTimeOff {
+    fn validate_fields(&self, validator: &mut Validator) {
+        validator.check(self.end_time > self.start_time, "end_time", "must be after start_time");
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateTimeOff {
     pub status: Option<ScheduleRequestStatus>,
     pub admin_id: Option<i64>,
     pub reason: Option<String>,
 }
+
+impl Validate for RequestUpdateTimeOff {
+    /// Status transitions are checked by `TimeOffRequestQueries::update_entity`.
+    fn validate_fields(&self, _validator: &mut Validator) {}
+}
+
+/// What to do with the shifts a member holds during time off being approved.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, ToSchema)]
+pub enum OverlappingShiftPolicy {
+    /// Keep the shifts and report them, so they can be handled by hand
+    #[default]
+    REPORT,
+    /// Turn the shifts into open shifts other members can pick up
+    CONVERT_TO_OPEN_SHIFTS,
+}
+
+/// Body of the endpoint approving time off.
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
+pub struct RequestApproveTimeOff {
+    pub overlapping_shifts: Option<OverlappingShiftPolicy>,
+}
+
+/// Outcome of approving time off.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TimeOffApproval {
+    pub request: TimeOffRequest,
+    /// Shifts of the member during the time off, as they were before the approval
+    pub overlapping_shifts: Vec<Shift>,
+    /// Open shifts created from `overlapping_shifts`, with `CONVERT_TO_OPEN_SHIFTS`
+    pub open_shift_ids: Vec<i64>,
+}
@@ -1,9 +1,13 @@
 use chrono::{ NaiveDate, NaiveDateTime };
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use mysql::*;
 use mysql::prelude::*;
 
 use crate::utilities::parse_chrono::{ convert_to_naive_date_time, convert_to_naive_date };
+use crate::prototypes::validate::{ Validate, Validator };
+
+use super::shift::ShiftDetail;
 
 pub fn create_work_schedules_table_query() -> String {
     "
@@ -20,7 +24,7 @@ pub fn create_work_schedules_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct WorkSchedule {
     pub id: i64,
     pub org_id: i64,
@@ -49,16 +53,59 @@ impl FromRow for WorkSchedule {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct RequestCreateWorkSchedule {
     pub org_id: i64,
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+impl Validate for RequestCreateWorkSchedule {
+    fn validate_fields(&self, validator: &mut Validator) {
+        validator.check(
+            self.end_date >= self.start_date,
+            "end_date",
+            "must not be before start_date"
+        );
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateWorkSchedule {
     pub start_date: Option<NaiveDate>,
     pub end_date: Option<NaiveDate>,
     pub publish: Option<bool>,
 }
+
+impl Validate for RequestUpdateWorkSchedule {
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let (Some(start_date), Some(end_date)) = (self.start_date, self.end_date) {
+            validator.check(end_date >= start_date, "end_date", "must not be before start_date");
+        }
+    }
+}
+
+/// A work schedule with all of its shifts, laid out for a week grid.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WorkScheduleWeek {
+    pub schedule: WorkSchedule,
+    /// Every day of the schedule, from `start_date` to `end_date`, with the shifts starting that day
+    pub days: Vec<ScheduleDay>,
+    /// Every member with at least one shift in the schedule
+    pub members: Vec<ScheduleMember>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleDay {
+    pub date: NaiveDate,
+    pub shifts: Vec<ShiftDetail>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleMember {
+    pub user_id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    /// Same days as `WorkScheduleWeek::days`, limited to the shifts of this member
+    pub days: Vec<ScheduleDay>,
+}
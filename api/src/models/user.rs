@@ -1,10 +1,12 @@
 use chrono::NaiveDate;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 
 use mysql::*;
 use mysql::prelude::*;
 
 use crate::utilities::parse_chrono::convert_to_naive_date;
+use crate::prototypes::validate::{ Validate, Validator };
 
 /// SQL statement to create the table `users`
 pub fn create_users_table_query() -> String {
@@ -23,7 +25,7 @@ pub fn create_users_table_query() -> String {
     ".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct User {
     pub id: i64,
     pub email: String,
@@ -54,7 +56,7 @@ impl FromRow for User {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PartialUser {
     pub id: i64,
     pub email: String,
@@ -83,7 +85,43 @@ impl FromRow for PartialUser {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What any user sees of the others, without their contact details or date of birth.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PublicUser {
+    pub id: i64,
+    pub first_name: String,
+    pub last_name: String,
+    pub avatar: Option<String>,
+}
+
+impl From<User> for PublicUser {
+    fn from(user: User) -> Self {
+        PublicUser {
+            id: user.id,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            avatar: user.avatar,
+        }
+    }
+}
+
+/// The user without its password hash, as the routes return it.
+impl From<User> for PartialUser {
+    fn from(user: User) -> Self {
+        PartialUser {
+            id: user.id,
+            email: user.email,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            date_of_birth: user.date_of_birth,
+            phone_number: user.phone_number,
+            avatar: user.avatar,
+            is_active: user.is_active,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RequestCreateUser {
     pub email: String,
     pub password: String,
@@ -93,7 +131,45 @@ pub struct RequestCreateUser {
     pub phone_number: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+impl Validate for RequestCreateUser {
+    fn validate_fields(&self, validator: &mut Validator) {
+        validator.email("email", &self.email);
+        // bcrypt only reads the first 72 bytes
+        validator.check(
+            (8..=72).contains(&self.password.len()),
+            "password",
+            "must be 8 to 72 characters"
+        );
+        validator.text("first_name", &self.first_name, 100);
+        validator.text("last_name", &self.last_name, 100);
+        validator.check(
+            self.date_of_birth < chrono::Local::now().date_naive(),
+            "date_of_birth",
+            "must be in the past"
+        );
+        if let Some(phone_number) = &self.phone_number {
+            validator.max_length("phone_number", phone_number, 20);
+        }
+    }
+}
+
+/// A user as it is inserted, its password already hashed, see `UserQueries::new_user`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NewUser {
+    pub email: String,
+    pub encrypted_password: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub date_of_birth: NaiveDate,
+    pub phone_number: Option<String>,
+}
+
+/// The fields were validated as a `RequestCreateUser`.
+impl Validate for NewUser {
+    fn validate_fields(&self, _validator: &mut Validator) {}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct RequestUpdateUser {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
@@ -102,7 +178,28 @@ pub struct RequestUpdateUser {
     pub is_active: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Validate for RequestUpdateUser {
+    fn validate_fields(&self, validator: &mut Validator) {
+        if let Some(first_name) = &self.first_name {
+            validator.text("first_name", first_name, 100);
+        }
+        if let Some(last_name) = &self.last_name {
+            validator.text("last_name", last_name, 100);
+        }
+        if let Some(date_of_birth) = self.date_of_birth {
+            validator.check(
+                date_of_birth < chrono::Local::now().date_naive(),
+                "date_of_birth",
+                "must be in the past"
+            );
+        }
+        if let Some(phone_number) = &self.phone_number {
+            validator.max_length("phone_number", phone_number, 20);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RequestUpdateUserCredentials {
     pub email: Option<String>,
     pub password: Option<String>,
@@ -0,0 +1,107 @@
+use std::sync::atomic::{ AtomicU64, Ordering };
+
+use crate::{ models::result::Result, storage::{ Connection, Queryable, Transaction } };
+
+/// Numbers the savepoints, so nested ones don't replace each other.
+static NEXT_SAVEPOINT: AtomicU64 = AtomicU64::new(0);
+
+/// A connection that can run a group of statements atomically.
+///
+/// On a `Connection`, `atomic` starts a transaction, committed only if the closure succeeds. On a
+/// `Transaction`, the closure runs inside a savepoint: a failure undoes only what the closure did,
+/// and the caller decides whether the rest of the transaction goes on. Atomic operations can so be
+/// composed into a larger one.
+pub trait Atomic: Queryable {
+    /// Runs `f` inside a transaction, rolling back everything it did if it fails.
+    fn atomic<T, F>(&mut self, f: F) -> Result<T> where F: FnOnce(&mut Transaction<'_>) -> Result<T>;
+}
+
+impl Atomic for Connection {
+    fn atomic<T, F>(&mut self, f: F) -> Result<T> where F: FnOnce(&mut Transaction<'_>) -> Result<T> {
+        let mut tx = self.start_transaction()?;
+        // dropping the transaction without committing it rolls it back
+        let result = f(&mut tx)?;
+        tx.commit()?;
+        Ok(result)
+    }
+}
+
+impl Atomic for Transaction<'_> {
+    fn atomic<T, F>(&mut self, f: F) -> Result<T> where F: FnOnce(&mut Transaction<'_>) -> Result<T> {
+        let savepoint = format!("atomic_{}", NEXT_SAVEPOINT.fetch_add(1, Ordering::Relaxed));
+        self.savepoint(&savepoint)?;
+        match f(self) {
+            Ok(result) => {
+                self.release_savepoint(&savepoint)?;
+                Ok(result)
+            }
+            Err(err) => {
+                self.rollback_to_savepoint(&savepoint)?;
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{ initialize_test_db, cleanup_test_db };
+
+    fn insert_user(conn: &mut impl Queryable, id: i64) -> Result<()> {
+        conn.query_drop(
+            format!(
+                "INSERT INTO users (id, email, encrypted_password, first_name, last_name, date_of_birth)
+                VALUES ({id}, 'user{id}@example.com', '', 'Test', 'User', '1990-01-01');"
+            )
+        )?;
+        Ok(())
+    }
+
+    fn user_ids(conn: &mut Connection) -> Result<Vec<i64>> {
+        Ok(conn.query("SELECT id FROM users ORDER BY id;")?)
+    }
+
+    #[test]
+    fn test_nested_atomic() -> Result<()> {
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+
+        // A failing inner block is rolled back on its own, the outer one still commits
+        conn.atomic(|tx| {
+            insert_user(tx, 1)?;
+            let inner = tx.atomic(|tx| {
+                insert_user(tx, 2)?;
+                Err::<(), _>("inner failure".into())
+            });
+            assert!(inner.is_err());
+            tx.atomic(|tx| insert_user(tx, 3))?;
+            Ok(())
+        })?;
+        assert_eq!(user_ids(&mut conn)?, vec![1, 3]);
+
+        // Deeper blocks are undone with the block they are part of
+        conn.atomic(|tx| {
+            let inner = tx.atomic(|tx| {
+                insert_user(tx, 4)?;
+                tx.atomic(|tx| insert_user(tx, 5))?;
+                Err::<(), _>("inner failure".into())
+            });
+            assert!(inner.is_err());
+            insert_user(tx, 6)
+        })?;
+        assert_eq!(user_ids(&mut conn)?, vec![1, 3, 6]);
+
+        // An outer failure rolls back the inner blocks that succeeded
+        let outer = conn.atomic(|tx| {
+            tx.atomic(|tx| insert_user(tx, 7))?;
+            Err::<(), _>("outer failure".into())
+        });
+        assert!(outer.is_err());
+        assert_eq!(user_ids(&mut conn)?, vec![1, 3, 6]);
+
+        cleanup_test_db(conn)?;
+
+        Ok(())
+    }
+}
@@ -1,28 +1,68 @@
 use std::{ fmt::Debug, sync::Arc };
 
-use mysql::{ PooledConn, prelude::{ FromRow, Queryable }, Params, Value };
+use mysql::{ prelude::FromRow, Params, Row, Value };
 use serde::{ de::DeserializeOwned, Serialize };
+use utoipa::ToSchema;
 
-use crate::{ models::result::Result, snowflake::SnowflakeGenerator };
+use crate::{
+    storage::Queryable,
+    models::{
+        result::{ Result, NotFoundError },
+        pagination::{ ListQuery, ListScope, SortOrder },
+        ResponseDataPage,
+    },
+    snowflake::SnowflakeGenerator,
+    utilities::logging::timed_statement,
+};
+
+use super::{ atomic::Atomic, validate::Validate };
 
 /// The `BasicQueries` trait defines a set of basic CRUD (Create, Read, Update, Delete) operations for database interaction.
 ///
 /// This trait is intended to be implemented for various models in the application,
 /// providing a standardized interface for common CRUD operations.
-pub trait BasicQueries {
+///
+/// Every operation accepts any `Queryable`, so it can run on a `storage::Connection` as well as
+/// inside a `storage::Transaction`. Creating entities takes an `Atomic` connection: the insert and its
+/// post-processing succeed or fail together.
+///
+/// Async code runs these operations through a `storage::Repository`, which keeps the table in
+/// MySQL or in memory.
+///
+/// The statements of the default implementations are timed and logged at debug level, see
+/// `timed_statement`.
+pub trait BasicQueries: Send + Sync + 'static {
     /// Specifies the model type associated with the query.
     ///
     /// This type should implement `DeserializeOwned`, `Serialize`, `Send`, `Sync`, `Debug`, `Clone`, and `FromRow` (crate `mysql`).
-    type Model: DeserializeOwned + Serialize + Send + Sync + Debug + Clone + FromRow;
+    /// The model and the DTOs also implement `ToSchema` (crate `utoipa`), which describes them in
+    /// the OpenAPI document.
+    type Model: DeserializeOwned +
+        Serialize +
+        ToSchema +
+        Send +
+        Sync +
+        Debug +
+        Clone +
+        FromRow +
+        'static;
 
     /// Data Transfer Object (DTO) for creating entities.
     ///
-    /// This type should implement `DeserializeOwned`, `Send`, `Sync`, and `Debug`.
-    type CreateDto: DeserializeOwned + Send + Sync + Debug;
+    /// This type should implement `DeserializeOwned`, `Send`, `Sync`, `Debug`, and `Validate`.
+    type CreateDto: DeserializeOwned + ToSchema + Send + Sync + Debug + Validate + 'static;
     /// DTO for updating entities.
     ///
-    /// This type should implement `DeserializeOwned`, `Send`, `Sync`, `Debug`, and `Default`.
-    type UpdateDto: DeserializeOwned + Send + Sync + Debug + Default;
+    /// This type should implement `DeserializeOwned`, `Send`, `Sync`, `Debug`, `Default`, and
+    /// `Validate`.
+    type UpdateDto: DeserializeOwned +
+        ToSchema +
+        Send +
+        Sync +
+        Debug +
+        Default +
+        Validate +
+        'static;
 
     /// Returns the table name associated with the model.
     ///
@@ -48,14 +88,14 @@ pub trait BasicQueries {
     /// Executes the SQL insert statement to create a new entity in the database.
     ///
     /// # Arguments
-    /// * `conn` - A mutable reference to a pooled database connection.
+    /// * `conn` - A mutable reference to a database connection or a transaction.
     /// * `snowflake_generator` - A reference to a Snowflake ID generator.
     /// * `create_dto` - A reference to an instance of the `CreateDto` type.
     ///
     /// # Returns
     /// A `Result` containing the Snowflake ID of the newly created entity.
     fn create_entity_exec(
-        conn: &mut PooledConn,
+        conn: &mut impl Queryable,
         snowflake_generator: Arc<SnowflakeGenerator>,
         create_dto: &Self::CreateDto
     ) -> Result<i64> {
@@ -72,15 +112,19 @@ pub trait BasicQueries {
         params_map.insert("id".to_string().into_bytes(), Value::from(id));
 
         // Execute the query with the updated parameters
-        conn.exec_drop(Self::insert_statement(), Params::Named(params_map))?;
+        timed_statement(&Self::insert_statement(), |statement| {
+            conn.exec_drop(statement, Params::Named(params_map))
+        })?;
 
         Ok(id)
     }
 
     /// Optional method for post-processing after creating a new entity.
     ///
+    /// Runs in the same transaction as the insert, see `create_entity`.
+    ///
     /// # Arguments
-    /// * `conn` - A mutable reference to a pooled database connection.
+    /// * `conn` - A mutable reference to the transaction creating the entity.
     /// * `snowflake_generator` - A reference to a Snowflake ID generator.
     /// * `create_dto` - An instance of the `CreateDto` type.
     /// * `id` - The Snowflake ID of the newly created entity.
@@ -88,7 +132,7 @@ pub trait BasicQueries {
     /// # Returns
     /// A `Result` containing the Snowflake ID of the newly created entity.
     fn create_entity_postprocessor(
-        _conn: &mut PooledConn,
+        _conn: &mut impl Atomic,
         _snowflake_generator: Arc<SnowflakeGenerator>,
         _create_dto: Self::CreateDto,
         id: i64
@@ -98,38 +142,44 @@ pub trait BasicQueries {
 
     /// Creates a new entity in the database.
     ///
+    /// The insert and `create_entity_postprocessor` run atomically, nothing is created if either
+    /// of them fails.
+    ///
     /// # Arguments
-    /// * `conn` - A mutable reference to a pooled database connection.
+    /// * `conn` - A mutable reference to a pooled database connection or a transaction.
     /// * `snowflake_generator` - A reference to a Snowflake ID generator.
     /// * `create_dto` - An instance of the `CreateDto` type.
     ///
     /// # Returns
     /// A `Result` containing the Snowflake ID of the newly created entity.
     fn create_entity(
-        conn: &mut PooledConn,
+        conn: &mut impl Atomic,
         snowflake_generator: Arc<SnowflakeGenerator>,
         create_dto: Self::CreateDto
     ) -> Result<i64> {
-        let id = Self::create_entity_exec(conn, snowflake_generator.clone(), &create_dto)?;
+        conn.atomic(|tx| {
+            let id = Self::create_entity_exec(tx, snowflake_generator.clone(), &create_dto)?;
 
-        Ok(Self::create_entity_postprocessor(conn, snowflake_generator, create_dto, id)?)
+            Self::create_entity_postprocessor(tx, snowflake_generator, create_dto, id)
+        })
     }
 
     /// Inserts multiple new entities into the database.
     ///
     /// This method allows batch insertion of multiple entities. It uses the `exec_batch` method
-    /// to execute the insert statement for each entity in the provided vector.
+    /// to execute the insert statement for each entity in the provided vector, atomically: either
+    /// every entity is inserted or none is.
     ///
     /// # Arguments
     ///
-    /// * `conn`: A mutable reference to a pooled database connection.
+    /// * `conn`: A mutable reference to a pooled database connection or a transaction.
     /// * `create_dtos`: A vector of `CreateDto` instances to be inserted.
     ///
     /// # Returns
     ///
     /// A `Result` indicating success or failure of the operation.
     fn create_many(
-        conn: &mut PooledConn,
+        conn: &mut impl Atomic,
         snowflake_generator: Arc<SnowflakeGenerator>,
         create_dtos: Vec<Self::CreateDto>
     ) -> Result<()> {
@@ -150,11 +200,15 @@ pub trait BasicQueries {
             })
             .collect::<Result<Vec<_>>>()?; // Collect into Result<Vec<Params>, _>
 
-        conn.exec_batch(
-            Self::insert_statement(),
-            params_iter.into_iter() // Now params_iter is Iterator<Item = Params>
-        )?;
-        Ok(())
+        conn.atomic(|tx| {
+            timed_statement(&Self::insert_statement(), |statement| {
+                tx.exec_batch(
+                    statement,
+                    params_iter.into_iter() // Now params_iter is Iterator<Item = Params>
+                )
+            })?;
+            Ok(())
+        })
     }
 
     /// Updates an existing entity in the database.
@@ -164,14 +218,48 @@ pub trait BasicQueries {
     ///
     /// # Arguments
     ///
-    /// * `conn`: A mutable reference to a pooled database connection.
+    /// * `conn`: A mutable reference to a database connection or a transaction.
     /// * `update_dto`: An instance of the `UpdateDto` type containing the updated values.
     ///
     /// # Returns
     ///
     /// A `Result` wrapping the number of affected rows. If no rows are affected, it implies the update
     /// operation did not change any existing data or the specified ID does not exist.
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64>;
+    fn update_entity(conn: &mut impl Atomic, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
+        Self::update_columns(conn, id, Self::update_params(update_dto))
+    }
+
+    /// Converts an update DTO into the columns it sets, with their new values.
+    ///
+    /// Fields left out of the DTO don't appear in the result.
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)>;
+
+    /// Sets `columns` on the entity with the given `id`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` wrapping the number of affected rows, zero when `columns` is empty.
+    fn update_columns(
+        conn: &mut impl Queryable,
+        id: i64,
+        columns: Vec<(String, Value)>
+    ) -> Result<u64> {
+        if columns.is_empty() {
+            return Ok(0);
+        }
+
+        let assignments = columns
+            .iter()
+            .map(|(column, _)| format!("{} = :{}", column, column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut params = columns;
+        params.push(("id".to_string(), id.into()));
+
+        let query = format!("UPDATE {} SET {} WHERE id = :id;", Self::table_name(), assignments);
+        timed_statement(&query, |statement| conn.exec_drop(statement, Params::from(params)))?;
+        Ok(conn.affected_rows())
+    }
 
     /// Deletes an entity from the database.
     ///
@@ -180,17 +268,17 @@ pub trait BasicQueries {
     ///
     /// # Arguments
     ///
-    /// * `conn`: A mutable reference to a pooled database connection.
+    /// * `conn`: A mutable reference to a database connection or a transaction.
     /// * `id`: The ID of the entity to be deleted.
     ///
     /// # Returns
     ///
     /// A `Result` wrapping the number of affected rows. A result of zero implies no record was found
     /// with the given ID.
-    fn delete_entity(conn: &mut PooledConn, id: i64) -> Result<u64> {
+    fn delete_entity(conn: &mut impl Queryable, id: i64) -> Result<u64> {
         let query = format!("DELETE FROM {} WHERE id = {}", Self::table_name(), id);
-        let query_result = conn.query_iter(&query)?;
-        Ok(query_result.affected_rows())
+        timed_statement(&query, |statement| conn.query_drop(statement))?;
+        Ok(conn.affected_rows())
     }
 
     /// Retrieves all entities of a specific model from the database.
@@ -199,13 +287,14 @@ pub trait BasicQueries {
     ///
     /// # Arguments
     ///
-    /// * `conn`: A mutable reference to a pooled database connection.
+    /// * `conn`: A mutable reference to a database connection or a transaction.
     ///
     /// # Returns
     ///
     /// A `Result` wrapping a vector of `Model` instances. If no records are found, an empty vector is returned.
-    fn find_all(conn: &mut PooledConn) -> Result<Vec<Self::Model>> {
-        Ok(conn.query(format!("SELECT * FROM {};", Self::table_name()))?)
+    fn find_all(conn: &mut impl Queryable) -> Result<Vec<Self::Model>> {
+        let query = format!("SELECT * FROM {};", Self::table_name());
+        Ok(timed_statement(&query, |statement| conn.query(statement))?)
     }
 
     /// Retrieves a single entity by its ID.
@@ -215,27 +304,153 @@ pub trait BasicQueries {
     ///
     /// # Arguments
     ///
-    /// * `conn`: A mutable reference to a pooled database connection.
+    /// * `conn`: A mutable reference to a database connection or a transaction.
     /// * `id`: The ID of the entity to be fetched.
     ///
     /// # Returns
     ///
     /// A `Result` wrapping the `Model` instance if found. If no record is found with the given ID,
     /// an error is returned.
-    fn find_by_id(conn: &mut PooledConn, id: i64) -> Result<Self::Model> {
+    fn find_by_id(conn: &mut impl Queryable, id: i64) -> Result<Self::Model> {
         // SQL query to select a row by ID
         let query = format!("SELECT * FROM {} WHERE id = {};", Self::table_name(), id);
 
         // Execute the query
-        let result: Option<Self::Model> = conn.exec_first(query, ())?;
+        let result: Option<Self::Model> = timed_statement(&query, |statement| {
+            conn.exec_first(statement, ())
+        })?;
 
         // Extract the first row from the result (if any)
         if let Some(model) = result {
             // Convert the row into a Self::Model struct
             Ok(model)
         } else {
-            // Return an error if no user is found
-            Err("User not found".into())
+            // Return an error if no row is found
+            Err(Box::new(NotFoundError(format!("{} not found", Self::table_name()))))
+        }
+    }
+
+    /// Columns list queries can filter on, see `ListQuery`.
+    ///
+    /// Only these columns end up in the generated SQL, no filtering is allowed by default.
+    fn filter_columns() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Columns list queries can sort on. They must not be nullable, the cursor of a page is
+    /// compared with them.
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id"]
+    }
+
+    /// Sort applied when a list query doesn't set `sort` or `order`.
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("id", SortOrder::ASC)
+    }
+
+    /// Retrieves a page of the entities in `scope` matching the filters of `list_query`.
+    ///
+    /// Pages are keyed by the Snowflake ID of their last row rather than an offset, so they stay
+    /// consistent while rows are inserted, and the database doesn't skip rows to reach a page.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn`: A mutable reference to a database connection or a transaction.
+    /// * `scope`: The rows the list is restricted to, `ListScope::all()` for the whole table.
+    /// * `list_query`: Page, sort and filters requested.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` wrapping the page. A `ListQueryError` is returned if the query sorts or filters
+    /// on a column the model doesn't allow.
+    fn find_page(
+        conn: &mut impl Queryable,
+        scope: &ListScope,
+        list_query: &ListQuery
+    ) -> Result<ResponseDataPage<Self::Model>> {
+        let table = Self::table_name();
+        let resolved = list_query.resolve(
+            &Self::sort_columns(),
+            &Self::filter_columns(),
+            Self::default_sort()
+        )?;
+        let (sort, order) = (resolved.sort, resolved.order);
+
+        let mut conditions: Vec<String> = Vec::new();
+        let mut params: Vec<(String, Value)> = Vec::new();
+        for (column, value) in &scope.columns {
+            conditions.push(format!("{}.{} = :scope_{}", table, column, column));
+            params.push((format!("scope_{}", column), value.clone()));
         }
+        conditions.extend(scope.condition.iter().cloned());
+        params.extend(scope.params.iter().cloned());
+        for (index, (column, operator, value)) in resolved.filters.into_iter().enumerate() {
+            conditions.push(format!("{}.{} {} :filter{}", table, column, operator.sql(), index));
+            params.push((format!("filter{}", index), value));
+        }
+
+        let to_params = |params: &Vec<(String, Value)>| {
+            if params.is_empty() { Params::Empty } else { Params::from(params.clone()) }
+        };
+        let where_clause = |conditions: &Vec<String>| {
+            if conditions.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE {}", conditions.join(" AND "))
+            }
+        };
+
+        let count_query = format!(
+            "SELECT COUNT(*) FROM {} {} {};",
+            table,
+            scope.joins,
+            where_clause(&conditions)
+        );
+        let total: Option<u64> = timed_statement(&count_query, |statement| {
+            conn.exec_first(statement, to_params(&params))
+        })?;
+
+        if let Some(cursor) = list_query.cursor {
+            // rows after the cursor in the sort order, ties on the sort column broken by id
+            conditions.push(
+                format!(
+                    "({table}.{sort}, {table}.id) {} \
+                    (SELECT {sort}, id FROM {table} WHERE id = :cursor)",
+                    if order == SortOrder::ASC { ">" } else { "<" },
+                    table = table,
+                    sort = sort
+                )
+            );
+            params.push(("cursor".to_string(), Value::from(cursor)));
+        }
+
+        // one more row than the page tells whether there is a next page
+        let limit = list_query.limit() as usize;
+        let page_query = format!(
+            "SELECT {table}.* FROM {table} {} {} \
+            ORDER BY {table}.{sort} {order}, {table}.id {order} LIMIT {};",
+            scope.joins,
+            where_clause(&conditions),
+            limit + 1,
+            table = table,
+            sort = sort,
+            order = order.to_string()
+        );
+        let mut rows: Vec<Row> = timed_statement(&page_query, |statement| {
+            conn.exec(statement, to_params(&params))
+        })?;
+
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().and_then(|row| row.get::<i64, _>("id"))
+        } else {
+            None
+        };
+        let data = rows
+            .into_iter()
+            .map(Self::Model::from_row_opt)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(ResponseDataPage { data, next_cursor, total: total.unwrap_or(0) })
     }
 }
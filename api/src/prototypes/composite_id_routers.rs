@@ -0,0 +1,229 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    Router,
+    routing::{ get, post, patch, delete },
+    http::StatusCode,
+    response::IntoResponse,
+};
+use crate::{
+    app::{ AppState, AppResult, ApiResponse },
+    utilities::app_error::AppError,
+    models::{ ResponseDataList, ResponseDataJson, user::User },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+use super::{
+    create_table::DatabaseTable,
+    composite_key_queries::CompositeKeyQueries,
+    validate::Validate,
+};
+
+type ParentKey = i64;
+type CompositeKey = (i64, i64);
+
+/// The `CompositeIdRouter` trait is the sibling of `UniqueIdRouter` for entities identified by a
+/// composite `(parent_id, id)` key rather than a Snowflake ID.
+///
+/// Routes are nested under `path()`, which must contain a single path parameter for the parent id,
+/// e.g. `organizations/:id/members`. The parameter is named `:id` so the path can live next to the
+/// parent's own `UniqueIdRouter` routes; the child id is matched as `:child_id`.
+#[async_trait]
+pub trait CompositeIdRouter: 'static {
+    /// The type that implements `DatabaseTable` and `CompositeKeyQueries`.
+    type Queries: DatabaseTable + CompositeKeyQueries;
+
+    /// Returns the base path for the routes, including the parent id parameter.
+    fn path() -> String;
+
+    /// Checks whether `user` may create, update or delete entities under `parent_id`.
+    ///
+    /// The default implementation allows any authenticated user.
+    async fn authorize_write(_state: &AppState, _user: &User, _parent_id: ParentKey) -> AppResult<()> {
+        Ok(())
+    }
+
+    /// Checks whether `user` may create the entity described by `create_dto`.
+    ///
+    /// The default implementation defers to `authorize_write`.
+    async fn authorize_create(
+        state: &AppState,
+        user: &User,
+        create_dto: &<Self::Queries as CompositeKeyQueries>::CreateDto
+    ) -> AppResult<()> {
+        Self::authorize_write(state, user, Self::Queries::parent_id(create_dto)).await
+    }
+
+    /// Checks whether `user` may apply `update_dto` to the entity `(parent_id, id)`.
+    ///
+    /// The default implementation defers to `authorize_write`.
+    async fn authorize_update(
+        state: &AppState,
+        user: &User,
+        (parent_id, _id): CompositeKey,
+        _update_dto: &<Self::Queries as CompositeKeyQueries>::UpdateDto
+    ) -> AppResult<()> {
+        Self::authorize_write(state, user, parent_id).await
+    }
+
+    /// Checks whether `user` may delete the entity `(parent_id, id)`.
+    ///
+    /// The default implementation defers to `authorize_write`.
+    async fn authorize_delete(
+        state: &AppState,
+        user: &User,
+        (parent_id, _id): CompositeKey
+    ) -> AppResult<()> {
+        Self::authorize_write(state, user, parent_id).await
+    }
+
+    /// Asynchronously retrieves an entity by its composite key.
+    async fn get_by_id(
+        Extension(state): Extension<AppState>,
+        Path((parent_id, id)): Path<CompositeKey>
+    ) -> ApiResponse {
+        let model = state.db(move |conn| Self::Queries::find_by_id(conn, parent_id, id)).await?;
+
+        let json = Json(ResponseDataJson { data: model });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Asynchronously retrieves all entities belonging to the parent.
+    async fn get_all(
+        Extension(state): Extension<AppState>,
+        Path(parent_id): Path<ParentKey>
+    ) -> AppResult<Json<ResponseDataList<<Self::Queries as CompositeKeyQueries>::Model>>> {
+        let models = state.db(move |conn| Self::Queries::find_by_parent_id(conn, parent_id)).await?;
+
+        Ok(Json(ResponseDataList { data: models }))
+    }
+
+    /// Asynchronously creates a new entity under the parent.
+    ///
+    /// The parent id referenced by the DTO must match the one in the path.
+    async fn create_entity(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(parent_id): Path<ParentKey>,
+        Json(create_dto): Json<<Self::Queries as CompositeKeyQueries>::CreateDto>
+    ) -> ApiResponse {
+        if Self::Queries::parent_id(&create_dto) != parent_id {
+            return Err(AppError::bad_request("Body does not match the path"));
+        }
+        create_dto.validate()?;
+        Self::authorize_create(&state, &user, &create_dto).await?;
+
+        match state.db(move |conn| Self::Queries::create_entity(conn, create_dto)).await {
+            Ok(_) => {
+                let json = Json(ResponseDataJson { data: parent_id });
+                let mut response = json.into_response();
+                *response.status_mut() = StatusCode::CREATED;
+                Ok(response)
+            }
+            Err(e) => Err(AppError::from(e)),
+        }
+    }
+
+    /// Asynchronously updates an existing entity.
+    async fn update_entity(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path((parent_id, id)): Path<CompositeKey>,
+        Json(update_dto): Json<<Self::Queries as CompositeKeyQueries>::UpdateDto>
+    ) -> ApiResponse {
+        update_dto.validate()?;
+        Self::authorize_update(&state, &user, (parent_id, id), &update_dto).await?;
+
+        match
+            state.db(move |conn| {
+                Self::Queries::update_entity(conn, parent_id, id, update_dto)
+            }).await
+        {
+            Ok(affected_rows) => {
+                let json = Json(ResponseDataJson { data: affected_rows });
+                let mut response = json.into_response();
+                *response.status_mut() = StatusCode::OK;
+                Ok(response)
+            }
+            Err(e) => { Err(AppError::from(e)) }
+        }
+    }
+
+    /// Asynchronously deletes an entity.
+    async fn delete_entity(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path((parent_id, id)): Path<CompositeKey>
+    ) -> ApiResponse {
+        Self::authorize_delete(&state, &user, (parent_id, id)).await?;
+
+        match state.db(move |conn| Self::Queries::delete_entity(conn, parent_id, id)).await {
+            Ok(affected_rows) => {
+                let json = Json(ResponseDataJson { data: affected_rows });
+                let mut response = json.into_response();
+                *response.status_mut() = StatusCode::OK;
+                Ok(response)
+            }
+            Err(e) => { Err(AppError::from(e)) }
+        }
+    }
+
+    /// Asynchronously creates the default routes for the entity.
+    async fn default_routes() -> Router {
+        Router::new()
+            .route("/", get(Self::get_all))
+            .route("/:child_id", get(Self::get_by_id))
+            .route("/", post(Self::create_entity))
+            .route("/:child_id", patch(Self::update_entity))
+            .route("/:child_id", delete(Self::delete_entity))
+    }
+
+    /// Asynchronously creates additional custom routes for the entity.
+    ///
+    /// The default implementation returns an empty router.
+    async fn more_routes() -> Router {
+        Router::new()
+    }
+
+    /// Asynchronously creates a combined router with both default and custom routes,
+    /// nested under the base path defined by `path()`.
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new().nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+    }
+
+    /// Describes the routes of `default_routes` for the OpenAPI document.
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/", "List").data_list::<<Self::Queries as CompositeKeyQueries>::Model>(),
+            RouteDoc::get("/:child_id", "Get by ID").data::<
+                <Self::Queries as CompositeKeyQueries>::Model
+            >(),
+            RouteDoc::post("/", "Create")
+                .body::<<Self::Queries as CompositeKeyQueries>::CreateDto>()
+                .created_id(),
+            RouteDoc::patch("/:child_id", "Update")
+                .body::<<Self::Queries as CompositeKeyQueries>::UpdateDto>()
+                .affected_rows(),
+            RouteDoc::delete("/:child_id", "Delete").affected_rows()
+        ]
+    }
+
+    /// Describes the routes of `more_routes` for the OpenAPI document.
+    fn more_route_docs() -> Vec<RouteDoc> {
+        Vec::new()
+    }
+
+    /// Describes the routes of `router` for the OpenAPI document, nested like the routes.
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        RouteDoc::nest(&format!("/{}", &Self::path()), docs)
+    }
+}
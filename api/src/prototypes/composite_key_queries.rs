@@ -0,0 +1,130 @@
+use std::fmt::Debug;
+
+use mysql::{ prelude::FromRow, Params };
+use serde::{ de::DeserializeOwned, Serialize };
+use utoipa::ToSchema;
+
+use crate::models::result::{ Result, NotFoundError };
+
+use super::{ atomic::Atomic, validate::Validate };
+use crate::storage::Queryable;
+
+/// The `CompositeKeyQueries` trait is the counterpart of `BasicQueries` for tables whose primary key
+/// is made of two ids instead of a single Snowflake ID, e.g. `org_members` keyed by `(org_id, user_id)`.
+///
+/// The first part of the key (`parent_key`) is the entity the rows belong to, the second part
+/// (`child_key`) identifies the row inside that parent.
+pub trait CompositeKeyQueries: Send + Sync + 'static {
+    /// Specifies the model type associated with the query.
+    type Model: DeserializeOwned +
+        Serialize +
+        ToSchema +
+        Send +
+        Sync +
+        Debug +
+        Clone +
+        FromRow +
+        'static;
+
+    /// Data Transfer Object (DTO) for creating entities.
+    type CreateDto: DeserializeOwned + ToSchema + Send + Sync + Debug + Validate + 'static;
+    /// DTO for updating entities.
+    type UpdateDto: DeserializeOwned +
+        ToSchema +
+        Send +
+        Sync +
+        Debug +
+        Default +
+        Validate +
+        'static;
+
+    /// Returns the table name associated with the model.
+    fn table_name() -> String;
+
+    /// Returns the column name of the first part of the key, e.g. `org_id`.
+    fn parent_key() -> String;
+
+    /// Returns the column name of the second part of the key, e.g. `user_id`.
+    fn child_key() -> String;
+
+    /// Returns the parent id a create DTO refers to.
+    fn parent_id(create_dto: &Self::CreateDto) -> i64;
+
+    /// Provides an SQL insert statement for the model.
+    fn insert_statement() -> String;
+
+    /// Converts a DTO into a set of parameters for the SQL insert statement.
+    fn insert_params(create_dto: &Self::CreateDto) -> Result<Params>;
+
+    /// Creates a new entity in the database.
+    fn create_entity(conn: &mut impl Atomic, create_dto: Self::CreateDto) -> Result<()> {
+        conn.exec_drop(Self::insert_statement(), Self::insert_params(&create_dto)?)?;
+        Ok(())
+    }
+
+    /// Updates the entity identified by `(parent_id, id)`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` wrapping the number of affected rows.
+    fn update_entity(
+        conn: &mut impl Atomic,
+        parent_id: i64,
+        id: i64,
+        update_dto: Self::UpdateDto
+    ) -> Result<u64>;
+
+    /// Deletes the entity identified by `(parent_id, id)`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` wrapping the number of affected rows.
+    fn delete_entity(conn: &mut impl Atomic, parent_id: i64, id: i64) -> Result<u64> {
+        conn.query_drop(
+            format!(
+                "DELETE FROM {} WHERE {} = {} AND {} = {};",
+                Self::table_name(),
+                Self::parent_key(),
+                parent_id,
+                Self::child_key(),
+                id
+            )
+        )?;
+
+        Ok(conn.affected_rows())
+    }
+
+    /// Retrieves all entities belonging to `parent_id`.
+    fn find_by_parent_id(conn: &mut impl Queryable, parent_id: i64) -> Result<Vec<Self::Model>> {
+        Ok(
+            conn.query(
+                format!(
+                    "SELECT * FROM {} WHERE {} = {};",
+                    Self::table_name(),
+                    Self::parent_key(),
+                    parent_id
+                )
+            )?
+        )
+    }
+
+    /// Retrieves the entity identified by `(parent_id, id)`.
+    fn find_by_id(conn: &mut impl Queryable, parent_id: i64, id: i64) -> Result<Self::Model> {
+        let query = format!(
+            "SELECT * FROM {} WHERE {} = {} AND {} = {};",
+            Self::table_name(),
+            Self::parent_key(),
+            parent_id,
+            Self::child_key(),
+            id
+        );
+
+        let result: Option<Self::Model> = conn.exec_first(query, ())?;
+
+        if let Some(model) = result {
+            Ok(model)
+        } else {
+            Err(Box::new(NotFoundError(format!("{} not found", Self::table_name()))))
+        }
+    }
+}
@@ -1,10 +1,12 @@
-use mysql::PooledConn;
 
 use crate::models::result::Result;
+use crate::storage::Connection;
 
 pub trait DatabaseTable {
     /// Creates the table in the database.
     ///
-    /// This method should handle the creation of the database table if it does not already exist.
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()>;
+    /// This method should handle the creation of the database table if it does not already exist,
+    /// and bring a table created by an older version up to the initial schema. Only databases
+    /// created before migrations existed go through it, later changes belong in `migrations`.
+    fn create_table(&self, conn: &mut Connection) -> Result<()>;
 }
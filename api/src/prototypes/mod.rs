@@ -1,3 +1,7 @@
 pub mod basic_queries;
 pub mod create_table;
 pub mod uniqueid_routers;
+pub mod composite_key_queries;
+pub mod composite_id_routers;
+pub mod atomic;
+pub mod validate;
@@ -11,10 +11,19 @@ use axum::{
 use crate::{
     app::{ AppState, AppResult, ApiResponse },
     utilities::app_error::AppError,
-    models::{ ResponseDataList, ResponseDataJson },
+    models::{
+        ResponseDataPage,
+        ResponseDataJson,
+        pagination::{ ListQuery, ListScope },
+        org_member::OrgPermission,
+        result::Result,
+    },
+    queries::org_member::OrgMemberQueries,
+    storage::{ Connection, Repository },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
 };
 
-use super::{ create_table::DatabaseTable, basic_queries::BasicQueries };
+use super::{ create_table::DatabaseTable, basic_queries::BasicQueries, validate::Validate };
 
 type PrimaryKey = i64;
 
@@ -35,65 +44,85 @@ pub trait UniqueIdRouter: 'static {
     /// A `String` representing the base path for the entity's routes.
     fn path() -> String;
 
+    /// Returns the organization the entity belongs to, if any.
+    ///
+    /// Only members of that organization can read the entity through `get_by_id`.
+    /// The default implementation returns `None`, letting any caller read it.
+    fn org_id_of(_conn: &mut Connection, _id: PrimaryKey) -> Result<Option<i64>> {
+        Ok(None)
+    }
+
     /// Asynchronously retrieves an entity by its ID.
     ///
     /// # Arguments
-    /// * `state` - Application state containing database pool and other configurations.
+    /// * `state` - Application state containing the storage and other configurations.
+    /// * `user` - The caller, who must belong to the organization returned by `org_id_of`.
     /// * `id` - The primary key ID of the entity to be fetched.
     ///
     /// # Returns
     /// An `ApiResponse` that wraps a JSON response containing the entity or an error message.
     async fn get_by_id(
         Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
         Path(id): Path<PrimaryKey>
     ) -> ApiResponse {
-        let mut conn = state.db_pool.get_conn()?;
-        match Self::Queries::find_by_id(&mut conn, id) {
-            Ok(model) => {
-                let json = Json(ResponseDataJson { data: model });
-                let mut response = json.into_response();
-                *response.status_mut() = StatusCode::OK;
-                Ok(response)
-            }
-            Err(_) => {
-                return Err(AppError::not_found("not found"));
+        let model = state.db(move |conn| -> AppResult<_> {
+            if let Some(org_id) = Self::org_id_of(conn, id)? {
+                OrgMemberQueries::ensure_permission(
+                    conn,
+                    org_id,
+                    user.id,
+                    OrgPermission::VIEW_ORGANIZATION
+                )?;
             }
-        }
+
+            Ok(<Self::Queries as BasicQueries>::find_by_id(conn, id)?)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: model });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
     }
 
-    /// Asynchronously retrieves all entities.
+    /// Asynchronously retrieves a page of entities.
     ///
     /// # Arguments
-    /// * `state` - Application state containing database pool and other configurations.
+    /// * `state` - Application state containing the storage and other configurations.
+    /// * `list_query` - Page, sort and filters read from the query string, see `ListQuery`.
     ///
     /// # Returns
-    /// An `AppResult` that wraps a JSON response containing a list of entities.
-    async fn get_all(Extension(
-        state,
-    ): Extension<AppState>) -> AppResult<Json<ResponseDataList<<Self::Queries as BasicQueries>::Model>>> {
-        let mut conn = state.db_pool.get_conn()?;
-        let models = Self::Queries::find_all(&mut conn).map_err(|_|
-            AppError::internal_server_error("Failed to fetch records")
-        )?;
-
-        Ok(Json(ResponseDataList { data: models }))
+    /// An `AppResult` that wraps a JSON response containing a page of entities.
+    async fn get_all(
+        Extension(state): Extension<AppState>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<<Self::Queries as BasicQueries>::Model>>> {
+        let page = state.storage.find_page::<Self::Queries>(ListScope::all(), list_query).await?;
+
+        Ok(Json(page))
     }
 
     /// Asynchronously creates a new entity.
     ///
     /// # Arguments
-    /// * `state` - Application state containing database pool and other configurations.
+    /// * `state` - Application state containing the storage and other configurations.
     /// * `create_dto` - The data transfer object used for creating the new entity.
     ///
+    /// The DTO is validated first, invalid fields are answered with `422 Unprocessable Entity`.
+    ///
     /// # Returns
     /// An `ApiResponse` that wraps a JSON response containing the ID of the newly created entity or an error message.
     async fn create_entity(
         Extension(state): Extension<AppState>,
         Json(create_dto): Json<<Self::Queries as BasicQueries>::CreateDto>
     ) -> ApiResponse {
-        let mut conn = state.db_pool.get_conn()?;
+        create_dto.validate()?;
+
         match
-            Self::Queries::create_entity(&mut conn, state.snowflake_generator.clone(), create_dto)
+            state.storage.create::<Self::Queries>(
+                state.snowflake_generator.clone(),
+                create_dto
+            ).await
         {
             Ok(id) => {
                 let json = Json(ResponseDataJson { data: id });
@@ -101,20 +130,19 @@ pub trait UniqueIdRouter: 'static {
                 *response.status_mut() = StatusCode::CREATED;
                 Ok(response)
             }
-            Err(e) => {
-                println!("error {e}");
-                Err(AppError::from(e))
-            }
+            Err(e) => Err(AppError::from(e)),
         }
     }
 
     /// Asynchronously updates an existing entity.
     ///
     /// # Arguments
-    /// * `state` - Application state containing database pool and other configurations.
+    /// * `state` - Application state containing the storage and other configurations.
     /// * `id` - The primary key ID of the entity to be updated.
     /// * `update_dto` - The data transfer object containing the updated data.
     ///
+    /// The DTO is validated first, invalid fields are answered with `422 Unprocessable Entity`.
+    ///
     /// # Returns
     /// An `ApiResponse` that wraps a JSON response with the number of affected rows or an error message.
     async fn update_entity(
@@ -122,8 +150,9 @@ pub trait UniqueIdRouter: 'static {
         Path(id): Path<PrimaryKey>,
         Json(update_dto): Json<<Self::Queries as BasicQueries>::UpdateDto>
     ) -> ApiResponse {
-        let mut conn = state.db_pool.get_conn()?;
-        match Self::Queries::update_entity(&mut conn, id, update_dto) {
+        update_dto.validate()?;
+
+        match state.storage.update::<Self::Queries>(id, update_dto).await {
             Ok(affected_rows) => {
                 let json = Json(ResponseDataJson { data: affected_rows });
                 let mut response = json.into_response();
@@ -137,7 +166,7 @@ pub trait UniqueIdRouter: 'static {
     /// Asynchronously deletes an entity.
     ///
     /// # Arguments
-    /// * `state` - Application state containing database pool and other configurations.
+    /// * `state` - Application state containing the storage and other configurations.
     /// * `id` - The primary key ID of the entity to be deleted.
     ///
     /// # Returns
@@ -146,8 +175,7 @@ pub trait UniqueIdRouter: 'static {
         Extension(state): Extension<AppState>,
         Path(id): Path<PrimaryKey>
     ) -> ApiResponse {
-        let mut conn = state.db_pool.get_conn()?;
-        match Self::Queries::delete_entity(&mut conn, id) {
+        match state.storage.delete::<Self::Queries>(id).await {
             Ok(affected_rows) => {
                 let json = Json(ResponseDataJson { data: affected_rows });
                 let mut response = json.into_response();
@@ -197,4 +225,36 @@ pub trait UniqueIdRouter: 'static {
 
         Router::new().nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
     }
+
+    /// Describes the routes of `default_routes` for the OpenAPI document.
+    ///
+    /// Implementations that override `default_routes` with routes taking or returning other types
+    /// override this method to match.
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/", "List").page::<<Self::Queries as BasicQueries>::Model>(),
+            RouteDoc::get("/:id", "Get by ID").data::<<Self::Queries as BasicQueries>::Model>(),
+            RouteDoc::post("/", "Create")
+                .body::<<Self::Queries as BasicQueries>::CreateDto>()
+                .created_id(),
+            RouteDoc::patch("/:id", "Update")
+                .body::<<Self::Queries as BasicQueries>::UpdateDto>()
+                .affected_rows(),
+            RouteDoc::delete("/:id", "Delete").affected_rows()
+        ]
+    }
+
+    /// Describes the routes of `more_routes` for the OpenAPI document.
+    ///
+    /// The default implementation returns no docs, like `more_routes` returns no routes.
+    fn more_route_docs() -> Vec<RouteDoc> {
+        Vec::new()
+    }
+
+    /// Describes the routes of `router` for the OpenAPI document, nested like the routes.
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        RouteDoc::nest(&format!("/{}", &Self::path()), docs)
+    }
 }
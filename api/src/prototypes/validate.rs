@@ -0,0 +1,210 @@
+use std::{ error::Error, fmt };
+
+use chrono_tz::Tz;
+
+use crate::utilities::app_error::FieldError;
+
+/// The `Validate` trait checks the fields of a request DTO before it reaches the queries.
+///
+/// Every create and update DTO of `BasicQueries` and `CompositeKeyQueries` implements it, and
+/// `UniqueIdRouter` and `CompositeIdRouter` validate the DTOs they receive. Handlers taking a DTO
+/// themselves call `validate` first. Checks that need the database, like permissions or
+/// overlapping shifts, stay in the queries.
+pub trait Validate {
+    /// Reports the invalid fields of the DTO to `validator`.
+    fn validate_fields(&self, validator: &mut Validator);
+
+    /// Checks every field, failing with all the field errors at once.
+    fn validate(&self) -> Result<(), ValidationError> {
+        let mut validator = Validator::default();
+        self.validate_fields(&mut validator);
+        validator.finish()
+    }
+}
+
+/// Collects the field errors of a DTO.
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Reports `field` with `message` unless `valid`.
+    pub fn check(&mut self, valid: bool, field: &str, message: impl Into<String>) {
+        if !valid {
+            self.errors.push(FieldError::new(field, message));
+        }
+    }
+
+    /// Text that can't be empty or longer than its column.
+    pub fn text(&mut self, field: &str, value: &str, max_length: usize) {
+        if value.trim().is_empty() {
+            self.check(false, field, "must not be empty");
+        } else {
+            self.max_length(field, value, max_length);
+        }
+    }
+
+    /// Text that can't be longer than its column, e.g. `VARCHAR(100)`.
+    pub fn max_length(&mut self, field: &str, value: &str, max_length: usize) {
+        self.check(
+            value.chars().count() <= max_length,
+            field,
+            format!("must be at most {} characters", max_length)
+        );
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) {
+        let valid = match value.split_once('@') {
+            Some((local, domain)) =>
+                !local.is_empty() &&
+                    !domain.contains('@') &&
+                    domain.contains('.') &&
+                    !domain.starts_with('.') &&
+                    !domain.ends_with('.') &&
+                    !value.chars().any(char::is_whitespace),
+            None => false,
+        };
+        self.check(valid, field, "must be an email address");
+        self.max_length(field, value, 100);
+    }
+
+    /// An amount like a pay rate.
+    pub fn non_negative(&mut self, field: &str, value: f32) {
+        self.check(value.is_finite() && value >= 0.0, field, "must not be negative");
+    }
+
+    pub fn at_least(&mut self, field: &str, value: i64, min: i64) {
+        self.check(value >= min, field, format!("must be at least {}", min));
+    }
+
+    /// A color stored in a `VARCHAR(6)`, six hexadecimal digits without `#`.
+    pub fn hex_color(&mut self, field: &str, value: &str) {
+        self.check(
+            value.len() == 6 && value.chars().all(|c| c.is_ascii_hexdigit()),
+            field,
+            "must be six hexadecimal digits, e.g. ff0000"
+        );
+    }
+
+    /// An IANA time zone name.
+    pub fn timezone(&mut self, field: &str, value: &str) {
+        self.check(
+            value.parse::<Tz>().is_ok(),
+            field,
+            "must be an IANA time zone, e.g. America/Los_Angeles"
+        );
+    }
+
+    /// Validates a DTO nested in this one, its fields are reported under `prefix`.
+    pub fn nested(&mut self, prefix: &str, dto: &impl Validate) {
+        let mut validator = Validator::default();
+        dto.validate_fields(&mut validator);
+        self.errors.extend(
+            validator.errors
+                .into_iter()
+                .map(|error| FieldError::new(format!("{}.{}", prefix, error.field), error.message))
+        );
+    }
+
+    pub fn finish(self) -> Result<(), ValidationError> {
+        if self.errors.is_empty() { Ok(()) } else { Err(ValidationError(self.errors)) }
+    }
+}
+
+/// The invalid fields of a request, answered with `422 Unprocessable Entity`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError(pub Vec<FieldError>);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = self.0
+            .iter()
+            .map(|error| format!("`{}` {}", error.field, error.message))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "Validation failed: {}", fields)
+    }
+}
+
+impl Error for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{ NaiveDate, NaiveTime };
+
+    use super::*;
+    use crate::models::{
+        org_job::RequestCreateOrgJob,
+        organization::RequestUpdateOrganization,
+        schedule::{
+            availability::RequestCreateAvailability,
+            availability_detail::{ RequestCreateAvailabilityDetail, DayOfWeek },
+        },
+        user::RequestCreateUser,
+    };
+
+    fn fields(error: ValidationError) -> Vec<String> {
+        error.0
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_dtos() {
+        let create_job = RequestCreateOrgJob {
+            org_id: 1,
+            name: " ".to_string(),
+            description: None,
+            base_pay_rate: -1.0,
+            color: Some("#ff0000".to_string()),
+        };
+        // every invalid field is reported at once
+        assert_eq!(
+            fields(create_job.validate().unwrap_err()),
+            vec!["name", "base_pay_rate", "color"]
+        );
+
+        let create_user = RequestCreateUser {
+            email: "user@localhost".to_string(),
+            password: "short".to_string(),
+            first_name: "John".to_string(),
+            last_name: "D".repeat(101),
+            date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+            phone_number: None,
+        };
+        assert_eq!(
+            fields(create_user.validate().unwrap_err()),
+            vec!["email", "password", "last_name"]
+        );
+
+        let update_org = RequestUpdateOrganization {
+            timezone: Some("Mars/Olympus_Mons".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(fields(update_org.validate().unwrap_err()), vec!["timezone"]);
+        let update_org = RequestUpdateOrganization {
+            timezone: Some("Europe/Paris".to_string()),
+            ..Default::default()
+        };
+        assert!(update_org.validate().is_ok());
+
+        let create_availability = RequestCreateAvailability {
+            user_id: 1,
+            org_id: 1,
+            start_date: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+            details: vec![RequestCreateAvailabilityDetail {
+                day_of_week: DayOfWeek::MONDAY,
+                is_available: true,
+                whole_day: false,
+                preferred_start_time: NaiveTime::from_hms_opt(17, 0, 0),
+                preferred_end_time: NaiveTime::from_hms_opt(9, 0, 0),
+            }],
+        };
+        assert_eq!(
+            fields(create_availability.validate().unwrap_err()),
+            vec!["details[0].preferred_end_time"]
+        );
+    }
+}
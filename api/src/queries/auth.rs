@@ -0,0 +1,282 @@
+use std::sync::Arc;
+
+use chrono::{ Duration, NaiveDateTime, Utc };
+use mysql::*;
+
+use crate::{
+    storage::{ Connection, Queryable },
+    models::{
+        auth::{ RefreshToken, AuthTokens, create_refresh_tokens_table_query },
+        user::User,
+        result::Result,
+    },
+    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
+    snowflake::SnowflakeGenerator,
+    utilities::token::{
+        create_access_token,
+        create_refresh_token,
+        decode_token,
+        TokenKind,
+        ACCESS_TOKEN_TTL_MINUTES,
+        REFRESH_TOKEN_TTL_DAYS,
+    },
+};
+
+use super::user::{ UserQueries, LoginForm };
+
+pub struct RefreshTokenQueries {}
+
+impl DatabaseTable for RefreshTokenQueries {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
+        let query = create_refresh_tokens_table_query();
+        conn.query_drop(query)?;
+        Ok(())
+    }
+}
+
+impl RefreshTokenQueries {
+    fn table_name() -> String {
+        "refresh_tokens".to_string()
+    }
+
+    /// Stores a new refresh token row for the user and returns its id.
+    pub fn create_entity(
+        conn: &mut impl Queryable,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        user_id: i64,
+        expires_at: NaiveDateTime
+    ) -> Result<i64> {
+        let id = snowflake_generator.generate_id();
+        conn.exec_drop(
+            format!(
+                "INSERT INTO {} (id, user_id, expires_at) VALUES (:id, :user_id, :expires_at)",
+                Self::table_name()
+            ),
+            params! {
+                "id" => id,
+                "user_id" => user_id,
+                "expires_at" => expires_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            }
+        )?;
+        Ok(id)
+    }
+
+    pub fn find_by_id(conn: &mut impl Queryable, id: i64) -> Result<RefreshToken> {
+        let result: Option<RefreshToken> = conn.exec_first(
+            format!("SELECT * FROM {} WHERE id = :id;", Self::table_name()),
+            params! { "id" => id }
+        )?;
+
+        if let Some(model) = result {
+            Ok(model)
+        } else {
+            Err("Refresh token not found".into())
+        }
+    }
+
+    pub fn revoke(conn: &mut impl Queryable, id: i64) -> Result<u64> {
+        conn.exec_drop(
+            format!("UPDATE {} SET revoked = TRUE WHERE id = :id AND revoked = FALSE;", Self::table_name()),
+            params! { "id" => id }
+        )?;
+        Ok(conn.affected_rows())
+    }
+
+    /// Revokes every outstanding refresh token of a user, e.g. after a password change.
+    pub fn revoke_all_for_user(conn: &mut impl Queryable, user_id: i64) -> Result<u64> {
+        conn.exec_drop(
+            format!(
+                "UPDATE {} SET revoked = TRUE WHERE user_id = :user_id AND revoked = FALSE;",
+                Self::table_name()
+            ),
+            params! { "user_id" => user_id }
+        )?;
+        Ok(conn.affected_rows())
+    }
+}
+
+pub struct AuthQueries;
+
+impl AuthQueries {
+    /// Checks the credentials and issues a new access/refresh token pair.
+    ///
+    /// Returns `None` if the email is unknown, the password is wrong or the account is inactive.
+    pub fn login(
+        conn: &mut impl Queryable,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        secret: &str,
+        login_form: LoginForm
+    ) -> Result<Option<AuthTokens>> {
+        match UserQueries::authenticate(conn, login_form)? {
+            Some(user) if user.is_active => {
+                Ok(Some(Self::issue_tokens(conn, snowflake_generator, secret, &user)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Exchanges a valid refresh token for a new token pair.
+    ///
+    /// The presented refresh token is revoked (rotation), so each refresh token can only be used once.
+    pub fn refresh(
+        conn: &mut impl Queryable,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        secret: &str,
+        refresh_token: &str
+    ) -> Result<Option<AuthTokens>> {
+        let claims = match decode_token(secret, refresh_token, TokenKind::Refresh) {
+            Ok(claims) => claims,
+            Err(_) => {
+                return Ok(None);
+            }
+        };
+        let token_id = match claims.jti {
+            Some(token_id) => token_id,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        // `revoke` only affects a row that is still active, which also guards against the same
+        // refresh token being redeemed twice concurrently
+        if RefreshTokenQueries::revoke(conn, token_id)? == 0 {
+            return Ok(None);
+        }
+        let stored = RefreshTokenQueries::find_by_id(conn, token_id)?;
+        if stored.user_id != claims.sub || stored.expires_at < Utc::now().naive_utc() {
+            return Ok(None);
+        }
+
+        let user: User = UserQueries::find_by_id(conn, stored.user_id)?;
+        if !user.is_active {
+            return Ok(None);
+        }
+        Ok(Some(Self::issue_tokens(conn, snowflake_generator, secret, &user)?))
+    }
+
+    /// Revokes the given refresh token. Invalid or already revoked tokens are ignored.
+    pub fn logout(conn: &mut impl Queryable, secret: &str, refresh_token: &str) -> Result<()> {
+        if let Ok(claims) = decode_token(secret, refresh_token, TokenKind::Refresh) {
+            if let Some(token_id) = claims.jti {
+                RefreshTokenQueries::revoke(conn, token_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn issue_tokens(
+        conn: &mut impl Queryable,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        secret: &str,
+        user: &User
+    ) -> Result<AuthTokens> {
+        let expires_at = Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+        let token_id = RefreshTokenQueries::create_entity(
+            conn,
+            snowflake_generator,
+            user.id,
+            expires_at.naive_utc()
+        )?;
+
+        Ok(AuthTokens {
+            user_id: user.id,
+            access_token: create_access_token(secret, user.id)?,
+            refresh_token: create_refresh_token(
+                secret,
+                user.id,
+                token_id,
+                expires_at.timestamp()
+            )?,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_TTL_MINUTES * 60,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use crate::{
+        models::user::RequestCreateUser,
+        tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST },
+    };
+
+    #[test]
+    fn test_auth_tokens() -> Result<()> {
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+        let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
+        let secret = "test-secret";
+
+        let user_id = UserQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            UserQueries::new_user(
+                RequestCreateUser {
+                    email: "auth@example.com".to_string(),
+                    password: "password1".to_string(),
+                    first_name: "Auth".to_string(),
+                    last_name: "User".to_string(),
+                    date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+                    phone_number: None,
+                },
+                TEST_HASH_COST
+            )?
+        )?;
+
+        // wrong password doesn't issue tokens
+        let tokens = AuthQueries::login(
+            &mut conn,
+            snowflake_generator.clone(),
+            secret,
+            LoginForm {
+                email: "auth@example.com".to_string(),
+                password: "wrong".to_string(),
+            }
+        )?;
+        assert!(tokens.is_none());
+
+        let tokens = AuthQueries::login(
+            &mut conn,
+            snowflake_generator.clone(),
+            secret,
+            LoginForm {
+                email: "auth@example.com".to_string(),
+                password: "password1".to_string(),
+            }
+        )?.expect("valid credentials should issue tokens");
+        assert_eq!(tokens.user_id, user_id);
+
+        // refresh rotates the token: the old one can't be reused
+        let refreshed = AuthQueries::refresh(
+            &mut conn,
+            snowflake_generator.clone(),
+            secret,
+            &tokens.refresh_token
+        )?.expect("refresh token should be valid");
+        assert!(
+            AuthQueries::refresh(
+                &mut conn,
+                snowflake_generator.clone(),
+                secret,
+                &tokens.refresh_token
+            )?.is_none()
+        );
+
+        // after logout the refreshed token is revoked too
+        AuthQueries::logout(&mut conn, secret, &refreshed.refresh_token)?;
+        assert!(
+            AuthQueries::refresh(
+                &mut conn,
+                snowflake_generator.clone(),
+                secret,
+                &refreshed.refresh_token
+            )?.is_none()
+        );
+
+        cleanup_test_db(conn)?;
+
+        Ok(())
+    }
+}
@@ -3,15 +3,20 @@ pub mod organization;
 pub mod org_member;
 pub mod org_job;
 pub mod schedule;
-use mysql::PooledConn;
+pub mod auth;
 
-use crate::{ prototypes::create_table::DatabaseTable, models::result::Result };
+use crate::{
+    prototypes::create_table::DatabaseTable,
+    models::result::Result,
+    storage::Connection,
+};
 
 use self::{
     user::UserQueries,
     organization::OrgQueries,
     org_job::OrgJobQueries,
     org_member::OrgMemberQueries,
+    auth::RefreshTokenQueries,
     schedule::{
         availability::AvailabilityRequestQueries,
         availability_detail::AvailabilityDetailQueries,
@@ -21,11 +26,14 @@ use self::{
         shift_cover::ShiftCoverQueries,
         shift_trade::ShiftTradeQueries,
         shift_pickup::ShiftPickupQueries,
+        timeoff_request::TimeOffRequestQueries,
     },
 };
 
-/// create tables
-pub fn create_tables(conn: &mut PooledConn) -> Result<()> {
+/// Creates the tables as the models define them.
+///
+/// Databases are set up with `migrations::migrate_up`, which must end up with the same tables.
+pub fn create_tables(conn: &mut Connection) -> Result<()> {
     let table_queries = vec![
         Box::new(UserQueries {}) as Box<dyn DatabaseTable>,
         Box::new(OrgQueries {}) as Box<dyn DatabaseTable>,
@@ -38,7 +46,9 @@ pub fn create_tables(conn: &mut PooledConn) -> Result<()> {
         Box::new(OpenShiftQueries {}) as Box<dyn DatabaseTable>,
         Box::new(ShiftCoverQueries {}) as Box<dyn DatabaseTable>,
         Box::new(ShiftTradeQueries {}) as Box<dyn DatabaseTable>,
-        Box::new(ShiftPickupQueries {}) as Box<dyn DatabaseTable>
+        Box::new(ShiftPickupQueries {}) as Box<dyn DatabaseTable>,
+        Box::new(TimeOffRequestQueries {}) as Box<dyn DatabaseTable>,
+        Box::new(RefreshTokenQueries {}) as Box<dyn DatabaseTable>
     ];
 
     for table_query in table_queries {
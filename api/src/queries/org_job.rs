@@ -1,9 +1,10 @@
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         org_job::{ OrgJob, RequestCreateOrgJob, RequestUpdateOrgJob, create_org_job_table },
+        pagination::ListScope,
         result::Result,
     },
     prototypes::create_table::DatabaseTable,
@@ -13,8 +14,27 @@ use crate::prototypes::basic_queries::BasicQueries;
 
 pub struct OrgJobQueries {}
 
+impl OrgJobQueries {
+    /// Restricts a list to the jobs of an organization.
+    pub fn org_scope(org_id: i64) -> ListScope {
+        ListScope::column("org_id", org_id)
+    }
+
+    /// Retrieves all jobs of an organization.
+    pub fn find_by_org_id(conn: &mut impl Queryable, org_id: i64) -> Result<Vec<OrgJob>> {
+        Ok(
+            conn.exec(
+                format!("SELECT * FROM {} WHERE org_id = :org_id;", Self::table_name()),
+                params! {
+                    "org_id" => org_id,
+                }
+            )?
+        )
+    }
+}
+
 impl DatabaseTable for OrgJobQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_org_job_table();
         conn.query_drop(query)?;
         Ok(())
@@ -30,9 +50,17 @@ impl BasicQueries for OrgJobQueries {
         "org_jobs".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["name"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "name"]
+    }
+
     fn insert_statement() -> String {
         format!(
-            "INSERT INTO {} (id, org_id, name, description, base_pay_rate) VALUES (:id, :org_id, :name, :description, :base_pay_rate)",
+            "INSERT INTO {} (id, org_id, name, description, base_pay_rate, color) VALUES (:id, :org_id, :name, :description, :base_pay_rate, :color)",
             Self::table_name()
         )
     }
@@ -44,38 +72,28 @@ impl BasicQueries for OrgJobQueries {
                 "name" => &create_dto.name,
                 "description" => &create_dto.description,
                 "base_pay_rate" => create_dto.base_pay_rate,
+                "color" => &create_dto.color,
             }
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = format!("UPDATE {} SET ", Self::table_name());
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(name) = update_dto.name {
-            query.push_str("name = :name, ");
             params.push(("name".to_string(), name.into()));
         }
         if let Some(description) = update_dto.description {
-            query.push_str("description = :description, ");
             params.push(("description".to_string(), description.into()));
         }
         if let Some(base_pay_rate) = update_dto.base_pay_rate {
-            query.push_str("base_pay_rate = :base_pay_rate, ");
             params.push(("base_pay_rate".to_string(), base_pay_rate.into()));
         }
+        if let Some(color) = update_dto.color {
+            params.push(("color".to_string(), color.into()));
+        }
 
-        // Remove trailing comma and space
-        query.pop();
-        query.pop();
-
-        query.push_str(&format!(" WHERE id = :id;"));
-        params.push(("id".to_string(), id.into()));
-
-        let params = Params::from(params);
-        let query_result = conn.exec_iter(&query, params)?;
-
-        Ok(query_result.affected_rows())
+        params
     }
 }
 
@@ -90,14 +108,14 @@ mod tests {
     use crate::queries::organization::OrgQueries;
     use crate::queries::user::UserQueries;
     use crate::snowflake::SnowflakeGenerator;
-    use crate::tests::{ initialize_test_db, cleanup_test_db };
+    use crate::tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST };
 
     use chrono::NaiveDate;
 
     #[test]
     fn test_org_job_queries() -> Result<()> {
-        let pool = initialize_test_db()?;
-        let mut conn = pool.get_conn()?;
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
 
         let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
 
@@ -114,7 +132,7 @@ mod tests {
         let owner_user_id: i64 = UserQueries::create_entity(
             &mut conn,
             snowflake_generator.clone(),
-            user
+            UserQueries::new_user(user, TEST_HASH_COST)?
         )?;
 
         // Create an organization
@@ -153,6 +171,10 @@ mod tests {
         assert_eq!(job.description, Some("Develops software".to_string()));
         assert_eq!(job.base_pay_rate, 60.0);
 
+        // The placeholder job created with the organization is listed as well
+        let org_jobs = OrgJobQueries::find_by_org_id(&mut conn, org_id)?;
+        assert_eq!(org_jobs.len(), 2);
+
         // Test deleting the job
         let deleted_rows = OrgJobQueries::delete_entity(&mut conn, job_id)?;
         assert_eq!(deleted_rows, 1);
@@ -1,59 +1,129 @@
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable, Transaction },
     models::{
         org_member::{
             OrgMember,
             RequestCreateOrgMember,
             RequestUpdateOrgMember,
+            OrgRole,
+            OrgPermission,
+            PermissionDeniedError,
+            LastOwnerError,
             create_org_members_table_query,
         },
         result::Result,
     },
-    prototypes::create_table::DatabaseTable,
+    prototypes::{
+        atomic::Atomic,
+        create_table::DatabaseTable,
+        composite_key_queries::CompositeKeyQueries,
+        validate::ValidationError,
+    },
+    utilities::app_error::FieldError,
 };
 
 pub struct OrgMemberQueries {}
 
 impl DatabaseTable for OrgMemberQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_org_members_table_query();
-        let stmt = conn.prep(query)?;
-        conn.exec_drop(stmt, ())?;
+        conn.query_drop(query)?;
 
         Ok(())
     }
 }
 
-impl OrgMemberQueries {
+impl CompositeKeyQueries for OrgMemberQueries {
+    type Model = OrgMember;
+    type CreateDto = RequestCreateOrgMember;
+    type UpdateDto = RequestUpdateOrgMember;
+
     fn table_name() -> String {
         "org_members".to_string()
     }
 
+    fn parent_key() -> String {
+        "org_id".to_string()
+    }
+
+    fn child_key() -> String {
+        "user_id".to_string()
+    }
+
+    fn parent_id(create_dto: &Self::CreateDto) -> i64 {
+        create_dto.org_id
+    }
+
     fn insert_statement() -> String {
         format!(
-            "INSERT INTO {} (user_id, org_id, job_id) VALUES (:user_id, :org_id, :job_id)",
+            "INSERT INTO {} (user_id, org_id, job_id, role) VALUES (:user_id, :org_id, :job_id, :role)",
             Self::table_name()
         )
     }
 
-    fn insert_params(create_dto: &RequestCreateOrgMember) -> Result<Params> {
+    fn insert_params(create_dto: &Self::CreateDto) -> Result<Params> {
         Ok(
             params! {
                 "user_id" => create_dto.user_id,
                 "org_id" => create_dto.org_id,
                 "job_id" => create_dto.job_id,
+                "role" => create_dto.role.clone().unwrap_or_default().to_string(),
             }
         )
     }
 
-    pub fn create_entity(conn: &mut PooledConn, create_dto: RequestCreateOrgMember) -> Result<()> {
-        conn.exec_drop(Self::insert_statement(), Self::insert_params(&create_dto)?)?;
-        Ok(())
+    fn create_entity(conn: &mut impl Atomic, create_dto: Self::CreateDto) -> Result<()> {
+        conn.atomic(|tx| {
+            Self::ensure_org_job(tx, create_dto.org_id, create_dto.job_id)?;
+            tx.exec_drop(Self::insert_statement(), Self::insert_params(&create_dto)?)?;
+            Ok(())
+        })
     }
 
-    pub fn update_entity(conn: &mut PooledConn, update_dto: RequestUpdateOrgMember) -> Result<u64> {
+    fn update_entity(
+        conn: &mut impl Atomic,
+        org_id: i64,
+        user_id: i64,
+        update_dto: Self::UpdateDto
+    ) -> Result<u64> {
+        conn.atomic(|tx| {
+            if let Some(job_id) = update_dto.job_id {
+                Self::ensure_org_job(tx, org_id, job_id)?;
+            }
+            if update_dto.role.as_ref().is_some_and(|role| *role != OrgRole::OWNER) {
+                Self::ensure_other_owner(tx, org_id, user_id)?;
+            }
+            Self::update_member(tx, org_id, user_id, update_dto)
+        })
+    }
+
+    fn delete_entity(conn: &mut impl Atomic, org_id: i64, user_id: i64) -> Result<u64> {
+        conn.atomic(|tx| {
+            Self::ensure_other_owner(tx, org_id, user_id)?;
+            tx.exec_drop(
+                format!(
+                    "DELETE FROM {} WHERE org_id = :org_id AND user_id = :user_id;",
+                    Self::table_name()
+                ),
+                params! {
+                    "org_id" => org_id,
+                    "user_id" => user_id,
+                }
+            )?;
+            Ok(tx.affected_rows())
+        })
+    }
+}
+
+impl OrgMemberQueries {
+    fn update_member(
+        tx: &mut Transaction<'_>,
+        org_id: i64,
+        user_id: i64,
+        update_dto: RequestUpdateOrgMember
+    ) -> Result<u64> {
         let mut query = format!("UPDATE {} SET ", Self::table_name());
         let mut params: Vec<(String, Value)> = Vec::new();
 
@@ -61,6 +131,10 @@ impl OrgMemberQueries {
             query.push_str("job_id = :job_id, ");
             params.push(("job_id".to_string(), job_id.into()));
         }
+        if let Some(role) = update_dto.role {
+            query.push_str("role = :role, ");
+            params.push(("role".to_string(), role.to_string().into()));
+        }
 
         // Remove last comma and space if there are updates
         if !params.is_empty() {
@@ -70,57 +144,128 @@ impl OrgMemberQueries {
             return Ok(0);
         }
 
-        query.push_str(
-            &format!(" WHERE org_id = {} AND user_id = {};", update_dto.org_id, update_dto.user_id)
-        );
+        query.push_str(&format!(" WHERE org_id = {} AND user_id = {};", org_id, user_id));
 
         // Convert Vec to Params::Named
         let params = Params::from(params);
 
-        // Use exec_fold to execute the query and process the result
-        let query_result = conn.exec_iter(&query, params)?;
+        tx.exec_drop(&query, params)?;
 
         // Return the number of affected rows
-        Ok(query_result.affected_rows())
+        Ok(tx.affected_rows())
     }
 
-    pub fn delete_entity(conn: &mut PooledConn, org_id: i64, user_id: i64) -> Result<u64> {
-        let query_result = conn.query_iter(
-            format!(
-                "DELETE FROM {} WHERE org_id = {} AND user_id = {};",
-                Self::table_name(),
-                org_id,
-                user_id
+    /// Fails with a `ValidationError` unless `job_id` is a job of the organization.
+    fn ensure_org_job(tx: &mut Transaction<'_>, org_id: i64, job_id: i64) -> Result<()> {
+        let job_org_id: Option<i64> = tx.exec_first(
+            "SELECT org_id FROM org_jobs WHERE id = :job_id;",
+            params! { "job_id" => job_id }
+        )?;
+
+        if job_org_id == Some(org_id) {
+            Ok(())
+        } else {
+            Err(
+                Box::new(
+                    ValidationError(
+                        vec![FieldError::new("job_id", "is not a job of the organization")]
+                    )
+                )
             )
+        }
+    }
+
+    /// Fails with a `LastOwnerError` if the user is the only owner of the organization.
+    ///
+    /// The owners are locked until the end of the transaction, so two owners can't demote or
+    /// remove each other at the same time.
+    fn ensure_other_owner(tx: &mut Transaction<'_>, org_id: i64, user_id: i64) -> Result<()> {
+        let owners: Vec<i64> = tx.exec(
+            format!(
+                "SELECT user_id FROM {} WHERE org_id = :org_id AND role = :role FOR UPDATE;",
+                <Self as CompositeKeyQueries>::table_name()
+            ),
+            params! {
+                "org_id" => org_id,
+                "role" => OrgRole::OWNER.to_string(),
+            }
         )?;
 
-        Ok(query_result.affected_rows())
+        if owners == [user_id] {
+            Err(Box::new(LastOwnerError { org_id }))
+        } else {
+            Ok(())
+        }
     }
 
-    pub fn find_by_id(conn: &mut PooledConn, org_id: i64, user_id: i64) -> Result<OrgMember> {
-        // SQL query to select an User in the Organization
-        let query = format!(
-            "SELECT * FROM {} WHERE org_id = {} AND user_id = {};",
-            Self::table_name(),
-            org_id,
-            user_id
-        );
+    /// Retrieves the members of an organization that have the given role.
+    pub fn find_org_members_by_role(
+        conn: &mut impl Queryable,
+        org_id: i64,
+        role: OrgRole
+    ) -> Result<Vec<OrgMember>> {
+        Ok(
+            conn.exec(
+                format!(
+                    "SELECT * FROM {} WHERE org_id = :org_id AND role = :role;",
+                    <Self as CompositeKeyQueries>::table_name()
+                ),
+                params! {
+                    "org_id" => org_id,
+                    "role" => role.to_string(),
+                }
+            )?
+        )
+    }
 
-        // Execute the query
-        let result: Option<OrgMember> = conn.exec_first(query, ())?;
+    /// Returns the role of the user in the organization, or `None` if the user isn't a member.
+    pub fn find_role(conn: &mut impl Queryable, org_id: i64, user_id: i64) -> Result<Option<OrgRole>> {
+        let role: Option<String> = conn.exec_first(
+            format!(
+                "SELECT role FROM {} WHERE org_id = :org_id AND user_id = :user_id;",
+                <Self as CompositeKeyQueries>::table_name()
+            ),
+            params! {
+                "org_id" => org_id,
+                "user_id" => user_id,
+            }
+        )?;
 
-        // Extract the first row from the result (if any)
-        if let Some(model) = result {
-            // Convert the row into a Self::Model struct
-            Ok(model)
-        } else {
-            // Return an error if no user is found
-            Err(From::from("User not found"))
+        match role {
+            Some(role) => Ok(Some(role.parse::<OrgRole>()?)),
+            None => Ok(None),
         }
     }
 
-    pub fn find_org_members(conn: &mut PooledConn, org_id: i64) -> Result<Vec<OrgMember>> {
-        Ok(conn.query(format!("SELECT * FROM {} WHERE org_id = {};", Self::table_name(), org_id))?)
+    /// Checks whether the user is a member of the organization with a role granting `permission`,
+    /// e.g. whether the user can approve requests in that organization.
+    pub fn has_permission(
+        conn: &mut impl Queryable,
+        org_id: i64,
+        user_id: i64,
+        permission: &OrgPermission
+    ) -> Result<bool> {
+        // every organization route checks a permission, this tags its request with the org
+        tracing::Span::current().record("org_id", org_id);
+        Ok(
+            Self::find_role(conn, org_id, user_id)?
+                .map(|role| role.has_permission(permission))
+                .unwrap_or(false)
+        )
+    }
+
+    /// Same as `has_permission`, but fails with a `PermissionDeniedError` if the permission is missing.
+    pub fn ensure_permission(
+        conn: &mut impl Queryable,
+        org_id: i64,
+        user_id: i64,
+        permission: OrgPermission
+    ) -> Result<()> {
+        if Self::has_permission(conn, org_id, user_id, &permission)? {
+            Ok(())
+        } else {
+            Err(Box::new(PermissionDeniedError { user_id, org_id, permission }))
+        }
     }
 }
 
@@ -137,28 +282,31 @@ mod tests {
     use crate::queries::organization::OrgQueries;
     use crate::queries::user::UserQueries;
     use crate::snowflake::SnowflakeGenerator;
-    use crate::tests::{ initialize_test_db, cleanup_test_db };
+    use crate::tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST };
     use crate::models::user::RequestCreateUser;
     use crate::models::organization::RequestCreateOrganization;
 
     #[test]
     fn test_org_member_queries() -> Result<()> {
-        let pool = initialize_test_db()?;
-        let mut conn = pool.get_conn()?;
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
         let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
 
         // Create user
         let user_id = UserQueries::create_entity(
             &mut conn,
             snowflake_generator.clone(),
-            RequestCreateUser {
-                email: "testuser@example.com".to_string(),
-                password: "password".to_string(),
-                first_name: "Test".to_string(),
-                last_name: "User".to_string(),
-                date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
-                phone_number: Some("1234567890".to_string()),
-            }
+            UserQueries::new_user(
+                RequestCreateUser {
+                    email: "testuser@example.com".to_string(),
+                    password: "password".to_string(),
+                    first_name: "Test".to_string(),
+                    last_name: "User".to_string(),
+                    date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+                    phone_number: Some("1234567890".to_string()),
+                },
+                TEST_HASH_COST
+            )?
         )?;
 
         // Insert an organization
@@ -184,15 +332,106 @@ mod tests {
             }
         )?;
 
-        OrgMemberQueries::update_entity(&mut conn, RequestUpdateOrgMember {
-            org_id,
-            user_id,
+        OrgMemberQueries::update_entity(&mut conn, org_id, user_id, RequestUpdateOrgMember {
             job_id: Some(job_id),
+            ..Default::default()
         })?;
 
         let member = OrgMemberQueries::find_by_id(&mut conn, org_id, user_id)?;
         assert_eq!(member.job_id, job_id);
 
+        // The creator of the organization is its owner
+        assert_eq!(member.role, OrgRole::OWNER);
+        assert!(
+            OrgMemberQueries::has_permission(
+                &mut conn,
+                org_id,
+                user_id,
+                &OrgPermission::MANAGE_ORGANIZATION
+            )?
+        );
+
+        // The only owner can be neither demoted nor removed
+        let demote_owner = RequestUpdateOrgMember {
+            role: Some(OrgRole::EMPLOYEE),
+            ..Default::default()
+        };
+        let error = OrgMemberQueries::update_entity(
+            &mut conn,
+            org_id,
+            user_id,
+            demote_owner.clone()
+        ).unwrap_err();
+        assert!(error.is::<LastOwnerError>());
+        let error = OrgMemberQueries::delete_entity(&mut conn, org_id, user_id).unwrap_err();
+        assert!(error.is::<LastOwnerError>());
+        assert_eq!(OrgMemberQueries::find_role(&mut conn, org_id, user_id)?, Some(OrgRole::OWNER));
+
+        // Members can only hold a job of their organization
+        let other_org_id = OrgQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: "Other Organization".to_string(),
+                description: None,
+                owner_id: user_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let other_job_id = OrgJobQueries::find_by_org_id(&mut conn, other_org_id)?[0].id;
+        let error = OrgMemberQueries::update_entity(&mut conn, org_id, user_id, RequestUpdateOrgMember {
+            job_id: Some(other_job_id),
+            ..Default::default()
+        }).unwrap_err();
+        assert!(error.is::<ValidationError>());
+
+        let second_owner_id = UserQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            UserQueries::new_user(
+                RequestCreateUser {
+                    email: "secondowner@example.com".to_string(),
+                    password: "password".to_string(),
+                    first_name: "Second".to_string(),
+                    last_name: "Owner".to_string(),
+                    date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+                    phone_number: None,
+                },
+                TEST_HASH_COST
+            )?
+        )?;
+        let error = OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+            user_id: second_owner_id,
+            org_id,
+            job_id: other_job_id,
+            role: Some(OrgRole::OWNER),
+        }).unwrap_err();
+        assert!(error.is::<ValidationError>());
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+            user_id: second_owner_id,
+            org_id,
+            job_id,
+            role: Some(OrgRole::OWNER),
+        })?;
+
+        // With another owner, the first one can step down to a plain employee
+        OrgMemberQueries::update_entity(&mut conn, org_id, user_id, demote_owner)?;
+        assert_eq!(OrgMemberQueries::find_role(&mut conn, org_id, user_id)?, Some(OrgRole::EMPLOYEE));
+        assert!(
+            OrgMemberQueries::ensure_permission(
+                &mut conn,
+                org_id,
+                user_id,
+                OrgPermission::APPROVE_REQUESTS
+            ).is_err()
+        );
+        assert_eq!(
+            OrgMemberQueries::find_org_members_by_role(&mut conn, org_id, OrgRole::EMPLOYEE)?.len(),
+            1
+        );
+        assert_eq!(OrgMemberQueries::find_by_parent_id(&mut conn, org_id)?.len(), 2);
+
         // Delete organization member
         let deleted_rows = OrgMemberQueries::delete_entity(&mut conn, org_id, user_id)?;
         assert_eq!(deleted_rows, 1);
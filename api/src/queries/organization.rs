@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         organization::{
             Organization,
@@ -11,11 +11,16 @@ use crate::{
             RequestUpdateOrganization,
             create_organizations_table_query,
         },
-        result::Result,
+        result::{ Result, NotFoundError },
         org_job::RequestCreateOrgJob,
-        org_member::RequestCreateOrgMember,
+        org_member::{ RequestCreateOrgMember, OrgRole },
+    },
+    prototypes::{
+        basic_queries::BasicQueries,
+        create_table::DatabaseTable,
+        composite_key_queries::CompositeKeyQueries,
+        atomic::Atomic,
     },
-    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
     snowflake::SnowflakeGenerator,
 };
 
@@ -24,7 +29,7 @@ use super::{ org_job::OrgJobQueries, org_member::OrgMemberQueries };
 pub struct OrgQueries {}
 
 impl OrgQueries {
-    pub fn find_by_id_with_owner(conn: &mut PooledConn, id: i64) -> Result<Organization> {
+    pub fn find_by_id_with_owner(conn: &mut impl Queryable, id: i64) -> Result<Organization> {
         // SQL query to select a user by ID
         let query =
             format!("
@@ -37,6 +42,7 @@ impl OrgQueries {
                 org.timezone as timezone,
                 org.owner_id as owner_id,
                 org.icon as icon,
+                org.pickup_mode as pickup_mode,
                 user.email as owner_email,
                 user.first_name as owner_first_name,
                 user.last_name as owner_last_name,
@@ -56,17 +62,16 @@ impl OrgQueries {
             // Convert the row into a User struct
             Ok(model)
         } else {
-            // Return an error if no user is found
-            Err(From::from("User not found"))
+            // Return an error if no organization is found
+            Err(Box::new(NotFoundError("Organization not found".to_string())))
         }
     }
 }
 
 impl DatabaseTable for OrgQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_organizations_table_query();
-        let stmt = conn.prep(query)?;
-        conn.exec_drop(stmt, ())?;
+        conn.query_drop(query)?;
 
         Ok(())
     }
@@ -83,6 +88,14 @@ impl BasicQueries for OrgQueries {
         "organizations".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["name", "owner_id", "is_active", "pickup_mode"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "name", "updated_at"]
+    }
+
     fn insert_statement() -> String {
         format!(
             "INSERT INTO {} (id, name, description, owner_id) VALUES (:id, :name, :description, :owner_id)",
@@ -101,7 +114,7 @@ impl BasicQueries for OrgQueries {
     }
 
     fn create_entity_postprocessor(
-        conn: &mut PooledConn,
+        conn: &mut impl Atomic,
         snowflake_generator: Arc<SnowflakeGenerator>,
         create_dto: Self::CreateDto,
         id: i64
@@ -120,50 +133,33 @@ impl BasicQueries for OrgQueries {
             }
         )?;
 
-        let _ = OrgMemberQueries::create_entity(conn, RequestCreateOrgMember {
+        OrgMemberQueries::create_entity(conn, RequestCreateOrgMember {
             org_id,
             user_id: create_dto.owner_id,
             job_id,
+            role: Some(OrgRole::OWNER),
         })?;
 
         Ok(org_id)
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE organizations SET ".to_string();
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(name) = update_dto.name {
-            query.push_str("name = :name, ");
             params.push(("name".to_string(), name.into()));
         }
         if let Some(description) = update_dto.description {
-            query.push_str("description = :description, ");
             params.push(("description".to_string(), description.into()));
         }
         if let Some(owner_id) = update_dto.owner_id {
-            query.push_str("owner_id = :owner_id, ");
             params.push(("owner_id".to_string(), owner_id.into()));
         }
-
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
+        if let Some(pickup_mode) = update_dto.pickup_mode {
+            params.push(("pickup_mode".to_string(), pickup_mode.to_string().into()));
         }
 
-        query.push_str(&format!(" WHERE id = {};", id));
-
-        // Convert Vec to Params::Named
-        let params = Params::from(params);
-
-        // Use exec_fold to execute the query and process the result
-        let query_result = conn.exec_iter(&query, params)?;
-
-        // Return the number of affected rows
-        Ok(query_result.affected_rows())
+        params
     }
 }
 
@@ -171,7 +167,7 @@ impl BasicQueries for OrgQueries {
 mod tests {
     use crate::models::user::RequestCreateUser;
     use crate::queries::user::UserQueries;
-    use crate::tests::{ initialize_test_db, cleanup_test_db };
+    use crate::tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST };
 
     use super::*;
     use chrono::NaiveDate;
@@ -179,8 +175,8 @@ mod tests {
     #[test]
     fn test_organization_workflow() -> Result<()> {
         // Setup database connection
-        let pool = initialize_test_db()?;
-        let mut conn = pool.get_conn()?;
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
 
         let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
 
@@ -209,7 +205,7 @@ mod tests {
                     let Some(user_id) = UserQueries::create_entity(
                         &mut conn,
                         snowflake_generator.clone(),
-                        user.clone()
+                        UserQueries::new_user(user.clone(), TEST_HASH_COST).ok()?
                     ).ok()
                 {
                     Some(user_id)
@@ -244,6 +240,28 @@ mod tests {
         assert_eq!(org.name, "Dummy Organization".to_string());
         assert_eq!(org.description, Some("A test organization".to_string()));
 
+        // An organization created in a failing transaction is rolled back with its job and owner
+        let mut rolled_back_org_id = 0;
+        let result: Result<()> = conn.atomic(|tx| {
+            rolled_back_org_id = OrgQueries::create_entity(
+                tx,
+                snowflake_generator.clone(),
+                RequestCreateOrganization {
+                    name: "Rolled back Organization".to_string(),
+                    description: None,
+                    owner_id: user_ids[1],
+                    timezone: None,
+                    icon: None,
+                }
+            )?;
+            assert!(OrgQueries::find_by_id(tx, rolled_back_org_id).is_ok());
+            Err("Abort".into())
+        });
+        assert!(result.is_err());
+        assert_eq!(OrgQueries::find_all(&mut conn)?.len(), 1);
+        assert_eq!(OrgJobQueries::find_all(&mut conn)?.len(), 1);
+        assert!(OrgMemberQueries::find_by_parent_id(&mut conn, rolled_back_org_id)?.is_empty());
+
         // Clean up: Drop the database
         cleanup_test_db(conn)?;
 
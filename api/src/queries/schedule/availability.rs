@@ -1,19 +1,37 @@
-use std::sync::Arc;
+use std::{ collections::{ HashMap, hash_map::Entry }, sync::Arc };
 
+use chrono::{ Datelike, Duration, NaiveDate };
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
-        schedule::availability::{
-            AvailabilityRequest,
-            RequestCreateAvailability,
-            RequestUpdateAvailability,
-            create_availability_requests_table_query,
+        schedule::{
+            availability::{
+                AvailabilityRequest,
+                RequestCreateAvailability,
+                RequestUpdateAvailability,
+                AvailabilityWithDetails,
+                AvailabilityWeek,
+                MemberAvailability,
+                AvailabilityDay,
+                create_availability_requests_table_query,
+            },
+            availability_detail::{ AvailabilityDetail, DayOfWeek },
+            request_status::ScheduleRequestStatus,
+            InvalidStatusTransitionError,
         },
-        result::Result,
+        org_member::OrgPermission,
+        pagination::{ ListScope, SortOrder },
+        result::{ Result, NotFoundError },
+    },
+    prototypes::{
+        basic_queries::BasicQueries,
+        composite_key_queries::CompositeKeyQueries,
+        create_table::DatabaseTable,
+        atomic::Atomic,
     },
-    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
+    queries::org_member::OrgMemberQueries,
     snowflake::SnowflakeGenerator,
 };
 
@@ -22,9 +40,14 @@ use super::availability_detail::AvailabilityDetailQueries;
 pub struct AvailabilityRequestQueries;
 
 impl AvailabilityRequestQueries {
+    /// Restricts a list to the availability of an organization.
+    pub fn org_scope(org_id: i64) -> ListScope {
+        ListScope::column("org_id", org_id)
+    }
+
     /// Fetches the latest approved availability request for a specific user in an organization.
     pub fn get_current_availability(
-        conn: &mut PooledConn,
+        conn: &mut impl Queryable,
         user_id: i64,
         org_id: i64
     ) -> Result<Option<AvailabilityRequest>> {
@@ -48,9 +71,152 @@ impl AvailabilityRequestQueries {
         )
     }
 
+    /// Fetches the approved availability request in effect on `date` for a user in an organization,
+    /// i.e. the latest one starting on or before that date.
+    pub fn get_availability_on(
+        conn: &mut impl Queryable,
+        user_id: i64,
+        org_id: i64,
+        date: NaiveDate
+    ) -> Result<Option<AvailabilityRequest>> {
+        let query =
+            "SELECT * FROM availability_requests
+            WHERE user_id = :user_id
+                AND org_id = :org_id
+                AND status = 'APPROVED'
+                AND start_date <= :date
+            ORDER BY start_date DESC, id DESC
+            LIMIT 1;";
+
+        Ok(
+            conn.exec_first(
+                query,
+                params! {
+                    "user_id" => user_id,
+                    "org_id" => org_id,
+                    "date" => date.to_string(),
+                }
+            )?
+        )
+    }
+
+    /// Same as `get_availability_on`, along with the details of the request.
+    pub fn get_availability_with_details_on(
+        conn: &mut impl Queryable,
+        user_id: i64,
+        org_id: i64,
+        date: NaiveDate
+    ) -> Result<Option<AvailabilityWithDetails>> {
+        match Self::get_availability_on(conn, user_id, org_id, date)? {
+            Some(request) => {
+                let details = AvailabilityDetailQueries::get_request_details(conn, request.id)?;
+                Ok(Some(AvailabilityWithDetails { request, details }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the id of the organization the availability is submitted to.
+    pub fn find_org_id(conn: &mut impl Queryable, id: i64) -> Result<i64> {
+        let org_id: Option<i64> = conn.exec_first(
+            "SELECT org_id FROM availability_requests WHERE id = :id;",
+            params! { "id" => id }
+        )?;
+        org_id.ok_or_else(|| NotFoundError("Availability request not found".to_string()).into())
+    }
+
+    /// Moves a request to `status`, checking that `admin_id` is allowed to approve requests in
+    /// the organization when given.
+    pub fn transition(
+        conn: &mut impl Atomic,
+        id: i64,
+        status: ScheduleRequestStatus,
+        admin_id: Option<i64>
+    ) -> Result<()> {
+        if let Some(admin_id) = admin_id {
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        conn.atomic(|tx| {
+            let request: AvailabilityRequest = tx
+                .exec_first(
+                    "SELECT * FROM availability_requests WHERE id = :id FOR UPDATE;",
+                    params! { "id" => id }
+                )?
+                .ok_or_else(|| NotFoundError("Availability request not found".to_string()))?;
+            if !request.status.can_transition_to(&status) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&request.status, &status)));
+            }
+            tx.exec_drop(
+                "UPDATE availability_requests SET status = :status WHERE id = :id;",
+                params! { "status" => status.to_string(), "id" => id }
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Lays out the availability in effect for every member of an organization over the 7 days
+    /// starting on `start_date`.
+    pub fn get_org_week(
+        conn: &mut impl Queryable,
+        org_id: i64,
+        start_date: NaiveDate
+    ) -> Result<AvailabilityWeek> {
+        let end_date = start_date + Duration::days(6);
+        // every approved request that may be in effect during the week, latest first
+        let requests: Vec<AvailabilityRequest> = conn.exec(
+            "SELECT * FROM availability_requests
+            WHERE org_id = :org_id AND status = 'APPROVED' AND start_date <= :end_date
+            ORDER BY start_date DESC, id DESC;",
+            params! { "org_id" => org_id, "end_date" => end_date.to_string() }
+        )?;
+
+        let mut details_by_request: HashMap<i64, Vec<AvailabilityDetail>> = HashMap::new();
+        let mut members = Vec::new();
+        for member in OrgMemberQueries::find_by_parent_id(conn, org_id)? {
+            let mut days = Vec::new();
+            for date in start_date.iter_days().take(7) {
+                let request = requests
+                    .iter()
+                    .find(|request| request.user_id == member.user_id && request.start_date <= date);
+                let detail = match request {
+                    Some(request) => {
+                        let day_of_week = DayOfWeek::from(date.weekday());
+                        let details = match details_by_request.entry(request.id) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) =>
+                                entry.insert(
+                                    AvailabilityDetailQueries::get_request_details(conn, request.id)?
+                                ),
+                        };
+                        details
+                            .iter()
+                            .find(|detail| detail.day_of_week == day_of_week)
+                            .cloned()
+                    }
+                    None => None,
+                };
+                days.push(AvailabilityDay {
+                    date,
+                    request_id: request.map(|request| request.id),
+                    detail,
+                });
+            }
+            members.push(MemberAvailability { user_id: member.user_id, days });
+        }
+
+        Ok(AvailabilityWeek { start_date, members })
+    }
+
     /// Retrieves all availability requests made by a specific user.
     pub fn get_all_availability_requests(
-        conn: &mut PooledConn,
+        conn: &mut impl Queryable,
         user_id: i64
     ) -> Result<Vec<AvailabilityRequest>> {
         let query = format!(
@@ -63,7 +229,7 @@ impl AvailabilityRequestQueries {
 }
 
 impl DatabaseTable for AvailabilityRequestQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_availability_requests_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -79,6 +245,18 @@ impl BasicQueries for AvailabilityRequestQueries {
         "availability_requests".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["user_id", "status", "start_date", "updated_at"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "start_date", "updated_at"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("start_date", SortOrder::DESC)
+    }
+
     fn insert_statement() -> String {
         format!(
             r"INSERT INTO {} (id, user_id, org_id, start_date) VALUES (:id, :user_id, :org_id, :start_date);",
@@ -97,7 +275,7 @@ impl BasicQueries for AvailabilityRequestQueries {
     }
 
     fn create_entity_postprocessor(
-        conn: &mut PooledConn,
+        conn: &mut impl Atomic,
         _snowflake_generator: Arc<SnowflakeGenerator>,
         create_dto: Self::CreateDto,
         id: i64
@@ -109,32 +287,25 @@ impl BasicQueries for AvailabilityRequestQueries {
         Ok(request_id)
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE availability_requests SET ".to_string();
+    fn update_entity(conn: &mut impl Atomic, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
+        if let Some(status) = &update_dto.status {
+            let request = Self::find_by_id(conn, id)?;
+            if !request.status.can_transition_to(status) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&request.status, status)));
+            }
+        }
+
+        Self::update_columns(conn, id, Self::update_params(update_dto))
+    }
+
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(status) = update_dto.status {
-            query.push_str("status = :status, ");
             params.push(("status".to_string(), status.to_string().into()));
-        } else {
-            return Ok(0);
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
-        }
-
-        query.push_str(&format!(" WHERE id = :id;"));
-        params.push(("id".to_string(), id.into()));
-
-        let params = Params::from(params);
-        let query_result = conn.exec_iter(&query, params)?;
-
-        Ok(query_result.affected_rows())
+        params
     }
 }
 
@@ -144,7 +315,7 @@ mod tests {
 
     use super::*;
     use crate::{
-        tests::{ initialize_test_db, cleanup_test_db },
+        tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST },
         queries::{ user::UserQueries, organization::OrgQueries },
         models::{
             organization::RequestCreateOrganization,
@@ -160,8 +331,8 @@ mod tests {
     #[test]
     fn test_availability_requests() -> Result<()> {
         // Setup database connection
-        let pool = initialize_test_db()?;
-        let mut conn = pool.get_conn()?;
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
         let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
 
         // Create a user and an organization for testing
@@ -306,23 +477,22 @@ mod tests {
     }
 
     fn create_test_user(
-        conn: &mut PooledConn,
+        conn: &mut Connection,
         snowflake_generator: Arc<SnowflakeGenerator>
     ) -> Result<i64> {
-        Ok(
-            UserQueries::create_entity(conn, snowflake_generator.clone(), RequestCreateUser {
-                email: "testuser@example.com".to_string(),
-                password: "password".to_string(),
-                first_name: "Test".to_string(),
-                last_name: "User".to_string(),
-                date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
-                phone_number: Some("1234567890".to_string()),
-            })?
-        )
+        let user = UserQueries::new_user(RequestCreateUser {
+            email: "testuser@example.com".to_string(),
+            password: "password".to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+            date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+            phone_number: Some("1234567890".to_string()),
+        }, TEST_HASH_COST)?;
+        Ok(UserQueries::create_entity(conn, snowflake_generator.clone(), user)?)
     }
 
     fn create_test_organization(
-        conn: &mut PooledConn,
+        conn: &mut Connection,
         snowflake_generator: Arc<SnowflakeGenerator>,
         user_id: i64
     ) -> Result<i64> {
@@ -1,4 +1,5 @@
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         schedule::availability_detail::{
             AvailabilityDetail,
@@ -10,12 +11,11 @@ use crate::{
     prototypes::create_table::DatabaseTable,
 };
 use mysql::*;
-use mysql::prelude::*;
 
 pub struct AvailabilityDetailQueries;
 
 impl DatabaseTable for AvailabilityDetailQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_availability_details_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -51,7 +51,7 @@ impl AvailabilityDetailQueries {
     }
 
     pub fn create_entity(
-        conn: &mut PooledConn,
+        conn: &mut impl Queryable,
         request_id: i64,
         create_dto: RequestCreateAvailabilityDetail
     ) -> Result<()> {
@@ -60,7 +60,7 @@ impl AvailabilityDetailQueries {
     }
 
     pub fn create_entities(
-        conn: &mut PooledConn,
+        conn: &mut impl Queryable,
         request_id: i64,
         create_dtos: Vec<RequestCreateAvailabilityDetail>
     ) -> Result<()> {
@@ -77,7 +77,7 @@ impl AvailabilityDetailQueries {
     }
 
     pub fn get_request_details(
-        conn: &mut PooledConn,
+        conn: &mut impl Queryable,
         request_id: i64
     ) -> Result<Vec<AvailabilityDetail>> {
         Ok(
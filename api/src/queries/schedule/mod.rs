@@ -12,25 +12,41 @@ pub mod shift_trade;
 mod tests {
     use std::sync::Arc;
 
-    use chrono::NaiveDate;
-
+    use chrono::{ Duration, Local, NaiveDate, NaiveTime };
+    
     use crate::{
+        storage::Connection,
         models::{
             result::Result,
             user::RequestCreateUser,
-            organization::RequestCreateOrganization,
+            organization::{ RequestCreateOrganization, RequestUpdateOrganization, PickupMode },
             schedule::{
                 work_schedule::RequestCreateWorkSchedule,
-                shift::{ RequestCreateShift, Shift },
+                shift::{
+                    RequestCreateShift,
+                    RequestUpdateShift,
+                    Shift,
+                    ShiftValidationError,
+                    ShiftValidationRule,
+                    SchedulingWarningKind,
+                    AvailabilityPolicy,
+                },
+                availability::{ RequestCreateAvailability, RequestUpdateAvailability },
+                availability_detail::{ RequestCreateAvailabilityDetail, DayOfWeek },
+                timeoff_request::{ RequestCreateTimeOff, RequestUpdateTimeOff },
+                request_status::ScheduleRequestStatus,
                 shift_trade::{ ShiftTrade, RequestCreateShiftTrade },
                 ShiftRequestStatus,
                 RequestUpdateShiftRequest,
+                InvalidStatusTransitionError,
                 shift_cover::RequestCreateShiftCover,
+                shift_pickup::RequestCreateShiftPickup,
+                open_shift::RequestCreateOpenShift,
             },
             org_job::RequestCreateOrgJob,
             org_member::RequestCreateOrgMember,
         },
-        tests::{ initialize_test_db, cleanup_test_db },
+        tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST },
         queries::{
             user::UserQueries,
             organization::OrgQueries,
@@ -39,11 +55,15 @@ mod tests {
                 shift::ShiftQueries,
                 shift_trade::ShiftTradeQueries,
                 shift_cover::ShiftCoverQueries,
+                shift_pickup::ShiftPickupQueries,
+                open_shift::OpenShiftQueries,
+                availability::AvailabilityRequestQueries,
+                timeoff_request::TimeOffRequestQueries,
             },
             org_job::OrgJobQueries,
             org_member::OrgMemberQueries,
         },
-        prototypes::basic_queries::BasicQueries,
+        prototypes::{ basic_queries::BasicQueries, composite_key_queries::CompositeKeyQueries },
         utilities::parse_chrono::parse_naive_date_time_from_str,
         snowflake::SnowflakeGenerator,
     };
@@ -51,8 +71,8 @@ mod tests {
     #[test]
     fn test_work_schedule_queries() -> Result<()> {
         // Setup database connection
-        let pool = initialize_test_db()?;
-        let mut conn = pool.get_conn()?;
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
         let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
 
         // Create a user
@@ -68,7 +88,7 @@ mod tests {
         let owner_user_id: i64 = UserQueries::create_entity(
             &mut conn,
             snowflake_generator.clone(),
-            user
+            UserQueries::new_user(user, TEST_HASH_COST)?
         )?;
 
         // Create an organization
@@ -115,58 +135,70 @@ mod tests {
         let employee1_user_id = UserQueries::create_entity(
             &mut conn,
             snowflake_generator.clone(),
-            RequestCreateUser {
-                email: "john_doe@example.com".to_string(),
-                password: "john_doe".to_string(),
-                first_name: "John".to_string(),
-                last_name: "Doe".to_string(),
-                date_of_birth: NaiveDate::from_ymd_opt(2000, 11, 2).unwrap(),
-                phone_number: Some("1234567890".to_string()),
-            }
+            UserQueries::new_user(
+                RequestCreateUser {
+                    email: "john_doe@example.com".to_string(),
+                    password: "john_doe".to_string(),
+                    first_name: "John".to_string(),
+                    last_name: "Doe".to_string(),
+                    date_of_birth: NaiveDate::from_ymd_opt(2000, 11, 2).unwrap(),
+                    phone_number: Some("1234567890".to_string()),
+                },
+                TEST_HASH_COST
+            )?
         )?;
 
-        let _ = OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
             org_id,
             user_id: employee1_user_id,
             job_id,
+            role: None,
         })?;
 
         let employee2_user_id = UserQueries::create_entity(
             &mut conn,
             snowflake_generator.clone(),
-            RequestCreateUser {
-                email: "lwilliamson@example.com".to_string(),
-                password: "lwilliamson".to_string(),
-                first_name: "Lori".to_string(),
-                last_name: "Williamson".to_string(),
-                date_of_birth: NaiveDate::from_ymd_opt(2000, 11, 2).unwrap(),
-                phone_number: Some("9095141197".to_string()),
-            }
+            UserQueries::new_user(
+                RequestCreateUser {
+                    email: "lwilliamson@example.com".to_string(),
+                    password: "lwilliamson".to_string(),
+                    first_name: "Lori".to_string(),
+                    last_name: "Williamson".to_string(),
+                    date_of_birth: NaiveDate::from_ymd_opt(2000, 11, 2).unwrap(),
+                    phone_number: Some("9095141197".to_string()),
+                },
+                TEST_HASH_COST
+            )?
         )?;
 
-        let _ = OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
             org_id,
             user_id: employee2_user_id,
             job_id,
+            role: None,
         })?;
 
         let employee3_user_id = UserQueries::create_entity(
             &mut conn,
             snowflake_generator.clone(),
-            RequestCreateUser {
-                email: "msosa@example.com".to_string(),
-                password: "msosa111".to_string(),
-                first_name: "Michele".to_string(),
-                last_name: "Sosa".to_string(),
-                date_of_birth: NaiveDate::from_ymd_opt(2000, 11, 2).unwrap(),
-                phone_number: Some("5122755798".to_string()),
-            }
+            UserQueries::new_user(
+                RequestCreateUser {
+                    email: "msosa@example.com".to_string(),
+                    password: "msosa111".to_string(),
+                    first_name: "Michele".to_string(),
+                    last_name: "Sosa".to_string(),
+                    date_of_birth: NaiveDate::from_ymd_opt(2000, 11, 2).unwrap(),
+                    phone_number: Some("5122755798".to_string()),
+                },
+                TEST_HASH_COST
+            )?
         )?;
 
-        let _ = OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
             org_id,
             user_id: employee3_user_id,
             job_id,
+            role: None,
         })?;
 
         // start create and send shift requests
@@ -179,6 +211,7 @@ mod tests {
                 end_time: parse_naive_date_time_from_str("2024-01-01 16:00:00")?,
                 pay_rate: Some(20.0),
                 note: Some("Morning Shift".to_string()),
+                availability_policy: None,
             },
             RequestCreateShift {
                 user_id: employee2_user_id,
@@ -188,6 +221,7 @@ mod tests {
                 end_time: parse_naive_date_time_from_str("2024-01-01 17:00:00")?,
                 pay_rate: Some(22.0),
                 note: None,
+                availability_policy: None,
             }
         ];
 
@@ -203,6 +237,87 @@ mod tests {
             println!("{shift:?}");
         }
 
+        // shifts are validated before being booked
+        let validation_rule = |result: Result<i64>| -> Option<ShiftValidationRule> {
+            result
+                .err()
+                .and_then(|e| e.downcast_ref::<ShiftValidationError>().map(|e| e.rule.clone()))
+        };
+        let shift_request = |user_id: i64, start_time: &str, end_time: &str| RequestCreateShift {
+            user_id,
+            schedule_id,
+            job_id,
+            start_time: parse_naive_date_time_from_str(start_time).unwrap(),
+            end_time: parse_naive_date_time_from_str(end_time).unwrap(),
+            pay_rate: None,
+            note: None,
+            availability_policy: None,
+        };
+
+        assert_eq!(
+            validation_rule(
+                ShiftQueries::create_entity(
+                    &mut conn,
+                    snowflake_generator.clone(),
+                    shift_request(employee1_user_id, "2024-01-01 15:00:00", "2024-01-01 20:00:00")
+                )
+            ),
+            Some(ShiftValidationRule::OVERLAP)
+        );
+        assert_eq!(
+            validation_rule(
+                ShiftQueries::create_entity(
+                    &mut conn,
+                    snowflake_generator.clone(),
+                    shift_request(employee1_user_id, "2024-01-02 15:00:00", "2024-01-02 09:00:00")
+                )
+            ),
+            Some(ShiftValidationRule::END_BEFORE_START)
+        );
+        assert_eq!(
+            validation_rule(
+                ShiftQueries::create_entity(
+                    &mut conn,
+                    snowflake_generator.clone(),
+                    shift_request(employee1_user_id, "2024-01-09 09:00:00", "2024-01-09 17:00:00")
+                )
+            ),
+            Some(ShiftValidationRule::OUTSIDE_SCHEDULE)
+        );
+
+        // back to back shifts don't overlap
+        let evening_shift_id = ShiftQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            shift_request(employee1_user_id, "2024-01-01 16:00:00", "2024-01-01 20:00:00")
+        )?;
+
+        // a batch is rejected as a whole when two of its shifts overlap
+        assert!(
+            ShiftQueries::create_many(
+                &mut conn,
+                snowflake_generator.clone(),
+                vec![
+                    shift_request(employee3_user_id, "2024-01-02 09:00:00", "2024-01-02 17:00:00"),
+                    shift_request(employee3_user_id, "2024-01-02 12:00:00", "2024-01-02 18:00:00")
+                ]
+            ).is_err()
+        );
+        assert_eq!(ShiftQueries::find_all(&mut conn)?.len(), all_shifts.len() + 1);
+
+        // a shift doesn't collide with itself when moved, but can't be moved onto another one
+        ShiftQueries::update_entity(&mut conn, evening_shift_id, RequestUpdateShift {
+            end_time: Some(parse_naive_date_time_from_str("2024-01-01 21:00:00")?),
+            ..Default::default()
+        })?;
+        assert!(
+            ShiftQueries::update_entity(&mut conn, evening_shift_id, RequestUpdateShift {
+                start_time: Some(parse_naive_date_time_from_str("2024-01-01 15:00:00")?),
+                ..Default::default()
+            }).is_err()
+        );
+        ShiftQueries::delete_entity(&mut conn, evening_shift_id)?;
+
         // test shift trade operations
         let shift_trade_request_id = ShiftTradeQueries::create_entity(
             &mut conn,
@@ -240,8 +355,67 @@ mod tests {
             Some("test declining shift trade request".to_string())
         );
 
+        // an employee can't act as the admin of a request
+        assert!(
+            ShiftTradeQueries::update_entity(
+                &mut conn,
+                shift_trade_request_id,
+                RequestUpdateShiftRequest {
+                    admin_id: Some(employee3_user_id),
+                    ..Default::default()
+                }
+            ).is_err()
+        );
+
+        // trades follow their workflow and past shifts can't change hands
+        let shift_trade_request_id = ShiftTradeQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateShiftTrade {
+                shift1_id: all_shifts[0].id,
+                shift2_id: all_shifts[1].id,
+            }
+        )?;
+        assert!(
+            ShiftTradeQueries::transition(
+                &mut conn,
+                shift_trade_request_id,
+                ShiftRequestStatus::APPROVED,
+                Some(owner_user_id),
+                None
+            )
+                .unwrap_err()
+                .is::<InvalidStatusTransitionError>()
+        );
+        ShiftTradeQueries::transition(
+            &mut conn,
+            shift_trade_request_id,
+            ShiftRequestStatus::PEER_ACCEPTED,
+            None,
+            None
+        )?;
+        let approve_error = ShiftTradeQueries::transition(
+            &mut conn,
+            shift_trade_request_id,
+            ShiftRequestStatus::APPROVED,
+            Some(owner_user_id),
+            None
+        ).unwrap_err();
+        assert_eq!(
+            approve_error.downcast_ref::<ShiftValidationError>().map(|e| e.rule.clone()),
+            Some(ShiftValidationRule::ALREADY_STARTED)
+        );
+        assert_eq!(
+            ShiftTradeQueries::find_by_id(&mut conn, shift_trade_request_id)?.status,
+            ShiftRequestStatus::PEER_ACCEPTED
+        );
+        assert_eq!(
+            ShiftQueries::find_by_id(&mut conn, all_shifts[0].id)?.user_id,
+            all_shifts[0].user_id
+        );
+
         // test shift cover operations
-        let _shift_cover_request_id = ShiftCoverQueries::create_entity(
+        let shift_cover_request_id = ShiftCoverQueries::create_entity(
             &mut conn,
             snowflake_generator.clone(),
             RequestCreateShiftCover {
@@ -250,6 +424,313 @@ mod tests {
             }
         )?;
 
+        // covers go to another member of the organization who is free during the shift
+        let cover_rule = |conn: &mut Connection, cover_user_id: i64| {
+            ShiftCoverQueries::create_entity(
+                conn,
+                snowflake_generator.clone(),
+                RequestCreateShiftCover {
+                    shift_id: all_shifts[0].id,
+                    cover_user_id,
+                }
+            )
+                .err()
+                .and_then(|e| e.downcast_ref::<ShiftValidationError>().map(|e| e.rule.clone()))
+        };
+        assert_eq!(
+            cover_rule(&mut conn, all_shifts[0].user_id),
+            Some(ShiftValidationRule::SAME_MEMBER)
+        );
+        assert_eq!(
+            cover_rule(&mut conn, all_shifts[1].user_id),
+            Some(ShiftValidationRule::OVERLAP)
+        );
+        let outsider_user_id = UserQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            UserQueries::new_user(
+                RequestCreateUser {
+                    email: "outsider@example.com".to_string(),
+                    password: "outsider".to_string(),
+                    first_name: "Out".to_string(),
+                    last_name: "Sider".to_string(),
+                    date_of_birth: NaiveDate::from_ymd_opt(2000, 11, 2).unwrap(),
+                    phone_number: None,
+                },
+                TEST_HASH_COST
+            )?
+        )?;
+        assert_eq!(
+            cover_rule(&mut conn, outsider_user_id),
+            Some(ShiftValidationRule::NOT_A_MEMBER)
+        );
+
+        // past shifts can't be handed over
+        ShiftCoverQueries::transition(
+            &mut conn,
+            shift_cover_request_id,
+            ShiftRequestStatus::PEER_ACCEPTED,
+            None,
+            None
+        )?;
+        let approve_error = ShiftCoverQueries::transition(
+            &mut conn,
+            shift_cover_request_id,
+            ShiftRequestStatus::APPROVED,
+            Some(owner_user_id),
+            None
+        ).unwrap_err();
+        assert_eq!(
+            approve_error.downcast_ref::<ShiftValidationError>().map(|e| e.rule.clone()),
+            Some(ShiftValidationRule::ALREADY_STARTED)
+        );
+        assert_eq!(
+            ShiftQueries::find_by_id(&mut conn, all_shifts[0].id)?.user_id,
+            all_shifts[0].user_id
+        );
+
+        // test scheduling warnings against availability and time off
+        let availability_id = AvailabilityRequestQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateAvailability {
+                user_id: employee2_user_id,
+                org_id,
+                start_date: NaiveDate::from_ymd_opt(2023, 12, 1).unwrap(),
+                details: vec![
+                    RequestCreateAvailabilityDetail {
+                        day_of_week: DayOfWeek::TUESDAY,
+                        is_available: false,
+                        whole_day: true,
+                        ..Default::default()
+                    },
+                    RequestCreateAvailabilityDetail {
+                        day_of_week: DayOfWeek::WEDNESDAY,
+                        is_available: true,
+                        whole_day: false,
+                        preferred_start_time: NaiveTime::from_hms_opt(9, 0, 0),
+                        preferred_end_time: NaiveTime::from_hms_opt(13, 0, 0),
+                    }
+                ],
+            }
+        )?;
+        AvailabilityRequestQueries::update_entity(
+            &mut conn,
+            availability_id,
+            RequestUpdateAvailability {
+                status: Some(ScheduleRequestStatus::APPROVED),
+            }
+        )?;
+
+        let time_off_id = TimeOffRequestQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateTimeOff {
+                user_id: employee3_user_id,
+                org_id,
+                start_time: parse_naive_date_time_from_str("2024-01-04 00:00:00")?,
+                end_time: parse_naive_date_time_from_str("2024-01-05 00:00:00")?,
+                reason: None,
+            }
+        )?;
+        TimeOffRequestQueries::update_entity(&mut conn, time_off_id, RequestUpdateTimeOff {
+            status: Some(ScheduleRequestStatus::APPROVED),
+            admin_id: Some(owner_user_id),
+            reason: None,
+        })?;
+        // Approved time off can only be cancelled
+        assert!(
+            TimeOffRequestQueries::transition(
+                &mut conn,
+                time_off_id,
+                ScheduleRequestStatus::DECLINED,
+                Some(owner_user_id)
+            )
+                .unwrap_err()
+                .is::<InvalidStatusTransitionError>()
+        );
+
+        let warning_kinds = |
+            conn: &mut Connection,
+            user_id: i64,
+            start_time: &str,
+            end_time: &str
+        | -> Result<Vec<SchedulingWarningKind>> {
+            Ok(
+                ShiftQueries::scheduling_warnings(
+                    conn,
+                    org_id,
+                    user_id,
+                    parse_naive_date_time_from_str(start_time)?,
+                    parse_naive_date_time_from_str(end_time)?
+                )?
+                    .into_iter()
+                    .map(|warning| warning.kind)
+                    .collect()
+            )
+        };
+        assert_eq!(
+            warning_kinds(&mut conn, employee2_user_id, "2024-01-02 09:00:00", "2024-01-02 17:00:00")?,
+            vec![SchedulingWarningKind::UNAVAILABLE]
+        );
+        assert_eq!(
+            warning_kinds(&mut conn, employee2_user_id, "2024-01-03 10:00:00", "2024-01-03 15:00:00")?,
+            vec![SchedulingWarningKind::OUTSIDE_PREFERRED_TIME]
+        );
+        assert!(
+            warning_kinds(
+                &mut conn,
+                employee2_user_id,
+                "2024-01-03 09:00:00",
+                "2024-01-03 13:00:00"
+            )?.is_empty()
+        );
+        assert_eq!(
+            warning_kinds(&mut conn, employee3_user_id, "2024-01-04 09:00:00", "2024-01-04 17:00:00")?,
+            vec![SchedulingWarningKind::TIME_OFF]
+        );
+
+        // the REJECT policy refuses shifts raising warnings, WARN books them anyway
+        let mut time_off_shift = shift_request(
+            employee3_user_id,
+            "2024-01-04 09:00:00",
+            "2024-01-04 17:00:00"
+        );
+        time_off_shift.availability_policy = Some(AvailabilityPolicy::REJECT);
+        assert_eq!(
+            validation_rule(
+                ShiftQueries::create_entity(
+                    &mut conn,
+                    snowflake_generator.clone(),
+                    time_off_shift.clone()
+                )
+            ),
+            Some(ShiftValidationRule::AVAILABILITY)
+        );
+        time_off_shift.availability_policy = Some(AvailabilityPolicy::WARN);
+        ShiftQueries::create_entity(&mut conn, snowflake_generator.clone(), time_off_shift)?;
+
+        // open shifts keep their dates and are picked up by as many members as they need
+        let next_week = Local::now().date_naive() + Duration::days(7);
+        let next_schedule_id = WorkScheduleQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date: next_week,
+                end_date: next_week + Duration::days(6),
+            }
+        )?;
+        let open_shift_start = next_week.and_hms_opt(9, 0, 0).unwrap();
+        let open_shift_id = OpenShiftQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateOpenShift {
+                schedule_id: next_schedule_id,
+                job_id,
+                start_time: open_shift_start,
+                end_time: open_shift_start + Duration::hours(8),
+                pay_rate: Some(25.0),
+                headcount: Some(2),
+            }
+        )?;
+        let open_shift = OpenShiftQueries::find_by_id(&mut conn, open_shift_id)?;
+        assert_eq!(open_shift.start_time, open_shift_start);
+        assert_eq!(open_shift.end_time, open_shift_start + Duration::hours(8));
+        assert_eq!((open_shift.headcount, open_shift.filled_count), (2, 0));
+
+        let pickup_ids = [employee1_user_id, employee2_user_id, employee3_user_id]
+            .into_iter()
+            .map(|user_id| {
+                ShiftPickupQueries::create_entity(
+                    &mut conn,
+                    snowflake_generator.clone(),
+                    RequestCreateShiftPickup {
+                        openshift_id: open_shift_id,
+                        user_id,
+                    }
+                )
+            })
+            .collect::<Result<Vec<i64>>>()?;
+        for pickup_id in &pickup_ids {
+            assert_eq!(
+                ShiftPickupQueries::find_by_id(&mut conn, *pickup_id)?.status,
+                ShiftRequestStatus::PENDING
+            );
+        }
+
+        for pickup_id in &pickup_ids[..2] {
+            ShiftPickupQueries::transition(
+                &mut conn,
+                snowflake_generator.clone(),
+                *pickup_id,
+                ShiftRequestStatus::APPROVED,
+                Some(owner_user_id),
+                None
+            )?;
+        }
+        let open_shift = OpenShiftQueries::find_by_id(&mut conn, open_shift_id)?;
+        assert!(open_shift.is_filled());
+        assert_eq!(
+            ShiftPickupQueries::find_by_id(&mut conn, pickup_ids[2])?.status,
+            ShiftRequestStatus::DECLINED
+        );
+        let picked_up_shifts = ShiftQueries::find_by_schedule_id(&mut conn, next_schedule_id)?;
+        assert_eq!(picked_up_shifts.len(), 2);
+        assert!(
+            picked_up_shifts
+                .iter()
+                .all(|shift| {
+                    shift.start_time == open_shift.start_time &&
+                        shift.end_time == open_shift.end_time &&
+                        shift.job_id == job_id &&
+                        shift.pay_rate == Some(25.0)
+                })
+        );
+
+        // organizations can hand out open shifts on a first come, first served basis
+        OrgQueries::update_entity(&mut conn, org_id, RequestUpdateOrganization {
+            pickup_mode: Some(PickupMode::FIRST_COME_FIRST_SERVED),
+            ..Default::default()
+        })?;
+        let next_open_shift_id = OpenShiftQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateOpenShift {
+                schedule_id: next_schedule_id,
+                job_id,
+                start_time: open_shift_start + Duration::days(1),
+                end_time: open_shift_start + Duration::days(1) + Duration::hours(8),
+                pay_rate: None,
+                headcount: None,
+            }
+        )?;
+        let first_pickup_id = ShiftPickupQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateShiftPickup {
+                openshift_id: next_open_shift_id,
+                user_id: employee3_user_id,
+            }
+        )?;
+        assert_eq!(
+            ShiftPickupQueries::find_by_id(&mut conn, first_pickup_id)?.status,
+            ShiftRequestStatus::APPROVED
+        );
+        assert_eq!(
+            validation_rule(
+                ShiftPickupQueries::create_entity(
+                    &mut conn,
+                    snowflake_generator.clone(),
+                    RequestCreateShiftPickup {
+                        openshift_id: next_open_shift_id,
+                        user_id: employee1_user_id,
+                    }
+                )
+            ),
+            Some(ShiftValidationRule::ALREADY_FILLED)
+        );
+
         // Clean up: Drop the database
         cleanup_test_db(conn)?;
 
@@ -1,7 +1,9 @@
+use std::sync::Arc;
+
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         schedule::open_shift::{
             OpenShift,
@@ -9,15 +11,47 @@ use crate::{
             RequestUpdateOpenShift,
             create_open_shifts_table_query,
         },
-        result::Result,
+        pagination::{ ListScope, SortOrder },
+        result::{ Result, NotFoundError },
     },
-    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
+    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable, atomic::Atomic },
+    snowflake::SnowflakeGenerator,
 };
 
+use super::{ shift::ShiftQueries, work_schedule::WorkScheduleQueries };
+
 pub struct OpenShiftQueries;
 
+impl OpenShiftQueries {
+    /// Restricts a list to the open shifts of a schedule.
+    pub fn schedule_scope(schedule_id: i64) -> ListScope {
+        ListScope::column("schedule_id", schedule_id)
+    }
+
+    /// Returns the id of the organization the open shift belongs to, through its schedule.
+    pub fn find_org_id(conn: &mut impl Queryable, id: i64) -> Result<i64> {
+        let org_id: Option<i64> = conn.exec_first(
+            "SELECT ws.org_id FROM open_shifts open_shift
+            JOIN work_schedules ws ON ws.id = open_shift.schedule_id
+            WHERE open_shift.id = :id;",
+            params! { "id" => id }
+        )?;
+        org_id.ok_or_else(|| NotFoundError("Open shift not found".to_string()).into())
+    }
+
+    /// Retrieves the open shifts of a schedule, ordered by start time.
+    pub fn find_by_schedule_id(conn: &mut impl Queryable, schedule_id: i64) -> Result<Vec<OpenShift>> {
+        Ok(
+            conn.exec(
+                "SELECT * FROM open_shifts WHERE schedule_id = :schedule_id ORDER BY start_time;",
+                params! { "schedule_id" => schedule_id }
+            )?
+        )
+    }
+}
+
 impl DatabaseTable for OpenShiftQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_open_shifts_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -33,9 +67,22 @@ impl BasicQueries for OpenShiftQueries {
         "open_shifts".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["job_id", "start_time", "end_time"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "start_time", "end_time"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("start_time", SortOrder::ASC)
+    }
+
     fn insert_statement() -> String {
         format!(
-            "INSERT INTO {} (id, schedule_id, job_id, start_time, end_time, pay_rate) VALUES (:id, :schedule_id, :job_id, :start_time, :end_time, :pay_rate)",
+            "INSERT INTO {} (id, schedule_id, job_id, start_time, end_time, pay_rate, headcount)
+              VALUES (:id, :schedule_id, :job_id, :start_time, :end_time, :pay_rate, :headcount)",
             Self::table_name()
         )
     }
@@ -45,50 +92,55 @@ impl BasicQueries for OpenShiftQueries {
             params! {
                 "schedule_id" => create_dto.schedule_id,
                 "job_id" => create_dto.job_id,
-                "start_time" => create_dto.start_time.format("%H:%M:%S").to_string(),
-                "end_time" => create_dto.end_time.format("%H:%M:%S").to_string(),
+                "start_time" => create_dto.start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "end_time" => create_dto.end_time.format("%Y-%m-%d %H:%M:%S").to_string(),
                 "pay_rate" => create_dto.pay_rate,
+                "headcount" => create_dto.headcount.unwrap_or(1),
             }
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = format!("UPDATE {} SET ", Self::table_name());
+    /// Creates an open shift, which must fall within its schedule like the shifts it turns into.
+    fn create_entity(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dto: Self::CreateDto
+    ) -> Result<i64> {
+        conn.atomic(|tx| {
+            let schedule = WorkScheduleQueries::find_by_id(tx, create_dto.schedule_id)?;
+            ShiftQueries::check_times(&schedule, create_dto.start_time, create_dto.end_time)?;
+
+            let id = Self::create_entity_exec(tx, snowflake_generator.clone(), &create_dto)?;
+
+            Self::create_entity_postprocessor(tx, snowflake_generator, create_dto, id)
+        })
+    }
+
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(job_id) = update_dto.job_id {
-            query.push_str("job_id = :job_id, ");
             params.push(("job_id".to_string(), job_id.into()));
         }
         if let Some(start_time) = update_dto.start_time {
-            query.push_str("start_time = :start_time, ");
             params.push((
                 "start_time".to_string(),
-                start_time.format("%H:%M:%S").to_string().into(),
+                start_time.format("%Y-%m-%d %H:%M:%S").to_string().into(),
             ));
         }
         if let Some(end_time) = update_dto.end_time {
-            query.push_str("end_time = :end_time, ");
-            params.push(("end_time".to_string(), end_time.format("%H:%M:%S").to_string().into()));
+            params.push((
+                "end_time".to_string(),
+                end_time.format("%Y-%m-%d %H:%M:%S").to_string().into(),
+            ));
         }
         if let Some(pay_rate) = update_dto.pay_rate {
-            query.push_str("pay_rate = :pay_rate, ");
             params.push(("pay_rate".to_string(), pay_rate.into()));
         }
-
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
+        if let Some(headcount) = update_dto.headcount {
+            params.push(("headcount".to_string(), headcount.into()));
         }
-        query.push_str(" WHERE id = :id;");
-        params.push(("id".to_string(), id.into()));
-
-        let params = Params::from(params);
-        let query_result = conn.exec_iter(&query, params)?;
 
-        Ok(query_result.affected_rows())
+        params
     }
 }
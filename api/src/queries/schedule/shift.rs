@@ -1,23 +1,335 @@
+use std::sync::Arc;
+
+use chrono::{ Datelike, Duration, NaiveDateTime, NaiveTime };
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         schedule::shift::{
             Shift,
+            ShiftDetail,
             RequestCreateShift,
             RequestUpdateShift,
+            ShiftValidationError,
+            ShiftValidationRule,
+            SchedulingWarning,
+            SchedulingWarningKind,
+            AvailabilityPolicy,
             create_shifts_table_query,
         },
-        result::Result,
+        schedule::{ availability_detail::DayOfWeek, work_schedule::WorkSchedule },
+        pagination::{ ListScope, SortOrder },
+        result::{ Result, NotFoundError },
     },
-    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
+    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable, atomic::Atomic },
+    snowflake::SnowflakeGenerator,
+};
+
+use super::{
+    work_schedule::WorkScheduleQueries,
+    availability::AvailabilityRequestQueries,
+    availability_detail::AvailabilityDetailQueries,
+    timeoff_request::TimeOffRequestQueries,
 };
 
 pub struct ShiftQueries {}
 
+impl ShiftQueries {
+    /// Restricts a list to the shifts of a schedule.
+    pub fn schedule_scope(schedule_id: i64) -> ListScope {
+        ListScope::column("schedule_id", schedule_id)
+    }
+
+    /// Checks a shift against the `ShiftValidationRule`s before it is saved.
+    ///
+    /// Besides `check_times`, the user must be free during the shift: only the shifts of the
+    /// schedule's organization count as overlapping. `exclude_id` is the shift being updated, so
+    /// that it doesn't collide with itself.
+    ///
+    /// The user is locked `FOR UPDATE`: called within the transaction that saves the shift, it
+    /// keeps a concurrent booking of the same user from slipping in between the check and the write.
+    pub fn validate(
+        conn: &mut impl Queryable,
+        user_id: i64,
+        schedule_id: i64,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+        exclude_id: Option<i64>
+    ) -> Result<()> {
+        let schedule = WorkScheduleQueries::find_by_id(conn, schedule_id)?;
+        Self::check_times(&schedule, start_time, end_time)?;
+
+        conn.exec_drop("SELECT id FROM users WHERE id = :user_id FOR UPDATE;", params! {
+            "user_id" => user_id,
+        })?;
+        let conflicting_shift = Self::find_overlapping_for_user(
+            conn,
+            user_id,
+            schedule.org_id,
+            start_time,
+            end_time
+        )?
+            .into_iter()
+            .find(|shift| Some(shift.id) != exclude_id);
+        if let Some(conflicting_shift) = conflicting_shift {
+            return Err(
+                Box::new(ShiftValidationError {
+                    rule: ShiftValidationRule::OVERLAP,
+                    message: format!(
+                        "User {} is already booked on shift {} from {} to {}",
+                        user_id,
+                        conflicting_shift.id,
+                        conflicting_shift.start_time,
+                        conflicting_shift.end_time
+                    ),
+                    conflicting_shift: Some(conflicting_shift),
+                    warnings: vec![],
+                })
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a shift ends after it starts and falls within its schedule: it must start on one
+    /// of the days of the schedule, overnight shifts may end the day after the schedule's
+    /// `end_date`.
+    pub fn check_times(
+        schedule: &WorkSchedule,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime
+    ) -> Result<()> {
+        if end_time <= start_time {
+            return Err(
+                Box::new(ShiftValidationError {
+                    rule: ShiftValidationRule::END_BEFORE_START,
+                    message: "The shift must end after it starts".to_string(),
+                    conflicting_shift: None,
+                    warnings: vec![],
+                })
+            );
+        }
+
+        if
+            start_time.date() < schedule.start_date ||
+            start_time.date() > schedule.end_date ||
+            end_time.date() > schedule.end_date + Duration::days(1)
+        {
+            return Err(
+                Box::new(ShiftValidationError {
+                    rule: ShiftValidationRule::OUTSIDE_SCHEDULE,
+                    message: format!(
+                        "The shift must fall within its schedule, from {} to {}",
+                        schedule.start_date,
+                        schedule.end_date
+                    ),
+                    conflicting_shift: None,
+                    warnings: vec![],
+                })
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns the id of the organization the shift belongs to, through its schedule.
+    pub fn find_org_id(conn: &mut impl Queryable, id: i64) -> Result<i64> {
+        let org_id: Option<i64> = conn.exec_first(
+            "SELECT ws.org_id FROM shifts shift
+            JOIN work_schedules ws ON ws.id = shift.schedule_id
+            WHERE shift.id = :id;",
+            params! { "id" => id }
+        )?;
+        org_id.ok_or_else(|| NotFoundError("Shift not found".to_string()).into())
+    }
+
+    /// Cross-checks a proposed shift with the member's availability in effect on each day the
+    /// shift spans and with their approved time off.
+    ///
+    /// Overnight shifts are checked against the availability of both days, for the hours they
+    /// cover on each of them.
+    pub fn scheduling_warnings(
+        conn: &mut impl Queryable,
+        org_id: i64,
+        user_id: i64,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime
+    ) -> Result<Vec<SchedulingWarning>> {
+        let mut warnings: Vec<SchedulingWarning> = Vec::new();
+
+        let mut date = start_time.date();
+        while date.and_time(NaiveTime::MIN) < end_time {
+            let day_start = date.and_time(NaiveTime::MIN).max(start_time);
+            let day_end = (date + Duration::days(1)).and_time(NaiveTime::MIN).min(end_time);
+            let day_of_week = DayOfWeek::from(date.weekday());
+
+            if
+                let Some(availability) = AvailabilityRequestQueries::get_availability_on(
+                    conn,
+                    user_id,
+                    org_id,
+                    date
+                )?
+            {
+                let details = AvailabilityDetailQueries::get_request_details(
+                    conn,
+                    availability.id
+                )?;
+                if
+                    let Some(detail) = details
+                        .iter()
+                        .find(|detail| detail.day_of_week == day_of_week)
+                {
+                    if !detail.is_available {
+                        warnings.push(SchedulingWarning {
+                            kind: SchedulingWarningKind::UNAVAILABLE,
+                            message: format!(
+                                "The member is unavailable on {}",
+                                day_of_week.to_string()
+                            ),
+                            time_off_request_id: None,
+                        });
+                    } else if !detail.whole_day {
+                        let starts_too_early = detail.preferred_start_time.is_some_and(
+                            |preferred_start| day_start.time() < preferred_start
+                        );
+                        let ends_too_late = detail.preferred_end_time.is_some_and(
+                            |preferred_end| day_end > date.and_time(preferred_end)
+                        );
+                        if starts_too_early || ends_too_late {
+                            warnings.push(SchedulingWarning {
+                                kind: SchedulingWarningKind::OUTSIDE_PREFERRED_TIME,
+                                message: format!(
+                                    "The shift is outside the member's preferred hours on {}",
+                                    day_of_week.to_string()
+                                ),
+                                time_off_request_id: None,
+                            });
+                        }
+                    }
+                }
+            }
+
+            date += Duration::days(1);
+        }
+
+        let time_off_requests = TimeOffRequestQueries::find_approved_overlapping(
+            conn,
+            user_id,
+            org_id,
+            start_time,
+            end_time
+        )?;
+        for time_off in time_off_requests {
+            warnings.push(SchedulingWarning {
+                kind: SchedulingWarningKind::TIME_OFF,
+                message: format!(
+                    "The member has approved time off from {} to {}",
+                    time_off.start_time,
+                    time_off.end_time
+                ),
+                time_off_request_id: Some(time_off.id),
+            });
+        }
+
+        Ok(warnings)
+    }
+
+    /// Refuses the shift if it raises scheduling warnings and was requested with the `REJECT`
+    /// availability policy.
+    fn apply_availability_policy(
+        conn: &mut impl Queryable,
+        create_dto: &RequestCreateShift
+    ) -> Result<()> {
+        if create_dto.availability_policy != Some(AvailabilityPolicy::REJECT) {
+            return Ok(());
+        }
+
+        let org_id = WorkScheduleQueries::find_by_id(conn, create_dto.schedule_id)?.org_id;
+        let warnings = Self::scheduling_warnings(
+            conn,
+            org_id,
+            create_dto.user_id,
+            create_dto.start_time,
+            create_dto.end_time
+        )?;
+        if !warnings.is_empty() {
+            return Err(
+                Box::new(ShiftValidationError {
+                    rule: ShiftValidationRule::AVAILABILITY,
+                    message: format!("The shift raises {} scheduling warning(s)", warnings.len()),
+                    conflicting_shift: None,
+                    warnings,
+                })
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn find_by_schedule_id(conn: &mut impl Queryable, schedule_id: i64) -> Result<Vec<Shift>> {
+        Ok(
+            conn.exec(
+                "SELECT * FROM shifts WHERE schedule_id = :schedule_id ORDER BY start_time;",
+                params! { "schedule_id" => schedule_id }
+            )?
+        )
+    }
+
+    /// Retrieves the shifts of a user in an organization overlapping the given window.
+    pub fn find_overlapping_for_user<Q: Queryable>(
+        conn: &mut Q,
+        user_id: i64,
+        org_id: i64,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime
+    ) -> Result<Vec<Shift>> {
+        Ok(
+            conn.exec(
+                "SELECT shift.* FROM shifts shift
+                JOIN work_schedules ws ON ws.id = shift.schedule_id
+                WHERE shift.user_id = :user_id
+                    AND ws.org_id = :org_id
+                    AND shift.start_time < :end_time
+                    AND shift.end_time > :start_time
+                ORDER BY shift.start_time;",
+                params! {
+                    "user_id" => user_id,
+                    "org_id" => org_id,
+                    "start_time" => start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    "end_time" => end_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                }
+            )?
+        )
+    }
+
+    /// Retrieves the shifts of a schedule with their job and user, ordered by start time.
+    pub fn find_details_by_schedule_id(
+        conn: &mut impl Queryable,
+        schedule_id: i64
+    ) -> Result<Vec<ShiftDetail>> {
+        Ok(
+            conn.exec(
+                "SELECT
+                    shift.*,
+                    job.name as job_name,
+                    job.color as job_color,
+                    user.first_name as user_first_name,
+                    user.last_name as user_last_name
+                FROM shifts shift
+                JOIN org_jobs job ON job.id = shift.job_id
+                JOIN users user ON user.id = shift.user_id
+                WHERE shift.schedule_id = :schedule_id
+                ORDER BY shift.start_time;",
+                params! { "schedule_id" => schedule_id }
+            )?
+        )
+    }
+}
+
 impl DatabaseTable for ShiftQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_shifts_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -33,6 +345,18 @@ impl BasicQueries for ShiftQueries {
         "shifts".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["user_id", "job_id", "start_time", "end_time"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "start_time", "end_time"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("start_time", SortOrder::ASC)
+    }
+
     fn insert_statement() -> String {
         format!(
             "INSERT INTO {} (id, user_id, schedule_id, job_id, start_time, end_time, pay_rate, note)
@@ -55,51 +379,430 @@ impl BasicQueries for ShiftQueries {
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE shifts SET ".to_string();
+    fn create_entity(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dto: Self::CreateDto
+    ) -> Result<i64> {
+        conn.atomic(|tx| {
+            Self::validate(
+                tx,
+                create_dto.user_id,
+                create_dto.schedule_id,
+                create_dto.start_time,
+                create_dto.end_time,
+                None
+            )?;
+            Self::apply_availability_policy(tx, &create_dto)?;
+
+            let id = Self::create_entity_exec(tx, snowflake_generator.clone(), &create_dto)?;
+
+            Self::create_entity_postprocessor(tx, snowflake_generator, create_dto, id)
+        })
+    }
+
+    /// Validates every shift, including against the other shifts of the batch, and inserts them
+    /// in the same transaction.
+    fn create_many(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dtos: Vec<Self::CreateDto>
+    ) -> Result<()> {
+        conn.atomic(|tx| {
+            for (index, create_dto) in create_dtos.iter().enumerate() {
+                Self::validate(
+                    tx,
+                    create_dto.user_id,
+                    create_dto.schedule_id,
+                    create_dto.start_time,
+                    create_dto.end_time,
+                    None
+                )?;
+                Self::apply_availability_policy(tx, create_dto)?;
+
+                let overlapping = create_dtos[..index]
+                    .iter()
+                    .position(
+                        |other|
+                            other.user_id == create_dto.user_id &&
+                            other.start_time < create_dto.end_time &&
+                            other.end_time > create_dto.start_time
+                    );
+                if let Some(other_index) = overlapping {
+                    return Err(
+                        Box::new(ShiftValidationError {
+                            rule: ShiftValidationRule::OVERLAP,
+                            message: format!(
+                                "Shifts {} and {} of the batch book user {} at the same time",
+                                other_index,
+                                index,
+                                create_dto.user_id
+                            ),
+                            conflicting_shift: None,
+                            warnings: vec![],
+                        })
+                    );
+                }
+            }
+
+            let params_iter = create_dtos
+                .iter()
+                .map(|create_dto| {
+                    let mut params_map = if
+                        let Params::Named(map) = Self::insert_params(create_dto)?
+                    {
+                        map
+                    } else {
+                        panic!("Expected named parameters");
+                    };
+
+                    let id = snowflake_generator.generate_id();
+                    params_map.insert("id".to_string().into_bytes(), Value::from(id));
+
+                    Ok(Params::Named(params_map))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            tx.exec_batch(Self::insert_statement(), params_iter)?;
+            Ok(())
+        })
+    }
+
+    fn update_entity(conn: &mut impl Atomic, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
+        conn.atomic(|tx| {
+            if update_dto.start_time.is_some() || update_dto.end_time.is_some() {
+                let shift = Self::find_by_id(tx, id)?;
+                Self::validate(
+                    tx,
+                    shift.user_id,
+                    shift.schedule_id,
+                    update_dto.start_time.unwrap_or(shift.start_time),
+                    update_dto.end_time.unwrap_or(shift.end_time),
+                    Some(id)
+                )?;
+            }
+
+            Self::update_columns(tx, id, Self::update_params(update_dto))
+        })
+    }
+
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(job_id) = update_dto.job_id {
-            query.push_str("job_id = :job_id, ");
             params.push(("job_id".to_string(), job_id.into()));
         }
         if let Some(start_time) = update_dto.start_time {
-            query.push_str("start_time = :start_time, ");
             params.push((
                 "start_time".to_string(),
                 start_time.format("%Y-%m-%d %H:%M:%S").to_string().into(),
             ));
         }
         if let Some(end_time) = update_dto.end_time {
-            query.push_str("end_time = :end_time, ");
             params.push((
                 "end_time".to_string(),
                 end_time.format("%Y-%m-%d %H:%M:%S").to_string().into(),
             ));
         }
         if let Some(pay_rate) = update_dto.pay_rate {
-            query.push_str("pay_rate = :pay_rate, ");
             params.push(("pay_rate".to_string(), pay_rate.into()));
         }
         if let Some(note) = update_dto.note {
-            query.push_str("note = :note, ");
             params.push(("note".to_string(), note.into()));
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ sync::Arc, thread };
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{
+        models::{
+            user::RequestCreateUser,
+            organization::RequestCreateOrganization,
+            schedule::{
+                work_schedule::RequestCreateWorkSchedule,
+                availability::{ RequestCreateAvailability, RequestUpdateAvailability },
+                availability_detail::RequestCreateAvailabilityDetail,
+                request_status::ScheduleRequestStatus,
+            },
+        },
+        queries::{ user::UserQueries, organization::OrgQueries, org_job::OrgJobQueries },
+        utilities::parse_chrono::parse_naive_date_time_from_str,
+        tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST },
+    };
+
+    /// Creates an organization owned by `owner_id`, returning a schedule for the first week of
+    /// 2024 and the job of its owner.
+    fn create_schedule(
+        conn: &mut Connection,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        owner_id: i64,
+        name: &str
+    ) -> Result<(i64, i64)> {
+        let org_id = OrgQueries::create_entity(
+            conn,
+            snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: name.to_string(),
+                description: None,
+                owner_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let schedule_id = WorkScheduleQueries::create_entity(
+            conn,
+            snowflake_generator,
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                end_date: NaiveDate::from_ymd_opt(2024, 1, 7).unwrap(),
+            }
+        )?;
+        let job_id = OrgJobQueries::find_by_org_id(conn, org_id)?[0].id;
+
+        Ok((schedule_id, job_id))
+    }
+
+    fn shift_request(
+        user_id: i64,
+        (schedule_id, job_id): (i64, i64),
+        start_time: &str,
+        end_time: &str
+    ) -> RequestCreateShift {
+        RequestCreateShift {
+            user_id,
+            schedule_id,
+            job_id,
+            start_time: parse_naive_date_time_from_str(start_time).unwrap(),
+            end_time: parse_naive_date_time_from_str(end_time).unwrap(),
+            pay_rate: None,
+            note: None,
+            availability_policy: None,
         }
+    }
+
+    fn create_test_user(
+        conn: &mut Connection,
+        snowflake_generator: Arc<SnowflakeGenerator>
+    ) -> Result<i64> {
+        let user = UserQueries::new_user(RequestCreateUser {
+            email: "owner@example.com".to_string(),
+            password: "password".to_string(),
+            first_name: "Owner".to_string(),
+            last_name: "User".to_string(),
+            date_of_birth: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            phone_number: None,
+        }, TEST_HASH_COST)?;
+        UserQueries::create_entity(conn, snowflake_generator, user)
+    }
+
+    fn overlap(result: Result<impl std::fmt::Debug>) -> ShiftValidationError {
+        let error = result.unwrap_err();
+        let error = error.downcast_ref::<ShiftValidationError>().expect("a validation error");
+        assert_eq!(error.rule, ShiftValidationRule::OVERLAP);
+        error.clone()
+    }
+
+    #[test]
+    fn test_shift_overlap() -> Result<()> {
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+        let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
+
+        let user_id = create_test_user(&mut conn, snowflake_generator.clone())?;
+        let schedule = create_schedule(&mut conn, snowflake_generator.clone(), user_id, "Cafe")?;
+        let other_schedule = create_schedule(
+            &mut conn,
+            snowflake_generator.clone(),
+            user_id,
+            "Bakery"
+        )?;
+
+        let morning_id = ShiftQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            shift_request(user_id, schedule, "2024-01-01 09:00:00", "2024-01-01 17:00:00")
+        )?;
+
+        // Only the shifts of the same organization collide, and only they are reported
+        ShiftQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            shift_request(user_id, other_schedule, "2024-01-01 12:00:00", "2024-01-01 20:00:00")
+        )?;
+        let error = overlap(
+            ShiftQueries::create_entity(
+                &mut conn,
+                snowflake_generator.clone(),
+                shift_request(user_id, schedule, "2024-01-01 16:00:00", "2024-01-01 20:00:00")
+            )
+        );
+        assert_eq!(error.conflicting_shift.map(|shift| shift.id), Some(morning_id));
+
+        // A batch is checked against the booked shifts, and nothing of it is saved on failure
+        let shift_count = ShiftQueries::find_all(&mut conn)?.len();
+        overlap(
+            ShiftQueries::create_many(
+                &mut conn,
+                snowflake_generator.clone(),
+                vec![
+                    shift_request(user_id, schedule, "2024-01-02 09:00:00", "2024-01-02 17:00:00"),
+                    shift_request(user_id, schedule, "2024-01-01 08:00:00", "2024-01-01 10:00:00")
+                ]
+            )
+        );
+        assert_eq!(ShiftQueries::find_all(&mut conn)?.len(), shift_count);
 
-        query.push_str(&format!(" WHERE id = :id;"));
-        params.push(("id".to_string(), id.into()));
+        // Moving a shift onto another one of the organization is refused as well
+        let evening_id = ShiftQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            shift_request(user_id, schedule, "2024-01-01 17:00:00", "2024-01-01 21:00:00")
+        )?;
+        let error = overlap(
+            ShiftQueries::update_entity(&mut conn, evening_id, RequestUpdateShift {
+                start_time: Some(parse_naive_date_time_from_str("2024-01-01 16:00:00")?),
+                ..Default::default()
+            })
+        );
+        assert_eq!(error.conflicting_shift.map(|shift| shift.id), Some(morning_id));
 
-        let params = Params::from(params);
-        let query_result = conn.exec_iter(&query, params)?;
+        // Of two concurrent bookings of the same hours, only one goes through
+        let results = thread::scope(|scope| {
+            let bookings = (0..2)
+                .map(|_| {
+                    let storage = storage.clone();
+                    let snowflake_generator = snowflake_generator.clone();
+                    scope.spawn(move || {
+                        let mut conn = storage.get_conn()?;
+                        ShiftQueries::create_entity(
+                            &mut conn,
+                            snowflake_generator,
+                            shift_request(
+                                user_id,
+                                schedule,
+                                "2024-01-03 09:00:00",
+                                "2024-01-03 17:00:00"
+                            )
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            bookings
+                .into_iter()
+                .map(|booking| booking.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        overlap(results.into_iter().find(|result| result.is_err()).unwrap());
 
-        Ok(query_result.affected_rows())
+        cleanup_test_db(conn)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overnight_shift_warnings() -> Result<()> {
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
+        let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
+
+        let user_id = create_test_user(&mut conn, snowflake_generator.clone())?;
+        let (schedule_id, _) = create_schedule(
+            &mut conn,
+            snowflake_generator.clone(),
+            user_id,
+            "Night Club"
+        )?;
+        let org_id = WorkScheduleQueries::find_by_id(&mut conn, schedule_id)?.org_id;
+
+        // Unavailable on Tuesdays, rather working until 23:00 on Wednesdays
+        let availability_id = AvailabilityRequestQueries::create_entity(
+            &mut conn,
+            snowflake_generator.clone(),
+            RequestCreateAvailability {
+                user_id,
+                org_id,
+                start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                details: vec![
+                    RequestCreateAvailabilityDetail {
+                        day_of_week: DayOfWeek::TUESDAY,
+                        is_available: false,
+                        whole_day: true,
+                        ..Default::default()
+                    },
+                    RequestCreateAvailabilityDetail {
+                        day_of_week: DayOfWeek::WEDNESDAY,
+                        is_available: true,
+                        whole_day: false,
+                        preferred_start_time: NaiveTime::from_hms_opt(18, 0, 0),
+                        preferred_end_time: NaiveTime::from_hms_opt(23, 0, 0),
+                    },
+                    RequestCreateAvailabilityDetail {
+                        day_of_week: DayOfWeek::THURSDAY,
+                        is_available: true,
+                        whole_day: true,
+                        ..Default::default()
+                    }
+                ],
+            }
+        )?;
+        AvailabilityRequestQueries::update_entity(
+            &mut conn,
+            availability_id,
+            RequestUpdateAvailability {
+                status: Some(ScheduleRequestStatus::APPROVED),
+            }
+        )?;
+
+        let warning_kinds = |
+            conn: &mut Connection,
+            start_time: &str,
+            end_time: &str
+        | -> Result<Vec<SchedulingWarningKind>> {
+            Ok(
+                ShiftQueries::scheduling_warnings(
+                    conn,
+                    org_id,
+                    user_id,
+                    parse_naive_date_time_from_str(start_time)?,
+                    parse_naive_date_time_from_str(end_time)?
+                )?
+                    .into_iter()
+                    .map(|warning| warning.kind)
+                    .collect()
+            )
+        };
+
+        // A Monday night shift running into Tuesday
+        assert_eq!(
+            warning_kinds(&mut conn, "2024-01-01 20:00:00", "2024-01-02 02:00:00")?,
+            vec![SchedulingWarningKind::UNAVAILABLE]
+        );
+        // Ending at midnight, it doesn't touch Tuesday
+        assert!(warning_kinds(&mut conn, "2024-01-01 18:00:00", "2024-01-02 00:00:00")?.is_empty());
+        // A Tuesday night shift is checked against Tuesday as well as Wednesday morning
+        assert_eq!(
+            warning_kinds(&mut conn, "2024-01-02 22:00:00", "2024-01-03 06:00:00")?,
+            vec![SchedulingWarningKind::UNAVAILABLE, SchedulingWarningKind::OUTSIDE_PREFERRED_TIME]
+        );
+        // A Wednesday night shift goes past the preferred end of Wednesday
+        assert_eq!(
+            warning_kinds(&mut conn, "2024-01-03 18:00:00", "2024-01-04 02:00:00")?,
+            vec![SchedulingWarningKind::OUTSIDE_PREFERRED_TIME]
+        );
+        assert!(warning_kinds(&mut conn, "2024-01-03 18:00:00", "2024-01-03 23:00:00")?.is_empty());
+
+        cleanup_test_db(conn)?;
+
+        Ok(())
     }
 }
@@ -1,21 +1,205 @@
+use std::sync::Arc;
+
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         schedule::{
             shift_cover::{ ShiftCover, RequestCreateShiftCover, create_shift_covers_table_query },
+            shift::{ Shift, ShiftValidationError, ShiftValidationRule },
             RequestUpdateShiftRequest,
+            ShiftRequestStatus,
+            InvalidStatusTransitionError,
         },
-        result::Result,
+        pagination::{ ListScope, SortOrder },
+        result::{ Result, NotFoundError },
+        org_member::OrgPermission,
     },
-    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
+    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable, atomic::Atomic },
+    queries::org_member::OrgMemberQueries,
+    snowflake::SnowflakeGenerator,
 };
 
+use super::shift::ShiftQueries;
+
 pub struct ShiftCoverQueries;
 
+impl ShiftCoverQueries {
+    /// Restricts a list to the covers of the shifts of an organization.
+    pub fn org_scope(org_id: i64) -> ListScope {
+        ListScope::filter("ws.org_id = :org_id", vec![("org_id", Value::from(org_id))]).join(
+            "JOIN shifts shift ON shift.id = shift_covers.shift_id
+            JOIN work_schedules ws ON ws.id = shift.schedule_id"
+        )
+    }
+
+    /// Returns the id of the organization the covered shift belongs to.
+    pub fn find_org_id(conn: &mut impl Queryable, id: i64) -> Result<i64> {
+        let org_id: Option<i64> = conn.exec_first(
+            "SELECT ws.org_id FROM shift_covers cover
+            JOIN shifts shift ON shift.id = cover.shift_id
+            JOIN work_schedules ws ON ws.id = shift.schedule_id
+            WHERE cover.id = :id;",
+            params! { "id" => id }
+        )?;
+        org_id.ok_or_else(|| NotFoundError("Shift cover not found".to_string()).into())
+    }
+
+    /// Retrieves the covers requested for a shift.
+    pub fn find_by_shift_id(conn: &mut impl Queryable, shift_id: i64) -> Result<Vec<ShiftCover>> {
+        Ok(
+            conn.exec(
+                "SELECT * FROM shift_covers WHERE shift_id = :shift_id ORDER BY updated_at DESC;",
+                params! { "shift_id" => shift_id }
+            )?
+        )
+    }
+
+    /// Makes sure `cover_user_id` can take over `shift`: they are another member of its
+    /// organization and are free during the shift.
+    ///
+    /// The membership is locked `FOR UPDATE`, as is the user by `ShiftQueries::validate`: within a
+    /// transaction, the checks hold until it ends.
+    fn check_cover_user(conn: &mut impl Queryable, shift: &Shift, cover_user_id: i64) -> Result<()> {
+        if shift.user_id == cover_user_id {
+            return Err(
+                Box::new(
+                    ShiftValidationError::new(
+                        ShiftValidationRule::SAME_MEMBER,
+                        "A member can't cover their own shift"
+                    )
+                )
+            );
+        }
+
+        let org_id = ShiftQueries::find_org_id(conn, shift.id)?;
+        let member: Option<i64> = conn.exec_first(
+            "SELECT user_id FROM org_members
+            WHERE org_id = :org_id AND user_id = :user_id FOR UPDATE;",
+            params! { "org_id" => org_id, "user_id" => cover_user_id }
+        )?;
+        if member.is_none() {
+            return Err(
+                Box::new(
+                    ShiftValidationError::new(
+                        ShiftValidationRule::NOT_A_MEMBER,
+                        format!("User {} is not a member of the organization", cover_user_id)
+                    )
+                )
+            );
+        }
+
+        ShiftQueries::validate(
+            conn,
+            cover_user_id,
+            shift.schedule_id,
+            shift.start_time,
+            shift.end_time,
+            None
+        )
+    }
+
+    /// Moves a cover to `status`, recording the admin acting on it and an optional note.
+    ///
+    /// Approving a cover hands the shift over to the cover user and cancels the other open covers
+    /// of the shift, in a single transaction. The cover is checked again within the transaction:
+    /// it must still be `PEER_ACCEPTED` and the shift must not have started. When the cover is
+    /// accepted or approved, the cover user must still be a member free during the shift, checked
+    /// with the shift locked.
+    pub fn transition(
+        conn: &mut impl Atomic,
+        id: i64,
+        status: ShiftRequestStatus,
+        admin_id: Option<i64>,
+        note: Option<String>
+    ) -> Result<()> {
+        if let Some(admin_id) = admin_id {
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        let cover = Self::find_by_id(conn, id)?;
+        if !cover.status.can_transition_to(&status, true) {
+            return Err(Box::new(InvalidStatusTransitionError::new(&cover.status, &status)));
+        }
+
+        conn.atomic(|tx| {
+            let current_status: Option<String> = tx.exec_first(
+                "SELECT status FROM shift_covers WHERE id = :id FOR UPDATE;",
+                params! { "id" => id }
+            )?;
+            let current_status: ShiftRequestStatus = current_status
+                .ok_or_else(|| NotFoundError("Shift cover not found".to_string()))?
+                .parse()?;
+            if !current_status.can_transition_to(&status, true) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&current_status, &status)));
+            }
+
+            if
+                matches!(status, ShiftRequestStatus::PEER_ACCEPTED | ShiftRequestStatus::APPROVED)
+            {
+                let shift: Shift = tx
+                    .exec_first(
+                        "SELECT * FROM shifts WHERE id = :shift_id FOR UPDATE;",
+                        params! { "shift_id" => cover.shift_id }
+                    )?
+                    .ok_or_else(|| NotFoundError("Shift not found".to_string()))?;
+                Self::check_cover_user(tx, &shift, cover.cover_user_id)?;
+            }
+
+            if status == ShiftRequestStatus::APPROVED {
+                let started: Option<bool> = tx.exec_first(
+                    "SELECT start_time <= NOW() FROM shifts WHERE id = :shift_id FOR UPDATE;",
+                    params! { "shift_id" => cover.shift_id }
+                )?;
+                if started.ok_or_else(|| NotFoundError("Shift not found".to_string()))? {
+                    return Err(
+                        Box::new(
+                            ShiftValidationError::new(
+                                ShiftValidationRule::ALREADY_STARTED,
+                                format!("Shift {} has already started", cover.shift_id)
+                            )
+                        )
+                    );
+                }
+
+                tx.exec_drop(
+                    "UPDATE shifts SET user_id = :cover_user_id WHERE id = :shift_id;",
+                    params! { "cover_user_id" => cover.cover_user_id, "shift_id" => cover.shift_id }
+                )?;
+                tx.exec_drop(
+                    "UPDATE shift_covers SET status = 'CANCELLED'
+                    WHERE shift_id = :shift_id AND id != :id AND status IN ('PENDING', 'PEER_ACCEPTED');",
+                    params! { "shift_id" => cover.shift_id, "id" => id }
+                )?;
+            }
+
+            tx.exec_drop(
+                "UPDATE shift_covers
+                SET status = :status, admin_id = COALESCE(:admin_id, admin_id), note = COALESCE(:note, note)
+                WHERE id = :id;",
+                params! {
+                    "status" => status.to_string(),
+                    "admin_id" => admin_id,
+                    "note" => note,
+                    "id" => id,
+                }
+            )?;
+
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
 impl DatabaseTable for ShiftCoverQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_shift_covers_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -31,6 +215,18 @@ impl BasicQueries for ShiftCoverQueries {
         "shift_covers".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["shift_id", "cover_user_id", "status", "admin_id", "updated_at"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "updated_at"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("updated_at", SortOrder::DESC)
+    }
+
     fn insert_statement() -> String {
         format!(
             r"INSERT INTO {} (id, shift_id, cover_user_id) VALUES (:id, :shift_id, :cover_user_id)",
@@ -47,34 +243,65 @@ impl BasicQueries for ShiftCoverQueries {
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE shift_covers SET ".to_string();
+    fn create_entity(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dto: Self::CreateDto
+    ) -> Result<i64> {
+        conn.atomic(|tx| {
+            let shift = ShiftQueries::find_by_id(tx, create_dto.shift_id)?;
+            Self::check_cover_user(tx, &shift, create_dto.cover_user_id)?;
+
+            let id = Self::create_entity_exec(tx, snowflake_generator.clone(), &create_dto)?;
+
+            Self::create_entity_postprocessor(tx, snowflake_generator, create_dto, id)
+        })
+    }
+
+    /// Updates the note, the admin or the status of a cover.
+    ///
+    /// Status changes must follow the cover workflow, see `ShiftRequestStatus::can_transition_to`.
+    /// Accepting and approving a cover are checked against the shift and must go through
+    /// `transition` instead.
+    fn update_entity(conn: &mut impl Atomic, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
+        if let Some(status) = &update_dto.status {
+            if matches!(status, ShiftRequestStatus::PEER_ACCEPTED | ShiftRequestStatus::APPROVED) {
+                return Err(
+                    "Shift covers must be accepted and approved with `ShiftCoverQueries::transition`".into()
+                );
+            }
+            let cover = Self::find_by_id(conn, id)?;
+            if !cover.status.can_transition_to(status, true) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&cover.status, status)));
+            }
+        }
+        if let Some(admin_id) = update_dto.admin_id {
+            // only members allowed to approve requests in the organization can act as admin
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        Self::update_columns(conn, id, Self::update_params(update_dto))
+    }
+
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(status) = update_dto.status {
-            query.push_str("status = :status, ");
             params.push(("status".to_string(), status.to_string().into()));
         }
         if let Some(admin_id) = update_dto.admin_id {
-            query.push_str("admin_id = :admin_id, ");
             params.push(("admin_id".to_string(), admin_id.into()));
         }
         if let Some(note) = update_dto.note {
-            query.push_str("note = :note, ");
             params.push(("note".to_string(), note.into()));
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
-        }
-        query.push_str(" WHERE id = :id;");
-        params.push(("id".to_string(), id.into()));
-
-        let query_result = conn.exec_iter(&query, params)?;
-        Ok(query_result.affected_rows())
+        params
     }
 }
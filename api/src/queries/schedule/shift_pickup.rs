@@ -1,7 +1,9 @@
+use std::sync::Arc;
+
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         schedule::{
             shift_pickup::{
@@ -9,17 +11,199 @@ use crate::{
                 RequestCreateShiftPickup,
                 create_shift_pickups_table_query,
             },
+            open_shift::OpenShift,
+            shift::{ ShiftValidationError, ShiftValidationRule },
             RequestUpdateShiftRequest,
+            ShiftRequestStatus,
+            InvalidStatusTransitionError,
         },
-        result::Result,
+        pagination::{ ListScope, SortOrder },
+        result::{ Result, NotFoundError },
+        org_member::OrgPermission,
+        organization::PickupMode,
     },
-    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
+    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable, atomic::Atomic },
+    queries::{ org_member::OrgMemberQueries, organization::OrgQueries },
+    snowflake::SnowflakeGenerator,
+};
+
+use super::{
+    open_shift::OpenShiftQueries,
+    shift::ShiftQueries,
+    work_schedule::WorkScheduleQueries,
 };
 
 pub struct ShiftPickupQueries;
 
+impl ShiftPickupQueries {
+    /// Restricts a list to the pickups of the open shifts of an organization.
+    pub fn org_scope(org_id: i64) -> ListScope {
+        ListScope::filter("ws.org_id = :org_id", vec![("org_id", Value::from(org_id))]).join(
+            "JOIN open_shifts open_shift ON open_shift.id = shift_pickups.openshift_id
+            JOIN work_schedules ws ON ws.id = open_shift.schedule_id"
+        )
+    }
+
+    /// Returns the id of the organization the open shift belongs to.
+    pub fn find_org_id(conn: &mut impl Queryable, id: i64) -> Result<i64> {
+        let org_id: Option<i64> = conn.exec_first(
+            "SELECT ws.org_id FROM shift_pickups pickup
+            JOIN open_shifts open_shift ON open_shift.id = pickup.openshift_id
+            JOIN work_schedules ws ON ws.id = open_shift.schedule_id
+            WHERE pickup.id = :id;",
+            params! { "id" => id }
+        )?;
+        org_id.ok_or_else(|| NotFoundError("Shift pickup not found".to_string()).into())
+    }
+
+    /// Makes sure `user_id` can pick up `open_shift`: it has spots left, and they are a member of
+    /// its organization who is free during the shift.
+    fn check_pickup(conn: &mut impl Queryable, open_shift: &OpenShift, user_id: i64) -> Result<()> {
+        if open_shift.is_filled() {
+            return Err(
+                Box::new(
+                    ShiftValidationError::new(
+                        ShiftValidationRule::ALREADY_FILLED,
+                        format!("Open shift {} has already been filled", open_shift.id)
+                    )
+                )
+            );
+        }
+
+        let schedule = WorkScheduleQueries::find_by_id(conn, open_shift.schedule_id)?;
+        if OrgMemberQueries::find_role(conn, schedule.org_id, user_id)?.is_none() {
+            return Err(
+                Box::new(
+                    ShiftValidationError::new(
+                        ShiftValidationRule::NOT_A_MEMBER,
+                        format!("User {} is not a member of the organization", user_id)
+                    )
+                )
+            );
+        }
+
+        ShiftQueries::validate(
+            conn,
+            user_id,
+            open_shift.schedule_id,
+            open_shift.start_time,
+            open_shift.end_time,
+            None
+        )
+    }
+
+    /// Moves a pickup to `status`, recording the admin acting on it and an optional note.
+    ///
+    /// Approving a pickup turns its open shift into a shift of the pickup user and fills one spot
+    /// of the open shift, in a single transaction. Once every spot is filled, the other open
+    /// pickups of the open shift are declined. The pickup is checked again within the transaction,
+    /// with the open shift locked: it must still be `PENDING`, the open shift must still be open
+    /// and not have started, and the pickup user must still pass `check_pickup`.
+    pub fn transition(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        id: i64,
+        status: ShiftRequestStatus,
+        admin_id: Option<i64>,
+        note: Option<String>
+    ) -> Result<()> {
+        if let Some(admin_id) = admin_id {
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        let pickup = Self::find_by_id(conn, id)?;
+        if !pickup.status.can_transition_to(&status, false) {
+            return Err(Box::new(InvalidStatusTransitionError::new(&pickup.status, &status)));
+        }
+
+        conn.atomic(|tx| {
+            let current_status: Option<String> = tx.exec_first(
+                "SELECT status FROM shift_pickups WHERE id = :id FOR UPDATE;",
+                params! { "id" => id }
+            )?;
+            let current_status: ShiftRequestStatus = current_status
+                .ok_or_else(|| NotFoundError("Shift pickup not found".to_string()))?
+                .parse()?;
+            if !current_status.can_transition_to(&status, false) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&current_status, &status)));
+            }
+
+            if status == ShiftRequestStatus::APPROVED {
+                let open_shift: OpenShift = tx
+                    .exec_first(
+                        "SELECT * FROM open_shifts WHERE id = :id FOR UPDATE;",
+                        params! { "id" => pickup.openshift_id }
+                    )?
+                    .ok_or_else(|| NotFoundError("Open shift not found".to_string()))?;
+                Self::check_pickup(tx, &open_shift, pickup.user_id)?;
+                let started: Option<bool> = tx.exec_first(
+                    "SELECT start_time <= NOW() FROM open_shifts WHERE id = :id;",
+                    params! { "id" => open_shift.id }
+                )?;
+                if started.unwrap_or_default() {
+                    return Err(
+                        Box::new(
+                            ShiftValidationError::new(
+                                ShiftValidationRule::ALREADY_STARTED,
+                                format!("Open shift {} has already started", open_shift.id)
+                            )
+                        )
+                    );
+                }
+
+                tx.exec_drop(
+                    ShiftQueries::insert_statement(),
+                    params! {
+                        "id" => snowflake_generator.generate_id(),
+                        "user_id" => pickup.user_id,
+                        "schedule_id" => open_shift.schedule_id,
+                        "job_id" => open_shift.job_id,
+                        "start_time" => open_shift.start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        "end_time" => open_shift.end_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        "pay_rate" => open_shift.pay_rate,
+                        "note" => None::<String>,
+                    }
+                )?;
+                tx.exec_drop(
+                    "UPDATE open_shifts SET filled_count = filled_count + 1 WHERE id = :id;",
+                    params! { "id" => open_shift.id }
+                )?;
+                // the last spot is taken, nobody else can pick the shift up
+                if open_shift.filled_count + 1 >= open_shift.headcount {
+                    tx.exec_drop(
+                        "UPDATE shift_pickups SET status = 'DECLINED'
+                        WHERE openshift_id = :openshift_id AND id != :id AND status = 'PENDING';",
+                        params! { "openshift_id" => open_shift.id, "id" => id }
+                    )?;
+                }
+            }
+
+            tx.exec_drop(
+                "UPDATE shift_pickups
+                SET status = :status, admin_id = COALESCE(:admin_id, admin_id), note = COALESCE(:note, note)
+                WHERE id = :id;",
+                params! {
+                    "status" => status.to_string(),
+                    "admin_id" => admin_id,
+                    "note" => note,
+                    "id" => id,
+                }
+            )?;
+
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
 impl DatabaseTable for ShiftPickupQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_shift_pickups_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -35,6 +219,18 @@ impl BasicQueries for ShiftPickupQueries {
         "shift_pickups".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["openshift_id", "user_id", "status", "admin_id", "updated_at"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "updated_at"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("updated_at", SortOrder::DESC)
+    }
+
     fn insert_statement() -> String {
         format!(
             r"INSERT INTO {} (id, openshift_id, user_id) VALUES (:id, :openshift_id, :user_id)",
@@ -51,35 +247,78 @@ impl BasicQueries for ShiftPickupQueries {
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE shift_pickups SET ".to_string();
+    /// Requests an open shift for a member.
+    ///
+    /// In organizations handing out open shifts on a `FIRST_COME_FIRST_SERVED` basis, the pickup
+    /// is approved right away.
+    fn create_entity(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dto: Self::CreateDto
+    ) -> Result<i64> {
+        conn.atomic(|tx| {
+            let open_shift = OpenShiftQueries::find_by_id(tx, create_dto.openshift_id)?;
+            Self::check_pickup(tx, &open_shift, create_dto.user_id)?;
+
+            let id = Self::create_entity_exec(tx, snowflake_generator.clone(), &create_dto)?;
+
+            let org_id = Self::find_org_id(tx, id)?;
+            if OrgQueries::find_by_id(tx, org_id)?.pickup_mode == PickupMode::FIRST_COME_FIRST_SERVED {
+                Self::transition(
+                    tx,
+                    snowflake_generator.clone(),
+                    id,
+                    ShiftRequestStatus::APPROVED,
+                    None,
+                    None
+                )?;
+            }
+
+            Self::create_entity_postprocessor(tx, snowflake_generator, create_dto, id)
+        })
+    }
+
+    /// Updates the note, the admin or the status of a pickup.
+    ///
+    /// Status changes must follow the pickup workflow, see `ShiftRequestStatus::can_transition_to`.
+    /// Approving a pickup creates a shift and must go through `transition` instead.
+    fn update_entity(conn: &mut impl Atomic, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
+        if let Some(status) = &update_dto.status {
+            if *status == ShiftRequestStatus::APPROVED {
+                return Err("Shift pickups must be approved with `ShiftPickupQueries::transition`".into());
+            }
+            let pickup = Self::find_by_id(conn, id)?;
+            if !pickup.status.can_transition_to(status, false) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&pickup.status, status)));
+            }
+        }
+        if let Some(admin_id) = update_dto.admin_id {
+            // only members allowed to approve requests in the organization can act as admin
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        Self::update_columns(conn, id, Self::update_params(update_dto))
+    }
+
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(status) = update_dto.status {
-            query.push_str("status = :status, ");
             params.push(("status".to_string(), status.to_string().into()));
         }
         if let Some(admin_id) = update_dto.admin_id {
-            query.push_str("admin_id = :admin_id, ");
             params.push(("admin_id".to_string(), admin_id.into()));
         }
         if let Some(note) = update_dto.note {
-            query.push_str("note = :note, ");
             params.push(("note".to_string(), note.into()));
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
-        }
-        query.push_str(&format!(" WHERE id = :id;"));
-        params.push(("id".to_string(), id.into()));
-
-        let params = Params::from(params);
-        let query_result = conn.exec_iter(&query, params)?;
-        Ok(query_result.affected_rows())
+        params
     }
 }
@@ -1,21 +1,201 @@
+use std::sync::Arc;
+
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         schedule::{
             shift_trade::{ ShiftTrade, RequestCreateShiftTrade, create_shift_trades_table_query },
+            shift::{ Shift, ShiftValidationError, ShiftValidationRule },
             RequestUpdateShiftRequest,
+            ShiftRequestStatus,
+            InvalidStatusTransitionError,
         },
-        result::Result,
+        pagination::{ ListScope, SortOrder },
+        result::{ Result, NotFoundError },
+        org_member::OrgPermission,
     },
-    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
+    prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable, atomic::Atomic },
+    queries::org_member::OrgMemberQueries,
+    snowflake::SnowflakeGenerator,
 };
 
+use super::shift::ShiftQueries;
+
 pub struct ShiftTradeQueries {}
 
+impl ShiftTradeQueries {
+    /// Restricts a list to the trades of the shifts of an organization.
+    pub fn org_scope(org_id: i64) -> ListScope {
+        ListScope::filter("ws.org_id = :org_id", vec![("org_id", Value::from(org_id))]).join(
+            "JOIN shifts shift ON shift.id = shift_trades.shift1_id
+            JOIN work_schedules ws ON ws.id = shift.schedule_id"
+        )
+    }
+
+    /// Returns the id of the organization the traded shifts belong to.
+    pub fn find_org_id(conn: &mut impl Queryable, id: i64) -> Result<i64> {
+        let org_id: Option<i64> = conn.exec_first(
+            "SELECT ws.org_id FROM shift_trades trade
+            JOIN shifts shift ON shift.id = trade.shift1_id
+            JOIN work_schedules ws ON ws.id = shift.schedule_id
+            WHERE trade.id = :id;",
+            params! { "id" => id }
+        )?;
+        org_id.ok_or_else(|| NotFoundError("Shift trade not found".to_string()).into())
+    }
+
+    /// Makes sure two shifts belong to the same organization.
+    fn check_same_org<Q: Queryable>(conn: &mut Q, shift1_id: i64, shift2_id: i64) -> Result<()> {
+        let org_ids: Vec<i64> = conn.exec(
+            "SELECT DISTINCT ws.org_id FROM shifts shift
+            JOIN work_schedules ws ON ws.id = shift.schedule_id
+            WHERE shift.id IN (:shift1_id, :shift2_id);",
+            params! { "shift1_id" => shift1_id, "shift2_id" => shift2_id }
+        )?;
+        if org_ids.len() > 1 {
+            return Err(
+                Box::new(
+                    ShiftValidationError::new(
+                        ShiftValidationRule::DIFFERENT_ORGANIZATIONS,
+                        "Shifts can only be traded inside an organization"
+                    )
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Makes sure neither of the shifts has started yet.
+    fn check_not_started<Q: Queryable>(conn: &mut Q, shift1_id: i64, shift2_id: i64) -> Result<()> {
+        let started_shift_id: Option<i64> = conn.exec_first(
+            "SELECT id FROM shifts
+            WHERE id IN (:shift1_id, :shift2_id) AND start_time <= NOW()
+            LIMIT 1;",
+            params! { "shift1_id" => shift1_id, "shift2_id" => shift2_id }
+        )?;
+        if let Some(started_shift_id) = started_shift_id {
+            return Err(
+                Box::new(
+                    ShiftValidationError::new(
+                        ShiftValidationRule::ALREADY_STARTED,
+                        format!("Shift {} has already started", started_shift_id)
+                    )
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Moves a trade to `status`, recording the admin acting on it and an optional note.
+    ///
+    /// Approving a trade swaps the members of the two shifts: both the status change and the swap
+    /// happen in a single transaction, after checking again that the trade is still
+    /// `PEER_ACCEPTED`, that both shifts are in the same organization, that neither has started
+    /// and that each member is free to work the shift they receive. The checks run with the trade
+    /// and both shifts locked, so two approvals can't double-book a member.
+    pub fn transition(
+        conn: &mut impl Atomic,
+        id: i64,
+        status: ShiftRequestStatus,
+        admin_id: Option<i64>,
+        note: Option<String>
+    ) -> Result<()> {
+        if let Some(admin_id) = admin_id {
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        let trade = Self::find_by_id(conn, id)?;
+        if !trade.status.can_transition_to(&status, true) {
+            return Err(Box::new(InvalidStatusTransitionError::new(&trade.status, &status)));
+        }
+
+        conn.atomic(|tx| {
+            let current_status: Option<String> = tx.exec_first(
+                "SELECT status FROM shift_trades WHERE id = :id FOR UPDATE;",
+                params! { "id" => id }
+            )?;
+            let current_status: ShiftRequestStatus = current_status
+                .ok_or_else(|| NotFoundError("Shift trade not found".to_string()))?
+                .parse()?;
+            if !current_status.can_transition_to(&status, true) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&current_status, &status)));
+            }
+
+            if status == ShiftRequestStatus::APPROVED {
+                let shifts: Vec<Shift> = tx.exec(
+                    "SELECT * FROM shifts WHERE id IN (:shift1_id, :shift2_id) FOR UPDATE;",
+                    params! { "shift1_id" => trade.shift1_id, "shift2_id" => trade.shift2_id }
+                )?;
+                Self::check_same_org(tx, trade.shift1_id, trade.shift2_id)?;
+                Self::check_not_started(tx, trade.shift1_id, trade.shift2_id)?;
+
+                let (shift1, shift2) = match (
+                    shifts.iter().find(|shift| shift.id == trade.shift1_id),
+                    shifts.iter().find(|shift| shift.id == trade.shift2_id),
+                ) {
+                    (Some(shift1), Some(shift2)) => (shift1, shift2),
+                    _ => {
+                        return Err(Box::new(NotFoundError("Shift not found".to_string())));
+                    }
+                };
+
+                // each member must be free to work the shift they receive
+                ShiftQueries::validate(
+                    tx,
+                    shift1.user_id,
+                    shift2.schedule_id,
+                    shift2.start_time,
+                    shift2.end_time,
+                    Some(shift1.id)
+                )?;
+                ShiftQueries::validate(
+                    tx,
+                    shift2.user_id,
+                    shift1.schedule_id,
+                    shift1.start_time,
+                    shift1.end_time,
+                    Some(shift2.id)
+                )?;
+
+                tx.exec_batch(
+                    "UPDATE shifts SET user_id = :user_id WHERE id = :id;",
+                    vec![
+                        params! { "user_id" => shift2.user_id, "id" => shift1.id },
+                        params! { "user_id" => shift1.user_id, "id" => shift2.id }
+                    ]
+                )?;
+            }
+
+            tx.exec_drop(
+                "UPDATE shift_trades
+                SET status = :status, admin_id = COALESCE(:admin_id, admin_id), note = COALESCE(:note, note)
+                WHERE id = :id;",
+                params! {
+                    "status" => status.to_string(),
+                    "admin_id" => admin_id,
+                    "note" => note,
+                    "id" => id,
+                }
+            )?;
+
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
 impl DatabaseTable for ShiftTradeQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_shift_trades_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -31,6 +211,18 @@ impl BasicQueries for ShiftTradeQueries {
         "shift_trades".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["shift1_id", "shift2_id", "status", "admin_id", "updated_at"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "updated_at"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("updated_at", SortOrder::DESC)
+    }
+
     fn insert_statement() -> String {
         format!(
             r"INSERT INTO {} (id, shift1_id, shift2_id) VALUES (:id, :shift1_id, :shift2_id);",
@@ -47,35 +239,65 @@ impl BasicQueries for ShiftTradeQueries {
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE shift_trades SET ".to_string();
+    fn create_entity(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dto: Self::CreateDto
+    ) -> Result<i64> {
+        if create_dto.shift1_id == create_dto.shift2_id {
+            return Err("A shift can't be traded with itself".into());
+        }
+
+        conn.atomic(|tx| {
+            Self::check_same_org(tx, create_dto.shift1_id, create_dto.shift2_id)?;
+
+            let id = Self::create_entity_exec(tx, snowflake_generator.clone(), &create_dto)?;
+
+            Self::create_entity_postprocessor(tx, snowflake_generator, create_dto, id)
+        })
+    }
+
+    /// Updates the note, the admin or the status of a trade.
+    ///
+    /// Status changes must follow the trade workflow, see `ShiftRequestStatus::can_transition_to`.
+    /// Approving a trade swaps its shifts and must go through `transition` instead.
+    fn update_entity(conn: &mut impl Atomic, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
+        if let Some(status) = &update_dto.status {
+            if *status == ShiftRequestStatus::APPROVED {
+                return Err("Shift trades must be approved with `ShiftTradeQueries::transition`".into());
+            }
+            let trade = Self::find_by_id(conn, id)?;
+            if !trade.status.can_transition_to(status, true) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&trade.status, status)));
+            }
+        }
+        if let Some(admin_id) = update_dto.admin_id {
+            // only members allowed to approve requests in the organization can act as admin
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        Self::update_columns(conn, id, Self::update_params(update_dto))
+    }
+
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(status) = update_dto.status {
-            query.push_str("status = :status, ");
             params.push(("status".to_string(), status.to_string().into()));
         }
         if let Some(admin_id) = update_dto.admin_id {
-            query.push_str("admin_id = :admin_id, ");
             params.push(("admin_id".to_string(), admin_id.into()));
         }
         if let Some(note) = update_dto.note {
-            query.push_str("note = :note, ");
             params.push(("note".to_string(), note.into()));
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
-        }
-
-        query.push_str(" WHERE id = :id;");
-        params.push(("id".to_string(), id.into()));
-
-        let query_result = conn.exec_iter(&query, params)?;
-        Ok(query_result.affected_rows())
+        params
     }
 }
@@ -1,21 +1,203 @@
-use mysql::{ *, prelude::Queryable };
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use mysql::*;
 
 use crate::{
-    prototypes::{ create_table::DatabaseTable, basic_queries::BasicQueries },
+    storage::{ Connection, Queryable, Transaction },
+    prototypes::{ create_table::DatabaseTable, basic_queries::BasicQueries, atomic::Atomic },
     models::{
-        schedule::timeoff_request::{
-            create_time_off_requests_table_query,
-            TimeOffRequest,
-            RequestCreateTimeOff,
-            RequestUpdateTimeOff,
+        schedule::{
+            timeoff_request::{
+                create_time_off_requests_table_query,
+                TimeOffRequest,
+                RequestCreateTimeOff,
+                RequestUpdateTimeOff,
+                OverlappingShiftPolicy,
+                TimeOffApproval,
+            },
+            request_status::ScheduleRequestStatus,
+            open_shift::RequestCreateOpenShift,
+            InvalidStatusTransitionError,
         },
-        result::Result,
+        pagination::{ ListScope, SortOrder },
+        result::{ Result, NotFoundError },
+        org_member::OrgPermission,
     },
+    queries::org_member::OrgMemberQueries,
+    snowflake::SnowflakeGenerator,
 };
 
+use super::{ open_shift::OpenShiftQueries, shift::ShiftQueries };
+
 pub struct TimeOffRequestQueries;
+
+impl TimeOffRequestQueries {
+    /// Restricts a list to the time off of an organization.
+    pub fn org_scope(org_id: i64) -> ListScope {
+        ListScope::column("org_id", org_id)
+    }
+
+    /// Returns the id of the organization the time off is requested in.
+    pub fn find_org_id(conn: &mut impl Queryable, id: i64) -> Result<i64> {
+        let org_id: Option<i64> = conn.exec_first(
+            "SELECT org_id FROM time_off_requests WHERE id = :id;",
+            params! { "id" => id }
+        )?;
+        org_id.ok_or_else(|| NotFoundError("Time off request not found".to_string()).into())
+    }
+
+    /// Retrieves the approved time off of a user in an organization overlapping the given window.
+    pub fn find_approved_overlapping(
+        conn: &mut impl Queryable,
+        user_id: i64,
+        org_id: i64,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime
+    ) -> Result<Vec<TimeOffRequest>> {
+        Ok(
+            conn.exec(
+                "SELECT * FROM time_off_requests
+                WHERE user_id = :user_id
+                    AND org_id = :org_id
+                    AND status = 'APPROVED'
+                    AND start_time < :end_time
+                    AND end_time > :start_time;",
+                params! {
+                    "user_id" => user_id,
+                    "org_id" => org_id,
+                    "start_time" => start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    "end_time" => end_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                }
+            )?
+        )
+    }
+
+    /// Locks a request for the rest of the transaction, making sure it can move to `status`.
+    fn lock_for_transition(
+        tx: &mut Transaction<'_>,
+        id: i64,
+        status: &ScheduleRequestStatus
+    ) -> Result<TimeOffRequest> {
+        let request: TimeOffRequest = tx
+            .exec_first(
+                "SELECT * FROM time_off_requests WHERE id = :id FOR UPDATE;",
+                params! { "id" => id }
+            )?
+            .ok_or_else(|| NotFoundError("Time off request not found".to_string()))?;
+        if !request.status.can_transition_to(status) {
+            return Err(Box::new(InvalidStatusTransitionError::new(&request.status, status)));
+        }
+
+        Ok(request)
+    }
+
+    /// Declines or cancels a request, recording the admin declining it.
+    ///
+    /// Approving time off may affect shifts and must go through `approve` instead.
+    pub fn transition(
+        conn: &mut impl Atomic,
+        id: i64,
+        status: ScheduleRequestStatus,
+        admin_id: Option<i64>
+    ) -> Result<()> {
+        if status == ScheduleRequestStatus::APPROVED {
+            return Err("Time off must be approved with `TimeOffRequestQueries::approve`".into());
+        }
+        if let Some(admin_id) = admin_id {
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        conn.atomic(|tx| {
+            Self::lock_for_transition(tx, id, &status)?;
+            tx.exec_drop(
+                "UPDATE time_off_requests
+                SET status = :status, admin_id = COALESCE(:admin_id, admin_id)
+                WHERE id = :id;",
+                params! { "status" => status.to_string(), "admin_id" => admin_id, "id" => id }
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Approves a request and reports the shifts the member holds during the time off.
+    ///
+    /// With `CONVERT_TO_OPEN_SHIFTS`, those shifts are turned into open shifts in the same
+    /// transaction as the approval.
+    pub fn approve(
+        conn: &mut impl Atomic,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        id: i64,
+        admin_id: i64,
+        policy: OverlappingShiftPolicy
+    ) -> Result<TimeOffApproval> {
+        let org_id = Self::find_org_id(conn, id)?;
+        OrgMemberQueries::ensure_permission(
+            conn,
+            org_id,
+            admin_id,
+            OrgPermission::APPROVE_REQUESTS
+        )?;
+
+        let (overlapping_shifts, open_shift_ids) = conn.atomic(|tx| {
+            let request = Self::lock_for_transition(tx, id, &ScheduleRequestStatus::APPROVED)?;
+
+            let overlapping_shifts = ShiftQueries::find_overlapping_for_user(
+                tx,
+                request.user_id,
+                request.org_id,
+                request.start_time,
+                request.end_time
+            )?;
+
+            let mut open_shift_ids = Vec::new();
+            if policy == OverlappingShiftPolicy::CONVERT_TO_OPEN_SHIFTS {
+                for shift in &overlapping_shifts {
+                    let open_shift_id = snowflake_generator.generate_id();
+                    let create_dto = RequestCreateOpenShift {
+                        schedule_id: shift.schedule_id,
+                        job_id: shift.job_id,
+                        start_time: shift.start_time,
+                        end_time: shift.end_time,
+                        pay_rate: shift.pay_rate,
+                        headcount: None,
+                    };
+                    let mut params_map = match OpenShiftQueries::insert_params(&create_dto)? {
+                        Params::Named(map) => map,
+                        _ => {
+                            return Err("Expected named parameters".into());
+                        }
+                    };
+                    params_map.insert("id".to_string().into_bytes(), Value::from(open_shift_id));
+                    tx.exec_drop(OpenShiftQueries::insert_statement(), Params::Named(params_map))?;
+                    tx.exec_drop("DELETE FROM shifts WHERE id = :id;", params! { "id" => shift.id })?;
+                    open_shift_ids.push(open_shift_id);
+                }
+            }
+
+            tx.exec_drop(
+                "UPDATE time_off_requests SET status = 'APPROVED', admin_id = :admin_id WHERE id = :id;",
+                params! { "admin_id" => admin_id, "id" => id }
+            )?;
+            Ok((overlapping_shifts, open_shift_ids))
+        })?;
+
+        Ok(TimeOffApproval {
+            request: Self::find_by_id(conn, id)?,
+            overlapping_shifts,
+            open_shift_ids,
+        })
+    }
+}
+
 impl DatabaseTable for TimeOffRequestQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_time_off_requests_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -31,6 +213,18 @@ impl BasicQueries for TimeOffRequestQueries {
         "time_off_requests".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["user_id", "status", "admin_id", "start_time", "end_time", "updated_at"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "start_time", "end_time", "updated_at"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("start_time", SortOrder::DESC)
+    }
+
     fn insert_statement() -> String {
         format!(
             "INSERT INTO {} (id, user_id, org_id, start_time, end_time, reason) VALUES (:id, :user_id, :org_id, :start_time, :end_time, :reason);",
@@ -50,40 +244,40 @@ impl BasicQueries for TimeOffRequestQueries {
         )
     }
 
-    fn update_entity(
-        conn: &mut mysql::PooledConn,
-        id: i64,
-        update_dto: Self::UpdateDto
-    ) -> crate::models::result::Result<u64> {
-        let mut query = format!("UPDATE {} SET ", Self::table_name());
+    fn update_entity(conn: &mut impl Atomic, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
+        if let Some(status) = &update_dto.status {
+            let request = Self::find_by_id(conn, id)?;
+            if !request.status.can_transition_to(status) {
+                return Err(Box::new(InvalidStatusTransitionError::new(&request.status, status)));
+            }
+        }
+        if let Some(admin_id) = update_dto.admin_id {
+            // only members allowed to approve requests in the organization can act as admin
+            let org_id = Self::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                admin_id,
+                OrgPermission::APPROVE_REQUESTS
+            )?;
+        }
+
+        Self::update_columns(conn, id, Self::update_params(update_dto))
+    }
+
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(status) = update_dto.status {
-            query.push_str("status = :status, ");
             params.push(("status".to_string(), status.to_string().into()));
         }
         if let Some(admin_id) = update_dto.admin_id {
-            query.push_str("admin_id = :admin_id, ");
             params.push(("admin_id".to_string(), admin_id.into()));
         }
         if let Some(reason) = update_dto.reason {
-            query.push_str("reason = :reason, ");
             params.push(("reason".to_string(), reason.into()));
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
-        }
-
-        query.push_str(" WHERE id = :id;");
-        params.push(("id".to_string(), id.into()));
-
-        let params = Params::from(params);
-        let query_result = conn.exec_iter(&query, params)?;
-        Ok(query_result.affected_rows())
+        params
     }
 }
@@ -1,29 +1,77 @@
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
         schedule::work_schedule::{
             WorkSchedule,
+            WorkScheduleWeek,
+            ScheduleDay,
+            ScheduleMember,
             RequestCreateWorkSchedule,
             RequestUpdateWorkSchedule,
             create_work_schedules_table_query,
         },
+        pagination::{ ListScope, SortOrder },
         result::Result,
     },
     prototypes::{ basic_queries::BasicQueries, create_table::DatabaseTable },
 };
 
+use super::shift::ShiftQueries;
+
 pub struct WorkScheduleQueries {}
 
 impl WorkScheduleQueries {
-    pub fn get_org_schedules(conn: &mut PooledConn, org_id: i64) -> Result<Vec<WorkSchedule>> {
-        Ok(conn.query(format!("SELECT * FROM work_schedules WHERE org_id = {};", org_id))?)
+    /// Restricts a list to the schedules of an organization.
+    pub fn org_scope(org_id: i64) -> ListScope {
+        ListScope::column("org_id", org_id)
+    }
+
+    /// Retrieves a schedule with its shifts grouped by day and by member.
+    pub fn get_week_view(conn: &mut impl Queryable, id: i64) -> Result<WorkScheduleWeek> {
+        let schedule = Self::find_by_id(conn, id)?;
+        let shifts = ShiftQueries::find_details_by_schedule_id(conn, id)?;
+
+        let group_by_day = |user_id: Option<i64>| -> Vec<ScheduleDay> {
+            schedule.start_date
+                .iter_days()
+                .take_while(|date| *date <= schedule.end_date)
+                .map(|date| ScheduleDay {
+                    date,
+                    shifts: shifts
+                        .iter()
+                        .filter(|shift| shift.start_time.date() == date)
+                        .filter(|shift| user_id.is_none() || user_id == Some(shift.user_id))
+                        .cloned()
+                        .collect(),
+                })
+                .collect()
+        };
+
+        let mut members: Vec<ScheduleMember> = Vec::new();
+        for shift in &shifts {
+            if !members.iter().any(|member| member.user_id == shift.user_id) {
+                members.push(ScheduleMember {
+                    user_id: shift.user_id,
+                    first_name: shift.user_first_name.clone(),
+                    last_name: shift.user_last_name.clone(),
+                    days: group_by_day(Some(shift.user_id)),
+                });
+            }
+        }
+        members.sort_by(|a, b| (&a.last_name, &a.first_name).cmp(&(&b.last_name, &b.first_name)));
+
+        Ok(WorkScheduleWeek {
+            days: group_by_day(None),
+            members,
+            schedule,
+        })
     }
 }
 
 impl DatabaseTable for WorkScheduleQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_work_schedules_table_query();
         conn.query_drop(query)?;
         Ok(())
@@ -39,6 +87,18 @@ impl BasicQueries for WorkScheduleQueries {
         "work_schedules".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        vec!["published", "start_date", "end_date"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "start_date", "end_date", "updated_at"]
+    }
+
+    fn default_sort() -> (&'static str, SortOrder) {
+        ("start_date", SortOrder::DESC)
+    }
+
     fn insert_statement() -> String {
         format!(
             "INSERT INTO {} (id, org_id, start_date, end_date) VALUES (:id, :org_id, :start_date, :end_date)",
@@ -56,37 +116,19 @@ impl BasicQueries for WorkScheduleQueries {
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE work_schedules SET ".to_string();
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(start_date) = update_dto.start_date {
-            query.push_str("start_date = :start_date, ");
             params.push(("start_date".to_string(), start_date.to_string().into()));
         }
         if let Some(end_date) = update_dto.end_date {
-            query.push_str("end_date = :end_date, ");
             params.push(("end_date".to_string(), end_date.to_string().into()));
         }
         if let Some(publish) = update_dto.publish {
-            query.push_str("published = :publish, ");
-            params.push(("publish".to_string(), publish.into()));
+            params.push(("published".to_string(), publish.into()));
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
-        }
-
-        query.push_str(&format!(" WHERE id = :id;"));
-        params.push(("id".to_string(), id.into()));
-
-        let params = Params::from(params);
-        let query_result = conn.exec_iter(&query, params)?;
-
-        Ok(query_result.affected_rows())
+        params
     }
 }
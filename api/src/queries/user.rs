@@ -1,28 +1,26 @@
-use std::env;
-
 use mysql::*;
-use mysql::prelude::*;
 use serde::{ Serialize, Deserialize };
+use utoipa::ToSchema;
 use bcrypt::{ hash, verify };
 
 use crate::{
+    storage::{ Connection, Queryable },
     models::{
-        user::{ User, RequestCreateUser, RequestUpdateUser, create_users_table_query },
-        result::Result,
+        user::{ User, NewUser, RequestCreateUser, RequestUpdateUser, create_users_table_query },
+        result::{ Result, NotFoundError },
     },
-    prototypes::create_table::DatabaseTable,
+    prototypes::{ create_table::DatabaseTable, validate::{ Validate, ValidationError, Validator } },
+    utilities::app_error::FieldError,
 };
 
 use crate::prototypes::basic_queries::BasicQueries;
 
-pub fn hash_password(password: &str) -> std::result::Result<String, bcrypt::BcryptError> {
-    dotenv::dotenv().ok();
-    let salt: u32 = env
-        ::var("HASH_COST")
-        .expect("HASH_COST must be set in the .env file")
-        .parse::<u32>()
-        .unwrap();
-    let hashed_password = hash(password, salt)?;
+/// Hashes `password` with bcrypt at `hash_cost`, see `AuthConfig::hash_cost`.
+pub fn hash_password(
+    password: &str,
+    hash_cost: u32
+) -> std::result::Result<String, bcrypt::BcryptError> {
+    let hashed_password = hash(password, hash_cost)?;
     Ok(hashed_password)
 }
 
@@ -35,21 +33,44 @@ pub fn verify_password(
 
 pub struct UserQueries {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct LoginForm {
     pub email: String,
     pub password: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PasswordChangeForm {
     pub id: i64,
     pub old_password: String,
     pub new_password: String,
 }
 
+impl Validate for PasswordChangeForm {
+    fn validate_fields(&self, validator: &mut Validator) {
+        // the same rule as the password of `RequestCreateUser`
+        validator.check(
+            (8..=72).contains(&self.new_password.len()),
+            "new_password",
+            "must be 8 to 72 characters"
+        );
+    }
+}
+
 impl UserQueries {
-    pub fn verify_password(conn: &mut PooledConn, login_form: LoginForm) -> Result<bool> {
+    /// The user to insert for `create_dto`, its password hashed at `hash_cost`.
+    pub fn new_user(create_dto: RequestCreateUser, hash_cost: u32) -> Result<NewUser> {
+        Ok(NewUser {
+            encrypted_password: hash_password(&create_dto.password, hash_cost)?,
+            email: create_dto.email,
+            first_name: create_dto.first_name,
+            last_name: create_dto.last_name,
+            date_of_birth: create_dto.date_of_birth,
+            phone_number: create_dto.phone_number,
+        })
+    }
+
+    pub fn verify_password(conn: &mut impl Queryable, login_form: LoginForm) -> Result<bool> {
         let encrypted_password: Option<String> = conn.exec_first(
             "SELECT encrypted_password FROM users WHERE email = :email;",
             params! { "email" => login_form.email }
@@ -61,14 +82,36 @@ impl UserQueries {
         }
     }
 
-    pub fn change_password(conn: &mut PooledConn, change_form: PasswordChangeForm) -> Result<()> {
+    /// Returns the user matching the login form, or `None` if the password doesn't match.
+    pub fn authenticate(conn: &mut impl Queryable, login_form: LoginForm) -> Result<Option<User>> {
+        let user: Option<User> = conn.exec_first(
+            "SELECT * FROM users WHERE email = :email;",
+            params! { "email" => login_form.email }
+        )?;
+        match user {
+            Some(user) if verify_password(&login_form.password, &user.encrypted_password)? => {
+                Ok(Some(user))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Replaces the password of the user, after checking their current one.
+    ///
+    /// Fails with a `ValidationError` on `old_password` if it's wrong, and a `NotFoundError` if
+    /// the user doesn't exist.
+    pub fn change_password(
+        conn: &mut impl Queryable,
+        change_form: PasswordChangeForm,
+        hash_cost: u32
+    ) -> Result<()> {
         let encrypted_password: Option<String> = conn.exec_first(
             "SELECT encrypted_password FROM users WHERE id = :id;",
             params! { "id" => change_form.id }
         )?;
         if let Some(encrypted_password) = encrypted_password {
             if verify_password(&change_form.old_password, &encrypted_password)? {
-                let encrypted_password = hash_password(&change_form.new_password)?;
+                let encrypted_password = hash_password(&change_form.new_password, hash_cost)?;
                 conn.exec_drop(
                     "UPDATE users SET encrypted_password = :encrypted_password WHERE id = :id;",
                     params! {
@@ -77,20 +120,25 @@ impl UserQueries {
                     }
                 )?;
             } else {
-                return Err("Wrong password.".into());
+                return Err(
+                    Box::new(
+                        ValidationError(
+                            vec![FieldError::new("old_password", "is not the current password")]
+                        )
+                    )
+                );
             }
         } else {
-            return Err("Account does not exist.".into());
+            return Err(Box::new(NotFoundError("User not found".to_string())));
         }
         Ok(())
     }
 }
 
 impl DatabaseTable for UserQueries {
-    fn create_table(&self, conn: &mut PooledConn) -> Result<()> {
+    fn create_table(&self, conn: &mut Connection) -> Result<()> {
         let query = create_users_table_query();
-        let stmt = conn.prep(query)?;
-        conn.exec_drop(stmt, ())?;
+        conn.query_drop(query)?;
 
         Ok(())
     }
@@ -99,7 +147,7 @@ impl DatabaseTable for UserQueries {
 impl BasicQueries for UserQueries {
     type Model = User;
 
-    type CreateDto = RequestCreateUser;
+    type CreateDto = NewUser;
 
     type UpdateDto = RequestUpdateUser;
 
@@ -107,6 +155,15 @@ impl BasicQueries for UserQueries {
         "users".to_string()
     }
 
+    fn filter_columns() -> Vec<&'static str> {
+        // not the email, listing users by it would tell which addresses have an account
+        vec!["first_name", "last_name", "is_active"]
+    }
+
+    fn sort_columns() -> Vec<&'static str> {
+        vec!["id", "first_name", "last_name"]
+    }
+
     fn insert_statement() -> String {
         format!(
             "INSERT INTO {} (id, email, encrypted_password, first_name, last_name, date_of_birth, phone_number)
@@ -116,11 +173,10 @@ impl BasicQueries for UserQueries {
     }
 
     fn insert_params(create_dto: &Self::CreateDto) -> Result<Params> {
-        let encrypted_password = hash_password(&create_dto.password)?;
         Ok(
             params! {
                 "email" => &create_dto.email,
-                "encrypted_password" => &encrypted_password,
+                "encrypted_password" => &create_dto.encrypted_password,
                 "first_name" => &create_dto.first_name,
                 "last_name" => &create_dto.last_name,
                 "date_of_birth" => create_dto.date_of_birth.to_string(),
@@ -129,45 +185,23 @@ impl BasicQueries for UserQueries {
         )
     }
 
-    fn update_entity(conn: &mut PooledConn, id: i64, update_dto: Self::UpdateDto) -> Result<u64> {
-        let mut query = "UPDATE users SET ".to_string();
+    fn update_params(update_dto: Self::UpdateDto) -> Vec<(String, Value)> {
         let mut params: Vec<(String, Value)> = Vec::new();
 
         if let Some(first_name) = update_dto.first_name {
-            query.push_str("first_name = :first_name, ");
             params.push(("first_name".to_string(), first_name.into()));
         }
         if let Some(last_name) = update_dto.last_name {
-            query.push_str("last_name = :last_name, ");
             params.push(("last_name".to_string(), last_name.into()));
         }
         if let Some(date_of_birth) = update_dto.date_of_birth {
-            query.push_str("date_of_birth = :date_of_birth, ");
             params.push(("date_of_birth".to_string(), date_of_birth.to_string().into()));
         }
         if let Some(phone_number) = update_dto.phone_number {
-            query.push_str("phone_number = :phone_number, ");
             params.push(("phone_number".to_string(), phone_number.into()));
         }
 
-        // Remove last comma and space if there are updates
-        if !params.is_empty() {
-            query.pop();
-            query.pop();
-        } else {
-            return Ok(0);
-        }
-
-        query.push_str(&format!(" WHERE id = {};", id));
-
-        // Convert Vec to Params::Named
-        let params = Params::from(params);
-
-        // Use exec_fold to execute the query and process the result
-        let query_result = conn.exec_iter(&query, params)?;
-
-        // Return the number of affected rows
-        Ok(query_result.affected_rows())
+        params
     }
 }
 
@@ -177,13 +211,13 @@ mod test {
 
     use super::*;
     use chrono::NaiveDate;
-    use crate::{ tests::{ initialize_test_db, cleanup_test_db }, snowflake::SnowflakeGenerator };
+    use crate::{ tests::{ initialize_test_db, cleanup_test_db, TEST_HASH_COST }, snowflake::SnowflakeGenerator };
 
     #[test]
     fn test_users_table() -> Result<()> {
         // Setup database connection
-        let pool = initialize_test_db()?;
-        let mut conn = pool.get_conn()?;
+        let storage = initialize_test_db()?;
+        let mut conn = storage.get_conn()?;
         let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
 
         let users = vec![
@@ -206,7 +240,8 @@ mod test {
         ];
 
         for user in &users {
-            UserQueries::create_entity(&mut conn, snowflake_generator.clone(), user.clone())?;
+            let user = UserQueries::new_user(user.clone(), TEST_HASH_COST)?;
+            UserQueries::create_entity(&mut conn, snowflake_generator.clone(), user)?;
         }
 
         assert!(
@@ -0,0 +1,76 @@
+use axum::{ async_trait, extract::FromRequestParts, http::{ request::Parts, header, HeaderMap }, Extension };
+
+use crate::{
+    app::AppState,
+    models::{ result::NotFoundError, user::User },
+    queries::user::UserQueries,
+    storage::Repository,
+    utilities::{ app_error::AppError, token::{ decode_token, TokenClaims, TokenKind } },
+};
+
+/// The user making the request, resolved from the `Authorization: Bearer <access token>` header.
+///
+/// Handlers take `AuthUser` as an argument to require an authenticated caller; the request is
+/// rejected with `401 Unauthorized` if the token is missing, invalid, expired, or belongs to an
+/// inactive account. Failing to look the account up is a `500 Internal Server Error`.
+#[derive(Debug, Clone)]
+pub struct AuthUser(pub User);
+
+/// Extracts the bearer token from the `Authorization` header, if any.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Verifies the access token of the request.
+pub fn access_claims(headers: &HeaderMap, secret: &str) -> Result<TokenClaims, AppError> {
+    let token = bearer_token(headers).ok_or_else(||
+        AppError::unauthorized("Missing bearer token")
+    )?;
+    decode_token(secret, token, TokenKind::Access).map_err(|_|
+        AppError::unauthorized("Invalid or expired token")
+    )
+}
+
+/// Retrieves the active account the verified `claims` were issued to.
+pub async fn authenticate(state: &AppState, claims: &TokenClaims) -> Result<User, AppError> {
+    let user = match state.storage.find_by_id::<UserQueries>(claims.sub).await {
+        Ok(user) => user,
+        Err(error) if error.is::<NotFoundError>() => {
+            return Err(AppError::unauthorized("Account does not exist."));
+        }
+        Err(error) => {
+            return Err(AppError::from(error));
+        }
+    };
+    if !user.is_active {
+        return Err(AppError::unauthorized("Account is deactivated."));
+    }
+
+    Ok(user)
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser where S: Send + Sync {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(app_state) = Extension::<AppState>
+            ::from_request_parts(parts, state).await
+            .map_err(|_| AppError::internal_server_error("Application state is missing"))?;
+
+        // the `require_auth` middleware already authenticated the caller, reuse its user if present
+        let user = match parts.extensions.get::<AuthUser>() {
+            Some(AuthUser(user)) => user.clone(),
+            None => {
+                let claims = access_claims(&parts.headers, &app_state.config.auth.jwt_secret)?;
+                authenticate(&app_state, &claims).await?
+            }
+        };
+        tracing::Span::current().record("user_id", user.id);
+
+        Ok(AuthUser(user))
+    }
+}
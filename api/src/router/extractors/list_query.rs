@@ -0,0 +1,21 @@
+use std::collections::HashMap;
+
+use axum::{ async_trait, extract::{ FromRequestParts, Query }, http::request::Parts };
+
+use crate::{ models::pagination::ListQuery, utilities::app_error::AppError };
+
+/// Reads the page, sort and filters of list endpoints from the query string, see `ListQuery`.
+///
+/// The request is rejected with `400 Bad Request` if a page parameter is invalid.
+#[async_trait]
+impl<S> FromRequestParts<S> for ListQuery where S: Send + Sync {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<HashMap<String, String>>
+            ::from_request_parts(parts, state).await
+            .map_err(|err| AppError::bad_request(err.body_text()))?;
+
+        ListQuery::from_params(params).map_err(|err| AppError::bad_request(err.to_string()))
+    }
+}
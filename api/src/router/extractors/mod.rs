@@ -0,0 +1,2 @@
+pub mod auth_user;
+pub mod list_query;
@@ -1,6 +1,47 @@
-use axum::{ response::Response, middleware::Next, extract::Request };
+use std::time::Instant;
 
+use axum::{ response::Response, middleware::Next, extract::{ MatchedPath, Request } };
+use tracing::{ field::Empty, Instrument, Span };
+
+use crate::metrics::metrics;
+
+/// Runs every request in a `request` span and logs its outcome.
+///
+/// The span starts with the method and path, the other middlewares and extractors record the
+/// rest of its fields as they learn them: the request id, the authenticated user and the
+/// organization whose permissions are checked. The status and latency are recorded once the
+/// response is ready, and counted in `metrics()` under the template of the matched route.
 pub async fn log_route(req: Request, next: Next) -> Response {
-    println!("Accessing route: {} {}", req.method().as_str().to_uppercase(), req.uri().path());
-    next.run(req).await
+    let span = tracing::info_span!(
+        "request",
+        request_id = Empty,
+        method = %req.method(),
+        path = %req.uri().path(),
+        status = Empty,
+        latency_ms = Empty,
+        user_id = Empty,
+        org_id = Empty
+    );
+    let method = req.method().clone();
+    let route = req.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let start = Instant::now();
+
+    async move {
+        let response = next.run(req).await;
+
+        let elapsed = start.elapsed();
+        metrics().observe_request(method.as_str(), route.as_deref(), response.status(), elapsed);
+
+        let span = Span::current();
+        span.record("status", response.status().as_u16());
+        span.record("latency_ms", elapsed.as_secs_f64() * 1000.0);
+        if response.status().is_server_error() {
+            tracing::error!("request failed");
+        } else {
+            tracing::info!("request completed");
+        }
+        response
+    }
+        .instrument(span)
+        .await
 }
@@ -1 +1,4 @@
 pub mod log_route;
+pub mod require_auth;
+pub mod request_id;
+pub mod reject_when_draining;
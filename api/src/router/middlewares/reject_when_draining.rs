@@ -0,0 +1,32 @@
+use axum::{
+    extract::Request,
+    http::{ header, HeaderValue, StatusCode },
+    middleware::Next,
+    response::{ IntoResponse, Response },
+    Extension,
+};
+
+use crate::{ app::AppState, utilities::app_error::{ AppError, ErrorCode } };
+
+/// Answers `503 Service Unavailable` while the server is shutting down, so the requests go to
+/// another instance instead of starting work that may not finish before the drain timeout.
+///
+/// Requests already being handled aren't affected. The connection is closed to move the client
+/// off this instance.
+pub async fn reject_when_draining(
+    Extension(state): Extension<AppState>,
+    req: Request,
+    next: Next
+) -> Response {
+    if !state.shutdown.is_draining() {
+        return next.run(req).await;
+    }
+
+    let mut response = AppError::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        ErrorCode::SHUTTING_DOWN,
+        "The server is shutting down"
+    ).into_response();
+    response.headers_mut().insert(header::CONNECTION, HeaderValue::from_static("close"));
+    response
+}
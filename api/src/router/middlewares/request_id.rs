@@ -0,0 +1,36 @@
+use axum::{
+    extract::Request,
+    http::{ HeaderName, HeaderValue },
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+
+use crate::{ app::AppState, utilities::app_error::REQUEST_ID };
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Gives every request an id, sent back in the `x-request-id` header and in error responses.
+///
+/// The id of the caller is kept if the request has one, otherwise a Snowflake ID is generated.
+/// It is recorded in the request span of `log_route`.
+pub async fn request_id(
+    Extension(state): Extension<AppState>,
+    req: Request,
+    next: Next
+) -> Response {
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| state.snowflake_generator.generate_id().to_string());
+
+    tracing::Span::current().record("request_id", request_id.as_str());
+    let mut response = REQUEST_ID.scope(request_id.clone(), next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
@@ -0,0 +1,121 @@
+use axum::{ extract::Request, http::Method, middleware::Next, response::{ IntoResponse, Response }, Extension };
+
+use crate::{
+    app::AppState,
+    router::extractors::auth_user::{ access_claims, authenticate, AuthUser },
+};
+
+/// Routes that can be reached without an access token: login, token refresh and registration.
+const PUBLIC_ROUTES: [(Method, &str); 3] = [
+    (Method::POST, "/auth/login"),
+    (Method::POST, "/auth/refresh"),
+    (Method::POST, "/users"),
+];
+
+/// Whether `method` and `path` are one of the `PUBLIC_ROUTES`.
+pub fn is_public_route(method: &Method, path: &str) -> bool {
+    PUBLIC_ROUTES.iter().any(|(public_method, public_path)| {
+        method == public_method && path == *public_path
+    })
+}
+
+/// Rejects requests without a valid access token of an active account, except for the public
+/// routes.
+///
+/// The account is stored in the request extensions for the `AuthUser` extractor, and its id
+/// recorded on the request span, also for the routes that don't extract it.
+pub async fn require_auth(
+    Extension(state): Extension<AppState>,
+    mut req: Request,
+    next: Next
+) -> Response {
+    if is_public_route(req.method(), req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let claims = match access_claims(req.headers(), &state.config.auth.jwt_secret) {
+        Ok(claims) => claims,
+        Err(e) => {
+            return e.into_response();
+        }
+    };
+    let user = match authenticate(&state, &claims).await {
+        Ok(user) => user,
+        Err(e) => {
+            return e.into_response();
+        }
+    };
+    tracing::Span::current().record("user_id", user.id);
+    req.extensions_mut().insert(AuthUser(user));
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ io::Write, sync::{ Arc, Mutex } };
+
+    use axum::{ body::Body, http::StatusCode, middleware, routing::get, Router };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        models::result::Result,
+        router::{
+            middlewares::log_route::log_route,
+            tests::{ initialize_memory_app_state, create_test_user },
+        },
+    };
+
+    /// Log output shared with the test.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_id_is_logged() -> Result<()> {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber
+            ::fmt()
+            .json()
+            .with_current_span(true)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let state = initialize_memory_app_state();
+        let (user_id, authorization) = create_test_user(&state, "logged@example.com")?;
+        // the route doesn't extract the `AuthUser`
+        let router = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .layer(middleware::from_fn(require_auth))
+            .layer(Extension(state))
+            .layer(middleware::from_fn(log_route));
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/ping")
+                    .header("Authorization", authorization)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone())?;
+        assert!(logs.contains(&format!("\"user_id\":{}", user_id)), "{}", logs);
+
+        Ok(())
+    }
+}
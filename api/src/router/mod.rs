@@ -1,5 +1,8 @@
 mod routes;
 mod middlewares;
+pub mod extractors;
 pub mod utils;
+#[cfg(test)]
 pub mod tests;
 pub mod router;
+pub mod openapi;
@@ -0,0 +1,550 @@
+use axum::http::{ Method, StatusCode };
+use utoipa::{
+    openapi::{
+        path::{ HttpMethod, OperationBuilder, Parameter, ParameterBuilder, ParameterIn },
+        request_body::RequestBodyBuilder,
+        response::ResponseBuilder,
+        security::{ HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme },
+        server::Server,
+        Array,
+        ComponentsBuilder,
+        Content,
+        ContentBuilder,
+        InfoBuilder,
+        ObjectBuilder,
+        OneOfBuilder,
+        OpenApi,
+        OpenApiBuilder,
+        Paths,
+        Ref,
+        RefOr,
+        Required,
+        Schema,
+        Type,
+    },
+    IntoParams,
+    PartialSchema,
+    ToSchema,
+};
+
+use crate::{
+    models::pagination::MAX_PAGE_LIMIT,
+    router::middlewares::require_auth::is_public_route,
+    utilities::app_error::ErrorResponse,
+};
+
+/// Name of the security scheme of the access tokens in the OpenAPI document.
+const BEARER_AUTH: &str = "bearer";
+
+/// Describes a route for the OpenAPI document served at `/api/openapi.json`.
+///
+/// Routers list the docs of their routes next to the routes themselves, with the same paths, e.g.
+/// `/:id` in `default_route_docs` for a route of `default_routes`. `RouteDoc::nest` then prefixes
+/// them the way `Router::nest` does. The `openapi` test checks the docs against the routes of
+/// `create_router`.
+#[derive(Clone)]
+pub struct RouteDoc {
+    pub method: Method,
+    /// The path as registered in axum, e.g. `/users/:id`
+    pub path: String,
+    pub summary: String,
+    tag: Option<String>,
+    /// Reachable without an access token, besides the routes `require_auth` lets through
+    public: bool,
+    query: Vec<Parameter>,
+    /// The JSON body and whether it is required
+    body: Option<(RefOr<Schema>, bool)>,
+    status: StatusCode,
+    /// The content type and schema of the success response
+    response: Option<(&'static str, RefOr<Schema>)>,
+    /// Component schemas referenced by the body and the response
+    schemas: Vec<(String, RefOr<Schema>)>,
+}
+
+impl RouteDoc {
+    pub fn new(method: Method, path: &str, summary: &str) -> Self {
+        RouteDoc {
+            method,
+            path: path.to_string(),
+            summary: summary.to_string(),
+            tag: None,
+            public: false,
+            query: Vec::new(),
+            body: None,
+            status: StatusCode::OK,
+            response: None,
+            schemas: Vec::new(),
+        }
+    }
+
+    pub fn get(path: &str, summary: &str) -> Self {
+        Self::new(Method::GET, path, summary)
+    }
+
+    pub fn post(path: &str, summary: &str) -> Self {
+        Self::new(Method::POST, path, summary)
+    }
+
+    pub fn patch(path: &str, summary: &str) -> Self {
+        Self::new(Method::PATCH, path, summary)
+    }
+
+    pub fn delete(path: &str, summary: &str) -> Self {
+        Self::new(Method::DELETE, path, summary)
+    }
+
+    /// Prefixes the paths of `docs` with `prefix`, like `Router::nest` does with the routes.
+    pub fn nest(prefix: &str, docs: Vec<RouteDoc>) -> Vec<RouteDoc> {
+        docs.into_iter()
+            .map(|mut doc| {
+                doc.path = match doc.path.as_str() {
+                    "/" => prefix.to_string(),
+                    path => format!("{}{}", prefix, path),
+                };
+                doc
+            })
+            .collect()
+    }
+
+    /// Groups `docs` under `tag` in the document.
+    pub fn tagged(tag: &str, docs: Vec<RouteDoc>) -> Vec<RouteDoc> {
+        docs.into_iter()
+            .map(|mut doc| {
+                doc.tag = Some(tag.to_string());
+                doc
+            })
+            .collect()
+    }
+
+    /// Marks a route served outside of `require_auth`.
+    pub fn public(mut self) -> Self {
+        self.public = true;
+        self
+    }
+
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Takes a JSON body of type `T`.
+    pub fn body<T: ToSchema>(mut self) -> Self {
+        self.body = Some((self.component::<T>(), true));
+        self
+    }
+
+    /// Takes an optional JSON body of type `T`, e.g. the note of a workflow action.
+    pub fn optional_body<T: ToSchema>(mut self) -> Self {
+        self.body = Some((self.component::<T>(), false));
+        self
+    }
+
+    /// Reads the query parameters of `T`.
+    pub fn query<T: IntoParams>(mut self) -> Self {
+        self.query.extend(T::into_params(|| Some(ParameterIn::Query)));
+        self
+    }
+
+    /// Responds a `ResponseDataJson` of a `T`.
+    pub fn data<T: ToSchema>(mut self) -> Self {
+        let data = self.component::<T>();
+        self.json(data)
+    }
+
+    /// Responds a `ResponseDataJson` of a `T`, or of `null`.
+    pub fn optional_data<T: ToSchema>(mut self) -> Self {
+        let data = OneOfBuilder::new()
+            .item(self.component::<T>())
+            .item(ObjectBuilder::new().schema_type(Type::Null))
+            .build();
+        self.json(Schema::OneOf(data).into())
+    }
+
+    /// Responds a `ResponseDataJson` of a list of `T`.
+    pub fn data_list<T: ToSchema>(mut self) -> Self {
+        let data = Array::new(self.component::<T>());
+        self.json(data.into())
+    }
+
+    /// Responds a `ResponseDataPage` of `T`, reading a `ListQuery`.
+    pub fn page<T: ToSchema>(mut self) -> Self {
+        let page = ObjectBuilder::new()
+            .property("data", Array::new(self.component::<T>()))
+            .required("data")
+            .property("next_cursor", Option::<i64>::schema())
+            .required("next_cursor")
+            .property("total", u64::schema())
+            .required("total")
+            .build();
+        self.query.extend(list_query_params());
+        self.response = Some(("application/json", page.into()));
+        self
+    }
+
+    /// Responds `201 Created` with the ID of the created entity.
+    pub fn created_id(self) -> Self {
+        self.json(i64::schema()).status(StatusCode::CREATED)
+    }
+
+    /// Responds the number of rows the update or the deletion affected.
+    pub fn affected_rows(self) -> Self {
+        self.json(u64::schema())
+    }
+
+    /// Responds a `ResponseDataJson` of a message, e.g. `Logged out successfully`.
+    pub fn message(self) -> Self {
+        self.json(String::schema())
+    }
+
+    /// Responds plain text.
+    pub fn text(mut self) -> Self {
+        self.response = Some(("text/plain", String::schema()));
+        self
+    }
+
+    fn json(mut self, data: RefOr<Schema>) -> Self {
+        let response = ObjectBuilder::new().property("data", data).required("data").build();
+        self.response = Some(("application/json", response.into()));
+        self
+    }
+
+    /// Registers `T` and the schemas it references, returning a reference to it.
+    fn component<T: ToSchema>(&mut self) -> RefOr<Schema> {
+        self.schemas.push((T::name().to_string(), T::schema()));
+        T::schemas(&mut self.schemas);
+        Ref::from_schema_name(T::name()).into()
+    }
+}
+
+/// The parameters of `ListQuery`, the filters aside.
+fn list_query_params() -> Vec<Parameter> {
+    let param = |name: &str, schema: ObjectBuilder, description: &str| {
+        ParameterBuilder::new()
+            .name(name)
+            .parameter_in(ParameterIn::Query)
+            .required(Required::False)
+            .schema(Some(schema))
+            .description(Some(description))
+            .build()
+    };
+
+    vec![
+        param(
+            "limit",
+            ObjectBuilder::new()
+                .schema_type(Type::Integer)
+                .minimum(Some(1))
+                .maximum(Some(MAX_PAGE_LIMIT)),
+            "Size of the page"
+        ),
+        param(
+            "cursor",
+            ObjectBuilder::new().schema_type(Type::Integer),
+            "The `next_cursor` of the previous page"
+        ),
+        param("sort", ObjectBuilder::new().schema_type(Type::String), "Column to sort on"),
+        param(
+            "order",
+            ObjectBuilder::new().schema_type(Type::String).enum_values(Some(["asc", "desc"])),
+            "Order of the sort"
+        )
+    ]
+}
+
+/// Builds the OpenAPI document of `routes`, the ones of `create_router` served under `/api`.
+pub fn openapi(routes: Vec<RouteDoc>) -> OpenApi {
+    let mut paths = Paths::new();
+    let mut components = ComponentsBuilder::new()
+        .schema_from::<ErrorResponse>()
+        .schemas_from_iter({
+            let mut schemas = Vec::new();
+            ErrorResponse::schemas(&mut schemas);
+            schemas
+        })
+        .security_scheme(
+            BEARER_AUTH,
+            SecurityScheme::Http(
+                HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()
+            )
+        );
+
+    for route in routes {
+        let mut operation = OperationBuilder::new()
+            .summary(Some(route.summary))
+            .tags(route.tag.map(|tag| vec![tag]))
+            .parameters(Some(path_params(&route.path).into_iter().chain(route.query)));
+
+        if let Some((schema, required)) = route.body {
+            let required = if required { Required::True } else { Required::False };
+            operation = operation.request_body(
+                Some(
+                    RequestBodyBuilder::new()
+                        .content("application/json", schema_content(schema))
+                        .required(Some(required))
+                        .build()
+                )
+            );
+        }
+
+        let mut response = ResponseBuilder::new().description(
+            route.status.canonical_reason().unwrap_or_default()
+        );
+        if let Some((content_type, schema)) = route.response {
+            response = response.content(content_type, schema_content(schema));
+        }
+        operation = operation.response(route.status.as_str(), response).response(
+            "default",
+            ResponseBuilder::new()
+                .description("An error, see `code`")
+                .content(
+                    "application/json",
+                    schema_content(Ref::from_schema_name(ErrorResponse::name()).into())
+                )
+        );
+
+        // an empty list overrides the security requirement of the document
+        if route.public || is_public_route(&route.method, &route.path) {
+            operation = operation.securities(Some(Vec::new()));
+        }
+
+        paths.add_path_operation(
+            openapi_path(&route.path),
+            vec![http_method(&route.method)],
+            operation
+        );
+        components = components.schemas_from_iter(route.schemas);
+    }
+
+    OpenApiBuilder::new()
+        .info(
+            InfoBuilder::new()
+                .title("Workbase Scheduler API")
+                .version(env!("CARGO_PKG_VERSION"))
+                .description(
+                    Some(
+                        "Routes take and return JSON. Successful responses wrap their content in \
+                        `data`, errors are an `ErrorResponse`."
+                    )
+                )
+        )
+        .servers(Some([Server::new("/api")]))
+        .paths(paths)
+        .components(Some(components.build()))
+        .security(Some([SecurityRequirement::new(BEARER_AUTH, Vec::<String>::new())]))
+        .build()
+}
+
+fn schema_content(schema: RefOr<Schema>) -> Content {
+    ContentBuilder::new().schema(Some(schema)).build()
+}
+
+/// Turns the path parameters of axum, `/users/:id`, into the ones of OpenAPI, `/users/{id}`.
+pub fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The parameters of `path`, all of them are Snowflake IDs.
+fn path_params(path: &str) -> Vec<Parameter> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix(':'))
+        .map(|name| {
+            ParameterBuilder::new()
+                .name(name)
+                .parameter_in(ParameterIn::Path)
+                .required(Required::True)
+                .schema(Some(i64::schema()))
+                .build()
+        })
+        .collect()
+}
+
+fn http_method(method: &Method) -> HttpMethod {
+    match *method {
+        Method::POST => HttpMethod::Post,
+        Method::PUT => HttpMethod::Put,
+        Method::PATCH => HttpMethod::Patch,
+        Method::DELETE => HttpMethod::Delete,
+        Method::GET => HttpMethod::Get,
+        _ => panic!("No route is served with {}", method),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use axum::{
+        body::Body,
+        extract::{ MatchedPath, Request },
+        http::header,
+        middleware::{ self, Next },
+        response::Response,
+        Router,
+    };
+    use serde_json::{ json, Value };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        router::{
+            router::{ create_docs_router, create_router, route_docs },
+            tests::{ initialize_memory_app_state, create_test_user },
+        },
+    };
+
+    const MATCHED_PATH_HEADER: &str = "x-matched-path";
+
+    /// Sends back the template of the route that handled the request.
+    async fn echo_matched_path(req: Request, next: Next) -> Response {
+        let matched_path = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string());
+        let mut response = next.run(req).await;
+        if let Some(path) = matched_path {
+            response.headers_mut().insert(MATCHED_PATH_HEADER, path.parse().unwrap());
+        }
+        response
+    }
+
+    /// The path templates registered in `router`.
+    ///
+    /// axum doesn't list them, they are read from the `Debug` output of the router which prints
+    /// each of them as `RouteId(3): "/users/:id"`.
+    fn route_templates(router: &Router) -> BTreeSet<String> {
+        format!("{:?}", router)
+            .split("RouteId(")
+            .skip(1)
+            .filter_map(|part| {
+                let rest = part.split_once(')')?.1.strip_prefix(": \"")?;
+                rest.split('"').next()
+            })
+            .filter(|path| !path.contains("__private__axum"))
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(reference)) => refs.push(reference),
+                        _ => collect_refs(value, refs),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| collect_refs(value, refs)),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openapi_matches_routes() {
+        let state = initialize_memory_app_state();
+        // the token has to belong to an account, `require_auth` rejects it before routing otherwise
+        let (_, authorization) = create_test_user(&state, "docs@example.com").unwrap();
+        let router = create_router(state).await;
+        let templates = route_templates(&router);
+        assert!(templates.contains("/users/:id"), "{:?}", templates);
+
+        // a method is routed when the route answers something else than 405 for it
+        let app = router.layer(middleware::from_fn(echo_matched_path));
+        let mut routes = BTreeSet::new();
+        for template in &templates {
+            let uri = template
+                .split('/')
+                .map(|segment| if segment.starts_with(':') { "1" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .method(method.clone())
+                            .uri(&uri)
+                            .header(header::AUTHORIZATION, &authorization)
+                            .body(Body::empty())
+                            .unwrap()
+                    ).await
+                    .unwrap();
+                let matched_path = response.headers().get(MATCHED_PATH_HEADER);
+                if
+                    response.status() != StatusCode::METHOD_NOT_ALLOWED &&
+                    matched_path.is_some_and(|path| path == template.as_str())
+                {
+                    routes.insert(format!("{} {}", method, template));
+                }
+            }
+        }
+
+        let docs = route_docs();
+        let documented = docs
+            .iter()
+            .map(|doc| format!("{} {}", doc.method, doc.path))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(documented.len(), docs.len(), "a route is documented twice");
+        let undocumented = routes.difference(&documented).collect::<Vec<_>>();
+        let unrouted = documented.difference(&routes).collect::<Vec<_>>();
+        assert!(
+            undocumented.is_empty() && unrouted.is_empty(),
+            "routes without docs: {:?}, docs without routes: {:?}",
+            undocumented,
+            unrouted
+        );
+
+        let document = serde_json::to_value(openapi(docs)).unwrap();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        let mut refs = Vec::new();
+        collect_refs(&document, &mut refs);
+        for reference in refs {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas.contains_key(name), "{} is not defined", reference);
+        }
+
+        let get_shift = &document["paths"]["/shifts/{id}"]["get"];
+        assert_eq!(get_shift["parameters"][0]["name"], "id");
+        let response = &get_shift["responses"]["200"]["content"]["application/json"]["schema"];
+        assert_eq!(response["properties"]["data"], json!({ "$ref": "#/components/schemas/Shift" }));
+        // the password hashes are never returned, so they aren't documented either
+        let get_user = &document["paths"]["/users/{id}"]["get"];
+        let response = &get_user["responses"]["200"]["content"]["application/json"]["schema"];
+        assert_eq!(
+            response["properties"]["data"],
+            json!({ "$ref": "#/components/schemas/PartialUser" })
+        );
+        assert!(
+            schemas.values().all(|schema| schema["properties"].get("encrypted_password").is_none())
+        );
+        // the public routes don't ask for a token
+        assert_eq!(document["paths"]["/auth/login"]["post"]["security"], json!([]));
+        assert!(document["paths"]["/auth/me"]["get"].get("security").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_docs_routes() {
+        let app = create_docs_router().await;
+        let get = |uri: &str| {
+            Request::builder().method(Method::GET).uri(uri).body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(get("/api/openapi.json")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let document: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(document["servers"][0]["url"], "/api");
+        assert!(document["paths"]["/users"]["post"].is_object());
+
+        let response = app.oneshot(get("/api/docs/")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap();
+        assert!(content_type.starts_with("text/html"));
+    }
+}
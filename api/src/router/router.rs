@@ -1,16 +1,106 @@
 use axum::{ middleware, routing::get, Router, Extension };
+use utoipa_swagger_ui::SwaggerUi;
 
-use crate::{ app::AppState, prototypes::uniqueid_routers::UniqueIdRouter };
+use crate::{
+    app::AppState,
+    metrics::get_metrics,
+    prototypes::{ uniqueid_routers::UniqueIdRouter, composite_id_routers::CompositeIdRouter },
+};
 
-use super::{ middlewares::log_route::log_route, routes::user::UserRouter };
+use super::{
+    openapi::{ openapi, RouteDoc },
+    middlewares::{
+        log_route::log_route,
+        require_auth::require_auth,
+        request_id::request_id,
+        reject_when_draining::reject_when_draining,
+    },
+    routes::{
+        user::UserRouter,
+        health::HealthRouter,
+        auth::AuthRouter,
+        organization::OrgRouter,
+        org_job::OrgJobRouter,
+        org_member::OrgMemberRouter,
+        schedule::{
+            work_schedule::WorkScheduleRouter,
+            shift::ShiftRouter,
+            shift_trade::ShiftTradeRouter,
+            shift_cover::ShiftCoverRouter,
+            shift_pickup::ShiftPickupRouter,
+            open_shift::OpenShiftRouter,
+            timeoff_request::TimeOffRouter,
+            availability::AvailabilityRouter,
+        },
+    },
+};
+
+/// Every route of the server: the API under `/api`, and the metrics, documentation and health
+/// probes at the root.
+///
+/// All of them run in the request span of `log_route` and are counted in `metrics()`.
+pub async fn create_app(app_state: AppState) -> Router {
+    Router::new()
+        .nest("/api", create_router(app_state.clone()).await)
+        .route("/metrics", get(get_metrics))
+        .merge(create_docs_router().await)
+        .merge(create_health_router(app_state).await)
+        .layer(middleware::from_fn(log_route))
+}
 
 pub async fn create_router(app_state: AppState) -> Router {
     Router::new()
         .merge(<UserRouter as UniqueIdRouter>::router().await)
+        .merge(AuthRouter::router().await)
+        .merge(<OrgRouter as UniqueIdRouter>::router().await)
+        .merge(OrgJobRouter::router().await)
+        .merge(<OrgMemberRouter as CompositeIdRouter>::router().await)
+        .merge(<WorkScheduleRouter as UniqueIdRouter>::router().await)
+        .merge(<ShiftRouter as UniqueIdRouter>::router().await)
+        .merge(<ShiftTradeRouter as UniqueIdRouter>::router().await)
+        .merge(<ShiftCoverRouter as UniqueIdRouter>::router().await)
+        .merge(<OpenShiftRouter as UniqueIdRouter>::router().await)
+        .merge(<ShiftPickupRouter as UniqueIdRouter>::router().await)
+        .merge(<TimeOffRouter as UniqueIdRouter>::router().await)
+        .merge(<AvailabilityRouter as UniqueIdRouter>::router().await)
+        .layer(middleware::from_fn(require_auth))
+        .layer(middleware::from_fn(reject_when_draining))
+        .layer(middleware::from_fn(request_id))
         .layer(Extension(app_state.clone()))
-        .layer(middleware::from_fn(log_route))
         .route(
             "/hello",
             get(|| async { "Hello, World!" })
         )
 }
+
+/// Describes the routes of `create_router`, in the same order, for the OpenAPI document.
+pub fn route_docs() -> Vec<RouteDoc> {
+    [
+        RouteDoc::tagged("Users", <UserRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Auth", AuthRouter::route_docs()),
+        RouteDoc::tagged("Organizations", <OrgRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Jobs", OrgJobRouter::route_docs()),
+        RouteDoc::tagged("Members", <OrgMemberRouter as CompositeIdRouter>::route_docs()),
+        RouteDoc::tagged("Schedules", <WorkScheduleRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Shifts", <ShiftRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Shift trades", <ShiftTradeRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Shift covers", <ShiftCoverRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Open shifts", <OpenShiftRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Shift pickups", <ShiftPickupRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Time off", <TimeOffRouter as UniqueIdRouter>::route_docs()),
+        RouteDoc::tagged("Availability", <AvailabilityRouter as UniqueIdRouter>::route_docs()),
+        // served after the authentication layer
+        vec![RouteDoc::get("/hello", "Say hello").text().public()],
+    ].concat()
+}
+
+/// The OpenAPI document of the `/api` routes at `/api/openapi.json`, and a Swagger UI to browse
+/// it at `/api/docs`. Both are served without authentication.
+pub async fn create_docs_router() -> Router {
+    SwaggerUi::new("/api/docs").url("/api/openapi.json", openapi(route_docs())).into()
+}
+
+/// The health probes, served at the root rather than under `/api` and without authentication.
+pub async fn create_health_router(app_state: AppState) -> Router {
+    HealthRouter::router().await.layer(Extension(app_state))
+}
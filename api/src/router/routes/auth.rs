@@ -0,0 +1,283 @@
+use axum::{ Extension, Json, response::IntoResponse, Router, routing::{ get, post } };
+use hyper::StatusCode;
+
+use crate::{
+    queries::{ auth::AuthQueries, user::LoginForm },
+    app::{ ApiResponse, AppState },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        auth::{ AuthTokens, RequestRefreshToken },
+        user::PartialUser,
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+pub struct AuthRouter;
+
+impl AuthRouter {
+    pub async fn router() -> Router {
+        Router::new().nest(
+            "/auth",
+            Router::new()
+                .route("/login", post(Self::login))
+                .route("/refresh", post(Self::refresh))
+                .route("/logout", post(Self::logout))
+                .route("/me", get(Self::me))
+        )
+    }
+
+    pub fn route_docs() -> Vec<RouteDoc> {
+        RouteDoc::nest(
+            "/auth",
+            vec![
+                RouteDoc::post("/login", "Log in").body::<LoginForm>().data::<AuthTokens>(),
+                RouteDoc::post("/refresh", "Exchange a refresh token for new tokens")
+                    .body::<RequestRefreshToken>()
+                    .data::<AuthTokens>(),
+                RouteDoc::post("/logout", "Revoke a refresh token")
+                    .body::<RequestRefreshToken>()
+                    .message(),
+                RouteDoc::get("/me", "Get the caller").data::<PartialUser>()
+            ]
+        )
+    }
+
+    pub async fn login(
+        Extension(state): Extension<AppState>,
+        Json(form): Json<LoginForm>
+    ) -> ApiResponse {
+        // verifying the password hash is slow, it runs on the blocking pool with the queries
+        let snowflake_generator = state.snowflake_generator.clone();
+        let jwt_secret = state.config.auth.jwt_secret.clone();
+        match
+            state.db(move |conn| {
+                AuthQueries::login(conn, snowflake_generator, &jwt_secret, form)
+            }).await?
+        {
+            Some(tokens) => {
+                let json = Json(ResponseDataJson { data: tokens });
+                let mut response = json.into_response();
+                *response.status_mut() = StatusCode::OK;
+                Ok(response)
+            }
+            None => Err(AppError::unauthorized("Invalid email or password.")),
+        }
+    }
+
+    pub async fn refresh(
+        Extension(state): Extension<AppState>,
+        Json(form): Json<RequestRefreshToken>
+    ) -> ApiResponse {
+        let snowflake_generator = state.snowflake_generator.clone();
+        let jwt_secret = state.config.auth.jwt_secret.clone();
+        match
+            state.db(move |conn| {
+                AuthQueries::refresh(conn, snowflake_generator, &jwt_secret, &form.refresh_token)
+            }).await?
+        {
+            Some(tokens) => {
+                let json = Json(ResponseDataJson { data: tokens });
+                let mut response = json.into_response();
+                *response.status_mut() = StatusCode::OK;
+                Ok(response)
+            }
+            None => Err(AppError::unauthorized("Invalid or expired refresh token.")),
+        }
+    }
+
+    pub async fn logout(
+        Extension(state): Extension<AppState>,
+        Json(form): Json<RequestRefreshToken>
+    ) -> ApiResponse {
+        let jwt_secret = state.config.auth.jwt_secret.clone();
+        state.db(move |conn| AuthQueries::logout(conn, &jwt_secret, &form.refresh_token)).await?;
+
+        let json = Json(ResponseDataJson { data: "Logged out successfully" });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn me(AuthUser(user): AuthUser) -> ApiResponse {
+        let json = Json(ResponseDataJson { data: PartialUser::from(user) });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use axum::{ body::Body, http::{ Request, StatusCode }, middleware, Extension, Router };
+    use chrono::NaiveDate;
+    use mysql::params;
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        prototypes::uniqueid_routers::UniqueIdRouter,
+        router::{
+            tests::initialize_test_app_state,
+            utils::extract_response_body,
+            middlewares::require_auth::require_auth,
+            routes::user::UserRouter,
+        },
+        models::{ user::RequestCreateUser, auth::AuthTokens, result::Result },
+        storage::Queryable,
+    };
+
+    // Initialize test environment with the authentication middleware in place
+    async fn initialize_test_router(state: AppState) -> Router {
+        Router::new()
+            .merge(AuthRouter::router().await)
+            .merge(UserRouter::router().await)
+            .layer(middleware::from_fn(require_auth))
+            .layer(Extension(state))
+    }
+
+    #[tokio::test]
+    async fn test_auth_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = initialize_test_router(state.clone()).await;
+
+        // Registration is reachable without a token
+        let create_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("Content-Type", "application/json")
+                    .body(
+                        Body::from(
+                            json!(RequestCreateUser {
+                                email: "auth@example.com".to_string(),
+                                password: "password123".to_string(),
+                                first_name: "Auth".to_string(),
+                                last_name: "User".to_string(),
+                                date_of_birth: NaiveDate::from_str("1990-01-01").unwrap(),
+                                phone_number: None,
+                            }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let user_id: i64 = extract_response_body(create_response).await.expect("error extracting body");
+
+        // Everything else requires a token
+        let unauthorized_response = router
+            .clone()
+            .oneshot(Request::builder().uri("/users").body(Body::empty()).unwrap()).await
+            .unwrap();
+        assert_eq!(unauthorized_response.status(), StatusCode::UNAUTHORIZED);
+
+        let login_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/auth/login")
+                    .header("Content-Type", "application/json")
+                    .body(
+                        Body::from(
+                            json!(LoginForm {
+                                email: "auth@example.com".to_string(),
+                                password: "password123".to_string(),
+                            }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(login_response.status(), StatusCode::OK);
+        let tokens: AuthTokens = extract_response_body(login_response).await.expect(
+            "error extracting tokens"
+        );
+        assert_eq!(tokens.user_id, user_id);
+
+        let me_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/auth/me")
+                    .header("Authorization", format!("Bearer {}", tokens.access_token))
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(me_response.status(), StatusCode::OK);
+        let me: PartialUser = extract_response_body(me_response).await.expect(
+            "error extracting user"
+        );
+        assert_eq!(me.id, user_id);
+
+        let refresh_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/auth/refresh")
+                    .header("Content-Type", "application/json")
+                    .body(
+                        Body::from(
+                            json!(RequestRefreshToken {
+                                refresh_token: tokens.refresh_token.clone(),
+                            }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(refresh_response.status(), StatusCode::OK);
+
+        // the refresh token was rotated, so it can't be used again
+        let reused_refresh_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/auth/refresh")
+                    .header("Content-Type", "application/json")
+                    .body(
+                        Body::from(
+                            json!(RequestRefreshToken {
+                                refresh_token: tokens.refresh_token,
+                            }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(reused_refresh_response.status(), StatusCode::UNAUTHORIZED);
+
+        // deactivated accounts are rejected on every route, not only the ones reading the caller
+        let users_request = || {
+            Request::builder()
+                .uri("/users")
+                .header("Authorization", format!("Bearer {}", tokens.access_token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(router.clone().oneshot(users_request()).await.unwrap().status(), StatusCode::OK);
+        let mut conn = state.storage.get_conn()?;
+        conn.exec_drop(
+            "UPDATE users SET is_active = FALSE WHERE id = :id;",
+            params! { "id" => user_id }
+        )?;
+        let deactivated_response = router.clone().oneshot(users_request()).await.unwrap();
+        assert_eq!(deactivated_response.status(), StatusCode::UNAUTHORIZED);
+
+        // failing to read the account is not the fault of the caller
+        conn.query_drop("ALTER TABLE users DROP COLUMN is_active;")?;
+        let failed_response = router.clone().oneshot(users_request()).await.unwrap();
+        assert_eq!(failed_response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+use std::time::{ Duration, Instant };
+
+use axum::{ Extension, Json, Router, routing::get };
+use chrono::Utc;
+use hyper::StatusCode;
+
+use crate::{
+    app::AppState,
+    migrations::{ applied_migrations, migrations, pending_migrations, Migration },
+    models::{
+        health::{
+            DatabaseHealth,
+            HealthStatus,
+            ResponseLiveness,
+            ResponseReadiness,
+            SchemaHealth,
+            SnowflakeHealth,
+        },
+        result::Result,
+    },
+    storage::{ Connection, Queryable, Storage },
+    utilities::blocking::with_conn,
+};
+
+/// How long the readiness check waits for the database before reporting it down.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Probes for the orchestrator, served outside of `/api` and without authentication.
+pub struct HealthRouter;
+
+impl HealthRouter {
+    pub async fn router() -> Router {
+        Router::new().nest(
+            "/health",
+            Router::new().route("/live", get(Self::live)).route("/ready", get(Self::ready))
+        )
+    }
+
+    /// The process is up and serving requests.
+    pub async fn live() -> Json<ResponseLiveness> {
+        Json(ResponseLiveness { status: HealthStatus::UP })
+    }
+
+    /// Whether the instance can handle requests: it isn't shutting down, the database answers,
+    /// its schema is the one the binary expects and the Snowflake generator's clock is sane.
+    ///
+    /// Responds `503 Service Unavailable` with the same breakdown when any check fails.
+    pub async fn ready(
+        Extension(state): Extension<AppState>
+    ) -> (StatusCode, Json<ResponseReadiness>) {
+        let (database, schema) = Self::check_storage(&state.storage).await;
+        let snowflake = Self::check_snowflake(&state);
+
+        let draining = state.shutdown.is_draining();
+        let ready =
+            !draining &&
+            [database.status, schema.status, snowflake.status]
+                .iter()
+                .all(|status| *status == HealthStatus::UP);
+        let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+        (
+            status,
+            Json(ResponseReadiness {
+                status: HealthStatus::from_ok(ready),
+                draining,
+                database,
+                schema,
+                snowflake,
+            }),
+        )
+    }
+
+    async fn check_storage(storage: &Storage) -> (DatabaseHealth, SchemaHealth) {
+        let expected_version = migrations()
+            .last()
+            .map(|migration| migration.version)
+            .unwrap_or(0);
+
+        let backend = storage.backend();
+        let check = with_conn(storage, move |conn| {
+            Self::check_database(conn, backend, expected_version)
+        });
+        let error = match tokio::time::timeout(DATABASE_TIMEOUT, check).await {
+            Ok(Ok(health)) => {
+                return health;
+            }
+            // the error may name the host or the user, it is only logged
+            Ok(Err(error)) => {
+                tracing::error!(backend, "database health check failed: {}", error);
+                "The database check failed".to_string()
+            }
+            Err(_) => format!("No answer within {} seconds", DATABASE_TIMEOUT.as_secs()),
+        };
+
+        (
+            DatabaseHealth {
+                status: HealthStatus::DOWN,
+                backend: backend.to_string(),
+                latency_ms: None,
+                error: Some(error),
+            },
+            SchemaHealth {
+                status: HealthStatus::DOWN,
+                expected_version,
+                applied_version: None,
+                pending: Vec::new(),
+                error: Some("The database is unavailable".to_string()),
+            },
+        )
+    }
+
+    /// Pings the database, then compares its migrations with the ones of the binary.
+    fn check_database(
+        conn: &mut Connection,
+        backend: &str,
+        expected_version: u32
+    ) -> Result<(DatabaseHealth, SchemaHealth)> {
+        let start = Instant::now();
+        conn.query_drop("SELECT 1;")?;
+        let database = DatabaseHealth {
+            status: HealthStatus::UP,
+            backend: backend.to_string(),
+            latency_ms: Some(start.elapsed().as_secs_f64() * 1000.0),
+            error: None,
+        };
+
+        let applied_version = applied_migrations(conn)?
+            .last()
+            .map(|migration| migration.version);
+        let schema = match pending_migrations(conn) {
+            Ok(pending) =>
+                SchemaHealth {
+                    status: HealthStatus::from_ok(pending.is_empty()),
+                    expected_version,
+                    applied_version,
+                    pending: pending.iter().map(Migration::label).collect(),
+                    error: None,
+                },
+            // unknown or edited migrations
+            Err(error) => {
+                tracing::error!("schema health check failed: {}", error);
+                SchemaHealth {
+                    status: HealthStatus::DOWN,
+                    expected_version,
+                    applied_version,
+                    pending: Vec::new(),
+                    error: Some(
+                        "The applied migrations don't match the ones of the binary".to_string()
+                    ),
+                }
+            }
+        };
+
+        Ok((database, schema))
+    }
+
+    fn check_snowflake(state: &AppState) -> SnowflakeHealth {
+        let last_timestamp = state.snowflake_generator.last_timestamp();
+        let clock_behind_ms = last_timestamp - Utc::now().timestamp_millis();
+
+        SnowflakeHealth {
+            status: HealthStatus::from_ok(clock_behind_ms <= 0),
+            machine_id: state.snowflake_generator.machine_id(),
+            last_timestamp,
+            clock_behind_ms: (clock_behind_ms > 0).then_some(clock_behind_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{ body::Body, http::{ Method, Request } };
+    use mysql::{ OptsBuilder, Pool, PoolConstraints, PoolOpts };
+    use serde::de::DeserializeOwned;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{ router::tests::initialize_memory_app_state, shutdown::Shutdown };
+
+    async fn get<T: DeserializeOwned>(state: &AppState, uri: &str) -> (StatusCode, T) {
+        let app = HealthRouter::router().await.layer(Extension(state.clone()));
+        let response = app
+            .oneshot(Request::builder().method(Method::GET).uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_health_routes() {
+        let state = initialize_memory_app_state();
+        state.snowflake_generator.generate_id();
+
+        let (status, live) = get::<ResponseLiveness>(&state, "/health/live").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(live.status, HealthStatus::UP);
+
+        let (status, ready) = get::<ResponseReadiness>(&state, "/health/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ready.status, HealthStatus::UP);
+        assert_eq!(ready.database.backend, "memory");
+        assert_eq!(ready.schema.applied_version, Some(ready.schema.expected_version));
+        assert_eq!(ready.snowflake.machine_id, 1);
+        assert!(ready.snowflake.last_timestamp > 0);
+
+        // a draining instance is taken out of rotation
+        state.shutdown.trigger();
+        let (status, ready) = get::<ResponseReadiness>(&state, "/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(ready.draining);
+        assert_eq!(ready.database.status, HealthStatus::UP);
+
+        // nothing listens on port 1, the pool keeps no idle connection to fail at creation
+        let opts = OptsBuilder::new()
+            .ip_or_hostname(Some("127.0.0.1"))
+            .tcp_port(1)
+            .pool_opts(PoolOpts::default().with_constraints(PoolConstraints::new(0, 1).unwrap()));
+        let state = AppState {
+            storage: Storage::MySql(Pool::new(opts).unwrap()),
+            snowflake_generator: state.snowflake_generator.clone(),
+            config: Arc::clone(&state.config),
+            shutdown: Shutdown::default(),
+        };
+
+        let (status, ready) = get::<ResponseReadiness>(&state, "/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(ready.status, HealthStatus::DOWN);
+        assert_eq!(ready.database.status, HealthStatus::DOWN);
+        // the error of the driver, naming the server, stays in the logs
+        assert_eq!(ready.database.error.as_deref(), Some("The database check failed"));
+        assert_eq!(ready.schema.status, HealthStatus::DOWN);
+        assert_eq!(ready.snowflake.status, HealthStatus::UP);
+    }
+}
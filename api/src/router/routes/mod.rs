@@ -1 +1,7 @@
 pub mod user;
+pub mod auth;
+pub mod organization;
+pub mod org_job;
+pub mod org_member;
+pub mod schedule;
+pub mod health;
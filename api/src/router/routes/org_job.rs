@@ -0,0 +1,174 @@
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post, patch, delete },
+};
+use hyper::StatusCode;
+
+use crate::{
+    storage::{ Connection, Repository },
+    queries::{ org_job::OrgJobQueries, org_member::OrgMemberQueries },
+    prototypes::{ basic_queries::BasicQueries, validate::Validate },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        org_job::{ OrgJob, RequestCreateOrgJob, RequestUpdateOrgJob },
+        org_member::OrgPermission,
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+/// Jobs of an organization, nested under `/organizations/:id/jobs`.
+pub struct OrgJobRouter;
+
+impl OrgJobRouter {
+    pub async fn router() -> Router {
+        Router::new().nest(
+            "/organizations/:id/jobs",
+            Router::new()
+                .route("/", get(Self::get_org_jobs))
+                .route("/", post(Self::create_org_job))
+                .route("/:job_id", get(Self::get_org_job))
+                .route("/:job_id", patch(Self::update_org_job))
+                .route("/:job_id", delete(Self::delete_org_job))
+        )
+    }
+
+    pub fn route_docs() -> Vec<RouteDoc> {
+        RouteDoc::nest(
+            "/organizations/:id/jobs",
+            vec![
+                RouteDoc::get("/", "List the jobs of an organization").page::<OrgJob>(),
+                RouteDoc::post("/", "Create a job")
+                    .body::<RequestCreateOrgJob>()
+                    .created_id(),
+                RouteDoc::get("/:job_id", "Get a job").data::<OrgJob>(),
+                RouteDoc::patch("/:job_id", "Update a job")
+                    .body::<RequestUpdateOrgJob>()
+                    .affected_rows(),
+                RouteDoc::delete("/:job_id", "Delete a job").affected_rows()
+            ]
+        )
+    }
+
+    /// Loads a job, making sure it belongs to the organization in the path.
+    fn find_org_job(conn: &mut Connection, org_id: i64, job_id: i64) -> AppResult<OrgJob> {
+        let job = OrgJobQueries::find_by_id(conn, job_id)?;
+
+        if job.org_id == org_id {
+            Ok(job)
+        } else {
+            Err(AppError::not_found(format!("{} not found", OrgJobQueries::table_name())))
+        }
+    }
+
+    pub async fn get_org_jobs(
+        Extension(state): Extension<AppState>,
+        Path(org_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<OrgJob>>> {
+        let page = state.storage.find_page::<OrgJobQueries>(
+            OrgJobQueries::org_scope(org_id),
+            list_query
+        ).await?;
+
+        Ok(Json(page))
+    }
+
+    pub async fn get_org_job(
+        Extension(state): Extension<AppState>,
+        Path((org_id, job_id)): Path<(i64, i64)>
+    ) -> ApiResponse {
+        let job = state.db(move |conn| Self::find_org_job(conn, org_id, job_id)).await?;
+
+        let json = Json(ResponseDataJson { data: job });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn create_org_job(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        Json(create_dto): Json<RequestCreateOrgJob>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.org_id != org_id {
+            return Err(AppError::bad_request("Body does not match the path"));
+        }
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::MANAGE_MEMBERS
+            )?;
+
+            OrgJobQueries::create_entity(conn, snowflake_generator, create_dto)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    pub async fn update_org_job(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path((org_id, job_id)): Path<(i64, i64)>,
+        Json(update_dto): Json<RequestUpdateOrgJob>
+    ) -> ApiResponse {
+        update_dto.validate()?;
+
+        let affected_rows = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::MANAGE_MEMBERS
+            )?;
+            Self::find_org_job(conn, org_id, job_id)?;
+
+            Ok(OrgJobQueries::update_entity(conn, job_id, update_dto)?)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn delete_org_job(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path((org_id, job_id)): Path<(i64, i64)>
+    ) -> ApiResponse {
+        let affected_rows = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::MANAGE_MEMBERS
+            )?;
+            Self::find_org_job(conn, org_id, job_id)?;
+
+            Ok(OrgJobQueries::delete_entity(conn, job_id)?)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+}
@@ -0,0 +1,424 @@
+use async_trait::async_trait;
+
+use crate::{
+    queries::org_member::OrgMemberQueries,
+    prototypes::composite_id_routers::CompositeIdRouter,
+    app::{ AppState, AppResult },
+    storage::Connection,
+    models::{
+        user::User,
+        result::Result,
+        org_member::{ OrgPermission, OrgRole, RequestCreateOrgMember, RequestUpdateOrgMember },
+    },
+};
+
+/// Members of an organization, keyed by `(org_id, user_id)`.
+pub struct OrgMemberRouter;
+
+#[async_trait]
+impl CompositeIdRouter for OrgMemberRouter {
+    type Queries = OrgMemberQueries;
+
+    fn path() -> String {
+        "organizations/:id/members".to_string()
+    }
+
+    async fn authorize_create(
+        state: &AppState,
+        user: &User,
+        create_dto: &RequestCreateOrgMember
+    ) -> AppResult<()> {
+        let grants_owner = create_dto.role == Some(OrgRole::OWNER);
+        Self::authorize_member_change(state, user, create_dto.org_id, move |_| Ok(grants_owner)).await
+    }
+
+    async fn authorize_update(
+        state: &AppState,
+        user: &User,
+        (org_id, member_id): (i64, i64),
+        update_dto: &RequestUpdateOrgMember
+    ) -> AppResult<()> {
+        let role = update_dto.role.clone();
+        Self::authorize_member_change(state, user, org_id, move |conn| {
+            Ok(match role {
+                None => false,
+                Some(OrgRole::OWNER) => true,
+                Some(_) =>
+                    OrgMemberQueries::find_role(conn, org_id, member_id)? == Some(OrgRole::OWNER),
+            })
+        }).await
+    }
+
+    async fn authorize_delete(
+        state: &AppState,
+        user: &User,
+        (org_id, member_id): (i64, i64)
+    ) -> AppResult<()> {
+        Self::authorize_member_change(state, user, org_id, move |conn| {
+            Ok(OrgMemberQueries::find_role(conn, org_id, member_id)? == Some(OrgRole::OWNER))
+        }).await
+    }
+}
+
+impl OrgMemberRouter {
+    /// Checks that `user` may manage the members of the organization, and its owners as well when
+    /// `changes_owner` tells that the change grants or revokes the OWNER role.
+    async fn authorize_member_change<F>(
+        state: &AppState,
+        user: &User,
+        org_id: i64,
+        changes_owner: F
+    ) -> AppResult<()>
+        where F: FnOnce(&mut Connection) -> Result<bool> + Send + 'static
+    {
+        let user_id = user.id;
+        state.db(move |conn| {
+            let permission = if changes_owner(conn)? {
+                OrgPermission::MANAGE_OWNERS
+            } else {
+                OrgPermission::MANAGE_MEMBERS
+            };
+            OrgMemberQueries::ensure_permission(conn, org_id, user_id, permission)
+        }).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::Body, http::{ Request, StatusCode }, Extension, Router };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{
+            tests::{ initialize_test_app_state, create_test_user },
+            utils::extract_response_body,
+            routes::{ organization::OrgRouter, org_job::OrgJobRouter },
+        },
+        prototypes::{ uniqueid_routers::UniqueIdRouter, composite_key_queries::CompositeKeyQueries },
+        models::{ org_member::{ OrgMember, OrgRole }, org_job::OrgJob, result::Result },
+    };
+
+    #[tokio::test]
+    async fn test_org_member_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = Router::new()
+            .merge(OrgRouter::router().await)
+            .merge(OrgJobRouter::router().await)
+            .merge(OrgMemberRouter::router().await)
+            .layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee_id, employee_auth) = create_test_user(&state, "employee@example.com")?;
+
+        let create_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/organizations")
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", &owner_auth)
+                    .body(
+                        Body::from(
+                            json!({ "name": "Test Organization", "owner_id": owner_id }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let org_id: i64 = extract_response_body(create_response).await.expect(
+            "error extracting body"
+        );
+
+        // Create a job for the organization
+        let job_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/organizations/{}/jobs", org_id))
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", &owner_auth)
+                    .body(
+                        Body::from(
+                            json!({
+                                "org_id": org_id,
+                                "name": "Cashier",
+                                "base_pay_rate": 20.0,
+                            }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(job_response.status(), StatusCode::CREATED);
+        let job_id: i64 = extract_response_body(job_response).await.expect("error extracting body");
+
+        let jobs_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/jobs", org_id))
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(jobs_response.status(), StatusCode::OK);
+        let jobs: Vec<OrgJob> = extract_response_body(jobs_response).await.expect(
+            "error extracting jobs"
+        );
+        assert!(jobs.iter().any(|job| job.id == job_id));
+
+        // Add the employee to the organization
+        let member_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/organizations/{}/members", org_id))
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", &owner_auth)
+                    .body(
+                        Body::from(
+                            json!({
+                                "org_id": org_id,
+                                "user_id": employee_id,
+                                "job_id": job_id,
+                            }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(member_response.status(), StatusCode::CREATED);
+
+        let get_member_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/members/{}", org_id, employee_id))
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(get_member_response.status(), StatusCode::OK);
+        let member: OrgMember = extract_response_body(get_member_response).await.expect(
+            "error extracting member"
+        );
+        assert_eq!(member.job_id, job_id);
+        assert_eq!(member.role, OrgRole::EMPLOYEE);
+
+        // Employees can't change roles
+        let promote_request = |auth: &str| {
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/organizations/{}/members/{}", org_id, employee_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(json!({ "role": "MANAGER" }).to_string()))
+                .unwrap()
+        };
+        let forbidden_response = router
+            .clone()
+            .oneshot(promote_request(&employee_auth)).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let promote_response = router.clone().oneshot(promote_request(&owner_auth)).await.unwrap();
+        assert_eq!(promote_response.status(), StatusCode::OK);
+
+        let members_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/members", org_id))
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        let members: Vec<OrgMember> = extract_response_body(members_response).await.expect(
+            "error extracting members"
+        );
+        assert_eq!(members.len(), 2);
+        assert!(
+            members
+                .iter()
+                .any(|member| member.user_id == employee_id && member.role == OrgRole::MANAGER)
+        );
+
+        // Remove the employee
+        let delete_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("/organizations/{}/members/{}", org_id, employee_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(delete_response.status(), StatusCode::OK);
+        let deleted_rows: u64 = extract_response_body(delete_response).await.expect(
+            "error extracting body"
+        );
+        assert_eq!(deleted_rows, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_owner_changes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = Router::new()
+            .merge(OrgRouter::router().await)
+            .merge(OrgMemberRouter::router().await)
+            .layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (admin_id, admin_auth) = create_test_user(&state, "admin@example.com")?;
+        let (employee_id, _) = create_test_user(&state, "employee@example.com")?;
+
+        let create_org = |name: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/organizations")
+                .header("Content-Type", "application/json")
+                .header("Authorization", &owner_auth)
+                .body(Body::from(json!({ "name": name, "owner_id": owner_id }).to_string()))
+                .unwrap()
+        };
+        let org_id: i64 = extract_response_body(
+            router.clone().oneshot(create_org("Test Organization")).await.unwrap()
+        ).await.expect("error extracting body");
+        let other_org_id: i64 = extract_response_body(
+            router.clone().oneshot(create_org("Other Organization")).await.unwrap()
+        ).await.expect("error extracting body");
+
+        let (job_id, other_job_id) = state.db(move |conn| -> Result<_> {
+            Ok((
+                OrgMemberQueries::find_by_id(conn, org_id, owner_id)?.job_id,
+                OrgMemberQueries::find_by_id(conn, other_org_id, owner_id)?.job_id,
+            ))
+        }).await?;
+
+        let add_member = |auth: &str, user_id: i64, job_id: i64, role: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/organizations/{}/members", org_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(
+                    Body::from(
+                        json!({
+                            "org_id": org_id,
+                            "user_id": user_id,
+                            "job_id": job_id,
+                            "role": role,
+                        }).to_string()
+                    )
+                )
+                .unwrap()
+        };
+        let update_member = |auth: &str, user_id: i64, body: serde_json::Value| {
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/organizations/{}/members/{}", org_id, user_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let remove_member = |auth: &str, user_id: i64| {
+            Request::builder()
+                .method("DELETE")
+                .uri(format!("/organizations/{}/members/{}", org_id, user_id))
+                .header("Authorization", auth)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // A job of another organization is rejected
+        let response = router
+            .clone()
+            .oneshot(add_member(&owner_auth, admin_id, other_job_id, "ADMIN")).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response = router
+            .clone()
+            .oneshot(update_member(&owner_auth, owner_id, json!({ "job_id": other_job_id }))).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = router
+            .clone()
+            .oneshot(add_member(&owner_auth, admin_id, job_id, "ADMIN")).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // Admins manage members, but only owners grant or revoke the OWNER role
+        let response = router
+            .clone()
+            .oneshot(add_member(&admin_auth, employee_id, job_id, "OWNER")).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = router
+            .clone()
+            .oneshot(add_member(&admin_auth, employee_id, job_id, "EMPLOYEE")).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = router
+            .clone()
+            .oneshot(update_member(&admin_auth, admin_id, json!({ "role": "OWNER" }))).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = router
+            .clone()
+            .oneshot(update_member(&admin_auth, owner_id, json!({ "role": "EMPLOYEE" }))).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = router.clone().oneshot(remove_member(&admin_auth, owner_id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Admins can still change the job of an owner
+        let response = router
+            .clone()
+            .oneshot(update_member(&admin_auth, owner_id, json!({ "job_id": job_id }))).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The last owner can't leave
+        let response = router.clone().oneshot(remove_member(&owner_auth, owner_id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = router
+            .clone()
+            .oneshot(update_member(&owner_auth, owner_id, json!({ "role": "ADMIN" }))).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        // Once another member is an owner, the first one can step down
+        let response = router
+            .clone()
+            .oneshot(update_member(&owner_auth, admin_id, json!({ "role": "OWNER" }))).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = router
+            .clone()
+            .oneshot(update_member(&owner_auth, owner_id, json!({ "role": "ADMIN" }))).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let roles = state.db(move |conn| -> Result<_> {
+            Ok((
+                OrgMemberQueries::find_role(conn, org_id, owner_id)?,
+                OrgMemberQueries::find_role(conn, org_id, admin_id)?,
+            ))
+        }).await?;
+        assert_eq!(roles, (Some(OrgRole::ADMIN), Some(OrgRole::OWNER)));
+
+        Ok(())
+    }
+}
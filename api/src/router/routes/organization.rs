@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post, patch, delete },
+};
+use hyper::StatusCode;
+
+use crate::{
+    queries::{ organization::OrgQueries, org_member::OrgMemberQueries },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        organization::{ RequestCreateOrganization, RequestUpdateOrganization },
+        org_member::OrgPermission,
+    },
+    router::extractors::auth_user::AuthUser,
+};
+
+pub struct OrgRouter;
+
+#[async_trait]
+impl UniqueIdRouter for OrgRouter {
+    type Queries = OrgQueries;
+
+    fn path() -> String {
+        "organizations".to_string()
+    }
+
+    /// Returns the organization along with its owner.
+    async fn get_by_id(
+        Extension(state): Extension<AppState>,
+        _user: AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let model = state.db(move |conn| OrgQueries::find_by_id_with_owner(conn, id)).await?;
+
+        let json = Json(ResponseDataJson { data: model });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    async fn default_routes() -> Router {
+        Router::new()
+            .route("/", get(Self::get_all))
+            .route("/:id", get(Self::get_by_id))
+            .route("/", post(Self::create_organization))
+            .route("/:id", patch(Self::update_organization))
+            .route("/:id", delete(Self::delete_organization))
+    }
+}
+
+impl OrgRouter {
+    /// Creates an organization owned by the caller.
+    pub async fn create_organization(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Json(create_dto): Json<RequestCreateOrganization>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.owner_id != user.id {
+            return Err(AppError::forbidden("Cannot create an organization for another user."));
+        }
+
+        // the owner's membership is created with the organization, which needs MySQL
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| {
+            OrgQueries::create_entity(conn, snowflake_generator, create_dto)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    pub async fn update_organization(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        Json(update_dto): Json<RequestUpdateOrganization>
+    ) -> ApiResponse {
+        update_dto.validate()?;
+
+        let affected_rows = state.db(move |conn| {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                id,
+                user.id,
+                OrgPermission::MANAGE_ORGANIZATION
+            )?;
+
+            OrgQueries::update_entity(conn, id, update_dto)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn delete_organization(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let affected_rows = state.db(move |conn| {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                id,
+                user.id,
+                OrgPermission::MANAGE_ORGANIZATION
+            )?;
+
+            OrgQueries::delete_entity(conn, id)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::Body, http::{ Request, StatusCode }, Extension };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{ tests::{ initialize_test_app_state, create_test_user }, utils::extract_response_body },
+        models::{ organization::{ Organization, PickupMode }, result::Result },
+    };
+
+    #[tokio::test]
+    async fn test_organization_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = OrgRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (_, employee_auth) = create_test_user(&state, "employee@example.com")?;
+
+        // Can't create an organization on behalf of someone else
+        let create_dto =
+            json!({
+            "name": "Test Organization",
+            "description": "A test organization",
+            "owner_id": owner_id,
+        });
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/organizations")
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", &employee_auth)
+                    .body(Body::from(create_dto.to_string()))
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let create_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/organizations")
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", &owner_auth)
+                    .body(Body::from(create_dto.to_string()))
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let org_id: i64 = extract_response_body(create_response).await.expect(
+            "error extracting body"
+        );
+
+        // The detail view includes the owner
+        let get_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}", org_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(get_response.status(), StatusCode::OK);
+        let org: Organization = extract_response_body(get_response).await.expect(
+            "error extracting organization"
+        );
+        assert_eq!(org.owner.map(|owner| owner.id), Some(owner_id));
+
+        // Only members allowed to manage the organization can update it
+        let update_dto = RequestUpdateOrganization {
+            name: Some("Renamed Organization".to_string()),
+            ..Default::default()
+        };
+        let update_request = |auth: &str| {
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/organizations/{}", org_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(json!({ "name": update_dto.name }).to_string()))
+                .unwrap()
+        };
+
+        let forbidden_response = router.clone().oneshot(update_request(&employee_auth)).await.unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let update_response = router.clone().oneshot(update_request(&owner_auth)).await.unwrap();
+        assert_eq!(update_response.status(), StatusCode::OK);
+
+        let mut conn = state.storage.get_conn()?;
+        let org = OrgQueries::find_by_id(&mut conn, org_id)?;
+        assert_eq!(Some(org.name), update_dto.name);
+        assert_eq!(org.pickup_mode, PickupMode::MANAGER_CHOICE);
+
+        // Open shifts can be handed out on a first come, first served basis
+        let pickup_mode_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PATCH")
+                    .uri(format!("/organizations/{}", org_id))
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", &owner_auth)
+                    .body(Body::from(json!({ "pickup_mode": "FIRST_COME_FIRST_SERVED" }).to_string()))
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(pickup_mode_response.status(), StatusCode::OK);
+        let org = OrgQueries::find_by_id(&mut conn, org_id)?;
+        assert_eq!(org.pickup_mode, PickupMode::FIRST_COME_FIRST_SERVED);
+
+        Ok(())
+    }
+}
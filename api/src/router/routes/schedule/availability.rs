@@ -0,0 +1,605 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::{ Path, Query },
+    response::IntoResponse,
+    Router,
+    routing::{ get, post },
+};
+use chrono::{ Local, NaiveDate };
+use hyper::StatusCode;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    storage::Connection,
+    queries::{
+        schedule::{
+            availability::AvailabilityRequestQueries,
+            availability_detail::AvailabilityDetailQueries,
+        },
+        org_member::OrgMemberQueries,
+    },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        org_member::OrgPermission,
+        schedule::{
+            availability::{
+                AvailabilityRequest,
+                AvailabilityWeek,
+                AvailabilityWithDetails,
+                RequestCreateAvailability,
+            },
+            request_status::ScheduleRequestStatus,
+        },
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+/// Weekly availability submitted by members of an organization.
+///
+/// Once approved, an availability is in effect from its `start_date` until the member gets a
+/// later one approved.
+pub struct AvailabilityRouter;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AvailabilityWeekQuery {
+    /// Defaults to today
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EffectiveAvailabilityQuery {
+    pub user_id: i64,
+    /// Defaults to today
+    pub date: Option<NaiveDate>,
+}
+
+#[async_trait]
+impl UniqueIdRouter for AvailabilityRouter {
+    type Queries = AvailabilityRequestQueries;
+
+    fn path() -> String {
+        "availability".to_string()
+    }
+
+    /// Availability is submitted through its organization, see `router`, and only changes status
+    /// through its workflow endpoints, see `more_routes`.
+    async fn default_routes() -> Router {
+        Router::new().route("/:id", get(Self::get_availability))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new()
+            .route("/:id/approve", post(Self::approve))
+            .route("/:id/decline", post(Self::decline))
+            .route("/:id/cancel", post(Self::cancel))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route(
+                "/organizations/:id/availability",
+                get(Self::get_org_availability).post(Self::create_availability)
+            )
+            .route("/organizations/:id/availability/week", get(Self::get_org_week))
+            .route("/organizations/:id/availability/effective", get(Self::get_effective_availability))
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/:id", "Get an availability with its details").data::<
+                AvailabilityWithDetails
+            >()
+        ]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::post("/:id/approve", "Approve an availability").data::<AvailabilityRequest>(),
+            RouteDoc::post("/:id/decline", "Decline an availability").data::<AvailabilityRequest>(),
+            RouteDoc::post("/:id/cancel", "Cancel an availability").data::<AvailabilityRequest>()
+        ]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/organizations/:id/availability",
+                    "List the availability history of an organization"
+                ).page::<AvailabilityRequest>(),
+                RouteDoc::post("/organizations/:id/availability", "Submit an availability")
+                    .body::<RequestCreateAvailability>()
+                    .created_id(),
+                RouteDoc::get(
+                    "/organizations/:id/availability/week",
+                    "Get the availability of every member over a week"
+                )
+                    .query::<AvailabilityWeekQuery>()
+                    .data::<AvailabilityWeek>(),
+                RouteDoc::get(
+                    "/organizations/:id/availability/effective",
+                    "Get the availability of a member in effect on a date"
+                )
+                    .query::<EffectiveAvailabilityQuery>()
+                    .optional_data::<AvailabilityWithDetails>()
+            ],
+        ].concat()
+    }
+}
+
+impl AvailabilityRouter {
+    fn find_availability(conn: &mut Connection, id: i64) -> AppResult<AvailabilityRequest> {
+        Ok(AvailabilityRequestQueries::find_by_id(conn, id)?)
+    }
+
+    /// Moves an availability to `status` and responds with the updated availability.
+    fn transition(
+        conn: &mut Connection,
+        id: i64,
+        status: ScheduleRequestStatus,
+        admin_id: Option<i64>
+    ) -> ApiResponse {
+        AvailabilityRequestQueries::transition(conn, id, status, admin_id)?;
+        let availability = Self::find_availability(conn, id)?;
+
+        let json = Json(ResponseDataJson { data: availability });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Returns the availability along with its details.
+    pub async fn get_availability(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let (request, details) = state.db(move |conn| -> AppResult<_> {
+            let org_id = AvailabilityRequestQueries::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+            let request = Self::find_availability(conn, id)?;
+            let details = AvailabilityDetailQueries::get_request_details(conn, id)?;
+            Ok((request, details))
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: AvailabilityWithDetails { request, details } });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Lists the availability history of an organization, optionally for a single member with
+    /// `?user_id=`.
+    pub async fn get_org_availability(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<AvailabilityRequest>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = AvailabilityRequestQueries::org_scope(org_id);
+            Ok(AvailabilityRequestQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    /// Returns the availability of every member in effect over the week starting on `?date=`.
+    pub async fn get_org_week(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        Query(query): Query<AvailabilityWeekQuery>
+    ) -> ApiResponse {
+        let start_date = query.date.unwrap_or_else(|| Local::now().date_naive());
+        let week = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            Ok(AvailabilityRequestQueries::get_org_week(conn, org_id, start_date)?)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: week });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Returns the availability of the `?user_id=` member in effect on `?date=`, or `null` if there
+    /// is none.
+    pub async fn get_effective_availability(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        Query(query): Query<EffectiveAvailabilityQuery>
+    ) -> ApiResponse {
+        let date = query.date.unwrap_or_else(|| Local::now().date_naive());
+        let availability = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            Ok(
+                AvailabilityRequestQueries::get_availability_with_details_on(
+                    conn,
+                    query.user_id,
+                    org_id,
+                    date
+                )?
+            )
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: availability });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Submits the weekly availability of the caller.
+    pub async fn create_availability(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        Json(create_dto): Json<RequestCreateAvailability>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.org_id != org_id {
+            return Err(AppError::bad_request("Body does not match the path"));
+        }
+        if create_dto.user_id != user.id {
+            return Err(AppError::forbidden("Cannot submit availability for another member."));
+        }
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| {
+            if OrgMemberQueries::find_role(conn, org_id, user.id)?.is_none() {
+                return Err(AppError::forbidden("Only members can submit availability."));
+            }
+
+            Ok(AvailabilityRequestQueries::create_entity(conn, snowflake_generator, create_dto)?)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    pub async fn approve(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_availability(conn, id)?;
+            Self::transition(conn, id, ScheduleRequestStatus::APPROVED, Some(user.id))
+        }).await
+    }
+
+    pub async fn decline(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_availability(conn, id)?;
+            Self::transition(conn, id, ScheduleRequestStatus::DECLINED, Some(user.id))
+        }).await
+    }
+
+    pub async fn cancel(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            if Self::find_availability(conn, id)?.user_id != user.id {
+                return Err(AppError::forbidden("The availability is not yours to cancel"));
+            }
+            Self::transition(conn, id, ScheduleRequestStatus::CANCELLED, None)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::Body, http::{ Request, StatusCode }, Extension };
+    use chrono::{ Datelike, Duration };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{ tests::{ initialize_test_app_state, create_test_user }, utils::extract_response_body },
+        queries::{ organization::OrgQueries, org_job::OrgJobQueries },
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::RequestCreateOrganization,
+            org_job::RequestCreateOrgJob,
+            org_member::RequestCreateOrgMember,
+            schedule::{ availability::AvailabilityWeek, availability_detail::DayOfWeek },
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_availability_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = AvailabilityRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee_id, employee_auth) = create_test_user(&state, "employee@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let org_id = OrgQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: "Test Organization".to_string(),
+                description: None,
+                owner_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let job_id = OrgJobQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrgJob {
+                org_id,
+                name: "Cashier".to_string(),
+                description: None,
+                base_pay_rate: 20.0,
+                color: None,
+            }
+        )?;
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+            org_id,
+            user_id: employee_id,
+            job_id,
+            role: None,
+        })?;
+
+        let post_request = |uri: String, auth: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let get_request = |uri: String, auth: &str| {
+            Request::builder().uri(uri).header("Authorization", auth).body(Body::empty()).unwrap()
+        };
+        let availability_body = |start_date: NaiveDate, days: Vec<serde_json::Value>| {
+            json!({
+                "user_id": employee_id,
+                "org_id": org_id,
+                "start_date": start_date,
+                "details": days,
+            })
+        };
+        let day = |day_of_week: DayOfWeek, is_available: bool| {
+            json!({
+                "day_of_week": day_of_week,
+                "is_available": is_available,
+                "whole_day": true,
+            })
+        };
+
+        // Every day of the week is listed at most once
+        let today = Local::now().date_naive();
+        let invalid_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/organizations/{}/availability", org_id),
+                    &employee_auth,
+                    availability_body(
+                        today,
+                        vec![day(DayOfWeek::MONDAY, true), day(DayOfWeek::MONDAY, false)]
+                    )
+                )
+            ).await
+            .unwrap();
+        assert_eq!(invalid_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Submit the current availability, a change next week, and one that gets declined
+        let mut availability_ids = vec![];
+        for start_date in [today, today + Duration::days(7), today + Duration::days(14)] {
+            let is_available = start_date == today;
+            let create_response = router
+                .clone()
+                .oneshot(
+                    post_request(
+                        format!("/organizations/{}/availability", org_id),
+                        &employee_auth,
+                        availability_body(
+                            start_date,
+                            vec![
+                                day(DayOfWeek::from(today.weekday()), is_available),
+                                day(DayOfWeek::from(today.succ_opt().unwrap().weekday()), true)
+                            ]
+                        )
+                    )
+                ).await
+                .unwrap();
+            assert_eq!(create_response.status(), StatusCode::CREATED);
+            let availability_id: i64 = extract_response_body(create_response).await.expect(
+                "error extracting body"
+            );
+            availability_ids.push(availability_id);
+        }
+
+        // Employees can't approve their own availability
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/availability/{}/approve", availability_ids[0]),
+                    &employee_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        for availability_id in &availability_ids[..2] {
+            let approve_response = router
+                .clone()
+                .oneshot(
+                    post_request(
+                        format!("/availability/{}/approve", availability_id),
+                        &owner_auth,
+                        json!({})
+                    )
+                ).await
+                .unwrap();
+            assert_eq!(approve_response.status(), StatusCode::OK);
+        }
+        let decline_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/availability/{}/decline", availability_ids[2]),
+                    &owner_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        let availability: AvailabilityRequest = extract_response_body(decline_response).await.expect(
+            "error extracting availability"
+        );
+        assert_eq!(availability.status, ScheduleRequestStatus::DECLINED);
+
+        // A declined availability can't be cancelled anymore
+        let conflict_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/availability/{}/cancel", availability_ids[2]),
+                    &employee_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        assert_eq!(conflict_response.status(), StatusCode::CONFLICT);
+
+        // The availability in effect depends on the date
+        for (date, availability_id) in [
+            (today + Duration::days(6), availability_ids[0]),
+            (today + Duration::days(20), availability_ids[1]),
+        ] {
+            let effective_response = router
+                .clone()
+                .oneshot(
+                    get_request(
+                        format!(
+                            "/organizations/{}/availability/effective?user_id={}&date={}",
+                            org_id,
+                            employee_id,
+                            date
+                        ),
+                        &employee_auth
+                    )
+                ).await
+                .unwrap();
+            assert_eq!(effective_response.status(), StatusCode::OK);
+            let availability: AvailabilityWithDetails = extract_response_body(
+                effective_response
+            ).await.expect("error extracting availability");
+            assert_eq!(availability.request.id, availability_id);
+            assert_eq!(availability.details.len(), 2);
+        }
+
+        // The week starting tomorrow switches to next week's availability on its last day
+        let week_response = router
+            .clone()
+            .oneshot(
+                get_request(
+                    format!(
+                        "/organizations/{}/availability/week?date={}",
+                        org_id,
+                        today + Duration::days(1)
+                    ),
+                    &owner_auth
+                )
+            ).await
+            .unwrap();
+        let week: AvailabilityWeek = extract_response_body(week_response).await.expect(
+            "error extracting week"
+        );
+        let employee = week.members
+            .iter()
+            .find(|member| member.user_id == employee_id)
+            .expect("employee missing from the week");
+        assert_eq!(employee.days.len(), 7);
+        assert!(employee.days[..6].iter().all(|day| day.request_id == Some(availability_ids[0])));
+        assert_eq!(employee.days[6].request_id, Some(availability_ids[1]));
+        assert_eq!(employee.days[6].detail.as_ref().map(|detail| detail.is_available), Some(false));
+        assert!(employee.days[1].detail.is_none());
+
+        let history_response = router
+            .clone()
+            .oneshot(
+                get_request(
+                    format!("/organizations/{}/availability?user_id={}", org_id, employee_id),
+                    &employee_auth
+                )
+            ).await
+            .unwrap();
+        let history: Vec<AvailabilityRequest> = extract_response_body(history_response).await.expect(
+            "error extracting history"
+        );
+        assert_eq!(
+            history
+                .iter()
+                .map(|availability| availability.id)
+                .collect::<Vec<_>>(),
+            availability_ids.into_iter().rev().collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
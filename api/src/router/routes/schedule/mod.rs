@@ -0,0 +1,8 @@
+pub mod work_schedule;
+pub mod shift;
+pub mod shift_trade;
+pub mod shift_cover;
+pub mod shift_pickup;
+pub mod open_shift;
+pub mod timeoff_request;
+pub mod availability;
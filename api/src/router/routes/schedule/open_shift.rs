@@ -0,0 +1,296 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::get,
+};
+use hyper::StatusCode;
+
+use crate::{
+    storage::Connection,
+    queries::{
+        schedule::{ open_shift::OpenShiftQueries, work_schedule::WorkScheduleQueries },
+        org_member::OrgMemberQueries,
+    },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        result::Result,
+        schedule::open_shift::{ OpenShift, RequestCreateOpenShift },
+        org_member::OrgPermission,
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+use super::shift::ShiftRouter;
+
+/// Shifts of a schedule that no member works yet, handed out through shift pickups.
+pub struct OpenShiftRouter;
+
+#[async_trait]
+impl UniqueIdRouter for OpenShiftRouter {
+    type Queries = OpenShiftQueries;
+
+    fn path() -> String {
+        "open-shifts".to_string()
+    }
+
+    fn org_id_of(conn: &mut Connection, id: i64) -> Result<Option<i64>> {
+        Ok(Some(OpenShiftQueries::find_org_id(conn, id)?))
+    }
+
+    /// Open shifts are listed and created through their schedule, see `router`.
+    async fn default_routes() -> Router {
+        Router::new().route("/:id", get(Self::get_by_id))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route(
+                "/schedules/:id/open-shifts",
+                get(Self::get_schedule_open_shifts).post(Self::create_open_shift)
+            )
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![RouteDoc::get("/:id", "Get an open shift").data::<OpenShift>()]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/schedules/:id/open-shifts",
+                    "List the open shifts of a schedule"
+                ).page::<OpenShift>(),
+                RouteDoc::post("/schedules/:id/open-shifts", "Create an open shift")
+                    .body::<RequestCreateOpenShift>()
+                    .created_id()
+            ],
+        ].concat()
+    }
+}
+
+impl OpenShiftRouter {
+    pub async fn get_schedule_open_shifts(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(schedule_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<OpenShift>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, schedule_id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = OpenShiftQueries::schedule_scope(schedule_id);
+            Ok(OpenShiftQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    pub async fn create_open_shift(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(schedule_id): Path<i64>,
+        Json(create_dto): Json<RequestCreateOpenShift>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.schedule_id != schedule_id {
+            return Err(AppError::bad_request("Body does not match the path"));
+        }
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, schedule_id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+            ShiftRouter::check_job(conn, schedule.org_id, create_dto.job_id)?;
+
+            Ok(OpenShiftQueries::create_entity(conn, snowflake_generator, create_dto)?)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::{ Body, to_bytes }, http::{ Request, StatusCode }, Extension };
+    use chrono::{ Duration, Local };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{ tests::{ initialize_test_app_state, create_test_user }, utils::extract_response_body },
+        queries::{ organization::OrgQueries, org_job::OrgJobQueries },
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::RequestCreateOrganization,
+            org_member::RequestCreateOrgMember,
+            schedule::work_schedule::RequestCreateWorkSchedule,
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_open_shift_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = OpenShiftRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee_id, employee_auth) = create_test_user(&state, "employee@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let mut create_org = |name: &str| -> Result<(i64, i64)> {
+            let org_id = OrgQueries::create_entity(
+                &mut conn,
+                state.snowflake_generator.clone(),
+                RequestCreateOrganization {
+                    name: name.to_string(),
+                    description: None,
+                    owner_id,
+                    timezone: None,
+                    icon: None,
+                }
+            )?;
+            let job_id = OrgJobQueries::find_by_org_id(&mut conn, org_id)?[0].id;
+            Ok((org_id, job_id))
+        };
+        let (org_id, job_id) = create_org("Test Organization")?;
+        let (_, other_job_id) = create_org("Other Organization")?;
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+            org_id,
+            user_id: employee_id,
+            job_id,
+            role: None,
+        })?;
+
+        let start_date = Local::now().date_naive() + Duration::days(7);
+        let schedule_id = WorkScheduleQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date,
+                end_date: start_date + Duration::days(6),
+            }
+        )?;
+
+        let start_time = start_date.and_hms_opt(9, 0, 0).unwrap();
+        let create_request = |auth: &str, job_id: i64, day: i64| {
+            let start_time = start_time + Duration::days(day);
+            Request::builder()
+                .method("POST")
+                .uri(format!("/schedules/{}/open-shifts", schedule_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(
+                    Body::from(
+                        json!({
+                            "schedule_id": schedule_id,
+                            "job_id": job_id,
+                            "start_time": start_time,
+                            "end_time": start_time + Duration::hours(8),
+                            "headcount": 2,
+                        }).to_string()
+                    )
+                )
+                .unwrap()
+        };
+
+        // Only members allowed to publish schedules create open shifts
+        let response = router
+            .clone()
+            .oneshot(create_request(&employee_auth, job_id, 0)).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The job must belong to the organization and the shift to the schedule
+        let response = router
+            .clone()
+            .oneshot(create_request(&owner_auth, other_job_id, 0)).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response = router.clone().oneshot(create_request(&owner_auth, job_id, 7)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(response.into_body(), usize::MAX).await.unwrap()
+        )?;
+        assert_eq!(body["details"]["rule"], "OUTSIDE_SCHEDULE");
+
+        let response = router.clone().oneshot(create_request(&owner_auth, job_id, 0)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let open_shift_id: i64 = extract_response_body(response).await.expect(
+            "error extracting body"
+        );
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/schedules/{}/open-shifts", schedule_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let open_shifts: Vec<OpenShift> = extract_response_body(response).await.expect(
+            "error extracting open shifts"
+        );
+        assert_eq!(open_shifts.len(), 1);
+        assert_eq!(open_shifts[0].id, open_shift_id);
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/open-shifts/{}", open_shift_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let open_shift: OpenShift = extract_response_body(response).await.expect(
+            "error extracting open shift"
+        );
+        assert_eq!(open_shift.start_time, start_time);
+        assert_eq!((open_shift.headcount, open_shift.filled_count), (2, 0));
+
+        Ok(())
+    }
+}
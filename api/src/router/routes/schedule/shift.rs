@@ -0,0 +1,566 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post, patch, delete },
+};
+use hyper::StatusCode;
+
+use crate::{
+    storage::Connection,
+    queries::{
+        schedule::{ shift::ShiftQueries, work_schedule::WorkScheduleQueries },
+        org_job::OrgJobQueries,
+        org_member::OrgMemberQueries,
+    },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::{ Validate, ValidationError },
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::{ AppError, FieldError },
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        result::{ Result, NotFoundError },
+        pagination::ListQuery,
+        schedule::shift::{
+            Shift,
+            ShiftCreated,
+            RequestCreateShift,
+            RequestUpdateShift,
+            SchedulingWarning,
+        },
+        org_member::OrgPermission,
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+pub struct ShiftRouter;
+
+#[async_trait]
+impl UniqueIdRouter for ShiftRouter {
+    type Queries = ShiftQueries;
+
+    fn path() -> String {
+        "shifts".to_string()
+    }
+
+    fn org_id_of(conn: &mut Connection, id: i64) -> Result<Option<i64>> {
+        Ok(Some(ShiftQueries::find_org_id(conn, id)?))
+    }
+
+    /// Shifts are listed and created through their schedule, see `router`.
+    async fn default_routes() -> Router {
+        Router::new()
+            .route("/:id", get(Self::get_by_id))
+            .route("/:id", patch(Self::update_shift))
+            .route("/:id", delete(Self::delete_shift))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new().route("/:id/warnings", get(Self::get_shift_warnings))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route(
+                "/schedules/:id/shifts",
+                get(Self::get_schedule_shifts).post(Self::create_shift)
+            )
+            .route("/schedules/:id/shifts/warnings", post(Self::get_proposed_shift_warnings))
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/:id", "Get a shift").data::<Shift>(),
+            RouteDoc::patch("/:id", "Update a shift")
+                .body::<RequestUpdateShift>()
+                .affected_rows(),
+            RouteDoc::delete("/:id", "Delete a shift").affected_rows()
+        ]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get(
+                "/:id/warnings",
+                "Get the scheduling warnings of a shift"
+            ).data_list::<SchedulingWarning>()
+        ]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/schedules/:id/shifts",
+                    "List the shifts of a schedule"
+                ).page::<Shift>(),
+                RouteDoc::post("/schedules/:id/shifts", "Create a shift")
+                    .body::<RequestCreateShift>()
+                    .data::<ShiftCreated>()
+                    .status(StatusCode::CREATED),
+                RouteDoc::post(
+                    "/schedules/:id/shifts/warnings",
+                    "Get the scheduling warnings a shift would raise, without creating it"
+                )
+                    .body::<RequestCreateShift>()
+                    .data_list::<SchedulingWarning>()
+            ],
+        ].concat()
+    }
+}
+
+impl ShiftRouter {
+    /// Makes sure the job belongs to the organization of the schedule, failing with a
+    /// `ValidationError` on `job_id` otherwise.
+    pub fn check_job(conn: &mut Connection, org_id: i64, job_id: i64) -> AppResult<()> {
+        match OrgJobQueries::find_by_id(conn, job_id) {
+            Ok(job) if job.org_id == org_id => Ok(()),
+            Err(err) if !err.is::<NotFoundError>() => Err(err.into()),
+            _ =>
+                Err(
+                    ValidationError(
+                        vec![FieldError::new("job_id", "is not a job of the organization")]
+                    ).into()
+                ),
+        }
+    }
+
+    pub async fn get_schedule_shifts(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(schedule_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<Shift>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, schedule_id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = ShiftQueries::schedule_scope(schedule_id);
+            Ok(ShiftQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    pub async fn create_shift(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(schedule_id): Path<i64>,
+        Json(create_dto): Json<RequestCreateShift>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.schedule_id != schedule_id {
+            return Err(AppError::bad_request("Body does not match the path"));
+        }
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let (id, warnings) = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, schedule_id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+            Self::check_job(conn, schedule.org_id, create_dto.job_id)?;
+            if OrgMemberQueries::find_role(conn, schedule.org_id, create_dto.user_id)?.is_none() {
+                return Err(
+                    ValidationError(
+                        vec![FieldError::new("user_id", "is not a member of the organization")]
+                    ).into()
+                );
+            }
+
+            let warnings = ShiftQueries::scheduling_warnings(
+                conn,
+                schedule.org_id,
+                create_dto.user_id,
+                create_dto.start_time,
+                create_dto.end_time
+            )?;
+            let id = ShiftQueries::create_entity(conn, snowflake_generator, create_dto)?;
+
+            Ok((id, warnings))
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: ShiftCreated { id, warnings } });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    /// Reports the scheduling warnings of an existing shift.
+    pub async fn get_shift_warnings(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let warnings = state.db(move |conn| -> AppResult<_> {
+            let org_id = ShiftQueries::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+            let shift = ShiftQueries::find_by_id(conn, id)?;
+            let warnings = ShiftQueries::scheduling_warnings(
+                conn,
+                org_id,
+                shift.user_id,
+                shift.start_time,
+                shift.end_time
+            )?;
+
+            Ok(warnings)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: warnings });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Reports the scheduling warnings a shift would raise, without creating it.
+    pub async fn get_proposed_shift_warnings(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(schedule_id): Path<i64>,
+        Json(create_dto): Json<RequestCreateShift>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        let warnings = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, schedule_id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+            let warnings = ShiftQueries::scheduling_warnings(
+                conn,
+                schedule.org_id,
+                create_dto.user_id,
+                create_dto.start_time,
+                create_dto.end_time
+            )?;
+
+            Ok(warnings)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: warnings });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn update_shift(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        Json(update_dto): Json<RequestUpdateShift>
+    ) -> ApiResponse {
+        update_dto.validate()?;
+
+        let affected_rows = state.db(move |conn| -> AppResult<_> {
+            let org_id = ShiftQueries::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+            if let Some(job_id) = update_dto.job_id {
+                Self::check_job(conn, org_id, job_id)?;
+            }
+
+            let affected_rows = ShiftQueries::update_entity(conn, id, update_dto)?;
+
+            Ok(affected_rows)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn delete_shift(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let affected_rows = state.db(move |conn| -> AppResult<_> {
+            let org_id = ShiftQueries::find_org_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+
+            let affected_rows = ShiftQueries::delete_entity(conn, id)?;
+
+            Ok(affected_rows)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::{ Body, to_bytes }, http::{ Request, StatusCode }, Extension };
+    use chrono::NaiveDate;
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{
+            tests::{ initialize_test_app_state, create_test_user },
+            utils::extract_response_body,
+        },
+        queries::organization::OrgQueries,
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::RequestCreateOrganization,
+            org_member::RequestCreateOrgMember,
+            schedule::work_schedule::RequestCreateWorkSchedule,
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_shift_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = ShiftRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee_id, employee_auth) = create_test_user(&state, "employee@example.com")?;
+        let (outsider_id, _) = create_test_user(&state, "outsider@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let mut create_org = |name: &str| -> Result<(i64, i64)> {
+            let org_id = OrgQueries::create_entity(
+                &mut conn,
+                state.snowflake_generator.clone(),
+                RequestCreateOrganization {
+                    name: name.to_string(),
+                    description: None,
+                    owner_id,
+                    timezone: None,
+                    icon: None,
+                }
+            )?;
+            let job_id = OrgJobQueries::find_by_org_id(&mut conn, org_id)?[0].id;
+            Ok((org_id, job_id))
+        };
+        let (org_id, job_id) = create_org("Test Organization")?;
+        let (_, other_job_id) = create_org("Other Organization")?;
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+            org_id,
+            user_id: employee_id,
+            job_id,
+            role: None,
+        })?;
+        let schedule_id = WorkScheduleQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                end_date: NaiveDate::from_ymd_opt(2024, 1, 7).unwrap(),
+            }
+        )?;
+
+        let create_request = |auth: &str, user_id: i64, job_id: i64, start: &str, end: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/schedules/{}/shifts", schedule_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(
+                    Body::from(
+                        json!({
+                            "user_id": user_id,
+                            "schedule_id": schedule_id,
+                            "job_id": job_id,
+                            "start_time": start,
+                            "end_time": end,
+                        }).to_string()
+                    )
+                )
+                .unwrap()
+        };
+        let morning = ("2024-01-01T09:00:00", "2024-01-01T17:00:00");
+
+        // Only members allowed to publish schedules create shifts
+        let response = router
+            .clone()
+            .oneshot(create_request(&employee_auth, employee_id, job_id, morning.0, morning.1)).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The job and the user must belong to the organization
+        let response = router
+            .clone()
+            .oneshot(
+                create_request(&owner_auth, employee_id, other_job_id, morning.0, morning.1)
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(response.into_body(), usize::MAX).await.unwrap()
+        )?;
+        assert_eq!(body["fields"][0]["field"], "job_id");
+        let response = router
+            .clone()
+            .oneshot(create_request(&owner_auth, outsider_id, job_id, morning.0, morning.1)).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(response.into_body(), usize::MAX).await.unwrap()
+        )?;
+        assert_eq!(body["fields"][0]["field"], "user_id");
+
+        let response = router
+            .clone()
+            .oneshot(create_request(&owner_auth, employee_id, job_id, morning.0, morning.1)).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: ShiftCreated = extract_response_body(response).await.expect(
+            "error extracting body"
+        );
+        assert!(created.warnings.is_empty());
+        let shift_id = created.id;
+
+        // A second booking of the same hours is refused with the shift it collides with
+        let response = router
+            .clone()
+            .oneshot(
+                create_request(
+                    &owner_auth,
+                    employee_id,
+                    job_id,
+                    "2024-01-01T16:00:00",
+                    "2024-01-01T20:00:00"
+                )
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(response.into_body(), usize::MAX).await.unwrap()
+        )?;
+        assert_eq!(body["details"]["rule"], "OVERLAP");
+        assert_eq!(body["details"]["conflicting_shift"]["id"], shift_id);
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/schedules/{}/shifts", schedule_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(response.into_body(), usize::MAX).await.unwrap()
+        )?;
+        assert_eq!(body["data"].as_array().map(Vec::len), Some(1));
+
+        let get_shift = |id: i64| {
+            Request::builder()
+                .uri(format!("/shifts/{}", id))
+                .header("Authorization", &owner_auth)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = router.clone().oneshot(get_shift(shift_id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let shift: Shift = extract_response_body(response).await.expect("error extracting shift");
+        assert_eq!(shift.user_id, employee_id);
+        let response = router.clone().oneshot(get_shift(shift_id + 1)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/shifts/{}/warnings", shift_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let warnings: Vec<SchedulingWarning> = extract_response_body(response).await.expect(
+            "error extracting warnings"
+        );
+        assert!(warnings.is_empty());
+
+        // Only members allowed to publish schedules move shifts
+        let update_request = |auth: &str| {
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/shifts/{}", shift_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(json!({ "end_time": "2024-01-01T18:00:00" }).to_string()))
+                .unwrap()
+        };
+        let response = router.clone().oneshot(update_request(&employee_auth)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = router.clone().oneshot(update_request(&owner_auth)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let shift: Shift = extract_response_body(
+            router.clone().oneshot(get_shift(shift_id)).await.unwrap()
+        ).await.expect("error extracting shift");
+        assert_eq!(shift.end_time.to_string(), "2024-01-01 18:00:00");
+
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("/shifts/{}", shift_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let deleted_rows: u64 = extract_response_body(response).await.expect(
+            "error extracting body"
+        );
+        assert_eq!(deleted_rows, 1);
+
+        Ok(())
+    }
+}
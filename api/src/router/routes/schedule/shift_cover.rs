@@ -0,0 +1,527 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post },
+};
+use hyper::StatusCode;
+
+use crate::{
+    metrics::metrics,
+    storage::Connection,
+    queries::{
+        schedule::{ shift::ShiftQueries, shift_cover::ShiftCoverQueries },
+        org_member::OrgMemberQueries,
+    },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        result::Result,
+        org_member::OrgPermission,
+        schedule::{
+            shift_cover::{ ShiftCover, RequestCreateShiftCover },
+            RequestShiftRequestAction,
+            ShiftRequestStatus,
+        },
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+/// Requests for another member to cover a shift.
+///
+/// A cover is requested by the member working the shift, accepted or declined by the cover user,
+/// then approved or declined by a manager. Approving it hands the shift over to the cover user.
+pub struct ShiftCoverRouter;
+
+#[async_trait]
+impl UniqueIdRouter for ShiftCoverRouter {
+    type Queries = ShiftCoverQueries;
+
+    fn path() -> String {
+        "shift-covers".to_string()
+    }
+
+    fn org_id_of(conn: &mut Connection, id: i64) -> Result<Option<i64>> {
+        Ok(Some(ShiftCoverQueries::find_org_id(conn, id)?))
+    }
+
+    /// Covers only change status through their workflow endpoints, see `more_routes`.
+    async fn default_routes() -> Router {
+        Router::new()
+            .route("/:id", get(Self::get_by_id))
+            .route("/", post(Self::create_shift_cover))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new()
+            .route("/:id/peer-accept", post(Self::peer_accept))
+            .route("/:id/peer-decline", post(Self::peer_decline))
+            .route("/:id/approve", post(Self::approve))
+            .route("/:id/decline", post(Self::decline))
+            .route("/:id/cancel", post(Self::cancel))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route("/organizations/:id/shift-covers", get(Self::get_org_shift_covers))
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/:id", "Get a shift cover").data::<ShiftCover>(),
+            RouteDoc::post("/", "Request a shift cover")
+                .body::<RequestCreateShiftCover>()
+                .created_id()
+        ]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::post("/:id/peer-accept", "Accept a shift cover as the other member")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftCover>(),
+            RouteDoc::post("/:id/peer-decline", "Decline a shift cover as the other member")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftCover>(),
+            RouteDoc::post("/:id/approve", "Approve a shift cover")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftCover>(),
+            RouteDoc::post("/:id/decline", "Decline a shift cover")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftCover>(),
+            RouteDoc::post("/:id/cancel", "Cancel a shift cover")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftCover>()
+        ]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/organizations/:id/shift-covers",
+                    "List the shift covers of an organization"
+                ).page::<ShiftCover>()
+            ],
+        ].concat()
+    }
+}
+
+impl ShiftCoverRouter {
+    /// Loads a cover, making sure the user works its shift (`requester`) or is the cover user.
+    fn find_cover_of(
+        conn: &mut Connection,
+        id: i64,
+        user_id: i64,
+        requester: bool
+    ) -> AppResult<ShiftCover> {
+        let cover = ShiftCoverQueries::find_by_id(conn, id)?;
+        let acting_user_id = if requester {
+            ShiftQueries::find_by_id(conn, cover.shift_id)?.user_id
+        } else {
+            cover.cover_user_id
+        };
+        if acting_user_id != user_id {
+            return Err(AppError::forbidden("The shift cover is not yours to act on"));
+        }
+
+        Ok(cover)
+    }
+
+    /// Moves a cover to `status` and responds with the updated cover.
+    fn transition(
+        conn: &mut Connection,
+        id: i64,
+        status: ShiftRequestStatus,
+        admin_id: Option<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let note = action.and_then(|Json(action)| action.note);
+        ShiftCoverQueries::transition(conn, id, status, admin_id, note)?;
+        let cover = ShiftCoverQueries::find_by_id(conn, id)?;
+
+        let json = Json(ResponseDataJson { data: cover });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn get_org_shift_covers(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<ShiftCover>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = ShiftCoverQueries::org_scope(org_id);
+            Ok(ShiftCoverQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    /// Requests a cover of one of the caller's shifts.
+    pub async fn create_shift_cover(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Json(create_dto): Json<RequestCreateShiftCover>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| -> AppResult<_> {
+            let shift = ShiftQueries::find_by_id(conn, create_dto.shift_id)?;
+            if shift.user_id != user.id {
+                return Err(
+                    AppError::forbidden("Cannot request a cover for the shift of another member.")
+                );
+            }
+
+            let id = ShiftCoverQueries::create_entity(
+                conn,
+                snowflake_generator,
+                create_dto
+            )?;
+
+            Ok(id)
+        }).await?;
+
+        metrics().shift_requests_created.with_label_values(&["cover"]).inc();
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    pub async fn peer_accept(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_cover_of(conn, id, user.id, false)?;
+            Self::transition(conn, id, ShiftRequestStatus::PEER_ACCEPTED, None, action)
+        }).await
+    }
+
+    pub async fn peer_decline(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_cover_of(conn, id, user.id, false)?;
+            Self::transition(conn, id, ShiftRequestStatus::PEER_DECLINED, None, action)
+        }).await
+    }
+
+    /// Approves a cover, handing the shift over to the cover user.
+    pub async fn approve(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let response = state.db(move |conn| {
+            ShiftCoverQueries::find_org_id(conn, id)?;
+            Self::transition(conn, id, ShiftRequestStatus::APPROVED, Some(user.id), action)
+        }).await?;
+
+        metrics().shift_requests_approved.with_label_values(&["cover"]).inc();
+        Ok(response)
+    }
+
+    pub async fn decline(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            ShiftCoverQueries::find_org_id(conn, id)?;
+            Self::transition(conn, id, ShiftRequestStatus::DECLINED, Some(user.id), action)
+        }).await
+    }
+
+    pub async fn cancel(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_cover_of(conn, id, user.id, true)?;
+            Self::transition(conn, id, ShiftRequestStatus::CANCELLED, None, action)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::Body, http::{ Request, StatusCode }, Extension };
+    use chrono::{ Duration, Local };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{ tests::{ initialize_test_app_state, create_test_user }, utils::extract_response_body },
+        queries::{
+            organization::OrgQueries,
+            org_job::OrgJobQueries,
+            org_member::OrgMemberQueries,
+            schedule::work_schedule::WorkScheduleQueries,
+        },
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::RequestCreateOrganization,
+            org_job::RequestCreateOrgJob,
+            org_member::RequestCreateOrgMember,
+            schedule::{ shift::RequestCreateShift, work_schedule::RequestCreateWorkSchedule },
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_shift_cover_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = ShiftCoverRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee1_id, employee1_auth) = create_test_user(&state, "employee1@example.com")?;
+        let (employee2_id, employee2_auth) = create_test_user(&state, "employee2@example.com")?;
+        let (employee3_id, employee3_auth) = create_test_user(&state, "employee3@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let org_id = OrgQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: "Test Organization".to_string(),
+                description: None,
+                owner_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let job_id = OrgJobQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrgJob {
+                org_id,
+                name: "Cashier".to_string(),
+                description: None,
+                base_pay_rate: 20.0,
+                color: None,
+            }
+        )?;
+        for user_id in [employee1_id, employee2_id, employee3_id] {
+            OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+                org_id,
+                user_id,
+                job_id,
+                role: None,
+            })?;
+        }
+
+        // Covers only concern shifts that haven't started
+        let start_date = Local::now().date_naive() + Duration::days(7);
+        let schedule_id = WorkScheduleQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date,
+                end_date: start_date + Duration::days(6),
+            }
+        )?;
+        let start_time = start_date.and_hms_opt(9, 0, 0).unwrap();
+        let shift_id = ShiftQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateShift {
+                user_id: employee1_id,
+                schedule_id,
+                job_id,
+                start_time,
+                end_time: start_time + Duration::hours(8),
+                pay_rate: None,
+                note: None,
+                availability_policy: None,
+            }
+        )?;
+
+        let post_request = |uri: String, auth: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        // Only the member working the shift can ask for a cover
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    "/shift-covers".to_string(),
+                    &employee2_auth,
+                    json!({ "shift_id": shift_id, "cover_user_id": employee2_id })
+                )
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let mut cover_ids = vec![];
+        for cover_user_id in [employee2_id, employee3_id] {
+            let create_response = router
+                .clone()
+                .oneshot(
+                    post_request(
+                        "/shift-covers".to_string(),
+                        &employee1_auth,
+                        json!({ "shift_id": shift_id, "cover_user_id": cover_user_id })
+                    )
+                ).await
+                .unwrap();
+            assert_eq!(create_response.status(), StatusCode::CREATED);
+            let cover_id: i64 = extract_response_body(create_response).await.expect(
+                "error extracting body"
+            );
+            cover_ids.push(cover_id);
+        }
+
+        // Only the cover user accepts the cover
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/shift-covers/{}/peer-accept", cover_ids[0]),
+                    &employee3_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        // The cover user must still be a member free during the shift when accepting and approving
+        let book_cover_user = |conn: &mut Connection| {
+            ShiftQueries::create_entity(
+                conn,
+                state.snowflake_generator.clone(),
+                RequestCreateShift {
+                    user_id: employee2_id,
+                    schedule_id,
+                    job_id,
+                    start_time: start_time + Duration::hours(4),
+                    end_time: start_time + Duration::hours(10),
+                    pay_rate: None,
+                    note: None,
+                    availability_policy: None,
+                }
+            )
+        };
+        let accept_request = || {
+            post_request(
+                format!("/shift-covers/{}/peer-accept", cover_ids[0]),
+                &employee2_auth,
+                json!({})
+            )
+        };
+        let approve_request = || {
+            post_request(
+                format!("/shift-covers/{}/approve", cover_ids[0]),
+                &owner_auth,
+                json!({ "note": "Thanks for covering" })
+            )
+        };
+
+        let overlapping_shift_id = book_cover_user(&mut conn)?;
+        let conflict_response = router.clone().oneshot(accept_request()).await.unwrap();
+        assert_eq!(conflict_response.status(), StatusCode::CONFLICT);
+        ShiftQueries::delete_entity(&mut conn, overlapping_shift_id)?;
+
+        let accept_response = router.clone().oneshot(accept_request()).await.unwrap();
+        assert_eq!(accept_response.status(), StatusCode::OK);
+
+        let overlapping_shift_id = book_cover_user(&mut conn)?;
+        let conflict_response = router.clone().oneshot(approve_request()).await.unwrap();
+        assert_eq!(conflict_response.status(), StatusCode::CONFLICT);
+        ShiftQueries::delete_entity(&mut conn, overlapping_shift_id)?;
+
+        OrgMemberQueries::delete_entity(&mut conn, org_id, employee3_id)?;
+        let not_a_member_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/shift-covers/{}/peer-accept", cover_ids[1]),
+                    &employee3_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        assert_eq!(not_a_member_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let approve_response = router.clone().oneshot(approve_request()).await.unwrap();
+        assert_eq!(approve_response.status(), StatusCode::OK);
+        let cover: ShiftCover = extract_response_body(approve_response).await.expect(
+            "error extracting cover"
+        );
+        assert_eq!(cover.status, ShiftRequestStatus::APPROVED);
+        assert_eq!(cover.admin_id, Some(owner_id));
+
+        // The shift was handed over and the other cover cancelled
+        assert_eq!(ShiftQueries::find_by_id(&mut conn, shift_id)?.user_id, employee2_id);
+        assert_eq!(
+            ShiftCoverQueries::find_by_id(&mut conn, cover_ids[1])?.status,
+            ShiftRequestStatus::CANCELLED
+        );
+
+        let covers_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/shift-covers", org_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        let covers: Vec<ShiftCover> = extract_response_body(covers_response).await.expect(
+            "error extracting covers"
+        );
+        assert_eq!(covers.len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,535 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post },
+};
+use hyper::StatusCode;
+
+use crate::{
+    metrics::metrics,
+    storage::Connection,
+    queries::{ schedule::shift_pickup::ShiftPickupQueries, org_member::OrgMemberQueries },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        result::Result,
+        org_member::OrgPermission,
+        schedule::{
+            shift_pickup::{ ShiftPickup, RequestCreateShiftPickup },
+            RequestShiftRequestAction,
+            ShiftRequestStatus,
+        },
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+    snowflake::SnowflakeGenerator,
+};
+
+/// Requests from members to pick up an open shift.
+///
+/// Depending on the `pickup_mode` of the organization, a pickup is approved right away or waits
+/// for a manager. Approving it turns the open shift into a shift of the member.
+pub struct ShiftPickupRouter;
+
+#[async_trait]
+impl UniqueIdRouter for ShiftPickupRouter {
+    type Queries = ShiftPickupQueries;
+
+    fn path() -> String {
+        "shift-pickups".to_string()
+    }
+
+    fn org_id_of(conn: &mut Connection, id: i64) -> Result<Option<i64>> {
+        Ok(Some(ShiftPickupQueries::find_org_id(conn, id)?))
+    }
+
+    /// Pickups only change status through their workflow endpoints, see `more_routes`.
+    async fn default_routes() -> Router {
+        Router::new()
+            .route("/:id", get(Self::get_by_id))
+            .route("/", post(Self::create_shift_pickup))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new()
+            .route("/:id/approve", post(Self::approve))
+            .route("/:id/decline", post(Self::decline))
+            .route("/:id/cancel", post(Self::cancel))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route("/organizations/:id/shift-pickups", get(Self::get_org_shift_pickups))
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/:id", "Get a shift pickup").data::<ShiftPickup>(),
+            RouteDoc::post("/", "Pick up an open shift")
+                .body::<RequestCreateShiftPickup>()
+                .data::<ShiftPickup>()
+                .status(StatusCode::CREATED)
+        ]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::post("/:id/approve", "Approve a shift pickup")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftPickup>(),
+            RouteDoc::post("/:id/decline", "Decline a shift pickup")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftPickup>(),
+            RouteDoc::post("/:id/cancel", "Cancel a shift pickup")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftPickup>()
+        ]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/organizations/:id/shift-pickups",
+                    "List the shift pickups of an organization"
+                ).page::<ShiftPickup>()
+            ],
+        ].concat()
+    }
+}
+
+impl ShiftPickupRouter {
+    /// Moves a pickup to `status` and responds with the updated pickup.
+    fn transition(
+        conn: &mut Connection,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        id: i64,
+        status: ShiftRequestStatus,
+        admin_id: Option<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let note = action.and_then(|Json(action)| action.note);
+        ShiftPickupQueries::transition(
+            conn,
+            snowflake_generator,
+            id,
+            status,
+            admin_id,
+            note
+        )?;
+        let pickup = ShiftPickupQueries::find_by_id(conn, id)?;
+
+        let json = Json(ResponseDataJson { data: pickup });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn get_org_shift_pickups(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<ShiftPickup>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = ShiftPickupQueries::org_scope(org_id);
+            Ok(ShiftPickupQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    /// Asks for an open shift on behalf of the caller.
+    pub async fn create_shift_pickup(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Json(create_dto): Json<RequestCreateShiftPickup>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.user_id != user.id {
+            return Err(AppError::forbidden("Cannot pick up a shift for another member."));
+        }
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let pickup = state.db(move |conn| -> AppResult<_> {
+            let id = ShiftPickupQueries::create_entity(
+                conn,
+                snowflake_generator,
+                create_dto
+            )?;
+            let pickup = ShiftPickupQueries::find_by_id(conn, id)?;
+
+            Ok(pickup)
+        }).await?;
+
+        metrics().shift_requests_created.with_label_values(&["pickup"]).inc();
+
+        let json = Json(ResponseDataJson { data: pickup });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    /// Approves a pickup, giving the open shift to the member.
+    pub async fn approve(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let snowflake_generator = state.snowflake_generator.clone();
+        let response = state.db(move |conn| {
+            ShiftPickupQueries::find_org_id(conn, id)?;
+            Self::transition(
+                conn,
+                snowflake_generator,
+                id,
+                ShiftRequestStatus::APPROVED,
+                Some(user.id),
+                action
+            )
+        }).await?;
+
+        metrics().shift_requests_approved.with_label_values(&["pickup"]).inc();
+        Ok(response)
+    }
+
+    pub async fn decline(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let snowflake_generator = state.snowflake_generator.clone();
+        state.db(move |conn| {
+            ShiftPickupQueries::find_org_id(conn, id)?;
+            Self::transition(
+                conn,
+                snowflake_generator,
+                id,
+                ShiftRequestStatus::DECLINED,
+                Some(user.id),
+                action
+            )
+        }).await
+    }
+
+    pub async fn cancel(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let snowflake_generator = state.snowflake_generator.clone();
+        state.db(move |conn| {
+            let pickup = ShiftPickupQueries::find_by_id(conn, id)?;
+            if pickup.user_id != user.id {
+                return Err(AppError::forbidden("The shift pickup is not yours to act on"));
+            }
+            Self::transition(
+                conn,
+                snowflake_generator,
+                id,
+                ShiftRequestStatus::CANCELLED,
+                None,
+                action
+            )
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::{ Body, to_bytes }, http::{ Request, StatusCode }, Extension };
+    use chrono::{ Duration, Local };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{ tests::{ initialize_test_app_state, create_test_user }, utils::extract_response_body },
+        queries::{
+            organization::OrgQueries,
+            org_job::OrgJobQueries,
+            org_member::OrgMemberQueries,
+            schedule::{
+                work_schedule::WorkScheduleQueries,
+                open_shift::OpenShiftQueries,
+                shift::ShiftQueries,
+            },
+        },
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::{ RequestCreateOrganization, RequestUpdateOrganization, PickupMode },
+            org_member::RequestCreateOrgMember,
+            schedule::{
+                open_shift::RequestCreateOpenShift,
+                shift::RequestCreateShift,
+                work_schedule::RequestCreateWorkSchedule,
+            },
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_shift_pickup_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = ShiftPickupRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee1_id, employee1_auth) = create_test_user(&state, "employee1@example.com")?;
+        let (employee2_id, employee2_auth) = create_test_user(&state, "employee2@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let org_id = OrgQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: "Test Organization".to_string(),
+                description: None,
+                owner_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let job_id = OrgJobQueries::find_by_org_id(&mut conn, org_id)?[0].id;
+        for user_id in [employee1_id, employee2_id] {
+            OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+                org_id,
+                user_id,
+                job_id,
+                role: None,
+            })?;
+        }
+
+        // Pickups only concern open shifts that haven't started
+        let start_date = Local::now().date_naive() + Duration::days(7);
+        let schedule_id = WorkScheduleQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date,
+                end_date: start_date + Duration::days(6),
+            }
+        )?;
+        let mut create_open_shift = |day: i64| {
+            let start_time = (start_date + Duration::days(day)).and_hms_opt(9, 0, 0).unwrap();
+            OpenShiftQueries::create_entity(
+                &mut conn,
+                state.snowflake_generator.clone(),
+                RequestCreateOpenShift {
+                    schedule_id,
+                    job_id,
+                    start_time,
+                    end_time: start_time + Duration::hours(8),
+                    pay_rate: None,
+                    headcount: None,
+                }
+            )
+        };
+        let open_shift_id = create_open_shift(0)?;
+        let next_open_shift_id = create_open_shift(1)?;
+        let later_open_shift_id = create_open_shift(2)?;
+
+        let post_request = |uri: String, auth: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let pickup_request = |auth: &str, open_shift_id: i64, user_id: i64| {
+            post_request(
+                "/shift-pickups".to_string(),
+                auth,
+                json!({ "openshift_id": open_shift_id, "user_id": user_id })
+            )
+        };
+
+        // Members only pick up shifts for themselves
+        let forbidden_response = router
+            .clone()
+            .oneshot(pickup_request(&employee1_auth, open_shift_id, employee2_id)).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        // By default, a manager chooses among the members asking for the shift
+        let mut pickup_ids = vec![];
+        for (auth, user_id) in [(&employee1_auth, employee1_id), (&employee2_auth, employee2_id)] {
+            let create_response = router
+                .clone()
+                .oneshot(pickup_request(auth, open_shift_id, user_id)).await
+                .unwrap();
+            assert_eq!(create_response.status(), StatusCode::CREATED);
+            let pickup: ShiftPickup = extract_response_body(create_response).await.expect(
+                "error extracting pickup"
+            );
+            assert_eq!(pickup.status, ShiftRequestStatus::PENDING);
+            pickup_ids.push(pickup.id);
+        }
+
+        let approve_request = |auth: &str, id: i64| {
+            post_request(format!("/shift-pickups/{}/approve", id), auth, json!({}))
+        };
+        let forbidden_response = router
+            .clone()
+            .oneshot(approve_request(&employee1_auth, pickup_ids[0])).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+        let missing_response = router
+            .clone()
+            .oneshot(approve_request(&owner_auth, pickup_ids[1] + 1)).await
+            .unwrap();
+        assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+
+        let approve_response = router
+            .clone()
+            .oneshot(approve_request(&owner_auth, pickup_ids[0])).await
+            .unwrap();
+        assert_eq!(approve_response.status(), StatusCode::OK);
+        let pickup: ShiftPickup = extract_response_body(approve_response).await.expect(
+            "error extracting pickup"
+        );
+        assert_eq!(pickup.status, ShiftRequestStatus::APPROVED);
+        assert_eq!(pickup.admin_id, Some(owner_id));
+
+        // The member got the shift, and the other member's pickup was declined
+        let shifts = ShiftQueries::find_by_schedule_id(&mut conn, schedule_id)?;
+        assert_eq!(shifts.len(), 1);
+        assert_eq!(shifts[0].user_id, employee1_id);
+        assert!(OpenShiftQueries::find_by_id(&mut conn, open_shift_id)?.is_filled());
+        let get_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/shift-pickups/{}", pickup_ids[1]))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        let pickup: ShiftPickup = extract_response_body(get_response).await.expect(
+            "error extracting pickup"
+        );
+        assert_eq!(pickup.status, ShiftRequestStatus::DECLINED);
+
+        let filled_response = router
+            .clone()
+            .oneshot(pickup_request(&employee2_auth, open_shift_id, employee2_id)).await
+            .unwrap();
+        assert_eq!(filled_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Only the member who asked cancels a pickup, and settled pickups can't be cancelled
+        let cancel_request = |auth: &str, id: i64| {
+            post_request(format!("/shift-pickups/{}/cancel", id), auth, json!({}))
+        };
+        let forbidden_response = router
+            .clone()
+            .oneshot(cancel_request(&employee2_auth, pickup_ids[0])).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+        let settled_response = router
+            .clone()
+            .oneshot(cancel_request(&employee1_auth, pickup_ids[0])).await
+            .unwrap();
+        assert_eq!(settled_response.status(), StatusCode::CONFLICT);
+
+        // A member booked on another shift in the meantime can't be given the open shift
+        let create_response = router
+            .clone()
+            .oneshot(pickup_request(&employee1_auth, later_open_shift_id, employee1_id)).await
+            .unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let pickup: ShiftPickup = extract_response_body(create_response).await.expect(
+            "error extracting pickup"
+        );
+        let conflict_start = (start_date + Duration::days(2)).and_hms_opt(12, 0, 0).unwrap();
+        ShiftQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateShift {
+                user_id: employee1_id,
+                schedule_id,
+                job_id,
+                start_time: conflict_start,
+                end_time: conflict_start + Duration::hours(4),
+                pay_rate: None,
+                note: None,
+                availability_policy: None,
+            }
+        )?;
+        let conflict_response = router
+            .clone()
+            .oneshot(approve_request(&owner_auth, pickup.id)).await
+            .unwrap();
+        assert_eq!(conflict_response.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(conflict_response.into_body(), usize::MAX).await.unwrap()
+        )?;
+        assert_eq!(body["details"]["rule"], "OVERLAP");
+        assert_eq!(OpenShiftQueries::find_by_id(&mut conn, later_open_shift_id)?.filled_count, 0);
+
+        // Organizations handing out open shifts first come, first served approve pickups at once
+        OrgQueries::update_entity(&mut conn, org_id, RequestUpdateOrganization {
+            pickup_mode: Some(PickupMode::FIRST_COME_FIRST_SERVED),
+            ..Default::default()
+        })?;
+        let create_response = router
+            .clone()
+            .oneshot(pickup_request(&employee2_auth, next_open_shift_id, employee2_id)).await
+            .unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let pickup: ShiftPickup = extract_response_body(create_response).await.expect(
+            "error extracting pickup"
+        );
+        assert_eq!(pickup.status, ShiftRequestStatus::APPROVED);
+
+        let pickups_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/shift-pickups", org_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        let pickups: Vec<ShiftPickup> = extract_response_body(pickups_response).await.expect(
+            "error extracting pickups"
+        );
+        assert_eq!(pickups.len(), 4);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,540 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post },
+};
+use hyper::StatusCode;
+
+use crate::{
+    metrics::metrics,
+    storage::Connection,
+    queries::{
+        schedule::{ shift::ShiftQueries, shift_trade::ShiftTradeQueries },
+        org_member::OrgMemberQueries,
+    },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        result::Result,
+        org_member::OrgPermission,
+        schedule::{
+            shift_trade::{ ShiftTrade, RequestCreateShiftTrade },
+            RequestShiftRequestAction,
+            ShiftRequestStatus,
+        },
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+/// Trades of two shifts between members.
+///
+/// A trade is requested by the member working `shift1`, accepted or declined by the member
+/// working `shift2`, then approved or declined by a manager. Approving it swaps the shifts.
+pub struct ShiftTradeRouter;
+
+#[async_trait]
+impl UniqueIdRouter for ShiftTradeRouter {
+    type Queries = ShiftTradeQueries;
+
+    fn path() -> String {
+        "shift-trades".to_string()
+    }
+
+    fn org_id_of(conn: &mut Connection, id: i64) -> Result<Option<i64>> {
+        Ok(Some(ShiftTradeQueries::find_org_id(conn, id)?))
+    }
+
+    /// Trades only change status through their workflow endpoints, see `more_routes`.
+    async fn default_routes() -> Router {
+        Router::new()
+            .route("/:id", get(Self::get_by_id))
+            .route("/", post(Self::create_shift_trade))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new()
+            .route("/:id/peer-accept", post(Self::peer_accept))
+            .route("/:id/peer-decline", post(Self::peer_decline))
+            .route("/:id/approve", post(Self::approve))
+            .route("/:id/decline", post(Self::decline))
+            .route("/:id/cancel", post(Self::cancel))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route("/organizations/:id/shift-trades", get(Self::get_org_shift_trades))
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/:id", "Get a shift trade").data::<ShiftTrade>(),
+            RouteDoc::post("/", "Request a shift trade")
+                .body::<RequestCreateShiftTrade>()
+                .created_id()
+        ]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::post("/:id/peer-accept", "Accept a shift trade as the other member")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftTrade>(),
+            RouteDoc::post("/:id/peer-decline", "Decline a shift trade as the other member")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftTrade>(),
+            RouteDoc::post("/:id/approve", "Approve a shift trade")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftTrade>(),
+            RouteDoc::post("/:id/decline", "Decline a shift trade")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftTrade>(),
+            RouteDoc::post("/:id/cancel", "Cancel a shift trade")
+                .optional_body::<RequestShiftRequestAction>()
+                .data::<ShiftTrade>()
+        ]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/organizations/:id/shift-trades",
+                    "List the shift trades of an organization"
+                ).page::<ShiftTrade>()
+            ],
+        ].concat()
+    }
+}
+
+impl ShiftTradeRouter {
+    /// Loads a trade, making sure the user works `shift1` (`requester`) or `shift2`.
+    fn find_trade_of(
+        conn: &mut Connection,
+        id: i64,
+        user_id: i64,
+        requester: bool
+    ) -> AppResult<ShiftTrade> {
+        let trade = ShiftTradeQueries::find_by_id(conn, id)?;
+        let shift_id = if requester { trade.shift1_id } else { trade.shift2_id };
+        let shift = ShiftQueries::find_by_id(conn, shift_id)?;
+        if shift.user_id != user_id {
+            return Err(AppError::forbidden("The shift trade is not yours to act on"));
+        }
+
+        Ok(trade)
+    }
+
+    /// Moves a trade to `status` and responds with the updated trade.
+    fn transition(
+        conn: &mut Connection,
+        id: i64,
+        status: ShiftRequestStatus,
+        admin_id: Option<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let note = action.and_then(|Json(action)| action.note);
+        ShiftTradeQueries::transition(conn, id, status, admin_id, note)?;
+        let trade = ShiftTradeQueries::find_by_id(conn, id)?;
+
+        let json = Json(ResponseDataJson { data: trade });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn get_org_shift_trades(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<ShiftTrade>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = ShiftTradeQueries::org_scope(org_id);
+            Ok(ShiftTradeQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    /// Requests a trade of one of the caller's shifts.
+    pub async fn create_shift_trade(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Json(create_dto): Json<RequestCreateShiftTrade>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| -> AppResult<_> {
+            let shift = ShiftQueries::find_by_id(conn, create_dto.shift1_id)?;
+            if shift.user_id != user.id {
+                return Err(AppError::forbidden("Cannot trade the shift of another member."));
+            }
+
+            let id = ShiftTradeQueries::create_entity(
+                conn,
+                snowflake_generator,
+                create_dto
+            )?;
+
+            Ok(id)
+        }).await?;
+
+        metrics().shift_requests_created.with_label_values(&["trade"]).inc();
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    pub async fn peer_accept(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_trade_of(conn, id, user.id, false)?;
+            Self::transition(conn, id, ShiftRequestStatus::PEER_ACCEPTED, None, action)
+        }).await
+    }
+
+    pub async fn peer_decline(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_trade_of(conn, id, user.id, false)?;
+            Self::transition(conn, id, ShiftRequestStatus::PEER_DECLINED, None, action)
+        }).await
+    }
+
+    /// Approves a trade, swapping the members of its shifts.
+    pub async fn approve(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        let response = state.db(move |conn| {
+            ShiftTradeQueries::find_org_id(conn, id)?;
+            Self::transition(conn, id, ShiftRequestStatus::APPROVED, Some(user.id), action)
+        }).await?;
+
+        metrics().shift_requests_approved.with_label_values(&["trade"]).inc();
+        Ok(response)
+    }
+
+    pub async fn decline(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            ShiftTradeQueries::find_org_id(conn, id)?;
+            Self::transition(conn, id, ShiftRequestStatus::DECLINED, Some(user.id), action)
+        }).await
+    }
+
+    pub async fn cancel(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        action: Option<Json<RequestShiftRequestAction>>
+    ) -> ApiResponse {
+        state.db(move |conn| {
+            Self::find_trade_of(conn, id, user.id, true)?;
+            Self::transition(conn, id, ShiftRequestStatus::CANCELLED, None, action)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::Body, http::{ Request, StatusCode }, Extension };
+    use chrono::{ Duration, Local };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{ tests::{ initialize_test_app_state, create_test_user }, utils::extract_response_body },
+        queries::{
+            organization::OrgQueries,
+            org_job::OrgJobQueries,
+            org_member::OrgMemberQueries,
+            schedule::work_schedule::WorkScheduleQueries,
+        },
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::RequestCreateOrganization,
+            org_job::RequestCreateOrgJob,
+            org_member::RequestCreateOrgMember,
+            schedule::{ shift::RequestCreateShift, work_schedule::RequestCreateWorkSchedule },
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_shift_trade_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = ShiftTradeRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee1_id, employee1_auth) = create_test_user(&state, "employee1@example.com")?;
+        let (employee2_id, employee2_auth) = create_test_user(&state, "employee2@example.com")?;
+        let (_, outsider_auth) = create_test_user(&state, "outsider@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let org_id = OrgQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: "Test Organization".to_string(),
+                description: None,
+                owner_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let job_id = OrgJobQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrgJob {
+                org_id,
+                name: "Cashier".to_string(),
+                description: None,
+                base_pay_rate: 20.0,
+                color: None,
+            }
+        )?;
+        for user_id in [employee1_id, employee2_id] {
+            OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+                org_id,
+                user_id,
+                job_id,
+                role: None,
+            })?;
+        }
+
+        // Trades only concern shifts that haven't started
+        let start_date = Local::now().date_naive() + Duration::days(7);
+        let schedule_id = WorkScheduleQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date,
+                end_date: start_date + Duration::days(6),
+            }
+        )?;
+        let mut create_shift = |user_id: i64, day: i64| {
+            let start_time = (start_date + Duration::days(day)).and_hms_opt(9, 0, 0).unwrap();
+            ShiftQueries::create_entity(
+                &mut conn,
+                state.snowflake_generator.clone(),
+                RequestCreateShift {
+                    user_id,
+                    schedule_id,
+                    job_id,
+                    start_time,
+                    end_time: start_time + Duration::hours(8),
+                    pay_rate: None,
+                    note: None,
+                    availability_policy: None,
+                }
+            )
+        };
+        let shift1_id = create_shift(employee1_id, 0)?;
+        let shift2_id = create_shift(employee2_id, 1)?;
+
+        let post_request = |uri: String, auth: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let create_body = json!({ "shift1_id": shift1_id, "shift2_id": shift2_id });
+
+        // Only the member working the first shift can request the trade
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                post_request("/shift-trades".to_string(), &employee2_auth, create_body.clone())
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let create_response = router
+            .clone()
+            .oneshot(post_request("/shift-trades".to_string(), &employee1_auth, create_body)).await
+            .unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let trade_id: i64 = extract_response_body(create_response).await.expect(
+            "error extracting body"
+        );
+
+        // Unknown trades are reported as such
+        for action in ["peer-accept", "approve", "cancel"] {
+            let missing_response = router
+                .clone()
+                .oneshot(
+                    post_request(
+                        format!("/shift-trades/{}/{}", trade_id + 1, action),
+                        &owner_auth,
+                        json!({})
+                    )
+                ).await
+                .unwrap();
+            assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+        }
+
+        // Managers approve trades once the peer accepted them
+        let early_approve_response = router
+            .clone()
+            .oneshot(
+                post_request(format!("/shift-trades/{}/approve", trade_id), &owner_auth, json!({}))
+            ).await
+            .unwrap();
+        assert_eq!(early_approve_response.status(), StatusCode::CONFLICT);
+
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/shift-trades/{}/peer-accept", trade_id),
+                    &employee1_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let accept_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/shift-trades/{}/peer-accept", trade_id),
+                    &employee2_auth,
+                    json!({ "note": "Sure" })
+                )
+            ).await
+            .unwrap();
+        assert_eq!(accept_response.status(), StatusCode::OK);
+        let trade: ShiftTrade = extract_response_body(accept_response).await.expect(
+            "error extracting trade"
+        );
+        assert_eq!(trade.status, ShiftRequestStatus::PEER_ACCEPTED);
+
+        // Employees can't approve trades
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/shift-trades/{}/approve", trade_id),
+                    &employee2_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let approve_response = router
+            .clone()
+            .oneshot(
+                post_request(format!("/shift-trades/{}/approve", trade_id), &owner_auth, json!({}))
+            ).await
+            .unwrap();
+        assert_eq!(approve_response.status(), StatusCode::OK);
+        let trade: ShiftTrade = extract_response_body(approve_response).await.expect(
+            "error extracting trade"
+        );
+        assert_eq!(trade.status, ShiftRequestStatus::APPROVED);
+        assert_eq!(trade.admin_id, Some(owner_id));
+
+        // The shifts changed hands
+        assert_eq!(ShiftQueries::find_by_id(&mut conn, shift1_id)?.user_id, employee2_id);
+        assert_eq!(ShiftQueries::find_by_id(&mut conn, shift2_id)?.user_id, employee1_id);
+
+        // Settled trades can't be cancelled anymore
+        let cancel_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/shift-trades/{}/cancel", trade_id),
+                    &employee2_auth,
+                    json!({})
+                )
+            ).await
+            .unwrap();
+        assert_eq!(cancel_response.status(), StatusCode::CONFLICT);
+
+        let trades_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/shift-trades", org_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        let trades: Vec<ShiftTrade> = extract_response_body(trades_response).await.expect(
+            "error extracting trades"
+        );
+        assert_eq!(trades.len(), 1);
+
+        // Members of other organizations can't read the trades
+        for uri in [
+            format!("/organizations/{}/shift-trades", org_id),
+            format!("/shift-trades/{}", trade_id),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("Authorization", &outsider_auth)
+                        .body(Body::empty())
+                        .unwrap()
+                ).await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,544 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post },
+};
+use hyper::StatusCode;
+
+use crate::{
+    metrics::metrics,
+    storage::Connection,
+    queries::{
+        schedule::{ shift::ShiftQueries, timeoff_request::TimeOffRequestQueries },
+        org_member::OrgMemberQueries,
+    },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        result::Result,
+        org_member::OrgPermission,
+        schedule::{
+            timeoff_request::{
+                TimeOffRequest,
+                TimeOffApproval,
+                RequestCreateTimeOff,
+                RequestApproveTimeOff,
+            },
+            shift::Shift,
+            request_status::ScheduleRequestStatus,
+        },
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+/// Time off requested by members of an organization.
+///
+/// Members submit and cancel their own requests, managers approve or decline them.
+pub struct TimeOffRouter;
+
+#[async_trait]
+impl UniqueIdRouter for TimeOffRouter {
+    type Queries = TimeOffRequestQueries;
+
+    fn path() -> String {
+        "time-off".to_string()
+    }
+
+    fn org_id_of(conn: &mut Connection, id: i64) -> Result<Option<i64>> {
+        Ok(Some(TimeOffRequestQueries::find_org_id(conn, id)?))
+    }
+
+    /// Requests are submitted through their organization, see `router`, and only change status
+    /// through their workflow endpoints, see `more_routes`.
+    async fn default_routes() -> Router {
+        Router::new().route("/:id", get(Self::get_by_id))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new()
+            .route("/:id/overlapping-shifts", get(Self::get_overlapping_shifts))
+            .route("/:id/approve", post(Self::approve))
+            .route("/:id/decline", post(Self::decline))
+            .route("/:id/cancel", post(Self::cancel))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route(
+                "/organizations/:id/time-off",
+                get(Self::get_org_time_off).post(Self::create_time_off)
+            )
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![RouteDoc::get("/:id", "Get a time off request").data::<TimeOffRequest>()]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get(
+                "/:id/overlapping-shifts",
+                "List the shifts the member holds during the time off"
+            ).data_list::<Shift>(),
+            RouteDoc::post("/:id/approve", "Approve a time off request")
+                .optional_body::<RequestApproveTimeOff>()
+                .data::<TimeOffApproval>(),
+            RouteDoc::post("/:id/decline", "Decline a time off request").data::<TimeOffRequest>(),
+            RouteDoc::post("/:id/cancel", "Cancel a time off request").data::<TimeOffRequest>()
+        ]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/organizations/:id/time-off",
+                    "List the time off of an organization"
+                ).page::<TimeOffRequest>(),
+                RouteDoc::post("/organizations/:id/time-off", "Request time off")
+                    .body::<RequestCreateTimeOff>()
+                    .created_id()
+            ],
+        ].concat()
+    }
+}
+
+impl TimeOffRouter {
+    fn find_time_off(conn: &mut Connection, id: i64) -> AppResult<TimeOffRequest> {
+        Ok(TimeOffRequestQueries::find_by_id(conn, id)?)
+    }
+
+    /// Lists the time off of an organization, optionally for a single member with `?user_id=`.
+    pub async fn get_org_time_off(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<TimeOffRequest>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = TimeOffRequestQueries::org_scope(org_id);
+            Ok(TimeOffRequestQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    /// Submits time off for the caller.
+    pub async fn create_time_off(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        Json(create_dto): Json<RequestCreateTimeOff>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.org_id != org_id {
+            return Err(AppError::bad_request("Body does not match the path"));
+        }
+        if create_dto.user_id != user.id {
+            return Err(AppError::forbidden("Cannot request time off for another member."));
+        }
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| {
+            if OrgMemberQueries::find_role(conn, org_id, user.id)?.is_none() {
+                return Err(AppError::forbidden("Only members can request time off."));
+            }
+
+            Ok(TimeOffRequestQueries::create_entity(conn, snowflake_generator, create_dto)?)
+        }).await?;
+
+        metrics().shift_requests_created.with_label_values(&["time_off"]).inc();
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    /// Reports the shifts the member holds during the requested time off.
+    pub async fn get_overlapping_shifts(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let shifts = state.db(move |conn| -> AppResult<_> {
+            let request = Self::find_time_off(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                request.org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+            Ok(
+                ShiftQueries::find_overlapping_for_user(
+                    conn,
+                    request.user_id,
+                    request.org_id,
+                    request.start_time,
+                    request.end_time
+                )?
+            )
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: shifts });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Approves time off, reporting or releasing the shifts the member holds during it.
+    pub async fn approve(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        body: Option<Json<RequestApproveTimeOff>>
+    ) -> ApiResponse {
+        let policy = body.and_then(|Json(body)| body.overlapping_shifts).unwrap_or_default();
+        let snowflake_generator = state.snowflake_generator.clone();
+        let approval = state.db(move |conn| -> AppResult<_> {
+            Self::find_time_off(conn, id)?;
+            Ok(TimeOffRequestQueries::approve(conn, snowflake_generator, id, user.id, policy)?)
+        }).await?;
+
+        metrics().shift_requests_approved.with_label_values(&["time_off"]).inc();
+
+        let json = Json(ResponseDataJson { data: approval });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn decline(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let request = state.db(move |conn| {
+            Self::find_time_off(conn, id)?;
+            TimeOffRequestQueries::transition(
+                conn,
+                id,
+                ScheduleRequestStatus::DECLINED,
+                Some(user.id)
+            )?;
+            Self::find_time_off(conn, id)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: request });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn cancel(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let request = state.db(move |conn| {
+            if Self::find_time_off(conn, id)?.user_id != user.id {
+                return Err(AppError::forbidden("The time off request is not yours to cancel"));
+            }
+            TimeOffRequestQueries::transition(conn, id, ScheduleRequestStatus::CANCELLED, None)?;
+            Self::find_time_off(conn, id)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: request });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::{ Body, to_bytes }, http::{ Request, StatusCode }, Extension };
+    use chrono::{ Duration, Local };
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{ tests::{ initialize_test_app_state, create_test_user }, utils::extract_response_body },
+        queries::{
+            organization::OrgQueries,
+            org_job::OrgJobQueries,
+            schedule::{ open_shift::OpenShiftQueries, work_schedule::WorkScheduleQueries },
+        },
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::RequestCreateOrganization,
+            org_job::RequestCreateOrgJob,
+            org_member::RequestCreateOrgMember,
+            schedule::{
+                shift::RequestCreateShift,
+                work_schedule::RequestCreateWorkSchedule,
+                timeoff_request::TimeOffApproval,
+            },
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_time_off_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = TimeOffRouter::router().await.layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee_id, employee_auth) = create_test_user(&state, "employee@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let org_id = OrgQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: "Test Organization".to_string(),
+                description: None,
+                owner_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let job_id = OrgJobQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrgJob {
+                org_id,
+                name: "Cashier".to_string(),
+                description: None,
+                base_pay_rate: 20.0,
+                color: None,
+            }
+        )?;
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+            org_id,
+            user_id: employee_id,
+            job_id,
+            role: None,
+        })?;
+
+        let start_date = Local::now().date_naive() + Duration::days(7);
+        let schedule_id = WorkScheduleQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateWorkSchedule {
+                org_id,
+                start_date,
+                end_date: start_date + Duration::days(6),
+            }
+        )?;
+        for day in [0, 1, 4] {
+            let start_time = (start_date + Duration::days(day)).and_hms_opt(9, 0, 0).unwrap();
+            ShiftQueries::create_entity(
+                &mut conn,
+                state.snowflake_generator.clone(),
+                RequestCreateShift {
+                    user_id: employee_id,
+                    schedule_id,
+                    job_id,
+                    start_time,
+                    end_time: start_time + Duration::hours(8),
+                    pay_rate: None,
+                    note: None,
+                    availability_policy: None,
+                }
+            )?;
+        }
+
+        let post_request = |uri: String, auth: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let time_off_body =
+            json!({
+            "user_id": employee_id,
+            "org_id": org_id,
+            "start_time": start_date.and_hms_opt(0, 0, 0).unwrap(),
+            "end_time": (start_date + Duration::days(2)).and_hms_opt(0, 0, 0).unwrap(),
+            "reason": "Family trip",
+        });
+
+        // Members only request time off for themselves
+        let forbidden_response = router
+            .clone()
+            .oneshot(
+                post_request(
+                    format!("/organizations/{}/time-off", org_id),
+                    &owner_auth,
+                    time_off_body.clone()
+                )
+            ).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let mut time_off_ids = vec![];
+        for _ in 0..2 {
+            let create_response = router
+                .clone()
+                .oneshot(
+                    post_request(
+                        format!("/organizations/{}/time-off", org_id),
+                        &employee_auth,
+                        time_off_body.clone()
+                    )
+                ).await
+                .unwrap();
+            assert_eq!(create_response.status(), StatusCode::CREATED);
+            let time_off_id: i64 = extract_response_body(create_response).await.expect(
+                "error extracting body"
+            );
+            time_off_ids.push(time_off_id);
+        }
+
+        // The member cancels one of the requests
+        let cancel_response = router
+            .clone()
+            .oneshot(
+                post_request(format!("/time-off/{}/cancel", time_off_ids[1]), &employee_auth, json!({}))
+            ).await
+            .unwrap();
+        assert_eq!(cancel_response.status(), StatusCode::OK);
+        let request: TimeOffRequest = extract_response_body(cancel_response).await.expect(
+            "error extracting request"
+        );
+        assert_eq!(request.status, ScheduleRequestStatus::CANCELLED);
+
+        let declined_response = router
+            .clone()
+            .oneshot(
+                post_request(format!("/time-off/{}/decline", time_off_ids[1]), &owner_auth, json!({}))
+            ).await
+            .unwrap();
+        assert_eq!(declined_response.status(), StatusCode::CONFLICT);
+
+        // Employees can't approve time off
+        let approve_request = |auth: &str| {
+            post_request(
+                format!("/time-off/{}/approve", time_off_ids[0]),
+                auth,
+                json!({ "overlapping_shifts": "CONVERT_TO_OPEN_SHIFTS" })
+            )
+        };
+        let forbidden_response = router
+            .clone()
+            .oneshot(approve_request(&employee_auth)).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        // The two shifts during the time off are released as open shifts
+        let approve_response = router.clone().oneshot(approve_request(&owner_auth)).await.unwrap();
+        assert_eq!(approve_response.status(), StatusCode::OK);
+        let approval: TimeOffApproval = extract_response_body(approve_response).await.expect(
+            "error extracting approval"
+        );
+        assert_eq!(approval.request.status, ScheduleRequestStatus::APPROVED);
+        assert_eq!(approval.request.admin_id, Some(owner_id));
+        assert_eq!(approval.overlapping_shifts.len(), 2);
+        assert_eq!(approval.open_shift_ids.len(), 2);
+
+        assert_eq!(ShiftQueries::find_by_schedule_id(&mut conn, schedule_id)?.len(), 1);
+        let open_shifts = OpenShiftQueries::find_by_schedule_id(&mut conn, schedule_id)?;
+        assert_eq!(
+            open_shifts
+                .iter()
+                .map(|open_shift| open_shift.start_time)
+                .collect::<Vec<_>>(),
+            approval.overlapping_shifts
+                .iter()
+                .map(|shift| shift.start_time)
+                .collect::<Vec<_>>()
+        );
+
+        let list_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/time-off?user_id={}", org_id, employee_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        let requests: Vec<TimeOffRequest> = extract_response_body(list_response).await.expect(
+            "error extracting requests"
+        );
+        assert_eq!(requests.len(), 2);
+
+        // Page through the requests one at a time, latest start first
+        let mut paged_ids = vec![];
+        let mut cursor: Option<i64> = None;
+        loop {
+            let mut uri = format!("/organizations/{}/time-off?limit=1", org_id);
+            if let Some(cursor) = cursor {
+                uri.push_str(&format!("&cursor={}", cursor));
+            }
+            let page_response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("Authorization", &owner_auth)
+                        .body(Body::empty())
+                        .unwrap()
+                ).await
+                .unwrap();
+            assert_eq!(page_response.status(), StatusCode::OK);
+            let body = to_bytes(page_response.into_body(), usize::MAX).await.unwrap();
+            let page: ResponseDataPage<TimeOffRequest> = serde_json::from_slice(&body).unwrap();
+            assert_eq!(page.total, 2);
+            paged_ids.extend(page.data.iter().map(|request| request.id));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        time_off_ids.reverse();
+        assert_eq!(paged_ids, time_off_ids);
+
+        let invalid_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/time-off?sort=password", org_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(invalid_response.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,482 @@
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, patch, delete },
+};
+use hyper::StatusCode;
+
+use crate::{
+    storage::Connection,
+    queries::{ schedule::work_schedule::WorkScheduleQueries, org_member::OrgMemberQueries },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+    },
+    app::{ ApiResponse, AppState, AppResult },
+    utilities::app_error::AppError,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::ListQuery,
+        result::Result,
+        schedule::work_schedule::{
+            WorkSchedule,
+            WorkScheduleWeek,
+            RequestCreateWorkSchedule,
+            RequestUpdateWorkSchedule,
+        },
+        org_member::OrgPermission,
+    },
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
+};
+
+pub struct WorkScheduleRouter;
+
+#[async_trait]
+impl UniqueIdRouter for WorkScheduleRouter {
+    type Queries = WorkScheduleQueries;
+
+    fn path() -> String {
+        "schedules".to_string()
+    }
+
+    fn org_id_of(conn: &mut Connection, id: i64) -> Result<Option<i64>> {
+        Ok(Some(WorkScheduleQueries::find_by_id(conn, id)?.org_id))
+    }
+
+    /// Schedules are listed and created through their organization, see `router`.
+    async fn default_routes() -> Router {
+        Router::new()
+            .route("/:id", get(Self::get_by_id))
+            .route("/:id", patch(Self::update_schedule))
+            .route("/:id", delete(Self::delete_schedule))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new().route("/:id/week", get(Self::get_week_view))
+    }
+
+    async fn router() -> Router {
+        let default_routes = Self::default_routes().await;
+        let custom_routes = Self::more_routes().await;
+
+        Router::new()
+            .nest(&format!("/{}", &Self::path()), default_routes.merge(custom_routes))
+            .route(
+                "/organizations/:id/schedules",
+                get(Self::get_org_schedules).post(Self::create_schedule)
+            )
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/:id", "Get a schedule").data::<WorkSchedule>(),
+            RouteDoc::patch("/:id", "Update or publish a schedule")
+                .body::<RequestUpdateWorkSchedule>()
+                .affected_rows(),
+            RouteDoc::delete("/:id", "Delete a schedule").affected_rows()
+        ]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/:id/week", "Get the shifts of a schedule by day and by member").data::<
+                WorkScheduleWeek
+            >()
+        ]
+    }
+
+    fn route_docs() -> Vec<RouteDoc> {
+        let docs = [Self::default_route_docs(), Self::more_route_docs()].concat();
+
+        [
+            RouteDoc::nest(&format!("/{}", &Self::path()), docs),
+            vec![
+                RouteDoc::get(
+                    "/organizations/:id/schedules",
+                    "List the schedules of an organization"
+                ).page::<WorkSchedule>(),
+                RouteDoc::post("/organizations/:id/schedules", "Create a schedule")
+                    .body::<RequestCreateWorkSchedule>()
+                    .created_id()
+            ],
+        ].concat()
+    }
+}
+
+impl WorkScheduleRouter {
+    pub async fn get_org_schedules(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<WorkSchedule>>> {
+        let page = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            let scope = WorkScheduleQueries::org_scope(org_id);
+            Ok(WorkScheduleQueries::find_page(conn, &scope, &list_query)?)
+        }).await?;
+
+        Ok(Json(page))
+    }
+
+    pub async fn create_schedule(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(org_id): Path<i64>,
+        Json(create_dto): Json<RequestCreateWorkSchedule>
+    ) -> ApiResponse {
+        create_dto.validate()?;
+
+        if create_dto.org_id != org_id {
+            return Err(AppError::bad_request("Body does not match the path"));
+        }
+        if create_dto.end_date < create_dto.start_date {
+            return Err(AppError::bad_request("The schedule ends before it starts"));
+        }
+
+        let snowflake_generator = state.snowflake_generator.clone();
+        let id = state.db(move |conn| -> AppResult<_> {
+            OrgMemberQueries::ensure_permission(
+                conn,
+                org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+
+            let id = WorkScheduleQueries::create_entity(
+                conn,
+                snowflake_generator,
+                create_dto
+            )?;
+
+            Ok(id)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    /// Updates the dates of a schedule, or publishes it with `publish: true`.
+    pub async fn update_schedule(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        Json(update_dto): Json<RequestUpdateWorkSchedule>
+    ) -> ApiResponse {
+        update_dto.validate()?;
+
+        let affected_rows = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+
+            let affected_rows = WorkScheduleQueries::update_entity(conn, id, update_dto)?;
+
+            Ok(affected_rows)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn delete_schedule(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let affected_rows = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::PUBLISH_SCHEDULES
+            )?;
+
+            let affected_rows = WorkScheduleQueries::delete_entity(conn, id)?;
+
+            Ok(affected_rows)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: affected_rows });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    /// Returns the schedule with all of its shifts grouped by day and by member.
+    pub async fn get_week_view(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        let week = state.db(move |conn| -> AppResult<_> {
+            let schedule = WorkScheduleQueries::find_by_id(conn, id)?;
+            OrgMemberQueries::ensure_permission(
+                conn,
+                schedule.org_id,
+                user.id,
+                OrgPermission::VIEW_ORGANIZATION
+            )?;
+
+            Ok(WorkScheduleQueries::get_week_view(conn, id)?)
+        }).await?;
+        let json = Json(ResponseDataJson { data: week });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{ body::{ Body, to_bytes }, http::{ Request, StatusCode }, Extension, Router };
+    use chrono::NaiveDate;
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot` method
+
+    use crate::{
+        router::{
+            tests::{ initialize_test_app_state, create_test_user },
+            utils::extract_response_body,
+            routes::schedule::shift::ShiftRouter,
+        },
+        queries::{ organization::OrgQueries, org_job::OrgJobQueries },
+        prototypes::composite_key_queries::CompositeKeyQueries,
+        models::{
+            organization::RequestCreateOrganization,
+            org_job::RequestCreateOrgJob,
+            org_member::RequestCreateOrgMember,
+            schedule::work_schedule::WorkScheduleWeek,
+            result::Result,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_work_schedule_routes() -> Result<()> {
+        let state = initialize_test_app_state().await?;
+        let router = Router::new()
+            .merge(WorkScheduleRouter::router().await)
+            .merge(ShiftRouter::router().await)
+            .layer(Extension(state.clone()));
+
+        let (owner_id, owner_auth) = create_test_user(&state, "owner@example.com")?;
+        let (employee_id, employee_auth) = create_test_user(&state, "employee@example.com")?;
+
+        let mut conn = state.storage.get_conn()?;
+        let org_id = OrgQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrganization {
+                name: "Test Organization".to_string(),
+                description: None,
+                owner_id,
+                timezone: None,
+                icon: None,
+            }
+        )?;
+        let job_id = OrgJobQueries::create_entity(
+            &mut conn,
+            state.snowflake_generator.clone(),
+            RequestCreateOrgJob {
+                org_id,
+                name: "Cashier".to_string(),
+                description: None,
+                base_pay_rate: 20.0,
+                color: Some("ff0000".to_string()),
+            }
+        )?;
+        OrgMemberQueries::create_entity(&mut conn, RequestCreateOrgMember {
+            org_id,
+            user_id: employee_id,
+            job_id,
+            role: None,
+        })?;
+
+        // Employees can't create schedules
+        let create_request = |auth: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/organizations/{}/schedules", org_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(
+                    Body::from(
+                        json!({
+                            "org_id": org_id,
+                            "start_date": "2024-01-01",
+                            "end_date": "2024-01-07",
+                        }).to_string()
+                    )
+                )
+                .unwrap()
+        };
+        let forbidden_response = router
+            .clone()
+            .oneshot(create_request(&employee_auth)).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let create_response = router.clone().oneshot(create_request(&owner_auth)).await.unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let schedule_id: i64 = extract_response_body(create_response).await.expect(
+            "error extracting body"
+        );
+
+        let schedules_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/organizations/{}/schedules", org_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        let schedules: Vec<WorkSchedule> = extract_response_body(schedules_response).await.expect(
+            "error extracting schedules"
+        );
+        assert_eq!(schedules.len(), 1);
+
+        // Add shifts for the owner and the employee
+        for (user_id, start_time, end_time) in [
+            (employee_id, "2024-01-01T09:00:00", "2024-01-01T17:00:00"),
+            (employee_id, "2024-01-03T09:00:00", "2024-01-03T17:00:00"),
+            (owner_id, "2024-01-01T12:00:00", "2024-01-01T20:00:00"),
+        ] {
+            let shift_response = router
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(format!("/schedules/{}/shifts", schedule_id))
+                        .header("Content-Type", "application/json")
+                        .header("Authorization", &owner_auth)
+                        .body(
+                            Body::from(
+                                json!({
+                                    "user_id": user_id,
+                                    "schedule_id": schedule_id,
+                                    "job_id": job_id,
+                                    "start_time": start_time,
+                                    "end_time": end_time,
+                                }).to_string()
+                            )
+                        )
+                        .unwrap()
+                ).await
+                .unwrap();
+            assert_eq!(shift_response.status(), StatusCode::CREATED);
+        }
+
+        // Double booking is refused with the conflicting shift
+        let shift_request = |start_time: &str, end_time: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/schedules/{}/shifts", schedule_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", &owner_auth)
+                .body(
+                    Body::from(
+                        json!({
+                            "user_id": employee_id,
+                            "schedule_id": schedule_id,
+                            "job_id": job_id,
+                            "start_time": start_time,
+                            "end_time": end_time,
+                        }).to_string()
+                    )
+                )
+                .unwrap()
+        };
+        let conflict_response = router
+            .clone()
+            .oneshot(shift_request("2024-01-01T16:00:00", "2024-01-01T22:00:00")).await
+            .unwrap();
+        assert_eq!(conflict_response.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(conflict_response.into_body(), usize::MAX).await.unwrap()
+        )?;
+        assert_eq!(body["details"]["rule"], "OVERLAP");
+        assert_eq!(body["details"]["conflicting_shift"]["user_id"], employee_id);
+
+        let invalid_response = router
+            .clone()
+            .oneshot(shift_request("2024-01-05T16:00:00", "2024-01-05T10:00:00")).await
+            .unwrap();
+        assert_eq!(invalid_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let week_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/schedules/{}/week", schedule_id))
+                    .header("Authorization", &owner_auth)
+                    .body(Body::empty())
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(week_response.status(), StatusCode::OK);
+        let week: WorkScheduleWeek = extract_response_body(week_response).await.expect(
+            "error extracting week"
+        );
+        assert_eq!(week.days.len(), 7);
+        assert_eq!(week.days[0].date, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(week.days[0].shifts.len(), 2);
+        assert_eq!(week.days[0].shifts[0].job_name, "Cashier");
+        assert_eq!(week.days[0].shifts[0].job_color, Some("ff0000".to_string()));
+        assert_eq!(week.days[1].shifts.len(), 0);
+        assert_eq!(week.members.len(), 2);
+
+        let employee = week.members
+            .iter()
+            .find(|member| member.user_id == employee_id)
+            .expect("employee missing from the week view");
+        assert_eq!(employee.days.len(), 7);
+        assert_eq!(employee.days[0].shifts.len(), 1);
+        assert_eq!(employee.days[2].shifts.len(), 1);
+
+        // Only members allowed to publish schedules can publish it
+        let publish_request = |auth: &str| {
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/schedules/{}", schedule_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", auth)
+                .body(Body::from(json!({ "publish": true }).to_string()))
+                .unwrap()
+        };
+        let forbidden_response = router
+            .clone()
+            .oneshot(publish_request(&employee_auth)).await
+            .unwrap();
+        assert_eq!(forbidden_response.status(), StatusCode::FORBIDDEN);
+
+        let publish_response = router.clone().oneshot(publish_request(&owner_auth)).await.unwrap();
+        assert_eq!(publish_response.status(), StatusCode::OK);
+        assert!(WorkScheduleQueries::find_by_id(&mut conn, schedule_id)?.published);
+
+        Ok(())
+    }
+}
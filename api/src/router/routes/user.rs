@@ -1,13 +1,32 @@
 use async_trait::async_trait;
-use axum::{ Extension, Json, response::IntoResponse, Router, routing::post };
+use axum::{
+    Extension,
+    Json,
+    extract::Path,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post, patch, delete },
+};
 use hyper::StatusCode;
 
 use crate::{
-    queries::user::{ UserQueries, LoginForm, PasswordChangeForm },
-    prototypes::uniqueid_routers::UniqueIdRouter,
-    app::{ ApiResponse, AppState },
+    queries::{ user::{ UserQueries, PasswordChangeForm }, auth::RefreshTokenQueries },
+    prototypes::{
+        uniqueid_routers::UniqueIdRouter,
+        basic_queries::BasicQueries,
+        validate::Validate,
+        atomic::Atomic,
+    },
+    app::{ ApiResponse, AppState, AppResult },
     utilities::app_error::AppError,
-    models::ResponseDataJson,
+    models::{
+        ResponseDataJson,
+        ResponseDataPage,
+        pagination::{ ListQuery, ListScope },
+        user::{ PartialUser, PublicUser, RequestCreateUser, RequestUpdateUser },
+    },
+    storage::Repository,
+    router::{ extractors::auth_user::AuthUser, openapi::RouteDoc },
 };
 
 pub struct UserRouter;
@@ -20,75 +39,185 @@ impl UniqueIdRouter for UserRouter {
         "users".to_string()
     }
 
-    async fn more_routes() -> Router {
+    /// Users are read without their password hash, and only update or delete themselves. The
+    /// list only shows their names, not their contact details.
+    async fn default_routes() -> Router {
         Router::new()
-            .route("/check-password", post(Self::verify_password))
-            .route("/change-password", post(Self::change_password))
+            .route("/", get(Self::get_users))
+            .route("/:id", get(Self::get_user))
+            .route("/", post(Self::create_user))
+            .route("/:id", patch(Self::update_user))
+            .route("/:id", delete(Self::delete_user))
+    }
+
+    async fn more_routes() -> Router {
+        Router::new().route("/change-password", post(Self::change_password))
+    }
+
+    fn default_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::get("/", "List users").page::<PublicUser>(),
+            RouteDoc::get("/:id", "Get a user").data::<PartialUser>(),
+            RouteDoc::post("/", "Register a user").body::<RequestCreateUser>().created_id(),
+            RouteDoc::patch("/:id", "Update the caller")
+                .body::<RequestUpdateUser>()
+                .affected_rows(),
+            RouteDoc::delete("/:id", "Delete the caller").affected_rows()
+        ]
+    }
+
+    fn more_route_docs() -> Vec<RouteDoc> {
+        vec![
+            RouteDoc::post("/change-password", "Change the password of the caller")
+                .body::<PasswordChangeForm>()
+                .message()
+        ]
     }
 }
 
 impl UserRouter {
-    pub async fn verify_password(
+    /// Registers a user, hashing the password at the configured cost.
+    pub async fn create_user(
         Extension(state): Extension<AppState>,
-        Json(form): Json<LoginForm>
+        Json(create_dto): Json<RequestCreateUser>
     ) -> ApiResponse {
-        let mut conn = state.db_pool.get_conn()?;
-        match UserQueries::verify_password(&mut conn, form) {
-            Ok(is_valid) => {
-                let json = Json(ResponseDataJson { data: is_valid });
-                let mut response = json.into_response();
-                *response.status_mut() = if is_valid {
-                    StatusCode::OK
-                } else {
-                    StatusCode::UNAUTHORIZED
-                };
-                Ok(response)
-            }
-            Err(e) => Err(AppError::from(e)),
+        create_dto.validate()?;
+
+        let hash_cost = state.config.auth.hash_cost;
+        let snowflake_generator = state.snowflake_generator.clone();
+        // hashing the password is slow, it runs on the blocking pool with the queries
+        let id = state.db(move |conn| -> AppResult<i64> {
+            let id = UserQueries::create_entity(
+                conn,
+                snowflake_generator,
+                UserQueries::new_user(create_dto, hash_cost)?
+            )?;
+            Ok(id)
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: id });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::CREATED;
+        Ok(response)
+    }
+
+    pub async fn get_user(Extension(state): Extension<AppState>, Path(id): Path<i64>) -> ApiResponse {
+        let user = state.storage.find_by_id::<UserQueries>(id).await?;
+
+        let json = Json(ResponseDataJson { data: PartialUser::from(user) });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
+    }
+
+    pub async fn get_users(
+        Extension(state): Extension<AppState>,
+        list_query: ListQuery
+    ) -> AppResult<Json<ResponseDataPage<PublicUser>>> {
+        let page = state.storage.find_page::<UserQueries>(ListScope::all(), list_query).await?;
+
+        Ok(
+            Json(ResponseDataPage {
+                data: page.data.into_iter().map(PublicUser::from).collect(),
+                next_cursor: page.next_cursor,
+                total: page.total,
+            })
+        )
+    }
+
+    pub async fn update_user(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>,
+        Json(update_dto): Json<RequestUpdateUser>
+    ) -> ApiResponse {
+        if id != user.id {
+            return Err(AppError::forbidden("Cannot update another account."));
+        }
+        Self::update_entity(Extension(state), Path(id), Json(update_dto)).await
+    }
+
+    pub async fn delete_user(
+        Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
+        Path(id): Path<i64>
+    ) -> ApiResponse {
+        if id != user.id {
+            return Err(AppError::forbidden("Cannot delete another account."));
         }
+        Self::delete_entity(Extension(state), Path(id)).await
     }
 
     pub async fn change_password(
         Extension(state): Extension<AppState>,
+        AuthUser(user): AuthUser,
         Json(form): Json<PasswordChangeForm>
     ) -> ApiResponse {
-        let mut conn = state.db_pool.get_conn()?;
-        match UserQueries::change_password(&mut conn, form) {
-            Ok(_) => {
-                let json = Json(ResponseDataJson { data: "Password changed successfully" });
-                let mut response = json.into_response();
-                *response.status_mut() = StatusCode::OK;
-                Ok(response)
-            }
-            Err(e) => {
-                return Err(AppError::bad_request(e.to_string()));
-            }
+        form.validate()?;
+
+        if form.id != user.id {
+            return Err(AppError::forbidden("Cannot change the password of another account."));
         }
+        // hashing the new password is slow, it runs on the blocking pool with the queries
+        let hash_cost = state.config.auth.hash_cost;
+        state.db(move |conn| {
+            conn.atomic(|tx| {
+                UserQueries::change_password(tx, form, hash_cost)?;
+                // sessions opened with the old password must log in again
+                RefreshTokenQueries::revoke_all_for_user(tx, user.id)?;
+                Ok(())
+            })
+        }).await?;
+
+        let json = Json(ResponseDataJson { data: "Password changed successfully" });
+        let mut response = json.into_response();
+        *response.status_mut() = StatusCode::OK;
+        Ok(response)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{ str::FromStr, sync::Arc };
 
     use super::*;
-    use axum::{ body::Body, http::{ Request, StatusCode }, Extension, Router };
+    use axum::{
+        body::{ to_bytes, Body },
+        http::{ Request, StatusCode },
+        middleware,
+        Extension,
+        Router,
+    };
     use chrono::NaiveDate;
+    use mysql::params;
     use serde_json::json;
     use tower::ServiceExt; // for `oneshot` method
 
     use crate::{
-        router::{ tests::initialize_test_app_state, utils::extract_response_body },
-        models::{ user::{ RequestCreateUser, User, RequestUpdateUser }, result::Result },
+        router::{
+            tests::{ initialize_test_app_state, initialize_memory_app_state, create_test_user },
+            utils::extract_response_body,
+            routes::auth::AuthRouter,
+            middlewares::request_id::request_id,
+        },
+        models::{
+            user::{ RequestCreateUser, PartialUser, PublicUser, RequestUpdateUser },
+            auth::AuthTokens,
+            result::Result,
+        },
+        queries::user::LoginForm,
+        utilities::{ app_error::{ ErrorCode, ErrorResponse }, token::create_access_token },
+        config::{ AppConfig, AuthConfig },
+        storage::Queryable,
     };
 
     // Initialize test environment
     async fn initialize_test_router() -> Router {
         let state = initialize_test_app_state().await.expect("failed to initialize test app state");
-        UserRouter::router().await.layer(Extension(state))
+        UserRouter::router().await.merge(AuthRouter::router().await).layer(Extension(state))
     }
 
-    async fn get_user_by_id(router: Router, id: i64) -> User {
+    async fn get_user_by_id(router: Router, id: i64) -> PartialUser {
         let get_response = router
             .clone()
             .oneshot(
@@ -131,7 +260,7 @@ mod tests {
         let created_user_id: i64 =
             extract_response_body(create_response).await.expect("error extracting body");
 
-        let created_user: User = get_user_by_id(router.clone(), created_user_id).await;
+        let created_user: PartialUser = get_user_by_id(router.clone(), created_user_id).await;
         assert_eq!(created_user.email, create_user_dto.email);
         assert_eq!(created_user.first_name, create_user_dto.first_name);
         assert_eq!(created_user.last_name, create_user_dto.last_name);
@@ -145,114 +274,361 @@ mod tests {
             .unwrap();
         assert_eq!(get_all_response.status(), StatusCode::OK);
         // Assert that the data length is 1
-        let all_users: Vec<User> = extract_response_body(get_all_response).await.expect(
-            "error extracting response body"
-        );
+        let body = to_bytes(get_all_response.into_body(), usize::MAX).await?;
+        // the password hashes are never returned
+        assert!(!String::from_utf8_lossy(&body).contains("encrypted_password"));
+        // nor the contact details of the other users
+        let body = String::from_utf8_lossy(&body);
+        assert!(!body.contains("email") && !body.contains("phone_number"));
+        let all_users: Vec<PublicUser> = serde_json::from_value(
+            serde_json::from_str::<serde_json::Value>(&body)?["data"].clone()
+        )?;
         assert_eq!(all_users.len(), 1);
 
+        let login_response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/auth/login")
+                    .header("Content-Type", "application/json")
+                    .body(
+                        Body::from(
+                            json!(LoginForm {
+                                email: "test@example.com".to_string(),
+                                password: "password123".to_string(),
+                            }).to_string()
+                        )
+                    )
+                    .unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(login_response.status(), StatusCode::OK);
+        let tokens: AuthTokens = extract_response_body(login_response).await.expect(
+            "error extracting tokens"
+        );
+
         // Test Update User
         let update_user_dto = RequestUpdateUser {
             phone_number: Some("9093334444".to_string()),
             ..Default::default()
         };
+        let update_request = |authorization: Option<String>| {
+            let mut builder = Request::builder()
+                .method("PATCH")
+                .uri(format!("/users/{}", created_user_id))
+                .header("Content-Type", "application/json");
+            if let Some(authorization) = authorization {
+                builder = builder.header("Authorization", authorization);
+            }
+            builder.body(Body::from(json!(update_user_dto).to_string())).unwrap()
+        };
+
+        let update_response = router.clone().oneshot(update_request(None)).await.unwrap();
+        assert_eq!(update_response.status(), StatusCode::UNAUTHORIZED);
 
         let update_response = router
             .clone()
-            .oneshot(
-                Request::builder()
-                    .method("PATCH")
-                    .uri(format!("/users/{}", created_user_id))
-                    .header("Content-Type", "application/json")
-                    .body(Body::from(json!(update_user_dto).to_string()))
-                    .unwrap()
-            ).await
+            .oneshot(update_request(Some(format!("Bearer {}", tokens.access_token)))).await
             .unwrap();
         assert_eq!(update_response.status(), StatusCode::OK);
 
-        let user: User = get_user_by_id(router.clone(), created_user_id).await;
+        let user: PartialUser = get_user_by_id(router.clone(), created_user_id).await;
         assert_eq!(user.phone_number, update_user_dto.phone_number);
 
-        let check_password_response = router
+        let login_response = router
             .clone()
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri("/users/check-password")
+                    .uri("/auth/login")
                     .header("Content-Type", "application/json")
                     .body(
                         Body::from(
                             json!(LoginForm {
                                 email: "test@example.com".to_string(),
-                                password: "password123".to_string(),
+                                password: "newasdasd".to_string(),
                             }).to_string()
                         )
                     )
                     .unwrap()
             ).await
             .unwrap();
-        assert_eq!(check_password_response.status(), StatusCode::OK);
+        assert_eq!(login_response.status(), StatusCode::UNAUTHORIZED);
+
+        let change_password_request = |old_password: &str, new_password: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/users/change-password")
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", tokens.access_token))
+                .body(
+                    Body::from(
+                        json!(PasswordChangeForm {
+                            id: created_user_id,
+                            old_password: old_password.to_string(),
+                            new_password: new_password.to_string(),
+                        }).to_string()
+                    )
+                )
+                .unwrap()
+        };
+        let wrong_password_response = router
+            .clone()
+            .oneshot(change_password_request("wrongpassword", "newpassword123")).await
+            .unwrap();
+        assert_eq!(wrong_password_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(wrong_password_response.into_body(), usize::MAX).await?
+        )?;
+        assert_eq!(body["fields"][0]["field"], "old_password");
+        let short_password_response = router
+            .clone()
+            .oneshot(change_password_request("password123", "short")).await
+            .unwrap();
+        assert_eq!(short_password_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_slice(
+            &to_bytes(short_password_response.into_body(), usize::MAX).await?
+        )?;
+        assert_eq!(body["fields"][0]["field"], "new_password");
+
+        let change_password_response = router
+            .clone()
+            .oneshot(change_password_request("password123", "newpassword123")).await
+            .unwrap();
+        assert_eq!(change_password_response.status(), StatusCode::OK);
 
-        let check_password_response = router
+        let login_response = router
             .clone()
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri("/users/check-password")
+                    .uri("/auth/login")
                     .header("Content-Type", "application/json")
                     .body(
                         Body::from(
                             json!(LoginForm {
                                 email: "test@example.com".to_string(),
-                                password: "newasdasd".to_string(),
+                                password: "newpassword123".to_string(),
                             }).to_string()
                         )
                     )
                     .unwrap()
             ).await
             .unwrap();
-        assert_eq!(check_password_response.status(), StatusCode::UNAUTHORIZED);
 
-        let change_password_response = router
+        assert_eq!(login_response.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_routes_in_memory() -> Result<()> {
+        // the CRUD routes only go through the storage, they run without a database
+        let state = initialize_memory_app_state();
+        let router = UserRouter::router()
+            .await
+            .layer(middleware::from_fn(request_id))
+            .layer(Extension(state.clone()));
+
+        let create_user_dto = RequestCreateUser {
+            email: "memory@example.com".to_string(),
+            password: "password123".to_string(),
+            first_name: "Memory".to_string(),
+            last_name: "User".to_string(),
+            date_of_birth: NaiveDate::from_str("1990-01-01").unwrap(),
+            phone_number: None,
+        };
+        let create_request = || {
+            Request::builder()
+                .method("POST")
+                .uri("/users")
+                .header("Content-Type", "application/json")
+                .body(Body::from(json!(create_user_dto).to_string()))
+                .unwrap()
+        };
+
+        let create_response = router.clone().oneshot(create_request()).await.unwrap();
+        assert_eq!(create_response.status(), StatusCode::CREATED);
+        let created_user_id: i64 =
+            extract_response_body(create_response).await.expect("error extracting body");
+
+        let created_user: PartialUser = get_user_by_id(router.clone(), created_user_id).await;
+        assert_eq!(created_user.email, create_user_dto.email);
+        assert_eq!(created_user.date_of_birth, create_user_dto.date_of_birth);
+        assert!(created_user.is_active);
+
+        // invalid fields are all reported
+        let invalid_response = router
             .clone()
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri("/users/change-password")
+                    .uri("/users")
                     .header("Content-Type", "application/json")
                     .body(
                         Body::from(
-                            json!(PasswordChangeForm {
-                                id: created_user_id,
-                                old_password: "password123".to_string(),
-                                new_password: "newpassword123".to_string(),
+                            json!(RequestCreateUser {
+                                email: "not an email".to_string(),
+                                first_name: "".to_string(),
+                                ..create_user_dto.clone()
                             }).to_string()
                         )
                     )
                     .unwrap()
             ).await
             .unwrap();
-        assert_eq!(change_password_response.status(), StatusCode::OK);
+        assert_eq!(invalid_response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error: ErrorResponse = serde_json::from_slice(
+            &to_bytes(invalid_response.into_body(), usize::MAX).await?
+        )?;
+        assert_eq!(error.code, ErrorCode::VALIDATION_FAILED);
+        let fields = error.fields
+            .into_iter()
+            .map(|field| field.field)
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec!["email", "first_name"]);
+
+        // emails are unique
+        let duplicate_response = router.clone().oneshot(create_request()).await.unwrap();
+        assert_eq!(duplicate_response.status(), StatusCode::CONFLICT);
+        let error: ErrorResponse = serde_json::from_slice(
+            &to_bytes(duplicate_response.into_body(), usize::MAX).await?
+        )?;
+        assert_eq!(error.code, ErrorCode::UNIQUE_VIOLATION);
+        assert_eq!(error.fields[0].field, "email");
+
+        // users only update and delete themselves
+        let authorization = format!(
+            "Bearer {}",
+            create_access_token(&state.config.auth.jwt_secret, created_user_id)?
+        );
+        let (other_user_id, other_authorization) = create_test_user(&state, "other@example.com")?;
+        let update_request = |authorization: &str| {
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/users/{}", created_user_id))
+                .header("Content-Type", "application/json")
+                .header("Authorization", authorization)
+                .body(
+                    Body::from(
+                        json!(RequestUpdateUser {
+                            first_name: Some("Renamed".to_string()),
+                            ..Default::default()
+                        }).to_string()
+                    )
+                )
+                .unwrap()
+        };
+        let delete_request = |authorization: &str| {
+            Request::builder()
+                .method("DELETE")
+                .uri(format!("/users/{}", created_user_id))
+                .header("Authorization", authorization)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let update_response = router
+            .clone()
+            .oneshot(update_request(&other_authorization)).await
+            .unwrap();
+        assert_eq!(update_response.status(), StatusCode::FORBIDDEN);
+        let update_response = router.clone().oneshot(update_request(&authorization)).await.unwrap();
+        assert_eq!(update_response.status(), StatusCode::OK);
+        let user: PartialUser = get_user_by_id(router.clone(), created_user_id).await;
+        assert_eq!(user.first_name, "Renamed");
+
+        let delete_response = router
+            .clone()
+            .oneshot(delete_request(&other_authorization)).await
+            .unwrap();
+        assert_eq!(delete_response.status(), StatusCode::FORBIDDEN);
+        let delete_response = router.clone().oneshot(delete_request(&authorization)).await.unwrap();
+        assert_eq!(delete_response.status(), StatusCode::OK);
+
+        let get_all_response = router
+            .clone()
+            .oneshot(Request::builder().uri("/users").body(Body::empty()).unwrap()).await
+            .unwrap();
+        let all_users: Vec<PublicUser> = extract_response_body(get_all_response).await.expect(
+            "error extracting response body"
+        );
+        assert_eq!(
+            all_users
+                .iter()
+                .map(|user| user.id)
+                .collect::<Vec<_>>(),
+            vec![other_user_id]
+        );
 
-        let check_password_response = router
+        // errors carry the id of the request
+        let get_response = router
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/users/check-password")
-                    .header("Content-Type", "application/json")
-                    .body(
-                        Body::from(
-                            json!(LoginForm {
-                                email: "test@example.com".to_string(),
-                                password: "newpassword123".to_string(),
-                            }).to_string()
-                        )
-                    )
+                    .uri(format!("/users/{}", created_user_id))
+                    .header("x-request-id", "test-request")
+                    .body(Body::empty())
                     .unwrap()
             ).await
             .unwrap();
+        assert_eq!(get_response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get_response.headers()["x-request-id"], "test-request");
+        let error: ErrorResponse = serde_json::from_slice(
+            &to_bytes(get_response.into_body(), usize::MAX).await?
+        )?;
+        assert_eq!(error.code, ErrorCode::NOT_FOUND);
+        assert_eq!(error.request_id, Some("test-request".to_string()));
 
-        assert_eq!(check_password_response.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_password_hash_cost_comes_from_the_config() -> Result<()> {
+        let cheap_state = initialize_memory_app_state();
+        let costly_state = AppState {
+            config: Arc::new(AppConfig {
+                auth: AuthConfig { hash_cost: 5, ..cheap_state.config.auth.clone() },
+                ..(*cheap_state.config).clone()
+            }),
+            ..initialize_memory_app_state()
+        };
+
+        // every state hashes with its own cost, not with the one of the state created last
+        for state in [costly_state, cheap_state] {
+            let router = UserRouter::router().await.layer(Extension(state.clone()));
+            let create_response = router
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/users")
+                        .header("Content-Type", "application/json")
+                        .body(
+                            Body::from(
+                                json!(RequestCreateUser {
+                                    email: "cost@example.com".to_string(),
+                                    password: "password123".to_string(),
+                                    first_name: "Cost".to_string(),
+                                    last_name: "User".to_string(),
+                                    date_of_birth: NaiveDate::from_str("1990-01-01").unwrap(),
+                                    phone_number: None,
+                                }).to_string()
+                            )
+                        )
+                        .unwrap()
+                ).await
+                .unwrap();
+            assert_eq!(create_response.status(), StatusCode::CREATED);
+            let user_id: i64 = extract_response_body(create_response).await?;
+
+            let encrypted_password: Option<String> = state.storage.get_conn()?.exec_first(
+                "SELECT encrypted_password FROM users WHERE id = :id;",
+                params! { "id" => user_id }
+            )?;
+            let prefix = format!("$2b${:02}$", state.config.auth.hash_cost);
+            assert!(encrypted_password.unwrap().starts_with(&prefix));
+        }
 
         Ok(())
     }
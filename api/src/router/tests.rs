@@ -1,54 +1,70 @@
-use std::{ sync::Arc, env };
-use dotenv::dotenv;
-use mysql::{ *, prelude::Queryable };
+use std::sync::Arc;
+
+use chrono::NaiveDate;
 
 use crate::{
-    models::result::Result,
+    models::{ result::Result, user::RequestCreateUser },
     app::AppState,
     snowflake::SnowflakeGenerator,
-    queries::create_tables,
+    queries::user::UserQueries,
+    config::{ AppConfig, AuthConfig },
+    storage::Storage,
+    shutdown::Shutdown,
+    tests::{ initialize_test_db, TEST_HASH_COST },
+    prototypes::basic_queries::BasicQueries,
+    utilities::token::create_access_token,
 };
 
-fn initialize_test_db() -> Result<()> {
-    dotenv().ok();
-    // Setup database connection (replace with your test database credentials)
-    let url: String = env
-        ::var("DATABASE_URL_TEST")
-        .expect("DATABASE_URL_TEST must be set in the .env file");
-
-    let pool = Pool::new(url.as_str())?;
-    let mut conn: PooledConn = pool.get_conn()?;
-
-    conn.query_drop("DROP DATABASE IF EXISTS worktest;")?;
-    conn.query_drop(
-        "CREATE DATABASE IF NOT EXISTS worktest DEFAULT CHARSET = utf8mb4 DEFAULT COLLATE = utf8mb4_unicode_ci;"
-    )?;
-    conn.query_drop("USE worktest;")?;
-
-    let _ = create_tables(&mut conn);
-
-    Ok(())
-}
-
-fn connect_to_db() -> Result<Pool> {
-    initialize_test_db()?;
-    let url: String = env
-        ::var("DATABASE_URL_TEST")
-        .expect("DATABASE_URL_TEST must be set in the .env file");
-
-    let pool = Pool::new(format!("{url}/worktest").as_str())?;
-
-    Ok(pool)
+/// The configuration of the test `AppState`s, passwords are hashed with the lowest cost.
+pub fn test_config() -> AppConfig {
+    AppConfig {
+        auth: AuthConfig { jwt_secret: "test-secret".to_string(), hash_cost: TEST_HASH_COST },
+        ..Default::default()
+    }
 }
 
 pub async fn initialize_test_app_state() -> Result<AppState> {
-    // Set up test database and other configurations
-    // Replace with actual test database initialization
-    let pool = connect_to_db()?;
+    // every test gets a storage of its own, see `tests::initialize_test_db`
     let state = AppState {
-        db_pool: pool,
+        storage: initialize_test_db()?,
         snowflake_generator: Arc::new(SnowflakeGenerator::new(1)),
+        config: Arc::new(test_config()),
+        shutdown: Shutdown::default(),
     };
 
     Ok(state)
 }
+
+/// An `AppState` keeping its tables in memory, even when the tests run against MySQL.
+pub fn initialize_memory_app_state() -> AppState {
+    AppState {
+        storage: Storage::memory().expect("the migrations run in memory"),
+        snowflake_generator: Arc::new(SnowflakeGenerator::new(1)),
+        config: Arc::new(test_config()),
+        shutdown: Shutdown::default(),
+    }
+}
+
+/// Creates a user directly in the database and returns its id along with the value of an
+/// `Authorization` header for it.
+pub fn create_test_user(state: &AppState, email: &str) -> Result<(i64, String)> {
+    let mut conn = state.storage.get_conn()?;
+    let user_id = UserQueries::create_entity(
+        &mut conn,
+        state.snowflake_generator.clone(),
+        UserQueries::new_user(
+            RequestCreateUser {
+                email: email.to_string(),
+                password: "password123".to_string(),
+                first_name: "Test".to_string(),
+                last_name: "User".to_string(),
+                date_of_birth: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                phone_number: None,
+            },
+            state.config.auth.hash_cost
+        )?
+    )?;
+    let token = create_access_token(&state.config.auth.jwt_secret, user_id)?;
+
+    Ok((user_id, format!("Bearer {}", token)))
+}
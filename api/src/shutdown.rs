@@ -0,0 +1,144 @@
+use std::{ future::Future, sync::{ Arc, Mutex }, time::Duration };
+
+use tokio::{ sync::watch, task::JoinHandle };
+
+/// Coordinates the graceful shutdown of the server.
+///
+/// Once triggered, by SIGTERM or SIGINT in `run`, the instance drains: the routes answer
+/// `503 Service Unavailable` to new requests, readiness reports the instance down, in-flight
+/// requests complete, and the background workers are told to stop and awaited.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    workers: Arc<Mutex<Vec<Worker>>>,
+}
+
+/// The name and the task of a background worker.
+type Worker = (String, JoinHandle<()>);
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            sender: Arc::new(watch::channel(false).0),
+            workers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl Shutdown {
+    /// Starts draining. Does nothing if the shutdown was already triggered.
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Completes once the shutdown is triggered.
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        // the sender lives as long as `self`, so this can't fail
+        let _ = receiver.wait_for(|draining| *draining).await;
+    }
+
+    /// Spawns a background worker, e.g. a job running on a schedule.
+    ///
+    /// `worker` gets a handle to this shutdown and must return soon after `triggered` completes,
+    /// typically by selecting on it between two units of work.
+    pub fn spawn_worker<F, Fut>(&self, name: impl Into<String>, worker: F)
+        where F: FnOnce(Shutdown) -> Fut, Fut: Future<Output = ()> + Send + 'static
+    {
+        let handle = tokio::spawn(worker(self.clone()));
+        self.workers.lock().unwrap().push((name.into(), handle));
+    }
+
+    /// Triggers the shutdown and waits up to `timeout` for the workers to return. Those still
+    /// running afterwards are aborted.
+    pub async fn stop_workers(&self, timeout: Duration) {
+        self.trigger();
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        for (name, mut handle) in workers {
+            match tokio::time::timeout_at(deadline, &mut handle).await {
+                Ok(Ok(())) => tracing::info!(worker = %name, "worker stopped"),
+                Ok(Err(error)) => tracing::error!(worker = %name, "worker failed: {}", error),
+                Err(_) => {
+                    tracing::warn!(worker = %name, "worker did not stop in time, aborting it");
+                    handle.abort();
+                }
+            }
+        }
+    }
+}
+
+/// Completes on SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c().await.expect("failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix
+            ::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv().await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => tracing::info!("SIGINT received"),
+        _ = terminate => tracing::info!("SIGTERM received"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{ AtomicBool, Ordering };
+
+    use axum::{ body::Body, http::{ header, Method, Request, StatusCode } };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::router::{ router::create_router, tests::initialize_memory_app_state };
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let state = initialize_memory_app_state();
+        let shutdown = state.shutdown.clone();
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let worker_stopped = stopped.clone();
+        shutdown.spawn_worker("ticker", |shutdown| async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(10));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.triggered() => break,
+                }
+            }
+            worker_stopped.store(true, Ordering::SeqCst);
+        });
+        shutdown.spawn_worker("stuck", |_| std::future::pending());
+
+        let request = || {
+            Request::builder().method(Method::GET).uri("/users/42").body(Body::empty()).unwrap()
+        };
+        let app = create_router(state).await;
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // the stuck worker is aborted after the timeout
+        shutdown.stop_workers(Duration::from_millis(100)).await;
+        assert!(shutdown.is_draining());
+        assert!(stopped.load(Ordering::SeqCst));
+
+        // new requests are refused while draining
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::CONNECTION], "close");
+    }
+}
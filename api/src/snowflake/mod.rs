@@ -1,11 +1,13 @@
 use chrono::Utc;
 use std::sync::Mutex;
 
+use crate::metrics::metrics;
+
 const EPOCH: i64 = 1704096000000; // Custom Epoch (2024-01-01T00:00:00Z)
 const MACHINE_ID_BITS: i64 = 5;
 const SEQUENCE_BITS: i64 = 12;
 
-const MAX_MACHINE_ID: i64 = (1 << MACHINE_ID_BITS) - 1;
+pub const MAX_MACHINE_ID: i64 = (1 << MACHINE_ID_BITS) - 1;
 const MAX_SEQUENCE: i64 = (1 << SEQUENCE_BITS) - 1;
 
 #[derive(Debug)]
@@ -27,7 +29,19 @@ impl SnowflakeGenerator {
         }
     }
 
+    pub fn machine_id(&self) -> i64 {
+        self.machine_id
+    }
+
+    /// Milliseconds since the Unix epoch at which the last ID was generated, 0 before the first.
+    ///
+    /// IDs stay unique only while the clock doesn't go back before it.
+    pub fn last_timestamp(&self) -> i64 {
+        *self.last_timestamp.lock().unwrap()
+    }
+
     pub fn generate_id(&self) -> i64 {
+        metrics().snowflake_ids.inc();
         let mut last_timestamp = self.last_timestamp.lock().unwrap();
         let mut sequence = self.sequence.lock().unwrap();
 
@@ -0,0 +1,243 @@
+use std::time::Instant;
+
+use mysql::{ prelude::FromRow, Params, Pool, PooledConn, Row, TxOpts };
+
+use crate::metrics::{ metrics, ConnectionInUse };
+
+#[cfg(test)]
+use super::memory::{ MemoryConnection, MemoryTransaction };
+
+/// Runs statements on a connection or a transaction, of either storage backend.
+///
+/// The provided methods mirror the ones of `mysql::prelude::Queryable`, so queries read the same
+/// as with the `mysql` crate. Rows failing to convert with `FromRow` are returned as
+/// `mysql::Error::FromRowError` instead of panicking.
+pub trait Queryable {
+    /// Runs a text query and returns its rows.
+    fn query_rows(&mut self, query: &str) -> mysql::Result<Vec<Row>>;
+
+    /// Runs a statement with `params` and returns its rows.
+    fn exec_rows(&mut self, query: &str, params: Params) -> mysql::Result<Vec<Row>>;
+
+    /// The number of rows the last statement changed.
+    fn affected_rows(&self) -> u64;
+
+    fn query_drop<Q: AsRef<str>>(&mut self, query: Q) -> mysql::Result<()> {
+        self.query_rows(query.as_ref()).map(drop)
+    }
+
+    fn query<T: FromRow, Q: AsRef<str>>(&mut self, query: Q) -> mysql::Result<Vec<T>> {
+        from_rows(self.query_rows(query.as_ref())?)
+    }
+
+    fn query_first<T: FromRow, Q: AsRef<str>>(&mut self, query: Q) -> mysql::Result<Option<T>> {
+        Ok(self.query(query)?.into_iter().next())
+    }
+
+    fn exec_drop<S: AsRef<str>, P: Into<Params>>(
+        &mut self,
+        statement: S,
+        params: P
+    ) -> mysql::Result<()> {
+        self.exec_rows(statement.as_ref(), params.into()).map(drop)
+    }
+
+    fn exec<T: FromRow, S: AsRef<str>, P: Into<Params>>(
+        &mut self,
+        statement: S,
+        params: P
+    ) -> mysql::Result<Vec<T>> {
+        from_rows(self.exec_rows(statement.as_ref(), params.into())?)
+    }
+
+    fn exec_first<T: FromRow, S: AsRef<str>, P: Into<Params>>(
+        &mut self,
+        statement: S,
+        params: P
+    ) -> mysql::Result<Option<T>> {
+        Ok(self.exec(statement, params)?.into_iter().next())
+    }
+
+    /// Runs a statement once for each set of parameters.
+    fn exec_batch<S, P, I>(&mut self, statement: S, params: I) -> mysql::Result<()>
+        where S: AsRef<str>, P: Into<Params>, I: IntoIterator<Item = P>
+    {
+        for params in params {
+            self.exec_rows(statement.as_ref(), params.into())?;
+        }
+        Ok(())
+    }
+}
+
+fn from_rows<T: FromRow>(rows: Vec<Row>) -> mysql::Result<Vec<T>> {
+    rows.into_iter()
+        .map(|row| T::from_row_opt(row).map_err(|error| mysql::Error::FromRowError(error.0)))
+        .collect()
+}
+
+impl Queryable for PooledConn {
+    fn query_rows(&mut self, query: &str) -> mysql::Result<Vec<Row>> {
+        mysql::prelude::Queryable::query(self, query)
+    }
+
+    fn exec_rows(&mut self, query: &str, params: Params) -> mysql::Result<Vec<Row>> {
+        mysql::prelude::Queryable::exec(self, query, params)
+    }
+
+    fn affected_rows(&self) -> u64 {
+        mysql::Conn::affected_rows(self)
+    }
+}
+
+impl Queryable for mysql::Transaction<'_> {
+    fn query_rows(&mut self, query: &str) -> mysql::Result<Vec<Row>> {
+        mysql::prelude::Queryable::query(self, query)
+    }
+
+    fn exec_rows(&mut self, query: &str, params: Params) -> mysql::Result<Vec<Row>> {
+        mysql::prelude::Queryable::exec(self, query, params)
+    }
+
+    fn affected_rows(&self) -> u64 {
+        mysql::Transaction::affected_rows(self)
+    }
+}
+
+/// A connection to the `Storage` of the application.
+#[derive(Debug)]
+pub enum Connection {
+    /// A connection of the MySQL pool, counted as in use until it is dropped
+    MySql(PooledConn, ConnectionInUse),
+    #[cfg(test)]
+    Memory(MemoryConnection),
+}
+
+impl Connection {
+    /// Takes a connection from `pool`, waiting for one to be released if it is exhausted.
+    ///
+    /// The wait and the connection in use are recorded in `metrics()`, whatever the connection is
+    /// taken for.
+    pub fn from_pool(pool: &Pool) -> mysql::Result<Connection> {
+        let metrics = metrics();
+        let start = Instant::now();
+        let conn = pool.get_conn()?;
+        metrics.db_connection_wait.observe(start.elapsed().as_secs_f64());
+        Ok(Connection::MySql(conn, metrics.connection_in_use()))
+    }
+
+    /// Starts a transaction, rolled back if it is dropped without `Transaction::commit`.
+    pub fn start_transaction(&mut self) -> mysql::Result<Transaction<'_>> {
+        match self {
+            Connection::MySql(conn, _) => {
+                Ok(Transaction::MySql(conn.start_transaction(TxOpts::default())?))
+            }
+            #[cfg(test)]
+            Connection::Memory(conn) => Ok(Transaction::Memory(conn.start_transaction()?)),
+        }
+    }
+}
+
+impl Queryable for Connection {
+    fn query_rows(&mut self, query: &str) -> mysql::Result<Vec<Row>> {
+        match self {
+            Connection::MySql(conn, _) => conn.query_rows(query),
+            #[cfg(test)]
+            Connection::Memory(conn) => conn.query_rows(query),
+        }
+    }
+
+    fn exec_rows(&mut self, query: &str, params: Params) -> mysql::Result<Vec<Row>> {
+        match self {
+            Connection::MySql(conn, _) => conn.exec_rows(query, params),
+            #[cfg(test)]
+            Connection::Memory(conn) => conn.exec_rows(query, params),
+        }
+    }
+
+    fn affected_rows(&self) -> u64 {
+        match self {
+            Connection::MySql(conn, _) => Queryable::affected_rows(conn),
+            #[cfg(test)]
+            Connection::Memory(conn) => conn.affected_rows(),
+        }
+    }
+}
+
+/// A transaction on a `Connection`.
+#[derive(Debug)]
+pub enum Transaction<'a> {
+    MySql(mysql::Transaction<'a>),
+    #[cfg(test)]
+    Memory(MemoryTransaction<'a>),
+}
+
+impl Transaction<'_> {
+    pub fn commit(self) -> mysql::Result<()> {
+        match self {
+            Transaction::MySql(tx) => tx.commit(),
+            #[cfg(test)]
+            Transaction::Memory(tx) => {
+                tx.commit();
+                Ok(())
+            }
+        }
+    }
+
+    /// Marks the current state of the transaction, which `rollback_to_savepoint` returns to.
+    ///
+    /// A savepoint replaces an older one with the same name.
+    pub fn savepoint(&mut self, name: &str) -> mysql::Result<()> {
+        match self {
+            Transaction::MySql(tx) => tx.query_drop(format!("SAVEPOINT {};", name)),
+            #[cfg(test)]
+            Transaction::Memory(tx) => {
+                tx.savepoint(name);
+                Ok(())
+            }
+        }
+    }
+
+    /// Undoes the statements run since the savepoint, without ending the transaction.
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> mysql::Result<()> {
+        match self {
+            Transaction::MySql(tx) => tx.query_drop(format!("ROLLBACK TO SAVEPOINT {};", name)),
+            #[cfg(test)]
+            Transaction::Memory(tx) => tx.rollback_to_savepoint(name),
+        }
+    }
+
+    /// Forgets the savepoint, keeping the statements run since.
+    pub fn release_savepoint(&mut self, name: &str) -> mysql::Result<()> {
+        match self {
+            Transaction::MySql(tx) => tx.query_drop(format!("RELEASE SAVEPOINT {};", name)),
+            #[cfg(test)]
+            Transaction::Memory(tx) => tx.release_savepoint(name),
+        }
+    }
+}
+
+impl Queryable for Transaction<'_> {
+    fn query_rows(&mut self, query: &str) -> mysql::Result<Vec<Row>> {
+        match self {
+            Transaction::MySql(tx) => tx.query_rows(query),
+            #[cfg(test)]
+            Transaction::Memory(tx) => tx.query_rows(query),
+        }
+    }
+
+    fn exec_rows(&mut self, query: &str, params: Params) -> mysql::Result<Vec<Row>> {
+        match self {
+            Transaction::MySql(tx) => tx.exec_rows(query, params),
+            #[cfg(test)]
+            Transaction::Memory(tx) => tx.exec_rows(query, params),
+        }
+    }
+
+    fn affected_rows(&self) -> u64 {
+        match self {
+            Transaction::MySql(tx) => Queryable::affected_rows(tx),
+            #[cfg(test)]
+            Transaction::Memory(tx) => tx.affected_rows(),
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use std::{ cmp::Ordering, collections::{ BTreeMap, BTreeSet }, sync::Arc };
+
+use chrono::NaiveDateTime;
+use mysql::Value;
+
+use super::schema::{ compare, date_value, same, sql_error, ForeignKey, OnDelete, TableSchema };
+
+/// The name `DATABASE()` returns in memory.
+pub const DATABASE_NAME: &str = "memory";
+
+/// A table kept in memory: its definition and its rows, with a value for each column.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub schema: TableSchema,
+    /// Rows in the order of the primary key, or of insertion without one
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// The tables of an in-memory database.
+///
+/// Tables are shared between clones until they change, so a statement or a transaction can work
+/// on a copy of the database and swap it in when it succeeds.
+#[derive(Debug, Clone, Default)]
+pub struct Database {
+    tables: BTreeMap<String, Arc<Table>>,
+}
+
+/// A row of a table.
+pub type RowId = (String, usize);
+
+impl Database {
+    /// The table named `name`, or the error MySQL returns for an unknown table.
+    pub fn table(&self, name: &str) -> Result<&Table, mysql::Error> {
+        self.tables
+            .get(name)
+            .map(|table| table.as_ref())
+            .ok_or_else(|| {
+                sql_error(1146, format!("Table '{}.{}' doesn't exist", DATABASE_NAME, name))
+            })
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.values().map(|table| table.as_ref())
+    }
+
+    fn table_mut(&mut self, name: &str) -> Result<&mut Table, mysql::Error> {
+        self.table(name)?;
+        Ok(Arc::make_mut(self.tables.get_mut(name).unwrap()))
+    }
+
+    /// Creates a table, returns `false` if it already exists and `if_not_exists` is set.
+    pub fn create_table(
+        &mut self,
+        schema: TableSchema,
+        if_not_exists: bool
+    ) -> Result<bool, mysql::Error> {
+        if self.tables.contains_key(&schema.name) {
+            return if if_not_exists {
+                Ok(false)
+            } else {
+                Err(sql_error(1050, format!("Table '{}' already exists", schema.name)))
+            };
+        }
+        let table = Table { schema, rows: Vec::new() };
+        self.check_foreign_keys(&table)?;
+        self.tables.insert(table.schema.name.clone(), Arc::new(table));
+        Ok(true)
+    }
+
+    /// Replaces a table with an altered version of it, after checking its rows still satisfy its
+    /// keys and foreign keys.
+    pub fn replace_table(&mut self, name: &str, table: Table) -> Result<(), mysql::Error> {
+        self.check_foreign_keys(&table)?;
+        for (index, row) in table.rows.iter().enumerate() {
+            table.check_keys(Some(index), row)?;
+            self.check_references(&table, row)?;
+        }
+        self.tables.remove(name);
+        self.tables.insert(table.schema.name.clone(), Arc::new(table));
+        Ok(())
+    }
+
+    /// Drops tables. Tables that don't exist are skipped if `if_exists` is set.
+    pub fn drop_tables(&mut self, names: &[String], if_exists: bool) -> Result<(), mysql::Error> {
+        let missing = names
+            .iter()
+            .filter(|name| !self.tables.contains_key(*name))
+            .map(|name| format!("{}.{}", DATABASE_NAME, name))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() && !if_exists {
+            return Err(sql_error(1051, format!("Unknown table '{}'", missing.join(","))));
+        }
+
+        let remaining = self.tables().filter(|table| !names.contains(&table.schema.name));
+        for table in remaining {
+            let foreign_key = table.schema.foreign_keys
+                .iter()
+                .find(|foreign_key| names.contains(&foreign_key.table));
+            if let Some(foreign_key) = foreign_key {
+                return Err(
+                    sql_error(
+                        3730,
+                        format!(
+                            "Cannot drop table '{}' referenced by a foreign key constraint '{}' on table '{}'.",
+                            foreign_key.table,
+                            foreign_key.name,
+                            table.schema.name
+                        )
+                    )
+                );
+            }
+        }
+
+        for name in names {
+            self.tables.remove(name);
+        }
+        Ok(())
+    }
+
+    /// Checks the tables the foreign keys of `table` reference exist and have the referenced
+    /// columns.
+    fn check_foreign_keys(&self, table: &Table) -> Result<(), mysql::Error> {
+        for foreign_key in &table.schema.foreign_keys {
+            let referenced = if foreign_key.table == table.schema.name {
+                &table.schema
+            } else {
+                match self.tables.get(&foreign_key.table) {
+                    Some(referenced) => &referenced.schema,
+                    None => {
+                        return Err(
+                            sql_error(
+                                1824,
+                                format!("Failed to open the referenced table '{}'", foreign_key.table)
+                            )
+                        );
+                    }
+                }
+            };
+            if
+                foreign_key.columns.len() != foreign_key.referenced_columns.len() ||
+                foreign_key.referenced_columns
+                    .iter()
+                    .any(|column| referenced.index_of(column).is_none())
+            {
+                return Err(
+                    sql_error(
+                        1822,
+                        format!(
+                            "Failed to add the foreign key constraint. Missing index for constraint '{}' in the referenced table '{}'",
+                            foreign_key.name,
+                            foreign_key.table
+                        )
+                    )
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the rows `row` of `table` references through its foreign keys exist.
+    fn check_references(&self, table: &Table, row: &[Value]) -> Result<(), mysql::Error> {
+        for foreign_key in &table.schema.foreign_keys {
+            let values = foreign_key.columns
+                .iter()
+                .map(|column| &row[table.schema.index_of(column).unwrap()])
+                .collect::<Vec<_>>();
+            if values.iter().any(|value| **value == Value::NULL) {
+                continue;
+            }
+
+            let referenced = if foreign_key.table == table.schema.name {
+                table
+            } else {
+                self.table(&foreign_key.table)?
+            };
+            let columns = foreign_key.referenced_columns
+                .iter()
+                .map(|column| referenced.schema.index_of(column).unwrap())
+                .collect::<Vec<_>>();
+            let exists = referenced.rows.iter().any(|candidate| {
+                columns
+                    .iter()
+                    .zip(&values)
+                    .all(|(index, value)| same(&candidate[*index], value))
+            });
+            if !exists {
+                return Err(
+                    sql_error(
+                        1452,
+                        format!(
+                            "Cannot add or update a child row: a foreign key constraint fails ({})",
+                            constraint_description(&table.schema.name, foreign_key)
+                        )
+                    )
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts a row, `values` holding the value of each column of `columns`. The other columns
+    /// get their default.
+    pub fn insert(
+        &mut self,
+        table_name: &str,
+        columns: &[usize],
+        values: Vec<Value>,
+        now: NaiveDateTime
+    ) -> Result<(), mysql::Error> {
+        let table = self.table(table_name)?;
+        let mut row = Vec::with_capacity(table.schema.columns.len());
+        for (index, column) in table.schema.columns.iter().enumerate() {
+            let value = match columns.iter().rposition(|position| *position == index) {
+                Some(position) => column.coerce(values[position].clone())?,
+                None if column.default_now => date_value(now),
+                None =>
+                    column.default.clone().ok_or_else(|| {
+                        sql_error(1364, format!("Field '{}' doesn't have a default value", column.name))
+                    })?,
+            };
+            row.push(value);
+        }
+
+        table.check_keys(None, &row)?;
+        self.check_references(table, &row)?;
+
+        let table = self.table_mut(table_name)?;
+        let position = table.position_of(&row);
+        table.rows.insert(position, row);
+        Ok(())
+    }
+
+    /// Updates rows, each change holding the position of a row and the new value of some of its
+    /// columns. Returns the number of rows that changed.
+    pub fn update(
+        &mut self,
+        table_name: &str,
+        changes: Vec<(usize, Vec<(usize, Value)>)>,
+        now: NaiveDateTime
+    ) -> Result<u64, mysql::Error> {
+        let mut changed_rows = 0;
+        let mut reorder = false;
+
+        for (row_index, assignments) in changes {
+            let table = self.table(table_name)?;
+            let old_row = &table.rows[row_index];
+            let mut row = old_row.clone();
+            for (column, value) in assignments.iter() {
+                row[*column] = table.schema.columns[*column].coerce(value.clone())?;
+            }
+            let changed = row
+                .iter()
+                .zip(old_row)
+                .any(|(new, old)| new != old);
+            if !changed {
+                continue;
+            }
+            for (index, column) in table.schema.columns.iter().enumerate() {
+                if column.update_now && !assignments.iter().any(|(assigned, _)| *assigned == index) {
+                    row[index] = date_value(now);
+                }
+            }
+
+            table.check_keys(Some(row_index), &row)?;
+            self.check_references(table, &row)?;
+            self.check_referenced_update(table_name, old_row, &row)?;
+            if let Some(key) = table.schema.primary_key() {
+                reorder |= key.columns.iter().any(|column| {
+                    let index = table.schema.index_of(column).unwrap();
+                    row[index] != old_row[index]
+                });
+            }
+
+            self.table_mut(table_name)?.rows[row_index] = row;
+            changed_rows += 1;
+        }
+
+        if reorder {
+            let table = self.table_mut(table_name)?;
+            let mut rows = std::mem::take(&mut table.rows);
+            rows.sort_by(|a, b| table.compare_primary_keys(a, b));
+            table.rows = rows;
+        }
+        Ok(changed_rows)
+    }
+
+    /// Rejects an update changing a value rows of other tables reference.
+    fn check_referenced_update(
+        &self,
+        table_name: &str,
+        old_row: &[Value],
+        row: &[Value]
+    ) -> Result<(), mysql::Error> {
+        let schema = &self.table(table_name)?.schema;
+        for (child, foreign_key) in self.references_to(table_name) {
+            let referenced = foreign_key.referenced_columns
+                .iter()
+                .map(|column| schema.index_of(column).unwrap())
+                .collect::<Vec<_>>();
+            if referenced.iter().all(|index| old_row[*index] == row[*index]) {
+                continue;
+            }
+            let columns = foreign_key.columns
+                .iter()
+                .map(|column| child.schema.index_of(column).unwrap())
+                .collect::<Vec<_>>();
+            let referencing = child.rows.iter().any(|child_row| {
+                columns
+                    .iter()
+                    .zip(&referenced)
+                    .all(|(column, index)| same(&child_row[*column], &old_row[*index]))
+            });
+            if referencing {
+                return Err(parent_row_error(&child.schema.name, foreign_key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes rows of a table, with the rows referencing them through a foreign key `ON DELETE
+    /// CASCADE`. Returns the number of rows deleted from the table.
+    ///
+    /// Rows referencing a deleted row through a foreign key `ON DELETE SET NULL` get `NULL`, and a
+    /// foreign key without an action rejects the delete.
+    pub fn delete(&mut self, table_name: &str, rows: &[usize]) -> Result<u64, mysql::Error> {
+        let mut deleted: BTreeSet<RowId> = rows
+            .iter()
+            .map(|row| (table_name.to_string(), *row))
+            .collect();
+        let mut set_null: BTreeMap<RowId, Vec<usize>> = BTreeMap::new();
+
+        let mut pending = deleted.iter().cloned().collect::<Vec<_>>();
+        while let Some((parent_name, parent_row)) = pending.pop() {
+            let parent = self.table(&parent_name)?;
+            let parent_row = &parent.rows[parent_row];
+            for (child, foreign_key) in self.references_to(&parent_name) {
+                let referenced = foreign_key.referenced_columns
+                    .iter()
+                    .map(|column| parent.schema.index_of(column).unwrap())
+                    .collect::<Vec<_>>();
+                let columns = foreign_key.columns
+                    .iter()
+                    .map(|column| child.schema.index_of(column).unwrap())
+                    .collect::<Vec<_>>();
+
+                for (child_index, child_row) in child.rows.iter().enumerate() {
+                    let references = columns
+                        .iter()
+                        .zip(&referenced)
+                        .all(|(column, index)| {
+                            compare(&child_row[*column], &parent_row[*index]) ==
+                                Some(Ordering::Equal)
+                        });
+                    if !references {
+                        continue;
+                    }
+                    let row_id = (child.schema.name.clone(), child_index);
+                    match foreign_key.on_delete {
+                        OnDelete::CASCADE => {
+                            if deleted.insert(row_id.clone()) {
+                                pending.push(row_id);
+                            }
+                        }
+                        OnDelete::SET_NULL => {
+                            set_null.entry(row_id).or_default().extend(columns.iter().copied());
+                        }
+                        OnDelete::RESTRICT => {
+                            if !deleted.contains(&row_id) {
+                                return Err(parent_row_error(&child.schema.name, foreign_key));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for ((table_name, row_index), columns) in set_null {
+            if deleted.contains(&(table_name.clone(), row_index)) {
+                continue;
+            }
+            let table = self.table_mut(&table_name)?;
+            for column in columns {
+                if !table.schema.columns[column].nullable {
+                    return Err(sql_error(1048, format!("Column '{}' cannot be null", table.schema.columns[column].name)));
+                }
+                table.rows[row_index][column] = Value::NULL;
+            }
+        }
+
+        // remove from the end so the positions of the rows still to remove don't move
+        for (table_name, row_index) in deleted.iter().rev() {
+            self.table_mut(table_name)?.rows.remove(*row_index);
+        }
+
+        Ok(rows.len() as u64)
+    }
+
+    /// The tables with a foreign key referencing `table_name`, with the foreign key.
+    fn references_to<'a>(
+        &'a self,
+        table_name: &'a str
+    ) -> impl Iterator<Item = (&'a Table, &'a ForeignKey)> + 'a {
+        self.tables().flat_map(move |table| {
+            table.schema.foreign_keys
+                .iter()
+                .filter(move |foreign_key| foreign_key.table == table_name)
+                .map(move |foreign_key| (table, foreign_key))
+        })
+    }
+}
+
+impl Table {
+    /// Rejects `row` if it repeats the values of a key of another row, with the error MySQL
+    /// returns. `row_index` is the position of the row when it is already in the table.
+    fn check_keys(&self, row_index: Option<usize>, row: &[Value]) -> Result<(), mysql::Error> {
+        for key in &self.schema.keys {
+            let columns = key.columns
+                .iter()
+                .map(|column| self.schema.index_of(column).unwrap())
+                .collect::<Vec<_>>();
+            if columns.iter().any(|index| row[*index] == Value::NULL) {
+                continue;
+            }
+            let duplicate = self.rows
+                .iter()
+                .enumerate()
+                .any(|(index, other)| {
+                    Some(index) != row_index &&
+                        columns.iter().all(|column| same(&other[*column], &row[*column]))
+                });
+            if duplicate {
+                let entry = columns
+                    .iter()
+                    .map(|index| self.schema.columns[*index].display(&row[*index]))
+                    .collect::<Vec<_>>()
+                    .join("-");
+                return Err(
+                    sql_error(
+                        1062,
+                        format!(
+                            "Duplicate entry '{}' for key '{}.{}'",
+                            entry,
+                            self.schema.name,
+                            key.name
+                        )
+                    )
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Where `row` goes to keep the rows in the order of the primary key.
+    fn position_of(&self, row: &[Value]) -> usize {
+        if self.schema.primary_key().is_none() {
+            return self.rows.len();
+        }
+        self.rows.partition_point(|other| self.compare_primary_keys(other, row) == Ordering::Less)
+    }
+
+    fn compare_primary_keys(&self, a: &[Value], b: &[Value]) -> Ordering {
+        let Some(key) = self.schema.primary_key() else {
+            return Ordering::Equal;
+        };
+        key.columns
+            .iter()
+            .map(|column| self.schema.index_of(column).unwrap())
+            .map(|index| compare(&a[index], &b[index]).unwrap_or(Ordering::Equal))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+fn constraint_description(table: &str, foreign_key: &ForeignKey) -> String {
+    let quoted = |columns: &[String]| {
+        columns
+            .iter()
+            .map(|column| format!("`{}`", column))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let on_delete = match foreign_key.on_delete {
+        OnDelete::CASCADE => " ON DELETE CASCADE",
+        OnDelete::SET_NULL => " ON DELETE SET NULL",
+        OnDelete::RESTRICT => "",
+    };
+    format!(
+        "`{}`.`{}`, CONSTRAINT `{}` FOREIGN KEY ({}) REFERENCES `{}` ({}){}",
+        DATABASE_NAME,
+        table,
+        foreign_key.name,
+        quoted(&foreign_key.columns),
+        foreign_key.table,
+        quoted(&foreign_key.referenced_columns),
+        on_delete
+    )
+}
+
+fn parent_row_error(child: &str, foreign_key: &ForeignKey) -> mysql::Error {
+    sql_error(
+        1451,
+        format!(
+            "Cannot delete or update a parent row: a foreign key constraint fails ({})",
+            constraint_description(child, foreign_key)
+        )
+    )
+}
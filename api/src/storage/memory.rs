@@ -0,0 +1,415 @@
+use std::{ sync::{ Arc, Mutex, MutexGuard }, thread::{ self, ThreadId } };
+
+use mysql::{ Params, Row };
+
+use super::{ connection::Queryable, database::Database, schema::sql_error, sql::execute };
+
+/// Tables kept in memory, for tests that shouldn't need a MySQL server.
+///
+/// The statements the application sends to MySQL run on it unchanged, see `sql::execute`, so
+/// every query, migration and route works the same on both. Each statement is atomic, and a
+/// transaction holds the store until it is committed or rolled back: transactions run one after
+/// the other, as if every row they read was locked `FOR UPDATE`.
+///
+/// Clones share the same tables.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    database: Arc<Mutex<Database>>,
+    /// The thread of the transaction holding the store, if any
+    tx_thread: Arc<Mutex<Option<ThreadId>>>,
+}
+
+impl MemoryStore {
+    /// A connection to the store.
+    pub fn get_conn(&self) -> MemoryConnection {
+        MemoryConnection { store: self.clone(), affected_rows: 0 }
+    }
+
+    /// Waits for the store, failing at once if a transaction of this thread holds it, as it
+    /// would never be released.
+    fn lock(&self) -> mysql::Result<MutexGuard<'_, Database>> {
+        if *self.tx_thread() == Some(thread::current().id()) {
+            return Err(
+                sql_error(
+                    1205,
+                    "Lock wait timeout exceeded; a transaction of this thread holds the store"
+                )
+            );
+        }
+
+        // a statement panicking leaves the tables as they were, they can still be used
+        Ok(self.database.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    fn tx_thread(&self) -> MutexGuard<'_, Option<ThreadId>> {
+        self.tx_thread.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A connection to a `MemoryStore`.
+#[derive(Debug)]
+pub struct MemoryConnection {
+    store: MemoryStore,
+    affected_rows: u64,
+}
+
+impl MemoryConnection {
+    /// Starts a transaction, which holds the store until it is dropped.
+    ///
+    /// Statements of other connections wait for the transaction to end. On the thread running
+    /// the transaction, where the wait would never end, they fail with the lock wait timeout
+    /// error of MySQL instead: a transaction must run all of its statements itself.
+    pub fn start_transaction(&mut self) -> mysql::Result<MemoryTransaction<'_>> {
+        let store = &self.store;
+        let guard = store.lock()?;
+        *store.tx_thread() = Some(thread::current().id());
+        let working = guard.clone();
+        Ok(MemoryTransaction { store, guard, working, savepoints: Vec::new(), affected_rows: 0 })
+    }
+}
+
+impl Queryable for MemoryConnection {
+    fn query_rows(&mut self, query: &str) -> mysql::Result<Vec<Row>> {
+        self.exec_rows(query, Params::Empty)
+    }
+
+    fn exec_rows(&mut self, query: &str, params: Params) -> mysql::Result<Vec<Row>> {
+        let mut database = self.store.lock()?;
+        let (rows, affected_rows) = run(&mut database, query, params)?;
+        self.affected_rows = affected_rows;
+        Ok(rows)
+    }
+
+    fn affected_rows(&self) -> u64 {
+        self.affected_rows
+    }
+}
+
+/// A transaction on a `MemoryStore`: its statements change a copy of the tables, which replaces
+/// them on `commit`. Dropping it without committing rolls it back.
+#[derive(Debug)]
+pub struct MemoryTransaction<'a> {
+    store: &'a MemoryStore,
+    guard: MutexGuard<'a, Database>,
+    working: Database,
+    /// The tables as they were at each savepoint, oldest first
+    savepoints: Vec<(String, Database)>,
+    affected_rows: u64,
+}
+
+impl MemoryTransaction<'_> {
+    pub fn commit(mut self) {
+        *self.guard = std::mem::take(&mut self.working);
+    }
+
+    /// Like MySQL, a savepoint replaces an older one with the same name.
+    pub fn savepoint(&mut self, name: &str) {
+        self.savepoints.retain(|(savepoint, _)| savepoint != name);
+        self.savepoints.push((name.to_string(), self.working.clone()));
+    }
+
+    /// Undoes the statements run since the savepoint, which is kept, and drops the later ones.
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> mysql::Result<()> {
+        let index = self.savepoint_index(name)?;
+        self.savepoints.truncate(index + 1);
+        self.working = self.savepoints[index].1.clone();
+        Ok(())
+    }
+
+    /// Drops the savepoint and the later ones, keeping the statements run since.
+    pub fn release_savepoint(&mut self, name: &str) -> mysql::Result<()> {
+        let index = self.savepoint_index(name)?;
+        self.savepoints.truncate(index);
+        Ok(())
+    }
+
+    fn savepoint_index(&self, name: &str) -> mysql::Result<usize> {
+        self.savepoints
+            .iter()
+            .position(|(savepoint, _)| savepoint == name)
+            .ok_or_else(|| sql_error(1305, format!("SAVEPOINT {} does not exist", name)))
+    }
+}
+
+impl Drop for MemoryTransaction<'_> {
+    fn drop(&mut self) {
+        // the store is released with the guard, right after
+        *self.store.tx_thread() = None;
+    }
+}
+
+impl Queryable for MemoryTransaction<'_> {
+    fn query_rows(&mut self, query: &str) -> mysql::Result<Vec<Row>> {
+        self.exec_rows(query, Params::Empty)
+    }
+
+    fn exec_rows(&mut self, query: &str, params: Params) -> mysql::Result<Vec<Row>> {
+        let (rows, affected_rows) = run(&mut self.working, query, params)?;
+        self.affected_rows = affected_rows;
+        Ok(rows)
+    }
+
+    fn affected_rows(&self) -> u64 {
+        self.affected_rows
+    }
+}
+
+/// Runs a statement on a copy of `database`, which replaces it if the statement succeeds.
+fn run(database: &mut Database, query: &str, params: Params) -> mysql::Result<(Vec<Row>, u64)> {
+    let mut copy = database.clone();
+    let output = execute(&mut copy, query, params)?;
+    *database = copy;
+    let affected_rows = output.affected_rows;
+    Ok((output.into_rows(), affected_rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ collections::HashMap, sync::Arc };
+
+    use chrono::NaiveDate;
+    use mysql::params;
+
+    use super::*;
+    use crate::{
+        models::{
+            org_job::{ OrgJob, RequestCreateOrgJob, RequestUpdateOrgJob },
+            organization::RequestCreateOrganization,
+            pagination::{ ListQuery, ListQueryError, ListScope },
+            result::Result,
+            schedule::work_schedule::RequestCreateWorkSchedule,
+            user::RequestCreateUser,
+        },
+        queries::{
+            org_job::OrgJobQueries,
+            org_member::OrgMemberQueries,
+            organization::OrgQueries,
+            schedule::work_schedule::WorkScheduleQueries,
+            user::UserQueries,
+        },
+        snowflake::SnowflakeGenerator,
+        storage::{ Repository, Storage },
+        tests::TEST_HASH_COST,
+    };
+
+    fn create_job_dto(org_id: i64, name: &str) -> RequestCreateOrgJob {
+        RequestCreateOrgJob {
+            org_id,
+            name: name.to_string(),
+            description: None,
+            base_pay_rate: 20.0,
+            color: None,
+        }
+    }
+
+    fn list_query(params: &[(&str, &str)]) -> ListQuery {
+        let params = params
+            .iter()
+            .map(|(param, value)| (param.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        ListQuery::from_params(params).unwrap()
+    }
+
+    /// Creates a user and an organization they own, and returns the ID of the organization.
+    async fn create_org(
+        storage: &Storage,
+        snowflake_generator: &Arc<SnowflakeGenerator>,
+        name: &str
+    ) -> Result<i64> {
+        let owner = UserQueries::new_user(RequestCreateUser {
+            email: format!("{}@example.com", name.to_lowercase()),
+            password: "password123".to_string(),
+            first_name: name.to_string(),
+            last_name: "Owner".to_string(),
+            date_of_birth: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            phone_number: None,
+        }, TEST_HASH_COST)?;
+        let owner_id = storage.create::<UserQueries>(snowflake_generator.clone(), owner).await?;
+        storage.create::<OrgQueries>(snowflake_generator.clone(), RequestCreateOrganization {
+            name: name.to_string(),
+            description: None,
+            owner_id,
+            timezone: None,
+            icon: None,
+        }).await
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_crud() -> Result<()> {
+        let storage = Storage::memory()?;
+        let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
+        let org_id = create_org(&storage, &snowflake_generator, "Bakery").await?;
+
+        // the post-processing of the organization ran with the insert
+        let members = storage.get_conn()?.query::<(i64, String), _>(
+            format!("SELECT user_id, role FROM org_members WHERE org_id = {};", org_id)
+        )?;
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].1, "OWNER");
+
+        let job_id = storage.create::<OrgJobQueries>(
+            snowflake_generator.clone(),
+            create_job_dto(org_id, "Cashier")
+        ).await?;
+        let job: OrgJob = storage.find_by_id::<OrgJobQueries>(job_id).await?;
+        assert_eq!(job.id, job_id);
+        assert_eq!(job.name, "Cashier");
+        assert_eq!(job.description, None);
+        assert_eq!(job.base_pay_rate, 20.0);
+
+        let update_dto = || RequestUpdateOrgJob {
+            description: Some("Runs the register".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(storage.update::<OrgJobQueries>(job_id, update_dto()).await?, 1);
+        // nothing changes the second time
+        assert_eq!(storage.update::<OrgJobQueries>(job_id, update_dto()).await?, 0);
+        let job: OrgJob = storage.find_by_id::<OrgJobQueries>(job_id).await?;
+        assert_eq!(job.description, Some("Runs the register".to_string()));
+
+        // clones share the tables
+        assert_eq!(storage.clone().delete::<OrgJobQueries>(job_id).await?, 1);
+        assert_eq!(storage.delete::<OrgJobQueries>(job_id).await?, 0);
+        assert!(storage.find_by_id::<OrgJobQueries>(job_id).await.is_err());
+
+        // columns without a value take their default
+        let schedule_dto = RequestCreateWorkSchedule {
+            org_id,
+            start_date: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2024, 3, 10).unwrap(),
+        };
+        let schedule_id = storage.create::<WorkScheduleQueries>(
+            snowflake_generator.clone(),
+            schedule_dto
+        ).await?;
+        let schedule = storage.find_by_id::<WorkScheduleQueries>(schedule_id).await?;
+        assert!(!schedule.published);
+
+        // foreign keys are enforced, with the errors of MySQL
+        let error = storage
+            .create::<OrgJobQueries>(snowflake_generator.clone(), create_job_dto(42, "Cook")).await
+            .unwrap_err();
+        assert!(
+            matches!(
+                error.downcast_ref::<mysql::Error>(),
+                Some(mysql::Error::MySqlError(error)) if error.code == 1452
+            )
+        );
+
+        // deleting the organization cascades to its members and schedules
+        assert_eq!(storage.delete::<OrgQueries>(org_id).await?, 1);
+        let mut conn = storage.get_conn()?;
+        assert_eq!(OrgMemberQueries::find_role(&mut conn, org_id, members[0].0)?, None);
+        assert!(storage.find_by_id::<WorkScheduleQueries>(schedule_id).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_pages() -> Result<()> {
+        let storage = Storage::memory()?;
+        let snowflake_generator = Arc::new(SnowflakeGenerator::new(1));
+        let org_id = create_org(&storage, &snowflake_generator, "Bakery").await?;
+        let other_org_id = create_org(&storage, &snowflake_generator, "Diner").await?;
+
+        let names = ["Barista", "Cashier", "Cook", "Host", "Server"];
+        for name in names {
+            let create_dto = create_job_dto(org_id, name);
+            storage.create::<OrgJobQueries>(snowflake_generator.clone(), create_dto).await?;
+        }
+        let create_dto = create_job_dto(other_org_id, "Cook");
+        storage.create::<OrgJobQueries>(snowflake_generator.clone(), create_dto).await?;
+
+        // page through the jobs of the first organization by name, two at a time
+        let mut paged_names = vec![];
+        let mut cursor: Option<i64> = None;
+        loop {
+            let mut params = vec![("sort", "name".to_string()), ("order", "desc".to_string())];
+            params.push(("limit", "2".to_string()));
+            if let Some(cursor) = cursor {
+                params.push(("cursor", cursor.to_string()));
+            }
+            let params = params
+                .iter()
+                .map(|(param, value)| (*param, value.as_str()))
+                .collect::<Vec<_>>();
+            let page = storage.find_page::<OrgJobQueries>(
+                OrgJobQueries::org_scope(org_id),
+                list_query(&params)
+            ).await?;
+            // with the placeholder job of the organization
+            assert_eq!(page.total, 6);
+            paged_names.extend(page.data.into_iter().map(|job| job.name));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(paged_names, vec!["Server", "Host", "Dummy", "Cook", "Cashier", "Barista"]);
+
+        let page = storage.find_page::<OrgJobQueries>(
+            ListScope::all(),
+            list_query(&[("name", "cook")])
+        ).await?;
+        assert_eq!(page.total, 2);
+
+        // the same checks as MySQL apply to sorts and filters
+        let unknown_filter = storage.find_page::<OrgJobQueries>(
+            ListScope::all(),
+            list_query(&[("base_pay_rate", "20")])
+        ).await;
+        assert!(unknown_filter.unwrap_err().downcast_ref::<ListQueryError>().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_transactions() -> Result<()> {
+        let store = MemoryStore::default();
+        let mut conn = store.get_conn();
+        conn.query_drop(
+            "CREATE TABLE counters (
+                id BIGINT NOT NULL PRIMARY KEY,
+                name VARCHAR(20) NOT NULL UNIQUE,
+                value INT NOT NULL DEFAULT 0
+            );"
+        )?;
+
+        // a statement failing changes nothing
+        let error = conn
+            .query_drop("INSERT INTO counters (id, name) VALUES (1, 'a'), (2, 'a');")
+            .unwrap_err();
+        assert!(matches!(error, mysql::Error::MySqlError(ref error) if error.code == 1062));
+        assert_eq!(conn.query_first::<i64, _>("SELECT COUNT(*) FROM counters;")?, Some(0));
+
+        conn.exec_drop(
+            "INSERT INTO counters (id, name) VALUES (:id, :name);",
+            params! { "id" => 1, "name" => "a" }
+        )?;
+        assert_eq!(conn.affected_rows(), 1);
+
+        // a transaction dropped without committing is rolled back
+        {
+            let mut tx = conn.start_transaction()?;
+            tx.query_drop("UPDATE counters SET value = value + 1;")?;
+            assert_eq!(tx.affected_rows(), 1);
+            assert_eq!(tx.query_first::<i64, _>("SELECT value FROM counters;")?, Some(1));
+        }
+        assert_eq!(conn.query_first::<i64, _>("SELECT value FROM counters;")?, Some(0));
+
+        // other connections see the changes of a transaction once it is committed
+        let mut tx = conn.start_transaction()?;
+        tx.exec_drop("UPDATE counters SET value = ? WHERE id = ?;", (5, 1))?;
+        assert!(tx.query_drop("INSERT INTO counters (id, name) VALUES (2, 'a');").is_err());
+        // on the thread of the transaction, where they would wait forever, they fail instead
+        let mut other = store.get_conn();
+        let error = other.query_drop("SELECT value FROM counters;").unwrap_err();
+        assert!(matches!(error, mysql::Error::MySqlError(ref error) if error.code == 1205));
+        assert!(other.start_transaction().is_err());
+        tx.commit();
+        assert_eq!(other.query_first::<i64, _>("SELECT value FROM counters;")?, Some(5));
+        assert_eq!(other.query_first::<i64, _>("SELECT COUNT(*) FROM counters;")?, Some(1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+pub mod connection;
+// the in-memory storage of the tests, see `Storage::Memory`
+#[cfg(test)]
+pub mod database;
+#[cfg(test)]
+pub mod memory;
+#[cfg(test)]
+pub mod schema;
+#[cfg(test)]
+pub mod sql;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mysql::Pool;
+
+use crate::{
+    models::{ result::Result, pagination::{ ListQuery, ListScope }, ResponseDataPage },
+    prototypes::basic_queries::BasicQueries,
+    snowflake::SnowflakeGenerator,
+    utilities::blocking::with_conn,
+};
+
+#[cfg(test)]
+use crate::migrations::migrate_up;
+#[cfg(test)]
+use self::memory::MemoryStore;
+
+pub use self::connection::{ Connection, Queryable, Transaction };
+
+/// The `Repository` trait runs the operations of `BasicQueries` against a storage backend.
+///
+/// Handlers go through a repository, usually the `Storage` of the `AppState`, for the operations
+/// that don't need a connection of their own.
+#[async_trait]
+pub trait Repository: Send + Sync {
+    /// Creates an entity and returns its Snowflake ID, see `BasicQueries::create_entity`.
+    async fn create<Q: BasicQueries>(
+        &self,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dto: Q::CreateDto
+    ) -> Result<i64>;
+
+    /// Retrieves an entity by its ID, see `BasicQueries::find_by_id`.
+    async fn find_by_id<Q: BasicQueries>(&self, id: i64) -> Result<Q::Model>;
+
+    /// Retrieves a page of entities, see `BasicQueries::find_page`.
+    async fn find_page<Q: BasicQueries>(
+        &self,
+        scope: ListScope,
+        list_query: ListQuery
+    ) -> Result<ResponseDataPage<Q::Model>>;
+
+    /// Updates an entity and returns the number of affected rows, see
+    /// `BasicQueries::update_entity`.
+    async fn update<Q: BasicQueries>(&self, id: i64, update_dto: Q::UpdateDto) -> Result<u64>;
+
+    /// Deletes an entity and returns the number of affected rows, see
+    /// `BasicQueries::delete_entity`.
+    async fn delete<Q: BasicQueries>(&self, id: i64) -> Result<u64>;
+}
+
+/// Where the application keeps its tables.
+#[derive(Debug, Clone)]
+pub enum Storage {
+    /// A MySQL database.
+    MySql(Pool),
+    /// Tables kept in memory, for tests. The same statements run on it as on MySQL.
+    ///
+    /// Only compiled in tests, like the SQL engine behind it.
+    #[cfg(test)]
+    Memory(MemoryStore),
+}
+
+impl Storage {
+    /// An in-memory storage with every migration applied.
+    #[cfg(test)]
+    pub fn memory() -> Result<Self> {
+        let storage = Storage::Memory(MemoryStore::default());
+        migrate_up(&mut storage.get_conn()?, None)?;
+        Ok(storage)
+    }
+
+    /// The name of the backend, as reported by the health checks.
+    pub fn backend(&self) -> &'static str {
+        match self {
+            Storage::MySql(_) => "mysql",
+            #[cfg(test)]
+            Storage::Memory(_) => "memory",
+        }
+    }
+
+    /// Takes a connection, waiting for one to be released if the MySQL pool is exhausted.
+    pub fn get_conn(&self) -> mysql::Result<Connection> {
+        match self {
+            Storage::MySql(pool) => Connection::from_pool(pool),
+            #[cfg(test)]
+            Storage::Memory(store) => Ok(Connection::Memory(store.get_conn())),
+        }
+    }
+}
+
+/// Every operation runs on the blocking thread pool with a connection of the storage.
+#[async_trait]
+impl Repository for Storage {
+    async fn create<Q: BasicQueries>(
+        &self,
+        snowflake_generator: Arc<SnowflakeGenerator>,
+        create_dto: Q::CreateDto
+    ) -> Result<i64> {
+        with_conn(self, move |conn| Q::create_entity(conn, snowflake_generator, create_dto)).await
+    }
+
+    async fn find_by_id<Q: BasicQueries>(&self, id: i64) -> Result<Q::Model> {
+        with_conn(self, move |conn| Q::find_by_id(conn, id)).await
+    }
+
+    async fn find_page<Q: BasicQueries>(
+        &self,
+        scope: ListScope,
+        list_query: ListQuery
+    ) -> Result<ResponseDataPage<Q::Model>> {
+        with_conn(self, move |conn| Q::find_page(conn, &scope, &list_query)).await
+    }
+
+    async fn update<Q: BasicQueries>(&self, id: i64, update_dto: Q::UpdateDto) -> Result<u64> {
+        with_conn(self, move |conn| Q::update_entity(conn, id, update_dto)).await
+    }
+
+    async fn delete<Q: BasicQueries>(&self, id: i64) -> Result<u64> {
+        with_conn(self, move |conn| Q::delete_entity(conn, id)).await
+    }
+}
@@ -0,0 +1,1040 @@
+use std::cmp::Ordering;
+
+use chrono::{ Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike };
+use mysql::{ MySqlError, Value };
+use sqlparser::ast::{
+    CharacterLength,
+    ColumnOption,
+    CreateTable,
+    DataType,
+    EnumMember,
+    Expr,
+    FunctionArguments,
+    Ident,
+    ObjectName,
+    ReferentialAction,
+    TableConstraint,
+    UnaryOperator,
+    Value as SqlValue,
+};
+
+/// The error MySQL returns with `code`, with the SQLSTATE it comes with.
+pub fn sql_error(code: u16, message: impl Into<String>) -> mysql::Error {
+    let state = match code {
+        1048 | 1052 | 1062 | 1451 | 1452 => "23000",
+        1050 => "42S01",
+        1051 | 1146 => "42S02",
+        1054 => "42S22",
+        1060 => "42S21",
+        1064 | 1091 | 1111 | 1235 | 1305 => "42000",
+        1136 => "21S01",
+        1241 | 1242 => "21000",
+        1265 => "01000",
+        1292 => "22007",
+        1406 => "22001",
+        _ => "HY000",
+    };
+    mysql::Error::MySqlError(MySqlError {
+        state: state.to_string(),
+        code,
+        message: message.into(),
+    })
+}
+
+/// How the in-memory storage keeps the values of a column, following its SQL type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnKind {
+    /// `BIGINT`, `INT` and `BOOLEAN`, kept as `Value::Int`
+    INTEGER,
+    /// `FLOAT`, kept as `Value::Float`
+    FLOAT,
+    /// `DOUBLE` and `DECIMAL`, kept as `Value::Double`
+    DOUBLE,
+    /// `DATE`, kept as a `Value::Date` at midnight
+    DATE,
+    /// `DATETIME` and `TIMESTAMP`, kept as `Value::Date`
+    DATETIME,
+    /// `TIME`, kept as `Value::Time`
+    TIME,
+    /// `VARCHAR`, `CHAR` and `TEXT`, kept as `Value::Bytes` of at most this many characters
+    TEXT(usize),
+    /// `ENUM`, kept as `Value::Bytes` holding one of the values, as spelled in the definition
+    ENUM(Vec<String>),
+}
+
+/// A column of a table, read from its definition in `CREATE TABLE` or `ALTER TABLE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub kind: ColumnKind,
+    /// The type as `information_schema.COLUMNS` reports it in `DATA_TYPE`, e.g. `varchar`
+    pub data_type: String,
+    /// The type as `information_schema.COLUMNS` reports it in `COLUMN_TYPE`, e.g. `varchar(100)`
+    pub column_type: String,
+    pub nullable: bool,
+    /// Value of the column when an insert leaves it out, `None` if the insert must set it
+    pub default: Option<Value>,
+    /// `DEFAULT CURRENT_TIMESTAMP`
+    pub default_now: bool,
+    /// `ON UPDATE CURRENT_TIMESTAMP`
+    pub update_now: bool,
+}
+
+/// A primary or unique key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    /// `PRIMARY` for the primary key
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+/// What deleting a referenced row does to the rows referencing it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum OnDelete {
+    RESTRICT,
+    CASCADE,
+    SET_NULL,
+}
+
+/// A foreign key of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    /// The name of the constraint, `<table>_ibfk_<n>` unless the definition names it
+    pub name: String,
+    pub columns: Vec<String>,
+    /// The referenced table
+    pub table: String,
+    pub referenced_columns: Vec<String>,
+    pub on_delete: OnDelete,
+}
+
+/// The definition of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnSchema>,
+    /// The primary key, first if the table has one, and the unique keys
+    pub keys: Vec<Key>,
+    pub foreign_keys: Vec<ForeignKey>,
+}
+
+impl TableSchema {
+    /// Reads the definition of a table from its `CREATE TABLE` statement.
+    ///
+    /// Indexes that aren't unique are skipped, they don't change what the table accepts.
+    pub fn from_create_table(create_table: &CreateTable) -> Result<Self, mysql::Error> {
+        let mut schema = TableSchema {
+            name: object_name(&create_table.name),
+            columns: Vec::new(),
+            keys: Vec::new(),
+            foreign_keys: Vec::new(),
+        };
+
+        for definition in &create_table.columns {
+            let options = definition.options.iter().map(|option| &option.option);
+            let column = ColumnSchema::new(&definition.name, &definition.data_type, options.clone())?;
+            schema.add_column(column, None)?;
+            schema.add_column_constraints(&definition.name.value, options)?;
+        }
+        for constraint in &create_table.constraints {
+            schema.add_constraint(constraint)?;
+        }
+
+        Ok(schema)
+    }
+
+    /// Position of a column in the rows of the table.
+    pub fn index_of(&self, column: &str) -> Option<usize> {
+        self.columns.iter().position(|schema| schema.name.eq_ignore_ascii_case(column))
+    }
+
+    /// Position of a column in the rows of the table, or the error MySQL returns for an unknown
+    /// column.
+    pub fn column_index(&self, column: &str) -> Result<usize, mysql::Error> {
+        self.index_of(column).ok_or_else(|| {
+            sql_error(1054, format!("Unknown column '{}' in 'field list'", column))
+        })
+    }
+
+    /// The primary key, if the table has one.
+    pub fn primary_key(&self) -> Option<&Key> {
+        self.keys.first().filter(|key| key.name == "PRIMARY")
+    }
+
+    /// Adds a column, at the end or after the column at `position - 1`.
+    pub fn add_column(
+        &mut self,
+        column: ColumnSchema,
+        position: Option<usize>
+    ) -> Result<(), mysql::Error> {
+        if self.index_of(&column.name).is_some() {
+            return Err(sql_error(1060, format!("Duplicate column name '{}'", column.name)));
+        }
+        let position = position.unwrap_or(self.columns.len());
+        self.columns.insert(position, column);
+        Ok(())
+    }
+
+    /// Adds the keys and foreign keys a column defines with its attributes, e.g. `UNIQUE`.
+    pub fn add_column_constraints<'a>(
+        &mut self,
+        column: &str,
+        options: impl IntoIterator<Item = &'a ColumnOption>
+    ) -> Result<(), mysql::Error> {
+        for option in options {
+            match option {
+                ColumnOption::Unique { is_primary: true, .. } => {
+                    self.add_key("PRIMARY".to_string(), vec![column.to_string()])?;
+                }
+                ColumnOption::Unique { is_primary: false, .. } => {
+                    self.add_key(column.to_string(), vec![column.to_string()])?;
+                }
+                ColumnOption::ForeignKey { foreign_table, referred_columns, on_delete, .. } => {
+                    self.add_foreign_key(
+                        None,
+                        vec![column.to_string()],
+                        foreign_table,
+                        referred_columns,
+                        *on_delete
+                    )?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a key or a foreign key of the table.
+    pub fn add_constraint(&mut self, constraint: &TableConstraint) -> Result<(), mysql::Error> {
+        match constraint {
+            TableConstraint::PrimaryKey { columns, .. } => {
+                self.add_key("PRIMARY".to_string(), idents(columns))?;
+            }
+            TableConstraint::Unique { name, index_name, columns, .. } => {
+                let name = index_name
+                    .as_ref()
+                    .or(name.as_ref())
+                    .map(|name| name.value.clone())
+                    .or_else(|| columns.first().map(|column| column.value.clone()))
+                    .unwrap_or_default();
+                self.add_key(name, idents(columns))?;
+            }
+            TableConstraint::ForeignKey {
+                name,
+                columns,
+                foreign_table,
+                referred_columns,
+                on_delete,
+                ..
+            } => {
+                self.add_foreign_key(
+                    name.as_ref().map(|name| name.value.clone()),
+                    idents(columns),
+                    foreign_table,
+                    referred_columns,
+                    *on_delete
+                )?;
+            }
+            TableConstraint::Index { .. } | TableConstraint::FulltextOrSpatial { .. } => {}
+            TableConstraint::Check { .. } => {
+                return Err(unsupported("CHECK constraints"));
+            }
+        }
+        Ok(())
+    }
+
+    fn add_key(&mut self, name: String, columns: Vec<String>) -> Result<(), mysql::Error> {
+        for column in &columns {
+            let index = self.index_of(column).ok_or_else(|| {
+                sql_error(1072, format!("Key column '{}' doesn't exist in table", column))
+            })?;
+            if name == "PRIMARY" {
+                self.columns[index].nullable = false;
+                if self.columns[index].default == Some(Value::NULL) {
+                    self.columns[index].default = None;
+                }
+            }
+        }
+
+        if name == "PRIMARY" {
+            if self.primary_key().is_some() {
+                return Err(sql_error(1068, "Multiple primary key defined"));
+            }
+            self.keys.insert(0, Key { name, columns });
+        } else {
+            if self.keys.iter().any(|key| key.name.eq_ignore_ascii_case(&name)) {
+                return Err(sql_error(1061, format!("Duplicate key name '{}'", name)));
+            }
+            self.keys.push(Key { name, columns });
+        }
+        Ok(())
+    }
+
+    fn add_foreign_key(
+        &mut self,
+        name: Option<String>,
+        columns: Vec<String>,
+        table: &ObjectName,
+        referenced_columns: &[Ident],
+        on_delete: Option<ReferentialAction>
+    ) -> Result<(), mysql::Error> {
+        for column in &columns {
+            self.column_index(column)?;
+        }
+        let on_delete = match on_delete {
+            Some(ReferentialAction::Cascade) => OnDelete::CASCADE,
+            Some(ReferentialAction::SetNull) => OnDelete::SET_NULL,
+            Some(ReferentialAction::SetDefault) => {
+                return Err(unsupported("ON DELETE SET DEFAULT"));
+            }
+            Some(ReferentialAction::Restrict | ReferentialAction::NoAction) | None =>
+                OnDelete::RESTRICT,
+        };
+        let name = name.unwrap_or_else(|| {
+            let generated = (1..)
+                .map(|n| format!("{}_ibfk_{}", self.name, n))
+                .find(|name| !self.foreign_keys.iter().any(|key| &key.name == name));
+            generated.unwrap_or_default()
+        });
+
+        self.foreign_keys.push(ForeignKey {
+            name,
+            columns,
+            table: object_name(table),
+            referenced_columns: idents(referenced_columns),
+            on_delete,
+        });
+        Ok(())
+    }
+}
+
+impl ColumnSchema {
+    /// Reads the definition of a column.
+    pub fn new<'a>(
+        name: &Ident,
+        data_type: &DataType,
+        options: impl IntoIterator<Item = &'a ColumnOption>
+    ) -> Result<Self, mysql::Error> {
+        let integer = |data_type: &str, display: Option<u64>| {
+            let column_type = match display {
+                Some(width) => format!("{}({})", data_type, width),
+                None => data_type.to_string(),
+            };
+            (ColumnKind::INTEGER, data_type.to_string(), column_type)
+        };
+        let text = |data_type: &str, length: &Option<CharacterLength>, default: usize| {
+            let length = match length {
+                Some(CharacterLength::IntegerLength { length, .. }) => *length as usize,
+                _ => default,
+            };
+            (ColumnKind::TEXT(length), data_type.to_string(), format!("{}({})", data_type, length))
+        };
+        let plain = |kind: ColumnKind, data_type: &str| {
+            (kind, data_type.to_string(), data_type.to_string())
+        };
+
+        let (kind, data_type, column_type) = match data_type {
+            DataType::TinyInt(width) => integer("tinyint", *width),
+            DataType::SmallInt(width) => integer("smallint", *width),
+            DataType::MediumInt(width) => integer("mediumint", *width),
+            DataType::Int(width) | DataType::Integer(width) => integer("int", *width),
+            DataType::BigInt(width) => integer("bigint", *width),
+            DataType::UnsignedTinyInt(width) => integer("tinyint unsigned", *width),
+            DataType::UnsignedSmallInt(width) => integer("smallint unsigned", *width),
+            DataType::UnsignedInt(width) | DataType::UnsignedInteger(width) => {
+                integer("int unsigned", *width)
+            }
+            DataType::UnsignedBigInt(width) => integer("bigint unsigned", *width),
+            DataType::Bool | DataType::Boolean => {
+                (ColumnKind::INTEGER, "tinyint".to_string(), "tinyint(1)".to_string())
+            }
+            DataType::Float(_) => plain(ColumnKind::FLOAT, "float"),
+            DataType::Double | DataType::DoublePrecision | DataType::Real => {
+                plain(ColumnKind::DOUBLE, "double")
+            }
+            DataType::Decimal(_) | DataType::Dec(_) | DataType::Numeric(_) => {
+                plain(ColumnKind::DOUBLE, "decimal")
+            }
+            DataType::Date => plain(ColumnKind::DATE, "date"),
+            DataType::Datetime(_) => plain(ColumnKind::DATETIME, "datetime"),
+            DataType::Timestamp(..) => plain(ColumnKind::DATETIME, "timestamp"),
+            DataType::Time(..) => plain(ColumnKind::TIME, "time"),
+            DataType::Varchar(length) | DataType::CharVarying(length) => {
+                text("varchar", length, 255)
+            }
+            DataType::Char(length) | DataType::Character(length) => text("char", length, 1),
+            DataType::TinyText => plain(ColumnKind::TEXT(255), "tinytext"),
+            DataType::Text => plain(ColumnKind::TEXT(65_535), "text"),
+            DataType::MediumText => plain(ColumnKind::TEXT(16_777_215), "mediumtext"),
+            DataType::LongText => plain(ColumnKind::TEXT(usize::MAX), "longtext"),
+            DataType::Enum(members, _) => {
+                let values = members
+                    .iter()
+                    .map(|member| {
+                        match member {
+                            EnumMember::Name(name) | EnumMember::NamedValue(name, _) =>
+                                name.clone(),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let quoted = values
+                    .iter()
+                    .map(|value| format!("'{}'", value.replace('\'', "''")))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                (ColumnKind::ENUM(values), "enum".to_string(), format!("enum({})", quoted))
+            }
+            other => {
+                return Err(unsupported(&format!("the {} type", other)));
+            }
+        };
+
+        let mut column = ColumnSchema {
+            name: name.value.clone(),
+            kind,
+            data_type,
+            column_type,
+            nullable: true,
+            default: None,
+            default_now: false,
+            update_now: false,
+        };
+
+        let mut default = None;
+        for option in options {
+            match option {
+                ColumnOption::Null => {
+                    column.nullable = true;
+                }
+                ColumnOption::NotNull | ColumnOption::Unique { is_primary: true, .. } => {
+                    column.nullable = false;
+                }
+                ColumnOption::Default(expr) => {
+                    default = Some(expr);
+                }
+                ColumnOption::OnUpdate(expr) if is_current_timestamp(expr) => {
+                    column.update_now = true;
+                }
+                ColumnOption::Unique { .. } |
+                ColumnOption::ForeignKey { .. } |
+                ColumnOption::DialectSpecific(_) |
+                ColumnOption::CharacterSet(_) |
+                ColumnOption::Comment(_) => {}
+                other => {
+                    return Err(unsupported(&format!("the column attribute {}", other)));
+                }
+            }
+        }
+
+        match default {
+            Some(expr) if is_current_timestamp(expr) => {
+                column.default_now = true;
+            }
+            Some(expr) => {
+                let value = literal(expr).ok_or_else(|| {
+                    sql_error(1067, format!("Invalid default value for '{}'", column.name))
+                })?;
+                let value = column
+                    .coerce(value)
+                    .map_err(|_| {
+                        sql_error(1067, format!("Invalid default value for '{}'", column.name))
+                    })?;
+                column.default = Some(value);
+            }
+            None if column.nullable => {
+                column.default = Some(Value::NULL);
+            }
+            None => {}
+        }
+
+        Ok(column)
+    }
+
+    /// The value a new `NOT NULL` column without a default gets in the rows already in the table.
+    pub fn implicit_default(&self) -> Value {
+        match &self.kind {
+            ColumnKind::INTEGER => Value::Int(0),
+            ColumnKind::FLOAT => Value::Float(0.0),
+            ColumnKind::DOUBLE => Value::Double(0.0),
+            ColumnKind::DATE | ColumnKind::DATETIME => Value::Date(0, 0, 0, 0, 0, 0, 0),
+            ColumnKind::TIME => Value::Time(false, 0, 0, 0, 0, 0),
+            ColumnKind::TEXT(_) => Value::from(""),
+            ColumnKind::ENUM(values) => Value::from(values[0].as_str()),
+        }
+    }
+
+    /// Converts `value` into the representation the column keeps, failing like MySQL in strict
+    /// mode when the column can't hold it.
+    pub fn coerce(&self, value: Value) -> Result<Value, mysql::Error> {
+        let incorrect = |type_name: &str, value: &Value| {
+            sql_error(
+                if type_name == "integer" {
+                    1366
+                } else {
+                    1292
+                },
+                format!(
+                    "Incorrect {} value: '{}' for column '{}' at row 1",
+                    type_name,
+                    display(value),
+                    self.name
+                )
+            )
+        };
+        let truncated = || {
+            sql_error(1265, format!("Data truncated for column '{}' at row 1", self.name))
+        };
+
+        if value == Value::NULL {
+            return if self.nullable {
+                Ok(Value::NULL)
+            } else {
+                Err(sql_error(1048, format!("Column '{}' cannot be null", self.name)))
+            };
+        }
+
+        match &self.kind {
+            ColumnKind::INTEGER =>
+                match value {
+                    Value::Int(int) => Ok(Value::Int(int)),
+                    Value::UInt(uint) =>
+                        i64::try_from(uint)
+                            .map(Value::Int)
+                            .map_err(|_| sql_error(1264, out_of_range(&self.name))),
+                    Value::Float(_) | Value::Double(_) =>
+                        Ok(Value::Int(number(&value).unwrap_or_default().round() as i64)),
+                    Value::Bytes(ref bytes) => {
+                        let text = String::from_utf8_lossy(bytes);
+                        let text = text.trim();
+                        text.parse::<i64>()
+                            .ok()
+                            .or_else(|| {
+                                text.parse::<f64>()
+                                    .ok()
+                                    .map(|float| float.round() as i64)
+                            })
+                            .map(Value::Int)
+                            .ok_or_else(|| incorrect("integer", &value))
+                    }
+                    _ => Err(incorrect("integer", &value)),
+                }
+            ColumnKind::FLOAT | ColumnKind::DOUBLE => {
+                let float = match value {
+                    Value::Int(_) | Value::UInt(_) | Value::Float(_) | Value::Double(_) => {
+                        number(&value)
+                    }
+                    Value::Bytes(ref bytes) =>
+                        String::from_utf8_lossy(bytes).trim().parse::<f64>().ok(),
+                    _ => None,
+                };
+                let float = float.ok_or_else(truncated)?;
+                Ok(
+                    if self.kind == ColumnKind::FLOAT {
+                        Value::Float(float as f32)
+                    } else {
+                        Value::Double(float)
+                    }
+                )
+            }
+            ColumnKind::DATE | ColumnKind::DATETIME => {
+                let type_name = if self.kind == ColumnKind::DATE { "date" } else { "datetime" };
+                if value == Value::Date(0, 0, 0, 0, 0, 0, 0) {
+                    return Ok(value);
+                }
+                let date_time = to_date_time(&value).ok_or_else(|| incorrect(type_name, &value))?;
+                Ok(
+                    if self.kind == ColumnKind::DATE {
+                        date_value(date_time.date().and_time(NaiveTime::MIN))
+                    } else {
+                        date_value(round_seconds(date_time))
+                    }
+                )
+            }
+            ColumnKind::TIME => {
+                let time = match value {
+                    Value::Time(..) => Some(value.clone()),
+                    Value::Date(..) => to_date_time(&value).map(|date_time| time_value(date_time.time())),
+                    Value::Bytes(ref bytes) => parse_time(&String::from_utf8_lossy(bytes)),
+                    _ => None,
+                };
+                time.ok_or_else(|| incorrect("time", &value))
+            }
+            ColumnKind::TEXT(max_length) => {
+                let text = display(&value);
+                if text.chars().count() > *max_length {
+                    return Err(
+                        sql_error(1406, format!("Data too long for column '{}' at row 1", self.name))
+                    );
+                }
+                Ok(Value::from(text))
+            }
+            ColumnKind::ENUM(values) => {
+                let found = match value {
+                    Value::Int(index) if index > 0 => values.get((index as usize) - 1),
+                    Value::Bytes(ref bytes) => {
+                        let text = String::from_utf8_lossy(bytes);
+                        values.iter().find(|candidate| compare_text(candidate, &text).is_eq())
+                    }
+                    _ => None,
+                };
+                found.map(|value| Value::from(value.as_str())).ok_or_else(truncated)
+            }
+        }
+    }
+
+    /// The text MySQL shows for a value of the column, e.g. in the message of a duplicate entry.
+    pub fn display(&self, value: &Value) -> String {
+        match (&self.kind, value) {
+            (ColumnKind::DATE, Value::Date(year, month, day, ..)) => {
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            }
+            _ => display(value),
+        }
+    }
+}
+
+fn out_of_range(column: &str) -> String {
+    format!("Out of range value for column '{}' at row 1", column)
+}
+
+/// The error for SQL the in-memory storage can't run.
+pub fn unsupported(what: &str) -> mysql::Error {
+    sql_error(1235, format!("The in-memory storage doesn't support {}", what))
+}
+
+/// The name of a table, without the database it may be qualified with.
+pub fn object_name(name: &ObjectName) -> String {
+    name.0
+        .last()
+        .map(|ident| ident.value.clone())
+        .unwrap_or_default()
+}
+
+fn idents(idents: &[Ident]) -> Vec<String> {
+    idents
+        .iter()
+        .map(|ident| ident.value.clone())
+        .collect()
+}
+
+/// Whether `expr` is `CURRENT_TIMESTAMP`, `CURRENT_TIMESTAMP()` or `NOW()`.
+fn is_current_timestamp(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(function) => {
+            let name = object_name(&function.name).to_uppercase();
+            let no_arguments = match &function.args {
+                FunctionArguments::None => true,
+                FunctionArguments::List(list) => list.args.is_empty(),
+                FunctionArguments::Subquery(_) => false,
+            };
+            no_arguments && ["CURRENT_TIMESTAMP", "NOW", "LOCALTIMESTAMP"].contains(&name.as_str())
+        }
+        Expr::Identifier(ident) => ident.value.eq_ignore_ascii_case("CURRENT_TIMESTAMP"),
+        _ => false,
+    }
+}
+
+/// The value of a literal, e.g. the default of a column.
+pub fn literal(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Value(value) =>
+            match value {
+                SqlValue::Number(number, _) =>
+                    Some(
+                        number
+                            .parse::<i64>()
+                            .map(Value::Int)
+                            .unwrap_or_else(|_| Value::Double(number.parse().unwrap_or_default()))
+                    ),
+                SqlValue::SingleQuotedString(text) | SqlValue::DoubleQuotedString(text) => {
+                    Some(Value::from(text.as_str()))
+                }
+                SqlValue::Boolean(boolean) => Some(Value::Int(*boolean as i64)),
+                SqlValue::Null => Some(Value::NULL),
+                _ => None,
+            }
+        Expr::UnaryOp { op: UnaryOperator::Minus, expr } =>
+            match literal(expr)? {
+                Value::Int(int) => Some(Value::Int(-int)),
+                Value::Double(double) => Some(Value::Double(-double)),
+                _ => None,
+            }
+        Expr::Nested(expr) => literal(expr),
+        _ => None,
+    }
+}
+
+/// The current time, as `CURRENT_TIMESTAMP` sets it.
+pub fn now() -> NaiveDateTime {
+    Local::now().naive_local().with_nanosecond(0).unwrap()
+}
+
+/// Compares two values the way MySQL does in conditions, `None` when one of them is `NULL`.
+///
+/// Strings compare without case, like the default collation. A string compared with a date, a
+/// time or a number is read as one.
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::NULL, _) | (_, Value::NULL) => None,
+        (Value::Bytes(a), Value::Bytes(b)) => {
+            Some(compare_text(&String::from_utf8_lossy(a), &String::from_utf8_lossy(b)))
+        }
+        (Value::Date(..), Value::Date(..)) => Some(date_key(a).cmp(&date_key(b))),
+        (Value::Time(..), Value::Time(..)) => Some(time_key(a).cmp(&time_key(b))),
+        (Value::Date(..), Value::Bytes(text)) =>
+            match parse_date_time(&String::from_utf8_lossy(text)) {
+                Some(date_time) => compare(a, &date_value(date_time)),
+                None => Some(compare_text(&display(a), &String::from_utf8_lossy(text))),
+            }
+        (Value::Time(..), Value::Bytes(text)) =>
+            match parse_time(&String::from_utf8_lossy(text)) {
+                Some(time) => compare(a, &time),
+                None => Some(compare_text(&display(a), &String::from_utf8_lossy(text))),
+            }
+        (Value::Bytes(_), Value::Date(..) | Value::Time(..)) => compare(b, a).map(Ordering::reverse),
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Int(_) | Value::UInt(_), Value::Int(_) | Value::UInt(_)) => {
+            Some(integer(a)?.cmp(&integer(b)?))
+        }
+        _ => number(a)?.partial_cmp(&number(b)?),
+    }
+}
+
+/// Compares two strings without case.
+pub fn compare_text(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+/// Whether two values are the same for a key or for `DISTINCT`, where two `NULL`s are the same.
+pub fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::NULL, Value::NULL) => true,
+        _ => compare(a, b) == Some(Ordering::Equal),
+    }
+}
+
+fn integer(value: &Value) -> Option<i128> {
+    match *value {
+        Value::Int(int) => Some(int.into()),
+        Value::UInt(uint) => Some(uint.into()),
+        _ => None,
+    }
+}
+
+/// A value read as a number, like MySQL reads a string in arithmetic: `'12abc'` is 12 and
+/// `'abc'` is 0.
+pub fn number(value: &Value) -> Option<f64> {
+    match *value {
+        Value::NULL => None,
+        Value::Int(int) => Some(int as f64),
+        Value::UInt(uint) => Some(uint as f64),
+        Value::Float(float) => Some(float.into()),
+        Value::Double(double) => Some(double),
+        Value::Bytes(ref bytes) => {
+            let text = String::from_utf8_lossy(bytes);
+            let text = text.trim();
+            let prefix = (0..=text.len())
+                .rev()
+                .filter(|end| text.is_char_boundary(*end))
+                .find_map(|end| text[..end].parse::<f64>().ok());
+            Some(prefix.unwrap_or_default())
+        }
+        Value::Date(year, month, day, hour, minute, second, _) => {
+            let date = (year as u64) * 10_000 + (month as u64) * 100 + (day as u64);
+            let time = (hour as u64) * 10_000 + (minute as u64) * 100 + (second as u64);
+            Some((date * 1_000_000 + time) as f64)
+        }
+        Value::Time(negative, days, hour, minute, second, _) => {
+            let hours = (days as u64) * 24 + (hour as u64);
+            let time = (hours * 10_000 + (minute as u64) * 100 + (second as u64)) as f64;
+            Some(if negative { -time } else { time })
+        }
+    }
+}
+
+/// The text MySQL shows for a value.
+pub fn display(value: &Value) -> String {
+    match *value {
+        Value::NULL => "NULL".to_string(),
+        Value::Bytes(ref bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        Value::Int(int) => int.to_string(),
+        Value::UInt(uint) => uint.to_string(),
+        Value::Float(float) => float.to_string(),
+        Value::Double(double) => double.to_string(),
+        Value::Date(year, month, day, hour, minute, second, micros) => {
+            let mut text = format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second
+            );
+            if micros > 0 {
+                text.push_str(&format!(".{:06}", micros));
+            }
+            text
+        }
+        Value::Time(negative, days, hour, minute, second, _) => {
+            format!(
+                "{}{:02}:{:02}:{:02}",
+                if negative { "-" } else { "" },
+                (days as u64) * 24 + (hour as u64),
+                minute,
+                second
+            )
+        }
+    }
+}
+
+fn date_key(value: &Value) -> (u16, u8, u8, u8, u8, u8, u32) {
+    match *value {
+        Value::Date(year, month, day, hour, minute, second, micros) => {
+            (year, month, day, hour, minute, second, micros)
+        }
+        _ => Default::default(),
+    }
+}
+
+fn time_key(value: &Value) -> i128 {
+    match *value {
+        Value::Time(negative, days, hour, minute, second, micros) => {
+            let seconds =
+                (days as i128) * 86_400 +
+                (hour as i128) * 3_600 +
+                (minute as i128) * 60 +
+                (second as i128);
+            let micros = seconds * 1_000_000 + (micros as i128);
+            if negative {
+                -micros
+            } else {
+                micros
+            }
+        }
+        _ => 0,
+    }
+}
+
+pub fn date_value(date_time: NaiveDateTime) -> Value {
+    Value::Date(
+        date_time.year() as u16,
+        date_time.month() as u8,
+        date_time.day() as u8,
+        date_time.hour() as u8,
+        date_time.minute() as u8,
+        date_time.second() as u8,
+        date_time.nanosecond() / 1_000
+    )
+}
+
+pub fn time_value(time: NaiveTime) -> Value {
+    Value::Time(
+        false,
+        0,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+        time.nanosecond() / 1_000
+    )
+}
+
+/// Rounds to the second, like a `DATETIME` column without fractional seconds.
+fn round_seconds(date_time: NaiveDateTime) -> NaiveDateTime {
+    let rounded = date_time.with_nanosecond(0).unwrap();
+    if date_time.nanosecond() >= 500_000_000 {
+        rounded + chrono::Duration::seconds(1)
+    } else {
+        rounded
+    }
+}
+
+/// A date, a date and time or a string holding one, as a date and time.
+pub fn to_date_time(value: &Value) -> Option<NaiveDateTime> {
+    match *value {
+        Value::Date(year, month, day, hour, minute, second, micros) => {
+            NaiveDate::from_ymd_opt(year.into(), month.into(), day.into()).and_then(|date| {
+                date.and_hms_micro_opt(hour.into(), minute.into(), second.into(), micros)
+            })
+        }
+        Value::Bytes(ref bytes) => parse_date_time(&String::from_utf8_lossy(bytes)),
+        _ => None,
+    }
+}
+
+/// A time or a string holding one, as a time of the day.
+pub fn to_time(value: &Value) -> Option<NaiveTime> {
+    let value = match value {
+        Value::Bytes(bytes) => parse_time(&String::from_utf8_lossy(bytes))?,
+        Value::Date(..) => {
+            return to_date_time(value).map(|date_time| date_time.time());
+        }
+        other => other.clone(),
+    };
+    match value {
+        Value::Time(false, 0, hour, minute, second, micros) => {
+            NaiveTime::from_hms_micro_opt(hour.into(), minute.into(), second.into(), micros)
+        }
+        _ => None,
+    }
+}
+
+/// Parses dates and times the way MySQL reads them from strings, with a space or a `T` between
+/// the date and the time.
+pub fn parse_date_time(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim().replacen('T', " ", 1);
+    NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M").ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+}
+
+/// Parses a `TIME` value, `HH:MM[:SS[.fraction]]`, where the hours may go past a day.
+pub fn parse_time(text: &str) -> Option<Value> {
+    let text = text.trim();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let (text, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let mut parts = text.split(':');
+    let hours = parts.next()?.parse::<u32>().ok()?;
+    let minutes = parts.next()?.parse::<u8>().ok()?;
+    let seconds = parts.next().map_or(Some(0), |seconds| seconds.parse::<u8>().ok())?;
+    if parts.next().is_some() || minutes > 59 || seconds > 59 || hours > 838 {
+        return None;
+    }
+    let micros = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<6}", &fraction[..fraction.len().min(6)]).parse::<u32>().ok()?
+    };
+    Some(Value::Time(negative, hours / 24, (hours % 24) as u8, minutes, seconds, micros))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlparser::{ ast::Statement, dialect::MySqlDialect, parser::Parser };
+
+    use super::*;
+    use crate::models::schedule::shift_cover::create_shift_covers_table_query;
+
+    fn parse(create_table: &str) -> TableSchema {
+        let statement = Parser::parse_sql(&MySqlDialect {}, create_table).unwrap().remove(0);
+        match statement {
+            Statement::CreateTable(create_table) => {
+                TableSchema::from_create_table(&create_table).unwrap()
+            }
+            other => panic!("Not a CREATE TABLE statement: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_table_schema() {
+        let schema = parse(&create_shift_covers_table_query());
+        let names = schema.columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![
+            "id",
+            "shift_id",
+            "cover_user_id",
+            "status",
+            "admin_id",
+            "note",
+            "updated_at",
+        ]);
+
+        let status = &schema.columns[schema.index_of("status").unwrap()];
+        assert!(matches!(status.kind, ColumnKind::ENUM(ref values) if values.len() == 6));
+        assert_eq!(status.column_type, "enum('PENDING','PEER_ACCEPTED','PEER_DECLINED','APPROVED','DECLINED','CANCELLED')");
+        assert!(!status.nullable);
+        assert_eq!(status.default, Some(Value::from("PENDING")));
+
+        let admin_id = &schema.columns[schema.index_of("admin_id").unwrap()];
+        assert!(admin_id.nullable);
+        assert_eq!(admin_id.default, Some(Value::NULL));
+
+        let updated_at = &schema.columns[schema.index_of("updated_at").unwrap()];
+        assert_eq!(updated_at.kind, ColumnKind::DATETIME);
+        assert_eq!(updated_at.data_type, "timestamp");
+        assert!(updated_at.default_now && updated_at.update_now);
+
+        let id = &schema.columns[0];
+        assert!(!id.nullable);
+        assert_eq!(id.default, None);
+        assert_eq!(schema.primary_key().unwrap().columns, vec!["id"]);
+        assert!(schema.index_of("unknown").is_none());
+
+        assert_eq!(schema.foreign_keys.len(), 3);
+        assert_eq!(schema.foreign_keys[0].name, "shift_covers_ibfk_1");
+        assert_eq!(schema.foreign_keys[0].table, "shifts");
+        assert_eq!(schema.foreign_keys[0].on_delete, OnDelete::CASCADE);
+        assert_eq!(schema.foreign_keys[2].on_delete, OnDelete::SET_NULL);
+    }
+
+    #[test]
+    fn test_coerce_values() {
+        let schema = parse(
+            "CREATE TABLE t (
+                id BIGINT NOT NULL PRIMARY KEY,
+                day DATE NOT NULL,
+                at TIMESTAMP NOT NULL,
+                starts TIME,
+                rate FLOAT,
+                code VARCHAR(3) UNIQUE,
+                status ENUM('OPEN', 'CLOSED') NOT NULL DEFAULT 'OPEN',
+                active BOOLEAN NOT NULL DEFAULT TRUE
+            );"
+        );
+        let column = |name: &str| &schema.columns[schema.index_of(name).unwrap()];
+        let code = |result: Result<Value, mysql::Error>| {
+            match result {
+                Err(mysql::Error::MySqlError(error)) => error.code,
+                other => panic!("Expected a MySQL error, got {:?}", other),
+            }
+        };
+
+        assert_eq!(column("id").coerce(Value::from("42")).unwrap(), Value::Int(42));
+        assert_eq!(code(column("id").coerce(Value::NULL)), 1048);
+        assert_eq!(code(column("id").coerce(Value::from("abc"))), 1366);
+        assert_eq!(
+            column("day").coerce(Value::from("2024-03-01 10:00:00")).unwrap(),
+            Value::Date(2024, 3, 1, 0, 0, 0, 0)
+        );
+        assert_eq!(
+            column("at").coerce(Value::from("2024-03-01T10:30:00")).unwrap(),
+            Value::Date(2024, 3, 1, 10, 30, 0, 0)
+        );
+        assert_eq!(code(column("at").coerce(Value::from("tomorrow"))), 1292);
+        assert_eq!(
+            column("starts").coerce(Value::from("09:15")).unwrap(),
+            Value::Time(false, 0, 9, 15, 0, 0)
+        );
+        assert_eq!(column("rate").coerce(Value::from(12.5)).unwrap(), Value::Float(12.5));
+        assert_eq!(column("rate").default, Some(Value::NULL));
+        assert_eq!(code(column("code").coerce(Value::from("ABCD"))), 1406);
+        assert_eq!(column("status").coerce(Value::from("closed")).unwrap(), Value::from("CLOSED"));
+        assert_eq!(code(column("status").coerce(Value::from("LOST"))), 1265);
+        assert_eq!(column("active").default, Some(Value::Int(1)));
+        assert_eq!(schema.keys[1], Key { name: "code".to_string(), columns: vec!["code".to_string()] });
+
+        assert_eq!(
+            compare(&Value::Date(2024, 3, 1, 0, 0, 0, 0), &Value::Date(2024, 2, 1, 0, 0, 0, 0)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(&Value::Date(2024, 3, 1, 0, 0, 0, 0), &Value::from("2024-03-01")),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(compare(&Value::from("Alice"), &Value::from("alice")), Some(Ordering::Equal));
+        assert_eq!(compare(&Value::Int(2), &Value::from("10")), Some(Ordering::Less));
+        assert_eq!(compare(&Value::NULL, &Value::Int(1)), None);
+    }
+}
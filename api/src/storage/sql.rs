@@ -0,0 +1,2507 @@
+use std::{ cmp::Ordering, collections::HashMap, sync::Arc };
+
+use chrono::{ Datelike, Duration, Months, NaiveDateTime, NaiveTime, Utc };
+use mysql::{ consts::ColumnType, Column, DriverError, Params, Row, Value };
+use sqlparser::{
+    ast::{
+        AlterTableOperation,
+        AssignmentTarget,
+        BinaryOperator,
+        CreateIndex,
+        DataType,
+        DateTimeField,
+        Delete,
+        Distinct,
+        DuplicateTreatment,
+        Expr,
+        FromTable,
+        Function,
+        FunctionArg,
+        FunctionArgExpr,
+        FunctionArguments,
+        GroupByExpr,
+        Ident,
+        Insert,
+        Interval,
+        JoinConstraint,
+        JoinOperator,
+        MySQLColumnPosition,
+        ObjectType,
+        OrderBy,
+        Query,
+        Select,
+        SelectItem,
+        SetExpr,
+        Statement,
+        TableFactor,
+        TableWithJoins,
+        UnaryOperator,
+        Value as SqlValue,
+    },
+    dialect::MySqlDialect,
+    parser::Parser,
+    tokenizer::{ Token, Tokenizer },
+};
+
+use super::{
+    database::{ Database, Table, DATABASE_NAME },
+    schema::{
+        compare,
+        compare_text,
+        date_value,
+        display,
+        number,
+        object_name,
+        same,
+        sql_error,
+        time_value,
+        to_date_time,
+        to_time,
+        unsupported,
+        ColumnKind,
+        ColumnSchema,
+        TableSchema,
+    },
+};
+
+/// What a statement returns: the rows of a query, or the number of rows a change affected.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub columns: Arc<[Column]>,
+    pub rows: Vec<Vec<Value>>,
+    pub affected_rows: u64,
+}
+
+impl Output {
+    fn affected(affected_rows: u64) -> Self {
+        Output { columns: Arc::from(Vec::new()), rows: Vec::new(), affected_rows }
+    }
+
+    /// The rows, as the MySQL driver returns them.
+    pub fn into_rows(self) -> Vec<Row> {
+        let columns = self.columns;
+        self.rows
+            .into_iter()
+            .map(|values| mysql_common::row::new_row(values, columns.clone()))
+            .collect()
+    }
+}
+
+/// Runs SQL statements on `database`, with the named (`:name`) or positional (`?`) parameters
+/// of `params`, and returns the output of the last one.
+///
+/// The SQL is read as MySQL reads it and runs with the semantics of MySQL in strict mode, so the
+/// queries of the application behave the same in memory: constraints fail with the error codes
+/// and messages of MySQL, strings compare without case and values are converted to the types of
+/// their columns. What the in-memory storage can't run fails with error 1235.
+///
+/// On error, the statements that already ran keep their changes: callers run each statement on a
+/// copy of the database to make it atomic.
+pub fn execute(database: &mut Database, sql: &str, params: Params) -> Result<Output, mysql::Error> {
+    let dialect = MySqlDialect {};
+    let mut tokens = Tokenizer::new(&dialect, sql).tokenize().map_err(syntax_error)?;
+    let mut positional = 0;
+    for token in tokens.iter_mut() {
+        if let Token::Placeholder(name) = token {
+            if name == "?" {
+                positional += 1;
+                *name = format!("?{}", positional);
+            }
+        }
+    }
+    let statements = Parser::new(&dialect)
+        .with_tokens(tokens)
+        .parse_statements()
+        .map_err(syntax_error)?;
+
+    let params = match params {
+        Params::Empty => HashMap::new(),
+        Params::Named(params) =>
+            params
+                .into_iter()
+                .map(|(name, value)| (format!(":{}", String::from_utf8_lossy(&name)), value))
+                .collect(),
+        Params::Positional(params) => {
+            if params.len() != positional {
+                return Err(
+                    mysql::Error::DriverError(
+                        DriverError::MismatchedStmtParams(positional as u16, params.len())
+                    )
+                );
+            }
+            params
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| (format!("?{}", index + 1), value))
+                .collect()
+        }
+    };
+
+    let now = super::schema::now();
+    let mut output = Output::affected(0);
+    for statement in &statements {
+        output = run(database, statement, &params, now)?;
+    }
+    Ok(output)
+}
+
+fn syntax_error(error: impl std::fmt::Display) -> mysql::Error {
+    sql_error(1064, format!("You have an error in your SQL syntax: {}", error))
+}
+
+fn run(
+    database: &mut Database,
+    statement: &Statement,
+    params: &HashMap<String, Value>,
+    now: NaiveDateTime
+) -> Result<Output, mysql::Error> {
+    let executor = Executor { database, params, now };
+    match statement {
+        Statement::Query(query) => executor.query(query, None),
+        Statement::Insert(insert) => {
+            let rows = executor.insert_rows(insert)?;
+            let (table, columns) = executor.insert_columns(insert)?;
+            let count = rows.len() as u64;
+            for row in rows {
+                database.insert(&table, &columns, row, now)?;
+            }
+            Ok(Output::affected(count))
+        }
+        Statement::Update { table, assignments, from, selection, returning, or } => {
+            if from.is_some() || returning.is_some() || or.is_some() {
+                return Err(unsupported(&format!("the statement {}", statement)));
+            }
+            let changes = executor.update_changes(table, assignments, selection.as_ref())?;
+            let mut affected_rows = 0;
+            for (table, changes) in changes {
+                affected_rows += database.update(&table, changes, now)?;
+            }
+            Ok(Output::affected(affected_rows))
+        }
+        Statement::Delete(delete) => {
+            let (table, rows) = executor.delete_rows(delete)?;
+            Ok(Output::affected(database.delete(&table, &rows)?))
+        }
+        Statement::CreateTable(create_table) => {
+            if create_table.query.is_some() || create_table.like.is_some() {
+                return Err(unsupported("CREATE TABLE ... AS and CREATE TABLE ... LIKE"));
+            }
+            let schema = TableSchema::from_create_table(create_table)?;
+            database.create_table(schema, create_table.if_not_exists)?;
+            Ok(Output::affected(0))
+        }
+        Statement::CreateIndex(create_index) => {
+            let table = alter_index(database.table(&object_name(&create_index.table_name))?, create_index)?;
+            database.replace_table(&object_name(&create_index.table_name), table)?;
+            Ok(Output::affected(0))
+        }
+        Statement::AlterTable { name, operations, .. } => {
+            let table = executor.alter_table(database.table(&object_name(name))?, operations)?;
+            database.replace_table(&object_name(name), table)?;
+            Ok(Output::affected(0))
+        }
+        Statement::Drop { object_type: ObjectType::Table, if_exists, names, temporary: false, .. } => {
+            let names = names.iter().map(object_name).collect::<Vec<_>>();
+            database.drop_tables(&names, *if_exists)?;
+            Ok(Output::affected(0))
+        }
+        other => Err(unsupported(&format!("the statement {}", other))),
+    }
+}
+
+/// A table of a `FROM` clause.
+#[derive(Debug, Clone)]
+struct Source {
+    table: String,
+    /// The alias of the table, or its name
+    qualifier: String,
+    columns: Vec<(String, Option<ColumnKind>)>,
+    /// Position of the first column of the table in the joined rows
+    offset: usize,
+}
+
+/// The rows of the tables of a `FROM` clause, joined together.
+struct Joined {
+    sources: Vec<Source>,
+    rows: Vec<JoinedRow>,
+}
+
+struct JoinedRow {
+    values: Vec<Value>,
+    /// The position of the row of each table in the table, `None` for the `NULL`s of a `LEFT
+    /// JOIN` or for the rows of a subquery
+    positions: Vec<Option<usize>>,
+}
+
+/// The values of a row of a single table, with its position in the table.
+type PositionedRow = (Option<usize>, Vec<Value>);
+
+/// Where the identifiers of an expression are looked up.
+#[derive(Clone, Copy)]
+struct Scope<'s> {
+    sources: &'s [Source],
+    row: &'s [Value],
+    /// The rows of the group aggregates run on
+    group: Option<&'s [&'s [Value]]>,
+    /// The scope of the query a subquery is in
+    outer: Option<&'s Scope<'s>>,
+}
+
+impl<'s> Scope<'s> {
+    const EMPTY: Scope<'static> = Scope { sources: &[], row: &[], group: None, outer: None };
+
+    /// Finds the column `idents` name in the sources of this scope, as the position of the source
+    /// and of the column in the source.
+    fn locate(&self, idents: &[Ident]) -> Result<Option<(usize, usize)>, mysql::Error> {
+        let (qualifier, column) = match idents {
+            [column] => (None, column),
+            [.., qualifier, column] => (Some(qualifier), column),
+            [] => {
+                return Ok(None);
+            }
+        };
+        let mut found = None;
+        for (source_index, source) in self.sources.iter().enumerate() {
+            if qualifier.is_some_and(|qualifier| qualifier.value != source.qualifier) {
+                continue;
+            }
+            let position = source.columns
+                .iter()
+                .position(|(name, _)| name.eq_ignore_ascii_case(&column.value));
+            if let Some(position) = position {
+                if found.is_some() {
+                    return Err(
+                        sql_error(1052, format!("Column '{}' in field list is ambiguous", column.value))
+                    );
+                }
+                found = Some((source_index, position));
+            }
+        }
+        Ok(found)
+    }
+
+    /// The value and the kind of the column `idents` name, looked up in the outer scopes if it
+    /// isn't in this one.
+    fn column(&self, idents: &[Ident]) -> Result<(Value, Option<ColumnKind>), mysql::Error> {
+        if let Some((source, position)) = self.locate(idents)? {
+            let source = &self.sources[source];
+            let value = self.row
+                .get(source.offset + position)
+                .cloned()
+                .unwrap_or(Value::NULL);
+            return Ok((value, source.columns[position].1.clone()));
+        }
+        match self.outer {
+            Some(outer) => outer.column(idents),
+            None => {
+                let name = idents
+                    .iter()
+                    .map(|ident| ident.value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                Err(sql_error(1054, format!("Unknown column '{}' in 'field list'", name)))
+            }
+        }
+    }
+}
+
+struct Executor<'a> {
+    database: &'a Database,
+    params: &'a HashMap<String, Value>,
+    now: NaiveDateTime,
+}
+
+/// An item of a select list, after expanding the wildcards.
+enum Projection<'q> {
+    Column(usize),
+    Expr(&'q Expr),
+}
+
+impl Executor<'_> {
+    fn query(&self, query: &Query, outer: Option<&Scope>) -> Result<Output, mysql::Error> {
+        if query.with.is_some() || query.fetch.is_some() || !query.limit_by.is_empty() {
+            return Err(unsupported(&format!("the query {}", query)));
+        }
+        let select = match query.body.as_ref() {
+            SetExpr::Select(select) => select,
+            SetExpr::Query(inner) if query.order_by.is_none() && query.limit.is_none() => {
+                return self.query(inner, outer);
+            }
+            _ => {
+                return Err(unsupported(&format!("the query {}", query)));
+            }
+        };
+
+        let mut output = self.select(select, query.order_by.as_ref(), outer)?;
+
+        let offset = match &query.offset {
+            Some(offset) => self.count(&offset.value)?,
+            None => 0,
+        };
+        let limit = match &query.limit {
+            Some(limit) => self.count(limit)?,
+            None => usize::MAX,
+        };
+        output.rows = output.rows.into_iter().skip(offset).take(limit).collect();
+        Ok(output)
+    }
+
+    /// The value of `LIMIT` or `OFFSET`.
+    fn count(&self, expr: &Expr) -> Result<usize, mysql::Error> {
+        match self.eval(expr, &Scope::EMPTY)? {
+            Value::Int(count) if count >= 0 => Ok(count as usize),
+            Value::UInt(count) => Ok(count as usize),
+            other => Err(syntax_error(format!("invalid LIMIT {}", display(&other)))),
+        }
+    }
+
+    fn select(
+        &self,
+        select: &Select,
+        order_by: Option<&OrderBy>,
+        outer: Option<&Scope>
+    ) -> Result<Output, mysql::Error> {
+        let joined = self.join(&select.from, outer)?;
+        let sources = joined.sources.as_slice();
+
+        let mut rows = Vec::new();
+        for row in &joined.rows {
+            let keep = match &select.selection {
+                Some(selection) => {
+                    let row_scope = Scope { sources, row: &row.values, group: None, outer };
+                    truth(&self.eval(selection, &row_scope)?) == Some(true)
+                }
+                None => true,
+            };
+            if keep {
+                rows.push(row.values.as_slice());
+            }
+        }
+
+        // the select list, with the wildcards expanded
+        let mut projections = Vec::new();
+        let mut names = Vec::new();
+        for item in &select.projection {
+            match item {
+                SelectItem::UnnamedExpr(expr) => {
+                    projections.push(Projection::Expr(expr));
+                    names.push(match expr {
+                        Expr::Identifier(ident) => ident.value.clone(),
+                        Expr::CompoundIdentifier(idents) => idents.last().unwrap().value.clone(),
+                        other => other.to_string(),
+                    });
+                }
+                SelectItem::ExprWithAlias { expr, alias } => {
+                    projections.push(Projection::Expr(expr));
+                    names.push(alias.value.clone());
+                }
+                SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => {
+                    let qualifier = match item {
+                        SelectItem::QualifiedWildcard(name, _) => Some(object_name(name)),
+                        _ => None,
+                    };
+                    let mut matched = false;
+                    for source in sources {
+                        if qualifier.as_ref().is_some_and(|qualifier| qualifier != &source.qualifier) {
+                            continue;
+                        }
+                        matched = true;
+                        for (position, (name, _)) in source.columns.iter().enumerate() {
+                            projections.push(Projection::Column(source.offset + position));
+                            names.push(name.clone());
+                        }
+                    }
+                    if !matched {
+                        return Err(
+                            sql_error(1051, format!("Unknown table '{}'", qualifier.unwrap_or_default()))
+                        );
+                    }
+                }
+            }
+        }
+
+        let group_by = match &select.group_by {
+            GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => exprs.as_slice(),
+            _ => {
+                return Err(unsupported(&format!("{}", select.group_by)));
+            }
+        };
+        let aggregate =
+            !group_by.is_empty() ||
+            select.having.is_some() ||
+            projections.iter().any(|projection| {
+                matches!(projection, Projection::Expr(expr) if has_aggregate(expr))
+            });
+
+        // the rows of each group, a group for each row unless the query aggregates
+        let groups: Vec<Vec<&[Value]>> = if !aggregate {
+            rows.iter()
+                .map(|row| vec![*row])
+                .collect()
+        } else if group_by.is_empty() {
+            vec![rows]
+        } else {
+            let mut groups: Vec<(Vec<Value>, Vec<&[Value]>)> = Vec::new();
+            for row in rows {
+                let row_scope = Scope { sources, row, group: None, outer };
+                let key = group_by
+                    .iter()
+                    .map(|expr| self.eval(expr, &row_scope))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let group = groups.iter_mut().find(|(other, _)| {
+                    other
+                        .iter()
+                        .zip(&key)
+                        .all(|(a, b)| same(a, b))
+                });
+                match group {
+                    Some((_, group)) => group.push(row),
+                    None => groups.push((key, vec![row])),
+                }
+            }
+            groups
+                .into_iter()
+                .map(|(_, rows)| rows)
+                .collect()
+        };
+
+        let width = sources
+            .iter()
+            .map(|source| source.columns.len())
+            .sum::<usize>();
+        let null_row = vec![Value::NULL; width];
+
+        // each output row with the group it comes from, for ORDER BY
+        let mut output: Vec<(Vec<Value>, usize)> = Vec::new();
+        for (index, group) in groups.iter().enumerate() {
+            let group_scope = Scope {
+                sources,
+                row: group.first().copied().unwrap_or(&null_row),
+                group: if aggregate { Some(group.as_slice()) } else { None },
+                outer,
+            };
+            if let Some(having) = &select.having {
+                if truth(&self.eval(having, &group_scope)?) != Some(true) {
+                    continue;
+                }
+            }
+            let values = projections
+                .iter()
+                .map(|projection| {
+                    match projection {
+                        Projection::Column(position) => Ok(group_scope.row[*position].clone()),
+                        Projection::Expr(expr) => self.eval(expr, &group_scope),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            output.push((values, index));
+        }
+
+        match &select.distinct {
+            None => {}
+            Some(Distinct::Distinct) => {
+                let mut distinct: Vec<(Vec<Value>, usize)> = Vec::new();
+                for (values, index) in output {
+                    let duplicate = distinct.iter().any(|(other, _)| {
+                        other
+                            .iter()
+                            .zip(&values)
+                            .all(|(a, b)| same(a, b))
+                    });
+                    if !duplicate {
+                        distinct.push((values, index));
+                    }
+                }
+                output = distinct;
+            }
+            Some(Distinct::On(_)) => {
+                return Err(unsupported("DISTINCT ON"));
+            }
+        }
+
+        if let Some(order_by) = order_by {
+            let mut keyed = Vec::with_capacity(output.len());
+            for (values, index) in output {
+                let group = &groups[index];
+                let group_scope = Scope {
+                    sources,
+                    row: group.first().copied().unwrap_or(&null_row),
+                    group: if aggregate { Some(group.as_slice()) } else { None },
+                    outer,
+                };
+                let mut keys = Vec::with_capacity(order_by.exprs.len());
+                for order in &order_by.exprs {
+                    keys.push(self.sort_key(&order.expr, &group_scope, select, &names, &values)?);
+                }
+                keyed.push((keys, values));
+            }
+            keyed.sort_by(|(a, _), (b, _)| {
+                for ((a, b), order) in a.iter().zip(b).zip(&order_by.exprs) {
+                    let ordering = match (a, b) {
+                        (Value::NULL, Value::NULL) => Ordering::Equal,
+                        (Value::NULL, _) => Ordering::Less,
+                        (_, Value::NULL) => Ordering::Greater,
+                        (a, b) => compare(a, b).unwrap_or(Ordering::Equal),
+                    };
+                    let ordering = if order.asc == Some(false) {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    };
+                    if ordering.is_ne() {
+                        return ordering;
+                    }
+                }
+                Ordering::Equal
+            });
+            output = keyed
+                .into_iter()
+                .map(|(_, values)| (values, 0))
+                .collect();
+        }
+
+        let rows = output
+            .into_iter()
+            .map(|(values, _)| values)
+            .collect::<Vec<_>>();
+        let columns = projections
+            .iter()
+            .zip(&names)
+            .enumerate()
+            .map(|(index, (projection, name))| {
+                let kind = match projection {
+                    Projection::Column(position) => column_kind(sources, *position),
+                    Projection::Expr(Expr::Identifier(ident)) => {
+                        column_at(sources, &Scope { sources, row: &[], group: None, outer: None }, std::slice::from_ref(ident))
+                    }
+                    Projection::Expr(Expr::CompoundIdentifier(idents)) => {
+                        column_at(sources, &Scope { sources, row: &[], group: None, outer: None }, idents)
+                    }
+                    Projection::Expr(_) => None,
+                };
+                let column_type = column_type(kind.as_ref(), rows.iter().map(|row| &row[index]));
+                Column::new(column_type).with_name(name.as_bytes())
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Output { columns: Arc::from(columns), rows, affected_rows: 0 })
+    }
+
+    /// The value an `ORDER BY` expression sorts a row on: a position or an alias of the select
+    /// list, or an expression. `ENUM` columns sort in the order of their values.
+    fn sort_key(
+        &self,
+        expr: &Expr,
+        scope: &Scope,
+        select: &Select,
+        names: &[String],
+        values: &[Value]
+    ) -> Result<Value, mysql::Error> {
+        match expr {
+            Expr::Value(SqlValue::Number(position, _)) => {
+                let position = position.parse::<usize>().unwrap_or_default();
+                if position == 0 || position > values.len() {
+                    return Err(
+                        sql_error(1054, format!("Unknown column '{}' in 'order clause'", position))
+                    );
+                }
+                return Ok(values[position - 1].clone());
+            }
+            Expr::Identifier(ident) => {
+                let alias = select.projection.iter().position(|item| {
+                    matches!(item, SelectItem::ExprWithAlias { alias, .. } if alias.value.eq_ignore_ascii_case(&ident.value))
+                });
+                if let Some(item) = alias {
+                    let position = names
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, _)| *index >= item)
+                        .find(|(_, name)| name.eq_ignore_ascii_case(&ident.value))
+                        .map(|(index, _)| index);
+                    if let Some(position) = position {
+                        return Ok(values[position].clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let idents = match expr {
+            Expr::Identifier(ident) => Some(std::slice::from_ref(ident)),
+            Expr::CompoundIdentifier(idents) => Some(idents.as_slice()),
+            _ => None,
+        };
+        if let Some(idents) = idents {
+            let (value, kind) = scope.column(idents)?;
+            if let (Some(ColumnKind::ENUM(members)), Value::Bytes(bytes)) = (&kind, &value) {
+                let text = String::from_utf8_lossy(bytes);
+                let position = members.iter().position(|member| *member == text);
+                return Ok(Value::Int(position.map_or(0, |position| (position as i64) + 1)));
+            }
+            return Ok(value);
+        }
+        self.eval(expr, scope)
+    }
+
+    /// Joins the tables of a `FROM` clause.
+    fn join(&self, from: &[TableWithJoins], outer: Option<&Scope>) -> Result<Joined, mysql::Error> {
+        let mut joined = Joined {
+            sources: Vec::new(),
+            rows: vec![JoinedRow { values: Vec::new(), positions: Vec::new() }],
+        };
+        for table in from {
+            let (source, rows) = self.relation(&table.relation, outer)?;
+            joined = self.combine(joined, source, rows, None, false, outer)?;
+            for join in &table.joins {
+                let (source, rows) = self.relation(&join.relation, outer)?;
+                let (constraint, left) = match &join.join_operator {
+                    JoinOperator::Inner(constraint) => (constraint, false),
+                    JoinOperator::LeftOuter(constraint) => (constraint, true),
+                    JoinOperator::CrossJoin => (&JoinConstraint::None, false),
+                    _ => {
+                        return Err(unsupported("this kind of join"));
+                    }
+                };
+                let condition = match constraint {
+                    JoinConstraint::On(condition) => Some(condition),
+                    JoinConstraint::None => None,
+                    _ => {
+                        return Err(unsupported("USING and NATURAL joins"));
+                    }
+                };
+                joined = self.combine(joined, source, rows, condition, left, outer)?;
+            }
+        }
+        Ok(joined)
+    }
+
+    /// Joins the rows of a table to the rows joined so far, keeping the pairs matching
+    /// `condition`, and with `left` the joined rows without a match.
+    fn combine(
+        &self,
+        joined: Joined,
+        mut source: Source,
+        rows: Vec<(Option<usize>, Vec<Value>)>,
+        condition: Option<&Expr>,
+        left: bool,
+        outer: Option<&Scope>
+    ) -> Result<Joined, mysql::Error> {
+        if joined.sources.iter().any(|other| other.qualifier == source.qualifier) {
+            return Err(sql_error(1066, format!("Not unique table/alias: '{}'", source.qualifier)));
+        }
+        source.offset = joined.sources
+            .iter()
+            .map(|source| source.columns.len())
+            .sum();
+        let width = source.columns.len();
+        let mut sources = joined.sources;
+        sources.push(source);
+
+        let mut combined = Vec::new();
+        for row in joined.rows {
+            let mut matched = false;
+            for (position, values) in &rows {
+                let mut candidate = row.values.clone();
+                candidate.extend(values.iter().cloned());
+                let keep = match condition {
+                    Some(condition) => {
+                        let scope = Scope { sources: &sources, row: &candidate, group: None, outer };
+                        truth(&self.eval(condition, &scope)?) == Some(true)
+                    }
+                    None => true,
+                };
+                if keep {
+                    matched = true;
+                    let mut positions = row.positions.clone();
+                    positions.push(*position);
+                    combined.push(JoinedRow { values: candidate, positions });
+                }
+            }
+            if left && !matched {
+                let mut values = row.values;
+                values.extend(std::iter::repeat_n(Value::NULL, width));
+                let mut positions = row.positions;
+                positions.push(None);
+                combined.push(JoinedRow { values, positions });
+            }
+        }
+        Ok(Joined { sources, rows: combined })
+    }
+
+    /// The rows of a table of a `FROM` clause, with their position in the table.
+    fn relation(
+        &self,
+        factor: &TableFactor,
+        outer: Option<&Scope>
+    ) -> Result<(Source, Vec<PositionedRow>), mysql::Error> {
+        match factor {
+            TableFactor::Table { name, alias, args: None, .. } => {
+                let table = match name.0.as_slice() {
+                    [schema, table] if schema.value.eq_ignore_ascii_case("information_schema") => {
+                        information_schema(self.database, &table.value)?
+                    }
+                    [schema, _] if schema.value != DATABASE_NAME => {
+                        return Err(
+                            sql_error(1049, format!("Unknown database '{}'", schema.value))
+                        );
+                    }
+                    _ => self.database.table(&object_name(name))?.clone(),
+                };
+                let source = Source {
+                    table: table.schema.name.clone(),
+                    qualifier: alias
+                        .as_ref()
+                        .map_or_else(|| table.schema.name.clone(), |alias| alias.name.value.clone()),
+                    columns: table.schema.columns
+                        .iter()
+                        .map(|column| (column.name.clone(), Some(column.kind.clone())))
+                        .collect(),
+                    offset: 0,
+                };
+                let rows = table.rows
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, row)| (Some(position), row))
+                    .collect();
+                Ok((source, rows))
+            }
+            TableFactor::Derived { lateral: false, subquery, alias: Some(alias) } => {
+                let output = self.query(subquery, outer)?;
+                let source = Source {
+                    table: alias.name.value.clone(),
+                    qualifier: alias.name.value.clone(),
+                    columns: output.columns
+                        .iter()
+                        .map(|column| (column.name_str().into_owned(), None))
+                        .collect(),
+                    offset: 0,
+                };
+                let rows = output.rows
+                    .into_iter()
+                    .map(|row| (None, row))
+                    .collect();
+                Ok((source, rows))
+            }
+            TableFactor::NestedJoin { table_with_joins, alias: None } => {
+                let joined = self.join(std::slice::from_ref(table_with_joins.as_ref()), outer)?;
+                if joined.sources.len() != 1 {
+                    return Err(unsupported("nested joins"));
+                }
+                let source = joined.sources.into_iter().next().unwrap();
+                let rows = joined.rows
+                    .into_iter()
+                    .map(|row| (row.positions[0], row.values))
+                    .collect();
+                Ok((source, rows))
+            }
+            other => Err(unsupported(&format!("the table {}", other))),
+        }
+    }
+
+    /// The rows `INSERT` inserts, with a value for each of its columns.
+    fn insert_rows(&self, insert: &Insert) -> Result<Vec<Vec<Value>>, mysql::Error> {
+        if
+            insert.ignore ||
+            insert.on.is_some() ||
+            insert.replace_into ||
+            insert.returning.is_some() ||
+            insert.or.is_some()
+        {
+            return Err(unsupported("INSERT IGNORE, REPLACE and ON DUPLICATE KEY UPDATE"));
+        }
+        let (_, columns) = self.insert_columns(insert)?;
+        let source = insert.source.as_ref().ok_or_else(|| unsupported("INSERT ... SET"))?;
+
+        let rows = match source.body.as_ref() {
+            SetExpr::Values(values) => {
+                values.rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|expr| self.eval(expr, &Scope::EMPTY))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            _ => self.query(source, None)?.rows,
+        };
+
+        for (index, row) in rows.iter().enumerate() {
+            if row.len() != columns.len() {
+                return Err(
+                    sql_error(
+                        1136,
+                        format!("Column count doesn't match value count at row {}", index + 1)
+                    )
+                );
+            }
+        }
+        Ok(rows)
+    }
+
+    /// The table `INSERT` inserts into, and the positions of its columns.
+    fn insert_columns(&self, insert: &Insert) -> Result<(String, Vec<usize>), mysql::Error> {
+        let name = object_name(&insert.table_name);
+        let schema = &self.database.table(&name)?.schema;
+        let columns = if insert.columns.is_empty() {
+            (0..schema.columns.len()).collect()
+        } else {
+            insert.columns
+                .iter()
+                .map(|column| schema.column_index(&column.value))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        Ok((name, columns))
+    }
+
+    /// The changes of `UPDATE`, by table: the position of each row and its new values.
+    #[allow(clippy::type_complexity)]
+    fn update_changes(
+        &self,
+        table: &TableWithJoins,
+        assignments: &[sqlparser::ast::Assignment],
+        selection: Option<&Expr>
+    ) -> Result<Vec<(String, Vec<(usize, Vec<(usize, Value)>)>)>, mysql::Error> {
+        let joined = self.join(std::slice::from_ref(table), None)?;
+        let sources = joined.sources.as_slice();
+        let empty = Scope { sources, row: &[], group: None, outer: None };
+
+        let mut targets = Vec::with_capacity(assignments.len());
+        for assignment in assignments {
+            let AssignmentTarget::ColumnName(name) = &assignment.target else {
+                return Err(unsupported("tuples of columns in SET"));
+            };
+            let (source, position) = empty.locate(&name.0)?.ok_or_else(|| {
+                sql_error(1054, format!("Unknown column '{}' in 'field list'", name))
+            })?;
+            targets.push((source, position, &assignment.value));
+        }
+
+        let mut changes: Vec<(String, Vec<(usize, Vec<(usize, Value)>)>)> = sources
+            .iter()
+            .map(|source| (source.table.clone(), Vec::new()))
+            .collect();
+        let mut updated = vec![Vec::<usize>::new(); sources.len()];
+
+        for row in &joined.rows {
+            let mut values = row.values.clone();
+            let matches = match selection {
+                Some(selection) => {
+                    let scope = Scope { sources, row: &values, group: None, outer: None };
+                    truth(&self.eval(selection, &scope)?) == Some(true)
+                }
+                None => true,
+            };
+            if !matches {
+                continue;
+            }
+
+            let mut row_changes: Vec<Vec<(usize, Value)>> = vec![Vec::new(); sources.len()];
+            for (source, position, expr) in &targets {
+                let scope = Scope { sources, row: &values, group: None, outer: None };
+                let value = self.eval(expr, &scope)?;
+                // later assignments see the values of the earlier ones, like in MySQL
+                values[sources[*source].offset + position] = value.clone();
+                row_changes[*source].push((*position, value));
+            }
+            for (source, assignments) in row_changes.into_iter().enumerate() {
+                let Some(position) = row.positions[source] else {
+                    continue;
+                };
+                if assignments.is_empty() || updated[source].contains(&position) {
+                    continue;
+                }
+                updated[source].push(position);
+                changes[source].1.push((position, assignments));
+            }
+        }
+
+        Ok(
+            changes
+                .into_iter()
+                .filter(|(_, changes)| !changes.is_empty())
+                .collect()
+        )
+    }
+
+    /// The table `DELETE` deletes from, and the positions of the rows it deletes.
+    fn delete_rows(&self, delete: &Delete) -> Result<(String, Vec<usize>), mysql::Error> {
+        let from = match &delete.from {
+            FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from) => from,
+        };
+        let table = match from.as_slice() {
+            [table] if
+                table.joins.is_empty() &&
+                delete.tables.is_empty() &&
+                delete.using.is_none() &&
+                delete.returning.is_none() &&
+                delete.order_by.is_empty() &&
+                delete.limit.is_none()
+            => table,
+            _ => {
+                return Err(unsupported("DELETE from several tables, with ORDER BY or with LIMIT"));
+            }
+        };
+
+        let joined = self.join(std::slice::from_ref(table), None)?;
+        let mut rows = Vec::new();
+        for row in &joined.rows {
+            let matches = match &delete.selection {
+                Some(selection) => {
+                    let scope = Scope {
+                        sources: &joined.sources,
+                        row: &row.values,
+                        group: None,
+                        outer: None,
+                    };
+                    truth(&self.eval(selection, &scope)?) == Some(true)
+                }
+                None => true,
+            };
+            if let (true, Some(position)) = (matches, row.positions[0]) {
+                rows.push(position);
+            }
+        }
+        Ok((joined.sources[0].table.clone(), rows))
+    }
+
+    /// The table after the operations of `ALTER TABLE`.
+    fn alter_table(
+        &self,
+        table: &Table,
+        operations: &[AlterTableOperation]
+    ) -> Result<Table, mysql::Error> {
+        let mut table = table.clone();
+        for operation in operations {
+            match operation {
+                AlterTableOperation::AddColumn {
+                    if_not_exists,
+                    column_def,
+                    column_position,
+                    ..
+                } => {
+                    if *if_not_exists && table.schema.index_of(&column_def.name.value).is_some() {
+                        continue;
+                    }
+                    let options = column_def.options.iter().map(|option| &option.option);
+                    let column = ColumnSchema::new(
+                        &column_def.name,
+                        &column_def.data_type,
+                        options.clone()
+                    )?;
+                    let value = if column.default_now {
+                        date_value(self.now)
+                    } else {
+                        column.default.clone().unwrap_or_else(|| column.implicit_default())
+                    };
+                    let position = column_position_index(&table.schema, column_position.as_ref())?;
+                    let position = position.unwrap_or(table.schema.columns.len());
+                    table.schema.add_column(column, Some(position))?;
+                    table.schema.add_column_constraints(&column_def.name.value, options)?;
+                    for row in table.rows.iter_mut() {
+                        row.insert(position, value.clone());
+                    }
+                }
+                AlterTableOperation::DropColumn { column_name, if_exists, .. } => {
+                    let Some(position) = table.schema.index_of(&column_name.value) else {
+                        if *if_exists {
+                            continue;
+                        }
+                        return Err(
+                            sql_error(
+                                1091,
+                                format!(
+                                    "Can't DROP '{}'; check that column/key exists",
+                                    column_name.value
+                                )
+                            )
+                        );
+                    };
+                    let name = table.schema.columns[position].name.clone();
+                    self.check_column_not_referenced(&table, &name)?;
+                    table.schema.columns.remove(position);
+                    for key in table.schema.keys.iter_mut() {
+                        key.columns.retain(|column| !column.eq_ignore_ascii_case(&name));
+                    }
+                    table.schema.keys.retain(|key| !key.columns.is_empty());
+                    for row in table.rows.iter_mut() {
+                        row.remove(position);
+                    }
+                }
+                AlterTableOperation::ChangeColumn {
+                    old_name,
+                    new_name,
+                    data_type,
+                    options,
+                    column_position,
+                } => {
+                    self.change_column(
+                        &mut table,
+                        old_name,
+                        new_name,
+                        data_type,
+                        options,
+                        column_position.as_ref()
+                    )?;
+                }
+                AlterTableOperation::ModifyColumn {
+                    col_name,
+                    data_type,
+                    options,
+                    column_position,
+                } => {
+                    self.change_column(
+                        &mut table,
+                        col_name,
+                        col_name,
+                        data_type,
+                        options,
+                        column_position.as_ref()
+                    )?;
+                }
+                AlterTableOperation::RenameColumn { old_column_name, new_column_name } => {
+                    let position = table.schema.column_index(&old_column_name.value)?;
+                    let mut column = table.schema.columns[position].clone();
+                    column.name = new_column_name.value.clone();
+                    self.replace_column(&mut table, position, column, None, &[])?;
+                }
+                AlterTableOperation::AddConstraint(constraint) => {
+                    table.schema.add_constraint(constraint)?;
+                }
+                AlterTableOperation::DropPrimaryKey => {
+                    if table.schema.primary_key().is_none() {
+                        return Err(
+                            sql_error(1091, "Can't DROP 'PRIMARY'; check that column/key exists")
+                        );
+                    }
+                    table.schema.keys.retain(|key| key.name != "PRIMARY");
+                }
+                AlterTableOperation::DropConstraint { name, if_exists, .. } => {
+                    let count = table.schema.keys.len() + table.schema.foreign_keys.len();
+                    table.schema.keys.retain(|key| !key.name.eq_ignore_ascii_case(&name.value));
+                    table.schema.foreign_keys.retain(|foreign_key| foreign_key.name != name.value);
+                    let dropped = table.schema.keys.len() + table.schema.foreign_keys.len() != count;
+                    if !dropped && !*if_exists {
+                        return Err(
+                            sql_error(
+                                1091,
+                                format!("Can't DROP '{}'; check that column/key exists", name.value)
+                            )
+                        );
+                    }
+                }
+                other => {
+                    return Err(unsupported(&format!("ALTER TABLE ... {}", other)));
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// Rejects dropping a column a foreign key uses, like MySQL does.
+    fn check_column_not_referenced(&self, table: &Table, column: &str) -> Result<(), mysql::Error> {
+        let own = table.schema.foreign_keys
+            .iter()
+            .find(|foreign_key| foreign_key.columns.iter().any(|name| name.eq_ignore_ascii_case(column)));
+        if let Some(foreign_key) = own {
+            return Err(
+                sql_error(
+                    1828,
+                    format!(
+                        "Cannot drop column '{}': needed in a foreign key constraint '{}'",
+                        column,
+                        foreign_key.name
+                    )
+                )
+            );
+        }
+        for other in self.database.tables() {
+            let referencing = other.schema.foreign_keys.iter().find(|foreign_key| {
+                foreign_key.table == table.schema.name &&
+                    foreign_key.referenced_columns
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(column))
+            });
+            if let Some(foreign_key) = referencing {
+                return Err(
+                    sql_error(
+                        1829,
+                        format!(
+                            "Cannot drop column '{}': needed in a foreign key constraint '{}' of table '{}'",
+                            column,
+                            foreign_key.name,
+                            other.schema.name
+                        )
+                    )
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn change_column(
+        &self,
+        table: &mut Table,
+        old_name: &Ident,
+        new_name: &Ident,
+        data_type: &DataType,
+        options: &[sqlparser::ast::ColumnOption],
+        column_position: Option<&MySQLColumnPosition>
+    ) -> Result<(), mysql::Error> {
+        let position = table.schema.column_index(&old_name.value)?;
+        let column = ColumnSchema::new(new_name, data_type, options)?;
+        self.replace_column(table, position, column, column_position, options)
+    }
+
+    /// Replaces the column at `position`, converting its values to the new definition.
+    fn replace_column(
+        &self,
+        table: &mut Table,
+        position: usize,
+        column: ColumnSchema,
+        column_position: Option<&MySQLColumnPosition>,
+        options: &[sqlparser::ast::ColumnOption]
+    ) -> Result<(), mysql::Error> {
+        let old_name = table.schema.columns[position].name.clone();
+        for row in table.rows.iter_mut() {
+            row[position] = column.coerce(row[position].clone()).map_err(|error| {
+                match error {
+                    mysql::Error::MySqlError(ref inner) if inner.code == 1048 => {
+                        sql_error(1138, "Invalid use of NULL value")
+                    }
+                    other => other,
+                }
+            })?;
+        }
+
+        let new_name = column.name.clone();
+        table.schema.columns[position] = column;
+        let rename = |name: &mut String| {
+            if name.eq_ignore_ascii_case(&old_name) {
+                *name = new_name.clone();
+            }
+        };
+        for key in table.schema.keys.iter_mut() {
+            key.columns.iter_mut().for_each(rename);
+        }
+        for foreign_key in table.schema.foreign_keys.iter_mut() {
+            foreign_key.columns.iter_mut().for_each(rename);
+        }
+        if old_name != new_name {
+            self.check_column_not_referenced_by_others(table, &old_name)?;
+        }
+        table.schema.add_column_constraints(&new_name, options)?;
+
+        if let Some(column_position) = column_position {
+            let column = table.schema.columns.remove(position);
+            let values = table.rows
+                .iter_mut()
+                .map(|row| row.remove(position))
+                .collect::<Vec<_>>();
+            let target = column_position_index(&table.schema, Some(column_position))?.unwrap_or(0);
+            table.schema.columns.insert(target, column);
+            for (row, value) in table.rows.iter_mut().zip(values) {
+                row.insert(target, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_column_not_referenced_by_others(&self, table: &Table, column: &str) -> Result<(), mysql::Error> {
+        let referenced = self.database.tables().any(|other| {
+            other.schema.name != table.schema.name &&
+                other.schema.foreign_keys.iter().any(|foreign_key| {
+                    foreign_key.table == table.schema.name &&
+                        foreign_key.referenced_columns
+                            .iter()
+                            .any(|name| name.eq_ignore_ascii_case(column))
+                })
+        });
+        if referenced {
+            return Err(unsupported("renaming a column other tables reference"));
+        }
+        Ok(())
+    }
+
+    fn eval(&self, expr: &Expr, scope: &Scope) -> Result<Value, mysql::Error> {
+        match expr {
+            Expr::Identifier(ident) => Ok(scope.column(std::slice::from_ref(ident))?.0),
+            Expr::CompoundIdentifier(idents) => Ok(scope.column(idents)?.0),
+            Expr::Value(value) => self.value(value),
+            Expr::Nested(expr) => self.eval(expr, scope),
+            Expr::UnaryOp { op, expr } => {
+                let value = self.eval(expr, scope)?;
+                match op {
+                    UnaryOperator::Not => Ok(boolean(truth(&value).map(|truth| !truth))),
+                    UnaryOperator::Plus => Ok(value),
+                    UnaryOperator::Minus =>
+                        match value {
+                            Value::NULL => Ok(Value::NULL),
+                            Value::Int(int) =>
+                                int
+                                    .checked_neg()
+                                    .map(Value::Int)
+                                    .ok_or_else(|| out_of_range(expr)),
+                            other => Ok(Value::Double(-number(&other).unwrap_or_default())),
+                        }
+                    _ => Err(unsupported(&format!("the operator {}", op))),
+                }
+            }
+            Expr::BinaryOp { left, op, right } => self.binary(left, op, right, scope),
+            Expr::IsNull(expr) => Ok(boolean(Some(self.eval(expr, scope)? == Value::NULL))),
+            Expr::IsNotNull(expr) => Ok(boolean(Some(self.eval(expr, scope)? != Value::NULL))),
+            Expr::IsTrue(expr) => Ok(boolean(Some(truth(&self.eval(expr, scope)?) == Some(true)))),
+            Expr::IsNotTrue(expr) => {
+                Ok(boolean(Some(truth(&self.eval(expr, scope)?) != Some(true))))
+            }
+            Expr::IsFalse(expr) => {
+                Ok(boolean(Some(truth(&self.eval(expr, scope)?) == Some(false))))
+            }
+            Expr::IsNotFalse(expr) => {
+                Ok(boolean(Some(truth(&self.eval(expr, scope)?) != Some(false))))
+            }
+            Expr::InList { expr, list, negated } => {
+                let value = self.eval(expr, scope)?;
+                let candidates = list
+                    .iter()
+                    .map(|candidate| self.eval(candidate, scope))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(boolean(contains(&value, &candidates).map(|found| found != *negated)))
+            }
+            Expr::InSubquery { expr, subquery, negated } => {
+                let output = self.query(subquery, Some(scope))?;
+                let found = if let Expr::Tuple(items) = expr.as_ref() {
+                    let values = items
+                        .iter()
+                        .map(|item| self.eval(item, scope))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    check_width(output.columns.len(), values.len())?;
+                    let results = output.rows
+                        .iter()
+                        .map(|row| equal_rows(&values, row))
+                        .collect::<Vec<_>>();
+                    if results.contains(&Some(true)) {
+                        Some(true)
+                    } else if results.contains(&None) {
+                        None
+                    } else {
+                        Some(false)
+                    }
+                } else {
+                    check_width(output.columns.len(), 1)?;
+                    let value = self.eval(expr, scope)?;
+                    let candidates = output.rows
+                        .into_iter()
+                        .map(|mut row| row.remove(0))
+                        .collect::<Vec<_>>();
+                    contains(&value, &candidates)
+                };
+                Ok(boolean(found.map(|found| found != *negated)))
+            }
+            Expr::Between { expr, negated, low, high } => {
+                let value = self.eval(expr, scope)?;
+                let low = compare(&value, &self.eval(low, scope)?).map(|ordering| ordering.is_ge());
+                let high = compare(&value, &self.eval(high, scope)?).map(|ordering| ordering.is_le());
+                let between = and(low, high);
+                Ok(boolean(between.map(|between| between != *negated)))
+            }
+            Expr::Like { negated, any: false, expr, pattern, escape_char } => {
+                let value = self.eval(expr, scope)?;
+                let pattern = self.eval(pattern, scope)?;
+                if value == Value::NULL || pattern == Value::NULL {
+                    return Ok(Value::NULL);
+                }
+                let escape = escape_char
+                    .as_ref()
+                    .and_then(|escape| escape.chars().next())
+                    .unwrap_or('\\');
+                let matches = like(&display(&value), &display(&pattern), escape);
+                Ok(boolean(Some(matches != *negated)))
+            }
+            Expr::Exists { subquery, negated } => {
+                let output = self.query(subquery, Some(scope))?;
+                Ok(boolean(Some(output.rows.is_empty() == *negated)))
+            }
+            Expr::Subquery(query) => {
+                let mut values = self.row_subquery(query, scope)?;
+                check_width(values.len(), 1)?;
+                Ok(values.remove(0))
+            }
+            Expr::Function(function) => self.function(function, scope),
+            Expr::Case { operand, conditions, results, else_result } => {
+                let operand = match operand {
+                    Some(operand) => Some(self.eval(operand, scope)?),
+                    None => None,
+                };
+                for (condition, result) in conditions.iter().zip(results) {
+                    let value = self.eval(condition, scope)?;
+                    let matches = match &operand {
+                        Some(operand) => compare(operand, &value) == Some(Ordering::Equal),
+                        None => truth(&value) == Some(true),
+                    };
+                    if matches {
+                        return self.eval(result, scope);
+                    }
+                }
+                match else_result {
+                    Some(result) => self.eval(result, scope),
+                    None => Ok(Value::NULL),
+                }
+            }
+            Expr::Cast { expr, data_type, format: None, .. } => {
+                let value = self.eval(expr, scope)?;
+                cast(value, data_type)
+            }
+            Expr::TypedString { data_type, value } => cast(Value::from(value.as_str()), data_type),
+            Expr::Tuple(_) => Err(sql_error(1241, "Operand should contain 1 column(s)")),
+            other => Err(unsupported(&format!("the expression {}", other))),
+        }
+    }
+
+    fn value(&self, value: &SqlValue) -> Result<Value, mysql::Error> {
+        match value {
+            SqlValue::Placeholder(name) =>
+                self.params.get(name).cloned().ok_or_else(|| {
+                    let name = name.trim_start_matches(':').to_string();
+                    mysql::Error::DriverError(DriverError::MissingNamedParameter(name))
+                }),
+            SqlValue::Number(number, _) =>
+                Ok(
+                    number
+                        .parse::<i64>()
+                        .map(Value::Int)
+                        .unwrap_or_else(|_| Value::Double(number.parse().unwrap_or_default()))
+                ),
+            SqlValue::SingleQuotedString(text) | SqlValue::DoubleQuotedString(text) => {
+                Ok(Value::from(text.as_str()))
+            }
+            SqlValue::Boolean(boolean) => Ok(Value::Int(*boolean as i64)),
+            SqlValue::Null => Ok(Value::NULL),
+            other => Err(unsupported(&format!("the value {}", other))),
+        }
+    }
+
+    /// The values of a row: the items of a tuple, the row of a subquery or a single value.
+    fn row(&self, expr: &Expr, scope: &Scope) -> Result<Vec<Value>, mysql::Error> {
+        match expr {
+            Expr::Tuple(items) =>
+                items
+                    .iter()
+                    .map(|item| self.eval(item, scope))
+                    .collect(),
+            Expr::Subquery(query) => self.row_subquery(query, scope),
+            Expr::Nested(inner) if matches!(inner.as_ref(), Expr::Tuple(_) | Expr::Subquery(_)) => {
+                self.row(inner, scope)
+            }
+            other => Ok(vec![self.eval(other, scope)?]),
+        }
+    }
+
+    /// The row a subquery returns, `NULL`s if it returns none.
+    fn row_subquery(&self, query: &Query, scope: &Scope) -> Result<Vec<Value>, mysql::Error> {
+        let output = self.query(query, Some(scope))?;
+        match output.rows.len() {
+            0 => Ok(vec![Value::NULL; output.columns.len()]),
+            1 => Ok(output.rows.into_iter().next().unwrap()),
+            _ => Err(sql_error(1242, "Subquery returns more than 1 row")),
+        }
+    }
+
+    fn binary(
+        &self,
+        left: &Expr,
+        op: &BinaryOperator,
+        right: &Expr,
+        scope: &Scope
+    ) -> Result<Value, mysql::Error> {
+        match op {
+            BinaryOperator::And => {
+                let left = truth(&self.eval(left, scope)?);
+                if left == Some(false) {
+                    return Ok(boolean(Some(false)));
+                }
+                Ok(boolean(and(left, truth(&self.eval(right, scope)?))))
+            }
+            BinaryOperator::Or => {
+                let left = truth(&self.eval(left, scope)?);
+                if left == Some(true) {
+                    return Ok(boolean(Some(true)));
+                }
+                let right = truth(&self.eval(right, scope)?);
+                Ok(
+                    boolean(match (left, right) {
+                        (_, Some(true)) => Some(true),
+                        (Some(false), Some(false)) => Some(false),
+                        _ => None,
+                    })
+                )
+            }
+            BinaryOperator::Eq |
+            BinaryOperator::NotEq |
+            BinaryOperator::Lt |
+            BinaryOperator::LtEq |
+            BinaryOperator::Gt |
+            BinaryOperator::GtEq |
+            BinaryOperator::Spaceship => {
+                let (left, right) = (self.row(left, scope)?, self.row(right, scope)?);
+                check_width(left.len(), right.len())?;
+                if *op == BinaryOperator::Spaceship {
+                    let same_rows = left
+                        .iter()
+                        .zip(&right)
+                        .all(|(a, b)| same(a, b));
+                    return Ok(boolean(Some(same_rows)));
+                }
+                if matches!(op, BinaryOperator::Eq | BinaryOperator::NotEq) {
+                    let equal = equal_rows(&left, &right);
+                    return Ok(boolean(equal.map(|equal| equal == (*op == BinaryOperator::Eq))));
+                }
+                // rows compare on their first different item
+                let mut ordering = Some(Ordering::Equal);
+                for (a, b) in left.iter().zip(&right) {
+                    ordering = compare(a, b);
+                    if ordering != Some(Ordering::Equal) {
+                        break;
+                    }
+                }
+                Ok(
+                    boolean(
+                        ordering.map(|ordering| {
+                            match op {
+                                BinaryOperator::Lt => ordering.is_lt(),
+                                BinaryOperator::LtEq => ordering.is_le(),
+                                BinaryOperator::Gt => ordering.is_gt(),
+                                _ => ordering.is_ge(),
+                            }
+                        })
+                    )
+                )
+            }
+            BinaryOperator::Plus | BinaryOperator::Minus => {
+                if let Expr::Interval(interval) = right {
+                    return self.add_interval(left, interval, *op == BinaryOperator::Minus, scope);
+                }
+                if let (Expr::Interval(interval), BinaryOperator::Plus) = (left, op) {
+                    return self.add_interval(right, interval, false, scope);
+                }
+                arithmetic(op, self.eval(left, scope)?, self.eval(right, scope)?)
+            }
+            BinaryOperator::Multiply |
+            BinaryOperator::Divide |
+            BinaryOperator::Modulo |
+            BinaryOperator::MyIntegerDivide => {
+                arithmetic(op, self.eval(left, scope)?, self.eval(right, scope)?)
+            }
+            other => Err(unsupported(&format!("the operator {}", other))),
+        }
+    }
+
+    /// `date + INTERVAL n unit`, or `-` with `subtract`.
+    fn add_interval(
+        &self,
+        date: &Expr,
+        interval: &Interval,
+        subtract: bool,
+        scope: &Scope
+    ) -> Result<Value, mysql::Error> {
+        let date = self.eval(date, scope)?;
+        let amount = self.eval(&interval.value, scope)?;
+        let (Some(date_time), Some(amount)) = (to_date_time(&date), number(&amount)) else {
+            return Ok(Value::NULL);
+        };
+        let amount = if subtract { -amount.round() } else { amount.round() } as i64;
+
+        let result = match interval.leading_field.as_ref() {
+            Some(DateTimeField::Microsecond | DateTimeField::Microseconds) => {
+                date_time.checked_add_signed(Duration::microseconds(amount))
+            }
+            Some(DateTimeField::Second) => date_time.checked_add_signed(Duration::seconds(amount)),
+            Some(DateTimeField::Minute) => date_time.checked_add_signed(Duration::minutes(amount)),
+            Some(DateTimeField::Hour) => date_time.checked_add_signed(Duration::hours(amount)),
+            Some(DateTimeField::Day) => date_time.checked_add_signed(Duration::days(amount)),
+            Some(DateTimeField::Week(_)) => date_time.checked_add_signed(Duration::weeks(amount)),
+            Some(field @ (DateTimeField::Month | DateTimeField::Quarter | DateTimeField::Year)) => {
+                let months = match field {
+                    DateTimeField::Month => amount,
+                    DateTimeField::Quarter => amount * 3,
+                    _ => amount * 12,
+                };
+                if months >= 0 {
+                    date_time.checked_add_months(Months::new(months as u32))
+                } else {
+                    date_time.checked_sub_months(Months::new(-months as u32))
+                }
+            }
+            _ => {
+                return Err(unsupported(&format!("the interval {}", interval)));
+            }
+        };
+        Ok(result.map_or(Value::NULL, date_value))
+    }
+
+    fn function(&self, function: &Function, scope: &Scope) -> Result<Value, mysql::Error> {
+        let name = object_name(&function.name).to_uppercase();
+        let (args, distinct) = match &function.args {
+            FunctionArguments::None => (Vec::new(), false),
+            FunctionArguments::List(list) => {
+                let args = list.args
+                    .iter()
+                    .map(|arg| {
+                        match arg {
+                            FunctionArg::Unnamed(arg) => Ok(arg),
+                            other => Err(unsupported(&format!("the argument {}", other))),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                (args, list.duplicate_treatment == Some(DuplicateTreatment::Distinct))
+            }
+            FunctionArguments::Subquery(_) => {
+                return Err(unsupported(&format!("the function {}", function)));
+            }
+        };
+        if function.filter.is_some() || function.over.is_some() {
+            return Err(unsupported(&format!("the function {}", function)));
+        }
+
+        if is_aggregate(&name) {
+            return self.aggregate(&name, &args, distinct, scope);
+        }
+
+        let exprs = args
+            .iter()
+            .map(|arg| {
+                match arg {
+                    FunctionArgExpr::Expr(expr) => Ok(expr),
+                    _ => Err(syntax_error(format!("invalid argument of {}", name))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let arity = |count: usize| {
+            if exprs.len() == count {
+                Ok(())
+            } else {
+                Err(
+                    sql_error(
+                        1582,
+                        format!("Incorrect parameter count in the call to native function '{}'", name)
+                    )
+                )
+            }
+        };
+
+        // functions evaluating their arguments lazily or specially
+        match name.as_str() {
+            "IF" => {
+                arity(3)?;
+                let condition = truth(&self.eval(exprs[0], scope)?);
+                return self.eval(exprs[if condition == Some(true) { 1 } else { 2 }], scope);
+            }
+            "COALESCE" | "IFNULL" => {
+                if name == "IFNULL" {
+                    arity(2)?;
+                }
+                for expr in &exprs {
+                    let value = self.eval(expr, scope)?;
+                    if value != Value::NULL {
+                        return Ok(value);
+                    }
+                }
+                return Ok(Value::NULL);
+            }
+            "DATE_ADD" | "DATE_SUB" | "ADDDATE" | "SUBDATE" => {
+                arity(2)?;
+                let subtract = name == "DATE_SUB" || name == "SUBDATE";
+                return match exprs[1] {
+                    Expr::Interval(interval) => self.add_interval(exprs[0], interval, subtract, scope),
+                    days => {
+                        let interval = Interval {
+                            value: Box::new(days.clone()),
+                            leading_field: Some(DateTimeField::Day),
+                            leading_precision: None,
+                            last_field: None,
+                            fractional_seconds_precision: None,
+                        };
+                        self.add_interval(exprs[0], &interval, subtract, scope)
+                    }
+                };
+            }
+            _ => {}
+        }
+
+        let values = exprs
+            .iter()
+            .map(|expr| self.eval(expr, scope))
+            .collect::<Result<Vec<_>, _>>()?;
+        let any_null = values.contains(&Value::NULL);
+
+        match name.as_str() {
+            "NOW" | "CURRENT_TIMESTAMP" | "LOCALTIMESTAMP" | "LOCALTIME" | "SYSDATE" => {
+                Ok(date_value(self.now))
+            }
+            "UTC_TIMESTAMP" => Ok(date_value(Utc::now().naive_utc().with_nanosecond_zero())),
+            "CURDATE" | "CURRENT_DATE" => Ok(date_value(self.now.date().and_time(NaiveTime::MIN))),
+            "CURTIME" | "CURRENT_TIME" => Ok(time_value(self.now.time())),
+            "DATABASE" | "SCHEMA" => Ok(Value::from(DATABASE_NAME)),
+            "DATE" => {
+                arity(1)?;
+                Ok(
+                    to_date_time(&values[0]).map_or(Value::NULL, |date_time| {
+                        date_value(date_time.date().and_time(NaiveTime::MIN))
+                    })
+                )
+            }
+            "TIME" => {
+                arity(1)?;
+                Ok(to_time(&values[0]).map_or(Value::NULL, time_value))
+            }
+            "TIMESTAMP" => {
+                if exprs.len() != 1 {
+                    arity(2)?;
+                }
+                let Some(date_time) = to_date_time(&values[0]) else {
+                    return Ok(Value::NULL);
+                };
+                if values.len() == 1 {
+                    return Ok(date_value(date_time));
+                }
+                Ok(add_time(date_time, &values[1]).map_or(Value::NULL, date_value))
+            }
+            "DATEDIFF" => {
+                arity(2)?;
+                match (to_date_time(&values[0]), to_date_time(&values[1])) {
+                    (Some(a), Some(b)) => Ok(Value::Int((a.date() - b.date()).num_days())),
+                    _ => Ok(Value::NULL),
+                }
+            }
+            "WEEKDAY" | "DAYOFWEEK" => {
+                arity(1)?;
+                Ok(
+                    to_date_time(&values[0]).map_or(Value::NULL, |date_time| {
+                        let weekday = date_time.weekday();
+                        Value::Int(
+                            if name == "WEEKDAY" {
+                                weekday.num_days_from_monday().into()
+                            } else {
+                                (weekday.num_days_from_sunday() + 1).into()
+                            }
+                        )
+                    })
+                )
+            }
+            "NULLIF" => {
+                arity(2)?;
+                Ok(
+                    if compare(&values[0], &values[1]) == Some(Ordering::Equal) {
+                        Value::NULL
+                    } else {
+                        values[0].clone()
+                    }
+                )
+            }
+            "GREATEST" | "LEAST" => {
+                if any_null || values.is_empty() {
+                    return Ok(Value::NULL);
+                }
+                let wanted = if name == "GREATEST" { Ordering::Greater } else { Ordering::Less };
+                let mut best = values[0].clone();
+                for value in &values[1..] {
+                    if compare(value, &best) == Some(wanted) {
+                        best = value.clone();
+                    }
+                }
+                Ok(best)
+            }
+            "LOWER" | "LCASE" | "UPPER" | "UCASE" => {
+                arity(1)?;
+                if any_null {
+                    return Ok(Value::NULL);
+                }
+                let text = display(&values[0]);
+                Ok(
+                    Value::from(
+                        if name.starts_with('L') {
+                            text.to_lowercase()
+                        } else {
+                            text.to_uppercase()
+                        }
+                    )
+                )
+            }
+            "CONCAT" => {
+                if any_null {
+                    return Ok(Value::NULL);
+                }
+                Ok(Value::from(values.iter().map(display).collect::<String>()))
+            }
+            "LENGTH" | "CHAR_LENGTH" => {
+                arity(1)?;
+                if any_null {
+                    return Ok(Value::NULL);
+                }
+                let text = display(&values[0]);
+                Ok(
+                    Value::Int(
+                        (if name == "LENGTH" { text.len() } else { text.chars().count() }) as i64
+                    )
+                )
+            }
+            "ABS" => {
+                arity(1)?;
+                match &values[0] {
+                    Value::NULL => Ok(Value::NULL),
+                    Value::Int(int) => Ok(Value::Int(int.abs())),
+                    other => Ok(Value::Double(number(other).unwrap_or_default().abs())),
+                }
+            }
+            _ => Err(sql_error(1305, format!("FUNCTION {}.{} does not exist", DATABASE_NAME, name.to_lowercase()))),
+        }
+    }
+
+    fn aggregate(
+        &self,
+        name: &str,
+        args: &[&FunctionArgExpr],
+        distinct: bool,
+        scope: &Scope
+    ) -> Result<Value, mysql::Error> {
+        let Some(group) = scope.group else {
+            return Err(sql_error(1111, "Invalid use of group function"));
+        };
+        let expr = match args {
+            [FunctionArgExpr::Wildcard] if name == "COUNT" && !distinct => {
+                return Ok(Value::Int(group.len() as i64));
+            }
+            [FunctionArgExpr::Expr(expr)] => expr,
+            _ => {
+                return Err(syntax_error(format!("invalid arguments of {}", name)));
+            }
+        };
+
+        let mut values: Vec<Value> = Vec::new();
+        for row in group {
+            let row_scope = Scope { sources: scope.sources, row, group: None, outer: scope.outer };
+            let value = self.eval(expr, &row_scope)?;
+            if value == Value::NULL {
+                continue;
+            }
+            if distinct && values.iter().any(|other| same(other, &value)) {
+                continue;
+            }
+            values.push(value);
+        }
+
+        match name {
+            "COUNT" => Ok(Value::Int(values.len() as i64)),
+            "MIN" | "MAX" => {
+                let wanted = if name == "MAX" { Ordering::Greater } else { Ordering::Less };
+                let mut best = Value::NULL;
+                for value in values {
+                    if best == Value::NULL || compare(&value, &best) == Some(wanted) {
+                        best = value;
+                    }
+                }
+                Ok(best)
+            }
+            "SUM" | "AVG" => {
+                if values.is_empty() {
+                    return Ok(Value::NULL);
+                }
+                if name == "SUM" && values.iter().all(|value| matches!(value, Value::Int(_))) {
+                    let mut sum: i64 = 0;
+                    for value in &values {
+                        if let Value::Int(int) = value {
+                            sum = sum.checked_add(*int).ok_or_else(|| out_of_range(expr))?;
+                        }
+                    }
+                    return Ok(Value::Int(sum));
+                }
+                let sum = values
+                    .iter()
+                    .filter_map(number)
+                    .sum::<f64>();
+                Ok(
+                    Value::Double(
+                        if name == "SUM" {
+                            sum
+                        } else {
+                            sum / (values.len() as f64)
+                        }
+                    )
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+trait WithNanosecondZero {
+    fn with_nanosecond_zero(self) -> Self;
+}
+
+impl WithNanosecondZero for NaiveDateTime {
+    fn with_nanosecond_zero(self) -> Self {
+        use chrono::Timelike;
+        self.with_nanosecond(0).unwrap_or(self)
+    }
+}
+
+/// The kind of the column at `position` of the joined rows.
+fn column_kind(sources: &[Source], position: usize) -> Option<ColumnKind> {
+    sources
+        .iter()
+        .find(|source| position >= source.offset && position < source.offset + source.columns.len())
+        .and_then(|source| source.columns[position - source.offset].1.clone())
+}
+
+/// The kind of the column `idents` name, if it is a column of the sources.
+fn column_at(sources: &[Source], scope: &Scope, idents: &[Ident]) -> Option<ColumnKind> {
+    let (source, position) = scope.locate(idents).ok()??;
+    sources[source].columns[position].1.clone()
+}
+
+/// The type of a column of the output, from the kind of the column it comes from or from its
+/// values.
+fn column_type<'v>(kind: Option<&ColumnKind>, mut values: impl Iterator<Item = &'v Value>) -> ColumnType {
+    match kind {
+        Some(ColumnKind::INTEGER) => ColumnType::MYSQL_TYPE_LONGLONG,
+        Some(ColumnKind::FLOAT) => ColumnType::MYSQL_TYPE_FLOAT,
+        Some(ColumnKind::DOUBLE) => ColumnType::MYSQL_TYPE_DOUBLE,
+        Some(ColumnKind::DATE) => ColumnType::MYSQL_TYPE_DATE,
+        Some(ColumnKind::DATETIME) => ColumnType::MYSQL_TYPE_DATETIME,
+        Some(ColumnKind::TIME) => ColumnType::MYSQL_TYPE_TIME,
+        Some(ColumnKind::TEXT(_) | ColumnKind::ENUM(_)) => ColumnType::MYSQL_TYPE_VAR_STRING,
+        None =>
+            match values.find(|value| **value != Value::NULL) {
+                Some(Value::Int(_)) => ColumnType::MYSQL_TYPE_LONGLONG,
+                Some(Value::UInt(_)) => ColumnType::MYSQL_TYPE_LONGLONG,
+                Some(Value::Float(_)) => ColumnType::MYSQL_TYPE_FLOAT,
+                Some(Value::Double(_)) => ColumnType::MYSQL_TYPE_DOUBLE,
+                Some(Value::Date(..)) => ColumnType::MYSQL_TYPE_DATETIME,
+                Some(Value::Time(..)) => ColumnType::MYSQL_TYPE_TIME,
+                Some(Value::Bytes(_)) => ColumnType::MYSQL_TYPE_VAR_STRING,
+                _ => ColumnType::MYSQL_TYPE_NULL,
+            }
+    }
+}
+
+/// The tables of `information_schema` the application reads: `TABLES` and `COLUMNS`.
+fn information_schema(database: &Database, name: &str) -> Result<Table, mysql::Error> {
+    let text = |name: &str| (name.to_string(), ColumnKind::TEXT(usize::MAX));
+    let (columns, rows): (Vec<(String, ColumnKind)>, Vec<Vec<Value>>) = match name.to_uppercase().as_str() {
+        "TABLES" => {
+            let columns = vec![
+                text("TABLE_SCHEMA"),
+                text("TABLE_NAME"),
+                text("TABLE_TYPE"),
+                ("TABLE_ROWS".to_string(), ColumnKind::INTEGER)
+            ];
+            let rows = database
+                .tables()
+                .map(|table| {
+                    vec![
+                        Value::from(DATABASE_NAME),
+                        Value::from(table.schema.name.as_str()),
+                        Value::from("BASE TABLE"),
+                        Value::Int(table.rows.len() as i64)
+                    ]
+                })
+                .collect();
+            (columns, rows)
+        }
+        "COLUMNS" => {
+            let columns = vec![
+                text("TABLE_SCHEMA"),
+                text("TABLE_NAME"),
+                text("COLUMN_NAME"),
+                ("ORDINAL_POSITION".to_string(), ColumnKind::INTEGER),
+                text("COLUMN_DEFAULT"),
+                text("IS_NULLABLE"),
+                text("DATA_TYPE"),
+                text("COLUMN_TYPE")
+            ];
+            let rows = database
+                .tables()
+                .flat_map(|table| {
+                    table.schema.columns
+                        .iter()
+                        .enumerate()
+                        .map(|(index, column)| {
+                            let default = if column.default_now {
+                                Value::from("CURRENT_TIMESTAMP")
+                            } else {
+                                match &column.default {
+                                    Some(Value::NULL) | None => Value::NULL,
+                                    Some(value) => Value::from(column.display(value)),
+                                }
+                            };
+                            vec![
+                                Value::from(DATABASE_NAME),
+                                Value::from(table.schema.name.as_str()),
+                                Value::from(column.name.as_str()),
+                                Value::Int((index as i64) + 1),
+                                default,
+                                Value::from(if column.nullable { "YES" } else { "NO" }),
+                                Value::from(column.data_type.as_str()),
+                                Value::from(column.column_type.as_str())
+                            ]
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            (columns, rows)
+        }
+        _ => {
+            return Err(
+                sql_error(1109, format!("Unknown table '{}' in information_schema", name))
+            );
+        }
+    };
+
+    let schema = TableSchema {
+        name: name.to_uppercase(),
+        columns: columns
+            .into_iter()
+            .map(|(name, kind)| ColumnSchema {
+                name,
+                kind,
+                data_type: String::new(),
+                column_type: String::new(),
+                nullable: true,
+                default: Some(Value::NULL),
+                default_now: false,
+                update_now: false,
+            })
+            .collect(),
+        keys: Vec::new(),
+        foreign_keys: Vec::new(),
+    };
+    Ok(Table { schema, rows })
+}
+
+/// Applies `CREATE INDEX` to a table: a unique index adds a key, other indexes change nothing.
+fn alter_index(table: &Table, create_index: &CreateIndex) -> Result<Table, mysql::Error> {
+    let mut table = table.clone();
+    let columns = create_index.columns
+        .iter()
+        .map(|column| {
+            match &column.expr {
+                Expr::Identifier(ident) => Ok(ident.clone()),
+                other => Err(unsupported(&format!("the index on {}", other))),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for column in &columns {
+        table.schema.column_index(&column.value)?;
+    }
+    if create_index.unique {
+        let constraint = sqlparser::ast::TableConstraint::Unique {
+            name: create_index.name.as_ref().and_then(|name| name.0.last().cloned()),
+            index_name: None,
+            index_type_display: sqlparser::ast::KeyOrIndexDisplay::None,
+            index_type: None,
+            columns,
+            index_options: Vec::new(),
+            characteristics: None,
+            nulls_distinct: sqlparser::ast::NullsDistinctOption::None,
+        };
+        table.schema.add_constraint(&constraint)?;
+    }
+    Ok(table)
+}
+
+/// The position a column goes to with `FIRST` or `AFTER column`.
+fn column_position_index(
+    schema: &TableSchema,
+    position: Option<&MySQLColumnPosition>
+) -> Result<Option<usize>, mysql::Error> {
+    match position {
+        None => Ok(None),
+        Some(MySQLColumnPosition::First) => Ok(Some(0)),
+        Some(MySQLColumnPosition::After(column)) => {
+            Ok(Some(schema.column_index(&column.value)? + 1))
+        }
+    }
+}
+
+/// Whether a value is true, `None` for `NULL`.
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::NULL => None,
+        Value::Date(..) => Some(true),
+        other => number(other).map(|number| number != 0.0),
+    }
+}
+
+fn boolean(value: Option<bool>) -> Value {
+    match value {
+        Some(value) => Value::Int(value as i64),
+        None => Value::NULL,
+    }
+}
+
+/// `AND` of two truth values.
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+/// Whether two rows are equal, `None` if it depends on a `NULL`.
+fn equal_rows(a: &[Value], b: &[Value]) -> Option<bool> {
+    let mut equal = Some(true);
+    for (a, b) in a.iter().zip(b) {
+        match compare(a, b) {
+            Some(Ordering::Equal) => {}
+            Some(_) => {
+                return Some(false);
+            }
+            None => {
+                equal = None;
+            }
+        }
+    }
+    equal
+}
+
+/// `value IN (candidates)`.
+fn contains(value: &Value, candidates: &[Value]) -> Option<bool> {
+    if *value == Value::NULL {
+        return None;
+    }
+    let mut found = Some(false);
+    for candidate in candidates {
+        match compare(value, candidate) {
+            Some(Ordering::Equal) => {
+                return Some(true);
+            }
+            None => {
+                found = None;
+            }
+            Some(_) => {}
+        }
+    }
+    found
+}
+
+fn check_width(actual: usize, expected: usize) -> Result<(), mysql::Error> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(sql_error(1241, format!("Operand should contain {} column(s)", expected)))
+    }
+}
+
+fn out_of_range(expr: &Expr) -> mysql::Error {
+    sql_error(1690, format!("BIGINT value is out of range in '{}'", expr))
+}
+
+fn arithmetic(op: &BinaryOperator, left: Value, right: Value) -> Result<Value, mysql::Error> {
+    if left == Value::NULL || right == Value::NULL {
+        return Ok(Value::NULL);
+    }
+    let overflow = || sql_error(1690, "BIGINT value is out of range");
+    if let (Value::Int(a), Value::Int(b)) = (&left, &right) {
+        let (a, b) = (*a, *b);
+        return match op {
+            BinaryOperator::Plus => a.checked_add(b).map(Value::Int).ok_or_else(overflow),
+            BinaryOperator::Minus => a.checked_sub(b).map(Value::Int).ok_or_else(overflow),
+            BinaryOperator::Multiply => a.checked_mul(b).map(Value::Int).ok_or_else(overflow),
+            BinaryOperator::Divide if b == 0 => Ok(Value::NULL),
+            BinaryOperator::Divide => Ok(Value::Double((a as f64) / (b as f64))),
+            BinaryOperator::Modulo | BinaryOperator::MyIntegerDivide if b == 0 => Ok(Value::NULL),
+            BinaryOperator::Modulo => Ok(Value::Int(a % b)),
+            _ => Ok(Value::Int(a / b)),
+        };
+    }
+    let (a, b) = (number(&left).unwrap_or_default(), number(&right).unwrap_or_default());
+    Ok(match op {
+        BinaryOperator::Plus => Value::Double(a + b),
+        BinaryOperator::Minus => Value::Double(a - b),
+        BinaryOperator::Multiply => Value::Double(a * b),
+        _ if b == 0.0 => Value::NULL,
+        BinaryOperator::Divide => Value::Double(a / b),
+        BinaryOperator::Modulo => Value::Double(a % b),
+        _ => Value::Int((a / b).trunc() as i64),
+    })
+}
+
+/// `TIMESTAMP(date, time)`: the date and time plus a time, which may be negative or longer than a
+/// day.
+fn add_time(date_time: NaiveDateTime, time: &Value) -> Option<NaiveDateTime> {
+    let time = match time {
+        Value::Bytes(bytes) => super::schema::parse_time(&String::from_utf8_lossy(bytes))?,
+        Value::Date(..) => time_value(to_time(time)?),
+        other => other.clone(),
+    };
+    let Value::Time(negative, days, hours, minutes, seconds, micros) = time else {
+        return None;
+    };
+    let duration =
+        Duration::days(days.into()) +
+        Duration::hours(hours.into()) +
+        Duration::minutes(minutes.into()) +
+        Duration::seconds(seconds.into()) +
+        Duration::microseconds(micros.into());
+    if negative {
+        date_time.checked_sub_signed(duration)
+    } else {
+        date_time.checked_add_signed(duration)
+    }
+}
+
+fn cast(value: Value, data_type: &DataType) -> Result<Value, mysql::Error> {
+    if value == Value::NULL {
+        return Ok(Value::NULL);
+    }
+    let type_name = data_type.to_string().to_uppercase();
+    Ok(match data_type {
+        DataType::Date => {
+            to_date_time(&value).map_or(Value::NULL, |date_time| {
+                date_value(date_time.date().and_time(NaiveTime::MIN))
+            })
+        }
+        DataType::Datetime(_) | DataType::Timestamp(..) => {
+            to_date_time(&value).map_or(Value::NULL, date_value)
+        }
+        DataType::Time(..) => to_time(&value).map_or(Value::NULL, time_value),
+        DataType::Char(_) | DataType::Varchar(_) | DataType::Text => Value::from(display(&value)),
+        DataType::Decimal(_) | DataType::Double | DataType::Float(_) => {
+            Value::Double(number(&value).unwrap_or_default())
+        }
+        _ if
+            type_name.starts_with("SIGNED") ||
+            type_name.starts_with("UNSIGNED") ||
+            type_name.starts_with("INT") ||
+            type_name.starts_with("BIGINT")
+        => {
+            Value::Int(number(&value).unwrap_or_default().round() as i64)
+        }
+        _ => {
+            return Err(unsupported(&format!("CAST to {}", data_type)));
+        }
+    })
+}
+
+/// `text LIKE pattern`, without case.
+fn like(text: &str, pattern: &str, escape: char) -> bool {
+    fn matches(text: &[char], pattern: &[char], escape: char) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('%', rest)) => (0..=text.len()).any(|skip| matches(&text[skip..], rest, escape)),
+            Some(('_', rest)) => !text.is_empty() && matches(&text[1..], rest, escape),
+            Some((first, rest)) => {
+                let (literal, rest) = if *first == escape && !rest.is_empty() {
+                    (rest[0], &rest[1..])
+                } else {
+                    (*first, rest)
+                };
+                match text.split_first() {
+                    Some((char, text)) => {
+                        compare_text(&char.to_string(), &literal.to_string()).is_eq() &&
+                            matches(text, rest, escape)
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+    let text = text.chars().collect::<Vec<_>>();
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    matches(&text, &pattern, escape)
+}
+
+fn is_aggregate(name: &str) -> bool {
+    matches!(name, "COUNT" | "SUM" | "MIN" | "MAX" | "AVG")
+}
+
+/// Whether an expression calls an aggregate function, outside of its subqueries.
+fn has_aggregate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(function) => {
+            if is_aggregate(&object_name(&function.name).to_uppercase()) {
+                return true;
+            }
+            match &function.args {
+                FunctionArguments::List(list) =>
+                    list.args.iter().any(|arg| {
+                        matches!(arg, FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) if has_aggregate(expr))
+                    }),
+                _ => false,
+            }
+        }
+        Expr::Nested(expr) |
+        Expr::UnaryOp { expr, .. } |
+        Expr::IsNull(expr) |
+        Expr::IsNotNull(expr) |
+        Expr::IsTrue(expr) |
+        Expr::IsNotTrue(expr) |
+        Expr::IsFalse(expr) |
+        Expr::IsNotFalse(expr) |
+        Expr::Cast { expr, .. } => has_aggregate(expr),
+        Expr::BinaryOp { left, right, .. } => has_aggregate(left) || has_aggregate(right),
+        Expr::Between { expr, low, high, .. } => {
+            has_aggregate(expr) || has_aggregate(low) || has_aggregate(high)
+        }
+        Expr::InList { expr, list, .. } => has_aggregate(expr) || list.iter().any(has_aggregate),
+        Expr::Like { expr, pattern, .. } => has_aggregate(expr) || has_aggregate(pattern),
+        Expr::Tuple(items) => items.iter().any(has_aggregate),
+        Expr::Case { operand, conditions, results, else_result } => {
+            operand.as_deref().is_some_and(has_aggregate) ||
+                conditions.iter().any(has_aggregate) ||
+                results.iter().any(has_aggregate) ||
+                else_result.as_deref().is_some_and(has_aggregate)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mysql::{ from_row, params };
+
+    use super::*;
+
+    fn run(database: &mut Database, sql: &str) -> Vec<Row> {
+        execute(database, sql, Params::Empty).unwrap().into_rows()
+    }
+
+    fn error_code(database: &mut Database, sql: &str) -> u16 {
+        match execute(database, sql, Params::Empty) {
+            Err(mysql::Error::MySqlError(error)) => error.code,
+            other => panic!("{} should fail, got {:?}", sql, other),
+        }
+    }
+
+    fn setup() -> Database {
+        let mut database = Database::default();
+        run(
+            &mut database,
+            "CREATE TABLE teams (
+                id BIGINT NOT NULL PRIMARY KEY,
+                name VARCHAR(20) NOT NULL UNIQUE
+            );
+            CREATE TABLE players (
+                id BIGINT NOT NULL PRIMARY KEY,
+                team_id BIGINT,
+                name VARCHAR(20) NOT NULL,
+                position ENUM('GOAL', 'DEFENSE', 'ATTACK') NOT NULL DEFAULT 'ATTACK',
+                joined DATE NOT NULL,
+                FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE SET NULL
+            );
+            INSERT INTO teams (id, name) VALUES (1, 'Red'), (2, 'Blue'), (3, 'Green');
+            INSERT INTO players (id, team_id, name, position, joined) VALUES
+                (1, 1, 'Ann', 'GOAL', '2024-01-10'),
+                (2, 1, 'Bob', 'DEFENSE', '2024-02-01'),
+                (3, 2, 'Cid', 'ATTACK', '2024-01-20'),
+                (4, NULL, 'Dee', 'DEFENSE', '2024-03-05');"
+        );
+        database
+    }
+
+    #[test]
+    fn test_select() {
+        let mut database = setup();
+
+        let rows = run(
+            &mut database,
+            "SELECT p.name, t.name AS team FROM players p
+            LEFT JOIN teams t ON t.id = p.team_id
+            WHERE p.joined >= '2024-01-15' ORDER BY p.name DESC;"
+        );
+        let rows = rows
+            .into_iter()
+            .map(from_row::<(String, Option<String>)>)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                ("Dee".to_string(), None),
+                ("Cid".to_string(), Some("Blue".to_string())),
+                ("Bob".to_string(), Some("Red".to_string()))
+            ]
+        );
+
+        // aggregates, with a row for an empty group
+        let rows = run(
+            &mut database,
+            "SELECT t.name, COUNT(p.id) AS players FROM teams t
+            LEFT JOIN players p ON p.team_id = t.id
+            GROUP BY t.id, t.name HAVING COUNT(p.id) < 2 ORDER BY players, 1;"
+        );
+        let rows = rows.into_iter().map(from_row::<(String, i64)>).collect::<Vec<_>>();
+        assert_eq!(rows, vec![("Green".to_string(), 0), ("Blue".to_string(), 1)]);
+        let count = run(&mut database, "SELECT COUNT(*), MAX(joined) FROM players WHERE id > 10;");
+        assert_eq!(from_row::<(i64, Option<String>)>(count[0].clone()), (0, None));
+
+        // enums sort in the order of their values, strings compare without case
+        let rows = run(
+            &mut database,
+            "SELECT name FROM players WHERE name IN ('ann', 'BOB', 'dee') ORDER BY position, id LIMIT 2 OFFSET 1;"
+        );
+        let names = rows.into_iter().map(from_row::<String>).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Bob", "Dee"]);
+
+        // row comparisons with a subquery, like the cursors of the pages
+        let rows = run(
+            &mut database,
+            "SELECT id FROM players
+            WHERE (joined, id) > (SELECT joined, id FROM players WHERE id = 3)
+            ORDER BY joined, id;"
+        );
+        let ids = rows.into_iter().map(from_row::<i64>).collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 4]);
+
+        // correlated subqueries and dates
+        let rows = run(
+            &mut database,
+            "SELECT teams.name, DATE(joined + INTERVAL 1 MONTH), TIMESTAMP(joined, '25:30:00') FROM teams
+            JOIN players ON players.team_id = teams.id
+            WHERE EXISTS (SELECT 1 FROM players other WHERE other.team_id = teams.id AND other.id <> players.id)
+            ORDER BY joined;"
+        );
+        let rows = rows
+            .into_iter()
+            .map(from_row::<(String, Value, Value)>)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "Red".to_string(),
+                    Value::Date(2024, 2, 10, 0, 0, 0, 0),
+                    Value::Date(2024, 1, 11, 1, 30, 0, 0),
+                ),
+                (
+                    "Red".to_string(),
+                    Value::Date(2024, 3, 1, 0, 0, 0, 0),
+                    Value::Date(2024, 2, 2, 1, 30, 0, 0),
+                )
+            ]
+        );
+
+        let rows = run(
+            &mut database,
+            "SELECT COALESCE(team_id, 0), IF(position = 'goal', 'yes', 'no'), DATABASE()
+            FROM players WHERE id = 1 OR name LIKE 'd%' ORDER BY id;"
+        );
+        let rows = rows.into_iter().map(from_row::<(i64, String, String)>).collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                (1, "yes".to_string(), "memory".to_string()),
+                (0, "no".to_string(), "memory".to_string())
+            ]
+        );
+
+        assert_eq!(error_code(&mut database, "SELECT id FROM players JOIN teams;"), 1052);
+        assert_eq!(error_code(&mut database, "SELECT nope FROM players;"), 1054);
+        assert_eq!(error_code(&mut database, "SELECT * FROM nope;"), 1146);
+        assert_eq!(error_code(&mut database, "SELECT (SELECT id FROM players);"), 1242);
+        assert_eq!(error_code(&mut database, "SELECT NOPE(1);"), 1305);
+        assert_eq!(error_code(&mut database, "SELEC 1;"), 1064);
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut database = setup();
+
+        // named and positional parameters
+        let output = execute(
+            &mut database,
+            "UPDATE players SET position = :position WHERE team_id = :team_id;",
+            params! { "position" => "attack", "team_id" => 1 }
+        ).unwrap();
+        assert_eq!(output.affected_rows, 2);
+        let output = execute(
+            &mut database,
+            "UPDATE players SET position = ? WHERE team_id = ?;",
+            Params::Positional(vec![Value::from("ATTACK"), Value::from(1)])
+        ).unwrap();
+        // the rows already had these values
+        assert_eq!(output.affected_rows, 0);
+        assert!(
+            matches!(
+                execute(&mut database, "SELECT :missing;", Params::Empty),
+                Err(mysql::Error::DriverError(DriverError::MissingNamedParameter(name))) if name == "missing"
+            )
+        );
+
+        // updates with a join, set null and cascades of foreign keys
+        let output = execute(
+            &mut database,
+            "UPDATE players p JOIN teams t ON t.id = p.team_id SET p.name = CONCAT(t.name, p.name)
+            WHERE t.name = 'Red';",
+            Params::Empty
+        ).unwrap();
+        assert_eq!(output.affected_rows, 2);
+        assert_eq!(error_code(&mut database, "UPDATE teams SET id = 5 WHERE id = 1;"), 1451);
+        assert_eq!(error_code(&mut database, "UPDATE players SET team_id = 9;"), 1452);
+        assert_eq!(error_code(&mut database, "UPDATE teams SET name = 'Blue' WHERE id = 1;"), 1062);
+        assert_eq!(error_code(&mut database, "UPDATE players SET position = 'COACH';"), 1265);
+        assert_eq!(error_code(&mut database, "UPDATE players SET name = NULL;"), 1048);
+
+        let output = execute(&mut database, "DELETE FROM teams WHERE id = 1;", Params::Empty);
+        assert_eq!(output.unwrap().affected_rows, 1);
+        let rows = run(&mut database, "SELECT name FROM players WHERE team_id IS NULL ORDER BY id;");
+        let names = rows.into_iter().map(from_row::<String>).collect::<Vec<_>>();
+        assert_eq!(names, vec!["RedAnn", "RedBob", "Dee"]);
+
+        assert_eq!(error_code(&mut database, "INSERT INTO teams (id) VALUES (4);"), 1364);
+        assert_eq!(error_code(&mut database, "INSERT INTO teams (id, name) VALUES (4);"), 1136);
+        run(&mut database, "INSERT INTO teams (id, name) SELECT id + 10, CONCAT(name, '!') FROM teams;");
+        let rows = run(&mut database, "SELECT name FROM teams WHERE id > 10 ORDER BY id;");
+        let names = rows.into_iter().map(from_row::<String>).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Blue!", "Green!"]);
+    }
+
+    #[test]
+    fn test_schema_changes() {
+        let mut database = setup();
+
+        run(
+            &mut database,
+            "ALTER TABLE players ADD COLUMN number INT NOT NULL DEFAULT 7 AFTER name,
+            CHANGE joined joined_on DATE NULL, MODIFY position VARCHAR(10) NOT NULL;"
+        );
+        let rows = run(
+            &mut database,
+            "SELECT COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE FROM information_schema.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'players' ORDER BY ORDINAL_POSITION;"
+        );
+        let columns = rows
+            .into_iter()
+            .map(from_row::<(String, String, String)>)
+            .map(|(name, column_type, nullable)| format!("{} {} {}", name, column_type, nullable))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                "id bigint NO",
+                "team_id bigint YES",
+                "name varchar(20) NO",
+                "number int NO",
+                "position varchar(10) NO",
+                "joined_on date YES"
+            ]
+        );
+        let rows = run(&mut database, "SELECT number, position FROM players WHERE id = 1;");
+        assert_eq!(from_row::<(i64, String)>(rows[0].clone()), (7, "GOAL".to_string()));
+
+        // values that don't fit the new definition fail the change
+        assert_eq!(error_code(&mut database, "ALTER TABLE players MODIFY team_id BIGINT NOT NULL;"), 1138);
+        assert_eq!(error_code(&mut database, "ALTER TABLE players DROP COLUMN nope;"), 1091);
+        assert_eq!(error_code(&mut database, "ALTER TABLE teams DROP COLUMN id;"), 1829);
+        assert_eq!(error_code(&mut database, "DROP TABLE teams;"), 3730);
+        assert_eq!(error_code(&mut database, "CREATE TABLE teams (id INT);"), 1050);
+
+        run(&mut database, "DROP TABLE IF EXISTS players, nope; CREATE UNIQUE INDEX team_name ON teams (name);");
+        let rows = run(
+            &mut database,
+            "SELECT TABLE_NAME FROM information_schema.TABLES WHERE TABLE_SCHEMA = DATABASE();"
+        );
+        assert_eq!(rows.into_iter().map(from_row::<String>).collect::<Vec<_>>(), vec!["teams"]);
+        assert_eq!(error_code(&mut database, "DROP TABLE players;"), 1051);
+    }
+}
@@ -1,34 +1,65 @@
-use std::env;
-use dotenv::dotenv;
+use std::{ env, sync::atomic::{ AtomicU32, Ordering } };
 use mysql::*;
-use mysql::prelude::*;
 
 use crate::models::result::Result;
-use crate::queries::create_tables;
+use crate::migrations::migrate_up;
+use crate::storage::{ Connection, Queryable, Storage };
 
-pub fn initialize_test_db() -> Result<Pool> {
-    dotenv().ok();
-    // Setup database connection (replace with your test database credentials)
-    let url: String = env
-        ::var("DATABASE_URL_TEST")
-        .expect("DATABASE_URL_TEST must be set in the .env file");
+/// bcrypt cost of the passwords hashed by the tests, the default one would slow them down.
+pub const TEST_HASH_COST: u32 = 4;
 
-    let pool = Pool::new(url.as_str())?;
-    let mut conn: PooledConn = pool.get_conn()?;
+/// Numbers the databases created by the tests of this process.
+static TEST_DATABASE_COUNT: AtomicU32 = AtomicU32::new(0);
 
-    conn.query_drop("DROP DATABASE IF EXISTS worktest;")?;
+/// Creates a migrated database of its own for a test on the MySQL server at `url` and returns
+/// its URL.
+///
+/// Every call gets a new database, named after the process and a counter, so tests can run in
+/// parallel against the same server.
+pub fn create_test_database(url: &str) -> Result<String> {
+    let database = format!(
+        "worktest_{}_{}",
+        std::process::id(),
+        TEST_DATABASE_COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+
+    let pool = Pool::new(url)?;
+    let mut conn = Connection::from_pool(&pool)?;
+
+    conn.query_drop(format!("DROP DATABASE IF EXISTS {};", database))?;
     conn.query_drop(
-        "CREATE DATABASE IF NOT EXISTS worktest DEFAULT CHARSET = utf8mb4 DEFAULT COLLATE = utf8mb4_unicode_ci;"
+        format!(
+            "CREATE DATABASE {} DEFAULT CHARSET = utf8mb4 DEFAULT COLLATE = utf8mb4_unicode_ci;",
+            database
+        )
     )?;
-    conn.query_drop("USE worktest;")?;
+    conn.query_drop(format!("USE {};", database))?;
 
-    let _ = create_tables(&mut conn);
+    migrate_up(&mut conn, None)?;
+
+    Ok(format!("{}/{}", url, database))
+}
 
-    Ok(pool)
+/// The storage of a test, with every migration applied.
+///
+/// Tests run in memory, or against the MySQL server at `DATABASE_URL_TEST` when it is set, each
+/// in a database of its own.
+pub fn initialize_test_db() -> Result<Storage> {
+    match env::var("DATABASE_URL_TEST") {
+        Ok(url) => Ok(Storage::MySql(Pool::new(create_test_database(&url)?.as_str())?)),
+        Err(_) => Storage::memory(),
+    }
 }
 
-pub fn cleanup_test_db(mut conn: PooledConn) -> Result<()> {
-    conn.query_drop("DROP DATABASE IF EXISTS worktest;")?;
+/// Drops the MySQL database `conn` is using, in-memory tables go away with their storage.
+pub fn cleanup_test_db(mut conn: Connection) -> Result<()> {
+    if let Connection::Memory(_) = conn {
+        return Ok(());
+    }
+    let database: Option<String> = conn.query_first("SELECT DATABASE();")?.flatten();
+    if let Some(database) = database {
+        conn.query_drop(format!("DROP DATABASE IF EXISTS {};", database))?;
+    }
 
     Ok(())
 }
@@ -1,75 +1,210 @@
 // utilities/app_error.rs
 use axum::{ http::StatusCode, response::IntoResponse, Json };
 use serde::{ Deserialize, Serialize };
+use utoipa::ToSchema;
 use std::fmt;
 use std::error::Error;
 
+use mysql::MySqlError;
+
+use crate::models::{
+    result::NotFoundError,
+    org_member::{ PermissionDeniedError, LastOwnerError },
+    pagination::ListQueryError,
+    schedule::{
+        shift::{ ShiftValidationError, ShiftValidationRule },
+        InvalidStatusTransitionError,
+    },
+};
+use crate::{ metrics::metrics, prototypes::validate::ValidationError };
+
 pub type BoxedError = Box<dyn Error + Send + Sync + 'static>;
 
+tokio::task_local! {
+    /// The id of the request being handled, set by the `request_id` middleware.
+    pub static REQUEST_ID: String;
+}
+
+/// Machine-readable kind of an error, sent as the `code` of error responses.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
+pub enum ErrorCode {
+    /// The request is malformed, e.g. an unknown sort column
+    BAD_REQUEST,
+    /// The access token is missing or invalid
+    UNAUTHORIZED,
+    /// The caller isn't allowed to do this
+    FORBIDDEN,
+    /// The entity doesn't exist
+    NOT_FOUND,
+    /// The request conflicts with the current state of the entity
+    CONFLICT,
+    /// A value that must be unique is already taken, see `fields`
+    UNIQUE_VIOLATION,
+    /// A reference to another entity is missing, or the entity is still referenced
+    FOREIGN_KEY_VIOLATION,
+    /// Some fields are invalid, see `fields`
+    VALIDATION_FAILED,
+    /// The entity can't move to the requested status
+    INVALID_STATUS_TRANSITION,
+    /// The database failed
+    DATABASE_ERROR,
+    /// The server is shutting down, retry on another instance
+    SHUTTING_DOWN,
+    /// Anything else
+    INTERNAL_ERROR,
+}
+
+/// Why the value of a request field was rejected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), message: message.into() }
+    }
+}
+
 #[derive(Debug)]
 pub struct AppError {
-    code: StatusCode,
+    status: StatusCode,
+    code: ErrorCode,
     message: String,
+    /// Fields of the request the error is about
+    fields: Vec<FieldError>,
+    /// Structured information about the error, sent along with the message
+    details: Option<serde_json::Value>,
 }
 
 impl AppError {
-    pub fn new(code: StatusCode, message: impl Into<String>) -> Self {
+    pub fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
         Self {
+            status,
             code,
             message: message.into(),
+            fields: Vec::new(),
+            details: None,
         }
     }
 
     pub fn internal_server_error(message: impl Into<String>) -> Self {
-        Self {
-            code: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.into(),
-        }
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::INTERNAL_ERROR, message)
     }
 
     pub fn bad_request(message: impl Into<String>) -> Self {
-        Self {
-            code: StatusCode::BAD_REQUEST,
-            message: message.into(),
-        }
+        Self::new(StatusCode::BAD_REQUEST, ErrorCode::BAD_REQUEST, message)
     }
 
     pub fn not_found(message: impl Into<String>) -> Self {
-        Self {
-            code: StatusCode::NOT_FOUND,
-            message: message.into(),
-        }
+        Self::new(StatusCode::NOT_FOUND, ErrorCode::NOT_FOUND, message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, ErrorCode::UNAUTHORIZED, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, ErrorCode::FORBIDDEN, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, ErrorCode::CONFLICT, message)
+    }
+
+    pub fn unprocessable_entity(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::VALIDATION_FAILED, message)
+    }
+
+    /// A `422 Unprocessable Entity` listing the invalid fields.
+    pub fn validation_failed(fields: Vec<FieldError>) -> Self {
+        Self::unprocessable_entity("Validation failed").with_fields(fields)
     }
 
     pub fn db_error(message: impl Into<String>) -> Self {
-        Self {
-            code: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.into(),
-        }
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::DATABASE_ERROR, message)
+    }
+
+    /// Replaces the error code, keeping the status.
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Attaches the fields of the request the error is about.
+    pub fn with_fields(mut self, fields: Vec<FieldError>) -> Self {
+        self.fields.extend(fields);
+        self
+    }
+
+    /// Attaches structured details to the error response.
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
     }
-    // You can add other methods here for different types of errors
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
-        println!("{}", self.message);
+        let code = format!("{:?}", self.code);
+        metrics().http_errors.with_label_values(&[self.status.as_str(), &code]).inc();
+        let request_id = REQUEST_ID.try_with(Clone::clone).ok();
+        let message = if self.status == StatusCode::INTERNAL_SERVER_ERROR {
+            // the message tells about the internals of the server, it stays in the logs
+            tracing::error!(code = ?self.code, request_id = ?request_id, "{}", self.message);
+            match self.code {
+                ErrorCode::DATABASE_ERROR => "The database failed to handle the request",
+                _ => "Internal server error",
+            }.to_string()
+        } else {
+            if self.status.is_server_error() {
+                tracing::error!(code = ?self.code, "{}", self.message);
+            } else {
+                tracing::debug!(code = ?self.code, "{}", self.message);
+            }
+            self.message
+        };
         (
-            self.code,
+            self.status,
             Json(ErrorResponse {
-                error: self.message.clone(),
+                error: message,
+                code: self.code,
+                request_id,
+                fields: self.fields,
+                details: self.details,
             }),
         ).into_response()
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct ErrorResponse {
-    error: String,
+/// The body of error responses.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ErrorResponse {
+    /// Human-readable description of the error
+    pub error: String,
+    pub code: ErrorCode,
+    /// Also sent in the `x-request-id` header, to find the request in the logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
 }
 
 impl fmt::Display for AppError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {}", self.code, self.message)
+        write!(f, "{}: {}", self.status, self.message)
     }
 }
 
@@ -77,7 +212,37 @@ impl Error for AppError {}
 
 impl From<Box<dyn Error + Send + Sync + 'static>> for AppError {
     fn from(err: Box<dyn Error + Send + Sync + 'static>) -> Self {
-        AppError::internal_server_error(format!("{}", err))
+        if let Some(err) = err.downcast_ref::<NotFoundError>() {
+            return AppError::not_found(err.to_string());
+        }
+        if let Some(err) = err.downcast_ref::<ValidationError>() {
+            return AppError::from(err.clone());
+        }
+        if let Some(err) = err.downcast_ref::<PermissionDeniedError>() {
+            return AppError::forbidden(err.to_string());
+        }
+        if let Some(err) = err.downcast_ref::<LastOwnerError>() {
+            return AppError::conflict(err.to_string());
+        }
+        if let Some(err) = err.downcast_ref::<ListQueryError>() {
+            return AppError::bad_request(err.to_string());
+        }
+        if let Some(err) = err.downcast_ref::<InvalidStatusTransitionError>() {
+            return AppError::conflict(err.to_string()).with_code(
+                ErrorCode::INVALID_STATUS_TRANSITION
+            );
+        }
+        if let Some(err) = err.downcast_ref::<ShiftValidationError>() {
+            let app_error = match err.rule {
+                ShiftValidationRule::OVERLAP => AppError::conflict(err.to_string()),
+                _ => AppError::unprocessable_entity(err.to_string()),
+            };
+            return app_error.with_details(err);
+        }
+        match err.downcast::<mysql::Error>() {
+            Ok(err) => AppError::from(*err),
+            Err(err) => AppError::internal_server_error(format!("{}", err)),
+        }
     }
 }
 
@@ -86,13 +251,159 @@ impl From<mysql::Error> for AppError {
         match error {
             mysql::Error::IoError(err) => AppError::db_error(format!("I/O Error: {}", err)),
             mysql::Error::CodecError(err) => AppError::db_error(format!("Codec Error: {}", err)),
-            mysql::Error::MySqlError(err) => AppError::db_error(format!("MySQL Error: {}", err)),
+            mysql::Error::MySqlError(err) => AppError::from(err),
             mysql::Error::DriverError(err) => AppError::db_error(format!("Driver Error: {}", err)),
             mysql::Error::UrlError(err) => AppError::db_error(format!("URL Error: {}", err)),
             mysql::Error::TlsError(err) => AppError::db_error(format!("TLS Error: {}", err)),
             mysql::Error::FromValueError(_value) => AppError::db_error(format!("From Value Error")),
+            // the values of the row are left out of the logs, they may be personal data
             mysql::Error::FromRowError(row) =>
-                AppError::db_error(format!("From Row Error: {:?}", row)),
+                AppError::db_error(
+                    format!(
+                        "From Row Error: row with columns {:?}",
+                        row
+                            .columns_ref()
+                            .iter()
+                            .map(|column| column.name_str().into_owned())
+                            .collect::<Vec<_>>()
+                    )
+                ),
+        }
+    }
+}
+
+/// MySQL error codes of constraint violations.
+const ER_DUP_ENTRY: u16 = 1062;
+const ER_ROW_IS_REFERENCED_2: u16 = 1451;
+const ER_NO_REFERENCED_ROW_2: u16 = 1452;
+
+impl From<MySqlError> for AppError {
+    fn from(error: MySqlError) -> Self {
+        match error.code {
+            ER_DUP_ENTRY => {
+                let fields = duplicate_key(&error.message)
+                    .map(|key| vec![FieldError::new(key, "is already taken")])
+                    .unwrap_or_default();
+                AppError::conflict("A unique value is already taken")
+                    .with_code(ErrorCode::UNIQUE_VIOLATION)
+                    .with_fields(fields)
+            }
+            ER_ROW_IS_REFERENCED_2 =>
+                AppError::conflict("The entity is still referenced by other entities").with_code(
+                    ErrorCode::FOREIGN_KEY_VIOLATION
+                ),
+            ER_NO_REFERENCED_ROW_2 => {
+                let fields = foreign_key(&error.message)
+                    .map(|key| vec![FieldError::new(key, "references a missing entity")])
+                    .unwrap_or_default();
+                AppError::unprocessable_entity("A referenced entity doesn't exist")
+                    .with_code(ErrorCode::FOREIGN_KEY_VIOLATION)
+                    .with_fields(fields)
+            }
+            _ => AppError::db_error(format!("MySQL Error: {}", error)),
+        }
+    }
+}
+
+/// The key of a duplicate entry error, `Duplicate entry 'x' for key 'users.email'` gives
+/// `email`. Keys of single `UNIQUE` columns are named after the column.
+fn duplicate_key(message: &str) -> Option<String> {
+    let key = message.rsplit_once(" for key ")?.1.trim_matches(|c| c == '\'' || c == '`');
+    Some(key.rsplit('.').next()?.to_string())
+}
+
+/// The column of a foreign key error, `... FOREIGN KEY (`org_id`) REFERENCES ...` gives
+/// `org_id`.
+fn foreign_key(message: &str) -> Option<String> {
+    let columns = message.split_once("FOREIGN KEY (")?.1.split_once(')')?.0;
+    Some(columns.trim_matches('`').to_string())
+}
+
+impl From<ValidationError> for AppError {
+    fn from(error: ValidationError) -> Self {
+        AppError::validation_failed(error.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{ body::to_bytes, response::IntoResponse };
+
+    use super::*;
+
+    #[test]
+    fn test_mysql_constraint_errors() {
+        let duplicate = AppError::from(MySqlError {
+            state: "23000".to_string(),
+            code: ER_DUP_ENTRY,
+            message: "Duplicate entry 'user@email.com' for key 'users.email'".to_string(),
+        });
+        assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+        assert_eq!(duplicate.code(), ErrorCode::UNIQUE_VIOLATION);
+        assert_eq!(duplicate.fields, vec![FieldError::new("email", "is already taken")]);
+
+        let missing_reference = AppError::from(MySqlError {
+            state: "23000".to_string(),
+            code: ER_NO_REFERENCED_ROW_2,
+            message: "Cannot add or update a child row: a foreign key constraint fails \
+                (`worktest`.`org_jobs`, CONSTRAINT `org_jobs_ibfk_1` FOREIGN KEY (`org_id`) \
+                REFERENCES `organizations` (`id`) ON DELETE CASCADE)".to_string(),
+        });
+        assert_eq!(missing_reference.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(missing_reference.code(), ErrorCode::FOREIGN_KEY_VIOLATION);
+        assert_eq!(missing_reference.fields[0].field, "org_id");
+
+        let boxed: BoxedError = Box::new(
+            mysql::Error::MySqlError(MySqlError {
+                state: "23000".to_string(),
+                code: ER_ROW_IS_REFERENCED_2,
+                message: "Cannot delete or update a parent row".to_string(),
+            })
+        );
+        assert_eq!(AppError::from(boxed).status(), StatusCode::CONFLICT);
+
+        let boxed: BoxedError = Box::new(NotFoundError("users not found".to_string()));
+        assert_eq!(AppError::from(boxed).code(), ErrorCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_error_response_body() {
+        let error = AppError::validation_failed(vec![FieldError::new("email", "is required")]);
+        let response = REQUEST_ID.scope("42".to_string(), async {
+            error.into_response()
+        }).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.code, ErrorCode::VALIDATION_FAILED);
+        assert_eq!(body.request_id, Some("42".to_string()));
+        assert_eq!(body.fields, vec![FieldError::new("email", "is required")]);
+    }
+
+    #[tokio::test]
+    async fn test_internal_errors_are_not_sent() {
+        let boxed: BoxedError = Box::new(
+            mysql::Error::MySqlError(MySqlError {
+                state: "42S02".to_string(),
+                code: 1146,
+                message: "Table 'worktest.users' doesn't exist".to_string(),
+            })
+        );
+        for (error, code) in [
+            (AppError::from(boxed), ErrorCode::DATABASE_ERROR),
+            (AppError::from(BoxedError::from("secret detail")), ErrorCode::INTERNAL_ERROR),
+        ] {
+            let response = REQUEST_ID.scope("42".to_string(), async {
+                error.into_response()
+            }).await;
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body.code, code);
+            assert_eq!(body.request_id, Some("42".to_string()));
+            assert!(!body.error.contains("worktest") && !body.error.contains("secret"));
         }
     }
 }
@@ -0,0 +1,52 @@
+use crate::storage::{ Connection, Storage };
+
+/// Runs blocking work, such as queries with the `mysql` crate or password hashing, on tokio's
+/// blocking thread pool so it doesn't stall the async worker threads.
+///
+/// `f` runs in the current `tracing` span, so its events are logged with the request they belong
+/// to. A panic in `f` is resumed in the calling task, as if `f` had run there.
+pub async fn run_blocking<T, F>(f: F) -> T
+    where F: FnOnce() -> T + Send + 'static, T: Send + 'static
+{
+    let span = tracing::Span::current();
+    match tokio::task::spawn_blocking(move || span.in_scope(f)).await {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(err) => panic!("blocking task failed: {}", err),
+    }
+}
+
+/// Takes a connection from `storage` and runs `f` with it on the blocking thread pool.
+///
+/// Getting the connection blocks as well when the pool is exhausted, so it happens on the
+/// blocking thread too.
+pub async fn with_conn<T, E, F>(storage: &Storage, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: From<mysql::Error> + Send + 'static
+{
+    let storage = storage.clone();
+    run_blocking(move || {
+        let mut conn = storage.get_conn()?;
+        f(&mut conn)
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_blocking() {
+        let runtime_thread = std::thread::current().id();
+        let blocking_thread = run_blocking(|| std::thread::current().id()).await;
+        assert_ne!(runtime_thread, blocking_thread);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "boom")]
+    async fn test_run_blocking_resumes_panics() {
+        run_blocking(|| panic!("boom")).await
+    }
+}
@@ -0,0 +1,34 @@
+use std::time::Instant;
+
+use tracing_subscriber::EnvFilter;
+
+use crate::config::LogConfig;
+
+/// Installs the global `tracing` subscriber, writing events of `config.level` and above to
+/// stdout in `config.format`.
+///
+/// Events carry the fields of the spans they happen in, e.g. the request span of `log_route`.
+/// Does nothing if a subscriber is already installed.
+pub fn init_tracing(config: &LogConfig) {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::new(&config.level));
+
+    let _ = match config.format.as_str() {
+        "json" => builder.json().with_current_span(true).with_span_list(false).try_init(),
+        _ => builder.try_init(),
+    };
+}
+
+/// Runs an SQL `statement` with `run` and logs it at debug level with the time it took.
+///
+/// Only the statement is logged, not its parameters, which may hold passwords or personal data.
+pub fn timed_statement<T>(statement: &str, run: impl FnOnce(&str) -> T) -> T {
+    let start = Instant::now();
+    let result = run(statement);
+    tracing::debug!(
+        target: "api::sql",
+        statement,
+        elapsed_ms = start.elapsed().as_secs_f64() * 1000.0,
+        "sql statement"
+    );
+    result
+}
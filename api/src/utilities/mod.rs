@@ -1,3 +1,6 @@
 pub mod parse_chrono;
 pub mod app_error;
 pub mod app_result;
+pub mod token;
+pub mod blocking;
+pub mod logging;
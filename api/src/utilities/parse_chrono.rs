@@ -35,6 +35,14 @@ pub fn convert_to_naive_time(value: Value) -> Result<NaiveTime> {
                 "Invalid time components".into()
             )
         }
+        Value::Time(false, 0, hour, minute, second, micro_seconds) => {
+            NaiveTime::from_hms_micro_opt(
+                hour as u32,
+                minute as u32,
+                second as u32,
+                micro_seconds
+            ).ok_or_else(|| "Invalid time components".into())
+        }
         _ => Err("Unsupported value type for date conversion".into()),
     }
 }
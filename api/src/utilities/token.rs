@@ -0,0 +1,100 @@
+use chrono::{ Duration, Utc };
+use jsonwebtoken::{ decode, encode, DecodingKey, EncodingKey, Header, Validation };
+use serde::{ Deserialize, Serialize };
+
+use crate::models::result::Result;
+
+/// Lifetime of an access token.
+pub const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+/// Lifetime of a refresh token.
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
+
+/// Claims carried by both access and refresh tokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// Id of the authenticated user.
+    pub sub: i64,
+    /// Id of the `refresh_tokens` row, only set on refresh tokens.
+    pub jti: Option<i64>,
+    pub kind: TokenKind,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Signs a short-lived access token for the user.
+pub fn create_access_token(secret: &str, user_id: i64) -> Result<String> {
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: user_id,
+        jti: None,
+        kind: TokenKind::Access,
+        iat: now.timestamp(),
+        exp: (now + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES)).timestamp(),
+    };
+    Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))?)
+}
+
+/// Signs a refresh token pointing at the stored `refresh_tokens` row `token_id`.
+pub fn create_refresh_token(
+    secret: &str,
+    user_id: i64,
+    token_id: i64,
+    exp: i64
+) -> Result<String> {
+    let claims = TokenClaims {
+        sub: user_id,
+        jti: Some(token_id),
+        kind: TokenKind::Refresh,
+        iat: Utc::now().timestamp(),
+        exp,
+    };
+    Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))?)
+}
+
+/// Verifies the signature and expiry of `token` and checks that it is of the expected kind.
+pub fn decode_token(secret: &str, token: &str, kind: TokenKind) -> Result<TokenClaims> {
+    let data = decode::<TokenClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default()
+    )?;
+    if data.claims.kind != kind {
+        return Err("Unexpected token type".into());
+    }
+    Ok(data.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_round_trip() -> Result<()> {
+        let secret = "test-secret";
+
+        let access_token = create_access_token(secret, 42)?;
+        let claims = decode_token(secret, &access_token, TokenKind::Access)?;
+        assert_eq!(claims.sub, 42);
+        assert_eq!(claims.jti, None);
+
+        // an access token can't be used as a refresh token and vice versa
+        assert!(decode_token(secret, &access_token, TokenKind::Refresh).is_err());
+
+        let exp = (Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS)).timestamp();
+        let refresh_token = create_refresh_token(secret, 42, 7, exp)?;
+        let claims = decode_token(secret, &refresh_token, TokenKind::Refresh)?;
+        assert_eq!(claims.jti, Some(7));
+
+        // tokens signed with another secret are rejected
+        assert!(decode_token("other-secret", &access_token, TokenKind::Access).is_err());
+
+        Ok(())
+    }
+}